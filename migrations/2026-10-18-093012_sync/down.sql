@@ -0,0 +1,25 @@
+DROP TABLE sync_conflicts;
+DROP TABLE sync_peers;
+DROP TRIGGER tracks_log_insert;
+DROP TRIGGER tracks_log_delete;
+DROP TRIGGER albums_log_insert;
+DROP TRIGGER albums_log_delete;
+DROP TRIGGER recordings_log_insert;
+DROP TRIGGER recordings_log_delete;
+DROP TRIGGER ensembles_log_insert;
+DROP TRIGGER ensembles_log_delete;
+DROP TRIGGER works_log_insert;
+DROP TRIGGER works_log_delete;
+DROP TRIGGER tags_log_insert;
+DROP TRIGGER tags_log_delete;
+DROP TRIGGER instruments_log_insert;
+DROP TRIGGER instruments_log_delete;
+DROP TRIGGER roles_log_insert;
+DROP TRIGGER roles_log_delete;
+DROP TRIGGER persons_log_insert;
+DROP TRIGGER persons_log_delete;
+DROP TABLE change_log;
+
+ALTER TABLE meta DROP COLUMN library_id;
+
+UPDATE meta SET schema_version = 1 WHERE id = 1;
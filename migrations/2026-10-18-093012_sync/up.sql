@@ -0,0 +1,137 @@
+-- Every migration that changes the schema must bump schema_version.
+
+-- Identifies a library across folders, so that a library can remember which
+-- other libraries it was synchronized with.
+ALTER TABLE meta ADD COLUMN library_id TEXT NOT NULL DEFAULT '';
+
+UPDATE meta SET schema_version = 2, library_id = LOWER(HEX(RANDOMBLOB(16))) WHERE id = 1;
+
+-- What happened to entities that no longer exist. Edits are told apart by
+-- `edited_at`, but a deleted row leaves nothing behind to compare, so its
+-- removal is recorded here. There is one row per entity: recreating an entity
+-- removes it again.
+CREATE TABLE change_log (
+    entity_kind TEXT NOT NULL CHECK (entity_kind IN ('person', 'role', 'instrument', 'tag', 'work', 'ensemble', 'recording', 'album', 'track')),
+    entity_id TEXT NOT NULL,
+    change TEXT NOT NULL CHECK (change IN ('delete', 'merge')),
+    -- The entity that references were moved to, if it was merged.
+    merged_into TEXT,
+    changed_at TIMESTAMP NOT NULL DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'now')),
+    PRIMARY KEY (entity_kind, entity_id)
+);
+
+CREATE INDEX change_log_changed_at ON change_log (changed_at);
+
+-- Other libraries this one was synchronized with.
+CREATE TABLE sync_peers (
+    library_id TEXT NOT NULL PRIMARY KEY,
+    last_synced_at TIMESTAMP NOT NULL
+);
+
+-- Entities that were edited in both libraries between two synchronizations.
+-- They are left alone until one side is edited again.
+CREATE TABLE sync_conflicts (
+    library_id TEXT NOT NULL REFERENCES sync_peers(library_id) ON DELETE CASCADE,
+    entity_kind TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    PRIMARY KEY (library_id, entity_kind, entity_id)
+);
+
+CREATE TRIGGER persons_log_delete AFTER DELETE ON persons
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('person', OLD.person_id, 'delete');
+END;
+
+CREATE TRIGGER persons_log_insert AFTER INSERT ON persons
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'person' AND entity_id = NEW.person_id;
+END;
+
+CREATE TRIGGER roles_log_delete AFTER DELETE ON roles
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('role', OLD.role_id, 'delete');
+END;
+
+CREATE TRIGGER roles_log_insert AFTER INSERT ON roles
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'role' AND entity_id = NEW.role_id;
+END;
+
+CREATE TRIGGER instruments_log_delete AFTER DELETE ON instruments
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('instrument', OLD.instrument_id, 'delete');
+END;
+
+CREATE TRIGGER instruments_log_insert AFTER INSERT ON instruments
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'instrument' AND entity_id = NEW.instrument_id;
+END;
+
+CREATE TRIGGER tags_log_delete AFTER DELETE ON tags
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('tag', OLD.tag_id, 'delete');
+END;
+
+CREATE TRIGGER tags_log_insert AFTER INSERT ON tags
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'tag' AND entity_id = NEW.tag_id;
+END;
+
+CREATE TRIGGER works_log_delete AFTER DELETE ON works
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('work', OLD.work_id, 'delete');
+END;
+
+CREATE TRIGGER works_log_insert AFTER INSERT ON works
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'work' AND entity_id = NEW.work_id;
+END;
+
+CREATE TRIGGER ensembles_log_delete AFTER DELETE ON ensembles
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('ensemble', OLD.ensemble_id, 'delete');
+END;
+
+CREATE TRIGGER ensembles_log_insert AFTER INSERT ON ensembles
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'ensemble' AND entity_id = NEW.ensemble_id;
+END;
+
+CREATE TRIGGER recordings_log_delete AFTER DELETE ON recordings
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('recording', OLD.recording_id, 'delete');
+END;
+
+CREATE TRIGGER recordings_log_insert AFTER INSERT ON recordings
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'recording' AND entity_id = NEW.recording_id;
+END;
+
+CREATE TRIGGER albums_log_delete AFTER DELETE ON albums
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('album', OLD.album_id, 'delete');
+END;
+
+CREATE TRIGGER albums_log_insert AFTER INSERT ON albums
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'album' AND entity_id = NEW.album_id;
+END;
+
+CREATE TRIGGER tracks_log_delete AFTER DELETE ON tracks
+BEGIN
+    INSERT OR REPLACE INTO change_log (entity_kind, entity_id, change)
+    VALUES ('track', OLD.track_id, 'delete');
+END;
+
+CREATE TRIGGER tracks_log_insert AFTER INSERT ON tracks
+BEGIN
+    DELETE FROM change_log WHERE entity_kind = 'track' AND entity_id = NEW.track_id;
+END;
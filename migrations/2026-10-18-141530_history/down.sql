@@ -0,0 +1,62 @@
+DROP TRIGGER plays_history_insert;
+DROP TRIGGER plays_history_update;
+DROP TRIGGER plays_history_delete;
+DROP TRIGGER album_recordings_history_insert;
+DROP TRIGGER album_recordings_history_update;
+DROP TRIGGER album_recordings_history_delete;
+DROP TRIGGER track_works_history_insert;
+DROP TRIGGER track_works_history_update;
+DROP TRIGGER track_works_history_delete;
+DROP TRIGGER recording_tags_history_insert;
+DROP TRIGGER recording_tags_history_update;
+DROP TRIGGER recording_tags_history_delete;
+DROP TRIGGER recording_ensembles_history_insert;
+DROP TRIGGER recording_ensembles_history_update;
+DROP TRIGGER recording_ensembles_history_delete;
+DROP TRIGGER recording_persons_history_insert;
+DROP TRIGGER recording_persons_history_update;
+DROP TRIGGER recording_persons_history_delete;
+DROP TRIGGER ensemble_persons_history_insert;
+DROP TRIGGER ensemble_persons_history_update;
+DROP TRIGGER ensemble_persons_history_delete;
+DROP TRIGGER work_tags_history_insert;
+DROP TRIGGER work_tags_history_update;
+DROP TRIGGER work_tags_history_delete;
+DROP TRIGGER work_instruments_history_insert;
+DROP TRIGGER work_instruments_history_update;
+DROP TRIGGER work_instruments_history_delete;
+DROP TRIGGER work_persons_history_insert;
+DROP TRIGGER work_persons_history_update;
+DROP TRIGGER work_persons_history_delete;
+DROP TRIGGER tracks_history_insert;
+DROP TRIGGER tracks_history_update;
+DROP TRIGGER tracks_history_delete;
+DROP TRIGGER albums_history_insert;
+DROP TRIGGER albums_history_update;
+DROP TRIGGER albums_history_delete;
+DROP TRIGGER recordings_history_insert;
+DROP TRIGGER recordings_history_update;
+DROP TRIGGER recordings_history_delete;
+DROP TRIGGER ensembles_history_insert;
+DROP TRIGGER ensembles_history_update;
+DROP TRIGGER ensembles_history_delete;
+DROP TRIGGER works_history_insert;
+DROP TRIGGER works_history_update;
+DROP TRIGGER works_history_delete;
+DROP TRIGGER tags_history_insert;
+DROP TRIGGER tags_history_update;
+DROP TRIGGER tags_history_delete;
+DROP TRIGGER instruments_history_insert;
+DROP TRIGGER instruments_history_update;
+DROP TRIGGER instruments_history_delete;
+DROP TRIGGER roles_history_insert;
+DROP TRIGGER roles_history_update;
+DROP TRIGGER roles_history_delete;
+DROP TRIGGER persons_history_insert;
+DROP TRIGGER persons_history_update;
+DROP TRIGGER persons_history_delete;
+DROP TABLE history_steps;
+DROP TABLE history;
+DROP TABLE history_state;
+
+UPDATE meta SET schema_version = 2 WHERE id = 1;
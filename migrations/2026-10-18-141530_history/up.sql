@@ -0,0 +1,714 @@
+-- Every migration that changes the schema must bump schema_version.
+
+UPDATE meta SET schema_version = 3 WHERE id = 1;
+
+-- User actions on the library that can be undone, such as an edit, a deletion
+-- or a merge.
+CREATE TABLE history (
+    change_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    action TEXT NOT NULL CHECK (action IN ('update', 'delete', 'merge')),
+    entity_kind TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    -- The entity's name when the change was made, because it may be gone.
+    entity_name TEXT NOT NULL,
+    -- The name of the entity that was merged into, for merges.
+    target_name TEXT,
+    changed_at TIMESTAMP NOT NULL DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+-- The statements that undo a change, one per changed row. They are run in
+-- reverse order.
+CREATE TABLE history_steps (
+    step_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    change_id INTEGER NOT NULL REFERENCES history(change_id) ON DELETE CASCADE,
+    undo_sql TEXT NOT NULL
+);
+
+CREATE INDEX history_steps_change_id ON history_steps (change_id);
+
+-- The change that is being recorded right now, if any. The triggers below only
+-- record steps while this is set, so that imports, metadata updates and
+-- undoing itself are not recorded.
+CREATE TABLE history_state (
+    -- Constrained to a single row.
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 1),
+    change_id INTEGER
+);
+
+INSERT INTO history_state (id, change_id) VALUES (1, NULL);
+
+-- Undoing a change counts as editing the restored rows again, so that they are
+-- passed on by the next synchronization. That is why `edited_at` is set to the
+-- time the undo statement runs rather than restored.
+
+CREATE TRIGGER persons_history_insert AFTER INSERT ON persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM persons WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER persons_history_update AFTER UPDATE ON persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE persons SET ' ||
+            'person_id = ' || QUOTE(OLD.person_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER persons_history_delete AFTER DELETE ON persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO persons (rowid, person_id, name, source, enable_updates, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.person_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+CREATE TRIGGER roles_history_insert AFTER INSERT ON roles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM roles WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER roles_history_update AFTER UPDATE ON roles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE roles SET ' ||
+            'role_id = ' || QUOTE(OLD.role_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER roles_history_delete AFTER DELETE ON roles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO roles (rowid, role_id, name, source, enable_updates, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.role_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+CREATE TRIGGER instruments_history_insert AFTER INSERT ON instruments
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM instruments WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER instruments_history_update AFTER UPDATE ON instruments
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE instruments SET ' ||
+            'instrument_id = ' || QUOTE(OLD.instrument_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER instruments_history_delete AFTER DELETE ON instruments
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO instruments (rowid, instrument_id, name, source, enable_updates, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.instrument_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+CREATE TRIGGER tags_history_insert AFTER INSERT ON tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM tags WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER tags_history_update AFTER UPDATE ON tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE tags SET ' ||
+            'tag_id = ' || QUOTE(OLD.tag_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'takes_value = ' || QUOTE(OLD.takes_value) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) || ', ' ||
+            'private = ' || QUOTE(OLD.private) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER tags_history_delete AFTER DELETE ON tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO tags (rowid, tag_id, name, takes_value, source, enable_updates, created_at, edited_at, last_used_at, private) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.tag_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.takes_value) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) || ', ' ||
+            QUOTE(OLD.private) ||
+            ')');
+END;
+
+CREATE TRIGGER works_history_insert AFTER INSERT ON works
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM works WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER works_history_update AFTER UPDATE ON works
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE works SET ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'parent_work_id = ' || QUOTE(OLD.parent_work_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) || ', ' ||
+            'relates_to = ' || QUOTE(OLD.relates_to) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER works_history_delete AFTER DELETE ON works
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO works (rowid, work_id, parent_work_id, sequence_number, name, source, enable_updates, created_at, edited_at, last_used_at, relates_to) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.parent_work_id) || ', ' ||
+            QUOTE(OLD.sequence_number) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) || ', ' ||
+            QUOTE(OLD.relates_to) ||
+            ')');
+END;
+
+CREATE TRIGGER ensembles_history_insert AFTER INSERT ON ensembles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM ensembles WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER ensembles_history_update AFTER UPDATE ON ensembles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE ensembles SET ' ||
+            'ensemble_id = ' || QUOTE(OLD.ensemble_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER ensembles_history_delete AFTER DELETE ON ensembles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO ensembles (rowid, ensemble_id, name, source, enable_updates, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.ensemble_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+CREATE TRIGGER recordings_history_insert AFTER INSERT ON recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM recordings WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recordings_history_update AFTER UPDATE ON recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE recordings SET ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) || ', ' ||
+            'comment = ' || QUOTE(OLD.comment) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recordings_history_delete AFTER DELETE ON recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO recordings (rowid, recording_id, work_id, source, enable_updates, created_at, edited_at, last_used_at, comment) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) || ', ' ||
+            QUOTE(OLD.comment) ||
+            ')');
+END;
+
+CREATE TRIGGER albums_history_insert AFTER INSERT ON albums
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM albums WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER albums_history_update AFTER UPDATE ON albums
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE albums SET ' ||
+            'album_id = ' || QUOTE(OLD.album_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER albums_history_delete AFTER DELETE ON albums
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO albums (rowid, album_id, name, source, enable_updates, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.album_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+CREATE TRIGGER tracks_history_insert AFTER INSERT ON tracks
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM tracks WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER tracks_history_update AFTER UPDATE ON tracks
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE tracks SET ' ||
+            'track_id = ' || QUOTE(OLD.track_id) || ', ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'recording_index = ' || QUOTE(OLD.recording_index) || ', ' ||
+            'path = ' || QUOTE(OLD.path) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER tracks_history_delete AFTER DELETE ON tracks
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO tracks (rowid, track_id, recording_id, recording_index, path, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.track_id) || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.recording_index) || ', ' ||
+            QUOTE(OLD.path) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+CREATE TRIGGER work_persons_history_insert AFTER INSERT ON work_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM work_persons WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER work_persons_history_update AFTER UPDATE ON work_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE work_persons SET ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'person_id = ' || QUOTE(OLD.person_id) || ', ' ||
+            'role_id = ' || QUOTE(OLD.role_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER work_persons_history_delete AFTER DELETE ON work_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO work_persons (rowid, work_id, person_id, role_id, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.person_id) || ', ' ||
+            QUOTE(OLD.role_id) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER work_instruments_history_insert AFTER INSERT ON work_instruments
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM work_instruments WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER work_instruments_history_update AFTER UPDATE ON work_instruments
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE work_instruments SET ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'instrument_id = ' || QUOTE(OLD.instrument_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER work_instruments_history_delete AFTER DELETE ON work_instruments
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO work_instruments (rowid, work_id, instrument_id, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.instrument_id) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER work_tags_history_insert AFTER INSERT ON work_tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM work_tags WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER work_tags_history_update AFTER UPDATE ON work_tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE work_tags SET ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'tag_id = ' || QUOTE(OLD.tag_id) || ', ' ||
+            'value = ' || QUOTE(OLD.value) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER work_tags_history_delete AFTER DELETE ON work_tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO work_tags (rowid, work_id, tag_id, value, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.tag_id) || ', ' ||
+            QUOTE(OLD.value) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER ensemble_persons_history_insert AFTER INSERT ON ensemble_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM ensemble_persons WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER ensemble_persons_history_update AFTER UPDATE ON ensemble_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE ensemble_persons SET ' ||
+            'ensemble_id = ' || QUOTE(OLD.ensemble_id) || ', ' ||
+            'person_id = ' || QUOTE(OLD.person_id) || ', ' ||
+            'instrument_id = ' || QUOTE(OLD.instrument_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) || ', ' ||
+            'role_id = ' || QUOTE(OLD.role_id) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER ensemble_persons_history_delete AFTER DELETE ON ensemble_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO ensemble_persons (rowid, ensemble_id, person_id, instrument_id, sequence_number, role_id) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.ensemble_id) || ', ' ||
+            QUOTE(OLD.person_id) || ', ' ||
+            QUOTE(OLD.instrument_id) || ', ' ||
+            QUOTE(OLD.sequence_number) || ', ' ||
+            QUOTE(OLD.role_id) ||
+            ')');
+END;
+
+CREATE TRIGGER recording_persons_history_insert AFTER INSERT ON recording_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM recording_persons WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recording_persons_history_update AFTER UPDATE ON recording_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE recording_persons SET ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'person_id = ' || QUOTE(OLD.person_id) || ', ' ||
+            'role_id = ' || QUOTE(OLD.role_id) || ', ' ||
+            'instrument_id = ' || QUOTE(OLD.instrument_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recording_persons_history_delete AFTER DELETE ON recording_persons
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO recording_persons (rowid, recording_id, person_id, role_id, instrument_id, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.person_id) || ', ' ||
+            QUOTE(OLD.role_id) || ', ' ||
+            QUOTE(OLD.instrument_id) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER recording_ensembles_history_insert AFTER INSERT ON recording_ensembles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM recording_ensembles WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recording_ensembles_history_update AFTER UPDATE ON recording_ensembles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE recording_ensembles SET ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'ensemble_id = ' || QUOTE(OLD.ensemble_id) || ', ' ||
+            'role_id = ' || QUOTE(OLD.role_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recording_ensembles_history_delete AFTER DELETE ON recording_ensembles
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO recording_ensembles (rowid, recording_id, ensemble_id, role_id, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.ensemble_id) || ', ' ||
+            QUOTE(OLD.role_id) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER recording_tags_history_insert AFTER INSERT ON recording_tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM recording_tags WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recording_tags_history_update AFTER UPDATE ON recording_tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE recording_tags SET ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'tag_id = ' || QUOTE(OLD.tag_id) || ', ' ||
+            'value = ' || QUOTE(OLD.value) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recording_tags_history_delete AFTER DELETE ON recording_tags
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO recording_tags (rowid, recording_id, tag_id, value, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.tag_id) || ', ' ||
+            QUOTE(OLD.value) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER track_works_history_insert AFTER INSERT ON track_works
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM track_works WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER track_works_history_update AFTER UPDATE ON track_works
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE track_works SET ' ||
+            'track_id = ' || QUOTE(OLD.track_id) || ', ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER track_works_history_delete AFTER DELETE ON track_works
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO track_works (rowid, track_id, work_id, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.track_id) || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER album_recordings_history_insert AFTER INSERT ON album_recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM album_recordings WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER album_recordings_history_update AFTER UPDATE ON album_recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE album_recordings SET ' ||
+            'album_id = ' || QUOTE(OLD.album_id) || ', ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER album_recordings_history_delete AFTER DELETE ON album_recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO album_recordings (rowid, album_id, recording_id, sequence_number) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.album_id) || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.sequence_number) ||
+            ')');
+END;
+
+CREATE TRIGGER plays_history_insert AFTER INSERT ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM plays WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER plays_history_update AFTER UPDATE ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE plays SET ' ||
+            'play_id = ' || QUOTE(OLD.play_id) || ', ' ||
+            'track_id = ' || QUOTE(OLD.track_id) || ', ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'played_at = ' || QUOTE(OLD.played_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER plays_history_delete AFTER DELETE ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO plays (rowid, play_id, track_id, recording_id, played_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.play_id) || ', ' ||
+            QUOTE(OLD.track_id) || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.played_at) ||
+            ')');
+END;
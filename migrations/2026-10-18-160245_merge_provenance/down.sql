@@ -0,0 +1,10 @@
+DROP TRIGGER merge_references_history_insert;
+DROP TRIGGER merge_references_history_update;
+DROP TRIGGER merge_references_history_delete;
+DROP TRIGGER merges_history_insert;
+DROP TRIGGER merges_history_update;
+DROP TRIGGER merges_history_delete;
+DROP TABLE merge_references;
+DROP TABLE merges;
+
+UPDATE meta SET schema_version = 3 WHERE id = 1;
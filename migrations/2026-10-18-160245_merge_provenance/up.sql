@@ -0,0 +1,110 @@
+-- Every migration that changes the schema must bump schema_version.
+
+UPDATE meta SET schema_version = 4 WHERE id = 1;
+
+-- Merges that can still be split again. `snapshot` holds the merged entity and
+-- the rows it owned as JSON, so that it can be restored with its original ID.
+CREATE TABLE merges (
+    merge_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    entity_kind TEXT NOT NULL,
+    from_id TEXT NOT NULL,
+    from_name TEXT NOT NULL,
+    -- The entity that now holds the references. Updated when that one is merged
+    -- into yet another entity in turn.
+    into_id TEXT NOT NULL,
+    snapshot TEXT NOT NULL,
+    merged_at TIMESTAMP NOT NULL DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+CREATE INDEX merges_into_id ON merges (entity_kind, into_id);
+
+-- The references a merge moved from the merged entity to the one it was merged
+-- into. A reference is identified by the row that holds it: its owner and, for
+-- ordered relations, its sequence number.
+CREATE TABLE merge_references (
+    reference_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    merge_id INTEGER NOT NULL REFERENCES merges(merge_id) ON DELETE CASCADE,
+    reference_kind TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    sequence_number INTEGER,
+    -- The value of a tag assignment, which merging into a plain tag discards.
+    value TEXT
+);
+
+CREATE INDEX merge_references_merge_id ON merge_references (merge_id);
+
+-- Undoing a merge also forgets that it happened.
+
+CREATE TRIGGER merges_history_insert AFTER INSERT ON merges
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM merges WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER merges_history_update AFTER UPDATE ON merges
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE merges SET ' ||
+            'merge_id = ' || QUOTE(OLD.merge_id) || ', ' ||
+            'entity_kind = ' || QUOTE(OLD.entity_kind) || ', ' ||
+            'from_id = ' || QUOTE(OLD.from_id) || ', ' ||
+            'from_name = ' || QUOTE(OLD.from_name) || ', ' ||
+            'into_id = ' || QUOTE(OLD.into_id) || ', ' ||
+            'snapshot = ' || QUOTE(OLD.snapshot) || ', ' ||
+            'merged_at = ' || QUOTE(OLD.merged_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER merges_history_delete AFTER DELETE ON merges
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO merges (rowid, merge_id, entity_kind, from_id, from_name, into_id, snapshot, merged_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.merge_id) || ', ' ||
+            QUOTE(OLD.entity_kind) || ', ' ||
+            QUOTE(OLD.from_id) || ', ' ||
+            QUOTE(OLD.from_name) || ', ' ||
+            QUOTE(OLD.into_id) || ', ' ||
+            QUOTE(OLD.snapshot) || ', ' ||
+            QUOTE(OLD.merged_at) ||
+            ')');
+END;
+
+CREATE TRIGGER merge_references_history_insert AFTER INSERT ON merge_references
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM merge_references WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER merge_references_history_update AFTER UPDATE ON merge_references
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE merge_references SET ' ||
+            'reference_id = ' || QUOTE(OLD.reference_id) || ', ' ||
+            'merge_id = ' || QUOTE(OLD.merge_id) || ', ' ||
+            'reference_kind = ' || QUOTE(OLD.reference_kind) || ', ' ||
+            'owner_id = ' || QUOTE(OLD.owner_id) || ', ' ||
+            'sequence_number = ' || QUOTE(OLD.sequence_number) || ', ' ||
+            'value = ' || QUOTE(OLD.value) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER merge_references_history_delete AFTER DELETE ON merge_references
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO merge_references (rowid, reference_id, merge_id, reference_kind, owner_id, sequence_number, value) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.reference_id) || ', ' ||
+            QUOTE(OLD.merge_id) || ', ' ||
+            QUOTE(OLD.reference_kind) || ', ' ||
+            QUOTE(OLD.owner_id) || ', ' ||
+            QUOTE(OLD.sequence_number) || ', ' ||
+            QUOTE(OLD.value) ||
+            ')');
+END;
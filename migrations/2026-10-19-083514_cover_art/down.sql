@@ -0,0 +1,73 @@
+DROP TRIGGER recordings_history_update;
+DROP TRIGGER recordings_history_delete;
+DROP TRIGGER albums_history_update;
+DROP TRIGGER albums_history_delete;
+
+ALTER TABLE recordings DROP COLUMN cover_art;
+ALTER TABLE albums DROP COLUMN cover_art;
+
+CREATE TRIGGER recordings_history_update AFTER UPDATE ON recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE recordings SET ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'work_id = ' || QUOTE(OLD.work_id) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) || ', ' ||
+            'comment = ' || QUOTE(OLD.comment) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER recordings_history_delete AFTER DELETE ON recordings
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO recordings (rowid, recording_id, work_id, source, enable_updates, created_at, edited_at, last_used_at, comment) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.work_id) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) || ', ' ||
+            QUOTE(OLD.comment) ||
+            ')');
+END;
+
+CREATE TRIGGER albums_history_update AFTER UPDATE ON albums
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE albums SET ' ||
+            'album_id = ' || QUOTE(OLD.album_id) || ', ' ||
+            'name = ' || QUOTE(OLD.name) || ', ' ||
+            'source = ' || QUOTE(OLD.source) || ', ' ||
+            'enable_updates = ' || QUOTE(OLD.enable_updates) || ', ' ||
+            'created_at = ' || QUOTE(OLD.created_at) || ', ' ||
+            'edited_at = STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            'last_used_at = ' || QUOTE(OLD.last_used_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER albums_history_delete AFTER DELETE ON albums
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO albums (rowid, album_id, name, source, enable_updates, created_at, edited_at, last_used_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.album_id) || ', ' ||
+            QUOTE(OLD.name) || ', ' ||
+            QUOTE(OLD.source) || ', ' ||
+            QUOTE(OLD.enable_updates) || ', ' ||
+            QUOTE(OLD.created_at) || ', ' ||
+            'STRFTIME(''%Y-%m-%d %H:%M:%f'', ''now'')' || ', ' ||
+            QUOTE(OLD.last_used_at) ||
+            ')');
+END;
+
+UPDATE meta SET schema_version = 4 WHERE id = 1;
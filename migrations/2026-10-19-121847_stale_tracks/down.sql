@@ -0,0 +1,3 @@
+DROP TABLE stale_tracks;
+
+UPDATE meta SET schema_version = 5 WHERE id = 1;
@@ -0,0 +1,12 @@
+-- Every migration that changes the schema must bump schema_version.
+
+UPDATE meta SET schema_version = 6 WHERE id = 1;
+
+-- Tracks whose file may no longer be named and tagged after the database,
+-- because something they are named after was edited. Only kept while automatic
+-- file maintenance is enabled. Like plays, this is local to the library and
+-- has no undo history.
+CREATE TABLE stale_tracks (
+    track_id TEXT NOT NULL PRIMARY KEY REFERENCES tracks(track_id) ON DELETE CASCADE,
+    marked_at TIMESTAMP NOT NULL DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'now'))
+);
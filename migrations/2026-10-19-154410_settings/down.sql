@@ -0,0 +1,3 @@
+DROP TABLE settings;
+
+UPDATE meta SET schema_version = 6 WHERE id = 1;
@@ -0,0 +1,12 @@
+-- Every migration that changes the schema must bump schema_version.
+
+UPDATE meta SET schema_version = 7 WHERE id = 1;
+
+-- Settings that belong to the library rather than to the computer it is opened
+-- on, like the patterns its files are named after. The keys are the names of
+-- the corresponding application settings, and the values are stored as text.
+-- Unlike plays, they are part of an exported library.
+CREATE TABLE settings (
+    key TEXT NOT NULL PRIMARY KEY,
+    value TEXT NOT NULL
+);
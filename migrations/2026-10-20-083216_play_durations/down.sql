@@ -0,0 +1,31 @@
+DROP TRIGGER plays_history_update;
+DROP TRIGGER plays_history_delete;
+
+CREATE TRIGGER plays_history_update AFTER UPDATE ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE plays SET ' ||
+            'play_id = ' || QUOTE(OLD.play_id) || ', ' ||
+            'track_id = ' || QUOTE(OLD.track_id) || ', ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'played_at = ' || QUOTE(OLD.played_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER plays_history_delete AFTER DELETE ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO plays (rowid, play_id, track_id, recording_id, played_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.play_id) || ', ' ||
+            QUOTE(OLD.track_id) || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.played_at) ||
+            ')');
+END;
+
+ALTER TABLE plays DROP COLUMN duration_ms;
+
+UPDATE meta SET schema_version = 7 WHERE id = 1;
@@ -0,0 +1,37 @@
+-- Every migration that changes the schema must bump schema_version.
+
+UPDATE meta SET schema_version = 8 WHERE id = 1;
+
+-- How long the played track was, so that listening time can be added up. Plays
+-- recorded before this column existed have none.
+ALTER TABLE plays ADD COLUMN duration_ms INTEGER;
+
+DROP TRIGGER plays_history_update;
+DROP TRIGGER plays_history_delete;
+
+CREATE TRIGGER plays_history_update AFTER UPDATE ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE plays SET ' ||
+            'play_id = ' || QUOTE(OLD.play_id) || ', ' ||
+            'track_id = ' || QUOTE(OLD.track_id) || ', ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'played_at = ' || QUOTE(OLD.played_at) || ', ' ||
+            'duration_ms = ' || QUOTE(OLD.duration_ms) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER plays_history_delete AFTER DELETE ON plays
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO plays (rowid, play_id, track_id, recording_id, played_at, duration_ms) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.play_id) || ', ' ||
+            QUOTE(OLD.track_id) || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.played_at) || ', ' ||
+            QUOTE(OLD.duration_ms) ||
+            ')');
+END;
@@ -0,0 +1,7 @@
+DROP TRIGGER skips_history_insert;
+DROP TRIGGER skips_history_update;
+DROP TRIGGER skips_history_delete;
+
+DROP TABLE skips;
+
+UPDATE meta SET schema_version = 8 WHERE id = 1;
@@ -0,0 +1,51 @@
+-- Every migration that changes the schema must bump schema_version.
+
+UPDATE meta SET schema_version = 9 WHERE id = 1;
+
+-- Tracks that were left before they counted as played, and how far into the
+-- track that happened. Like plays, this is listening history and stays with
+-- the library.
+CREATE TABLE skips (
+    skip_id TEXT NOT NULL PRIMARY KEY,
+    track_id TEXT REFERENCES tracks(track_id) ON DELETE SET NULL,
+    recording_id TEXT NOT NULL REFERENCES recordings(recording_id) ON DELETE CASCADE,
+    position_ms INTEGER NOT NULL,
+    skipped_at TIMESTAMP NOT NULL DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+CREATE INDEX skips_track_id ON skips (track_id);
+CREATE INDEX skips_recording_id ON skips (recording_id, skipped_at);
+
+CREATE TRIGGER skips_history_insert AFTER INSERT ON skips
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'DELETE FROM skips WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER skips_history_update AFTER UPDATE ON skips
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'UPDATE skips SET ' ||
+            'skip_id = ' || QUOTE(OLD.skip_id) || ', ' ||
+            'track_id = ' || QUOTE(OLD.track_id) || ', ' ||
+            'recording_id = ' || QUOTE(OLD.recording_id) || ', ' ||
+            'position_ms = ' || QUOTE(OLD.position_ms) || ', ' ||
+            'skipped_at = ' || QUOTE(OLD.skipped_at) ||
+            ' WHERE rowid = ' || NEW.rowid);
+END;
+
+CREATE TRIGGER skips_history_delete AFTER DELETE ON skips
+WHEN (SELECT change_id FROM history_state WHERE id = 1) IS NOT NULL
+BEGIN
+    INSERT INTO history_steps (change_id, undo_sql)
+    VALUES ((SELECT change_id FROM history_state WHERE id = 1), 'INSERT INTO skips (rowid, skip_id, track_id, recording_id, position_ms, skipped_at) VALUES (' ||
+            OLD.rowid || ', ' ||
+            QUOTE(OLD.skip_id) || ', ' ||
+            QUOTE(OLD.track_id) || ', ' ||
+            QUOTE(OLD.recording_id) || ', ' ||
+            QUOTE(OLD.position_ms) || ', ' ||
+            QUOTE(OLD.skipped_at) ||
+            ')');
+END;
@@ -0,0 +1,470 @@
+//! The subcommands, each printing what it produces to stdout.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use musicus_library::{
+    db::tables::Source,
+    library::{query::LibraryResults, LibraryQuery},
+    Library,
+};
+use serde::Serialize;
+
+use crate::{process, Kind, Targets};
+
+/// The metadata the app downloads unless a library is configured otherwise.
+///
+/// Keep this in line with `metadata_url` in the top-level `meson.build`.
+const DEFAULT_METADATA_URL: &str = "https://musicus.johrpan.de/musicus_metadata_latest.musdb";
+
+/// How a command that did not fail went.
+pub enum Outcome {
+    Done,
+    NeedsAttention,
+}
+
+impl Outcome {
+    fn from_warnings(warnings: usize) -> Self {
+        if warnings == 0 {
+            Outcome::Done
+        } else {
+            Outcome::NeedsAttention
+        }
+    }
+}
+
+/// An item in the output of `list` and `search`.
+#[derive(Serialize)]
+struct Item {
+    id: String,
+    name: String,
+}
+
+impl Item {
+    fn new(id: impl Into<String>, name: impl ToString) -> Self {
+        Self {
+            id: id.into(),
+            name: name.to_string(),
+        }
+    }
+}
+
+pub fn search(library: &Library, text: &str, json: bool) -> Result<Outcome> {
+    let results = library.search(&LibraryQuery::default(), text)?;
+
+    if json {
+        print_json(&results)?;
+    } else {
+        print_results(&results);
+    }
+
+    Ok(Outcome::Done)
+}
+
+fn print_results(results: &LibraryResults) {
+    let sections = [
+        (
+            "Composers",
+            results
+                .composers
+                .iter()
+                .map(|person| Item::new(&person.person_id, person))
+                .collect::<Vec<_>>(),
+        ),
+        (
+            "Performers",
+            results
+                .performers
+                .iter()
+                .map(|person| Item::new(&person.person_id, person))
+                .collect(),
+        ),
+        (
+            "Ensembles",
+            results
+                .ensembles
+                .iter()
+                .map(|ensemble| Item::new(&ensemble.ensemble_id, ensemble))
+                .collect(),
+        ),
+        (
+            "Instruments",
+            results
+                .instruments
+                .iter()
+                .map(|instrument| Item::new(&instrument.instrument_id, instrument))
+                .collect(),
+        ),
+        (
+            "Works",
+            results
+                .works
+                .iter()
+                .map(|work| Item::new(&work.work_id, work))
+                .collect(),
+        ),
+        (
+            "Recordings",
+            results
+                .recordings
+                .iter()
+                .map(|recording| Item::new(&recording.recording_id, recording))
+                .collect(),
+        ),
+        (
+            "Albums",
+            results
+                .albums
+                .iter()
+                .map(|album| Item::new(&album.album_id, album))
+                .collect(),
+        ),
+        (
+            "Tags",
+            results
+                .tags
+                .iter()
+                .map(|tag| Item::new(&tag.tag.tag_id, tag))
+                .collect(),
+        ),
+    ];
+
+    let mut first = true;
+    for (title, items) in sections {
+        if items.is_empty() {
+            continue;
+        }
+
+        if !first {
+            println!();
+        }
+        first = false;
+
+        println!("{title}");
+        print_items(&items);
+    }
+}
+
+pub fn list(library: &Library, kind: Kind, json: bool) -> Result<Outcome> {
+    let items = match kind {
+        Kind::Person => library
+            .list_persons()?
+            .into_iter()
+            .map(|person| Item::new(person.person_id, person.name))
+            .collect(),
+        Kind::Role => library
+            .list_roles()?
+            .into_iter()
+            .map(|role| Item::new(role.role_id, role.name))
+            .collect(),
+        Kind::Instrument => library
+            .list_instruments()?
+            .into_iter()
+            .map(|instrument| Item::new(instrument.instrument_id, instrument.name))
+            .collect(),
+        Kind::Ensemble => library
+            .list_ensembles()?
+            .into_iter()
+            .map(|item| Item::new(item.ensemble.ensemble_id, item.ensemble.name))
+            .collect(),
+        Kind::Work => library
+            .list_works()?
+            .into_iter()
+            .map(|item| {
+                let name = with_composers(item.work.name.to_string(), &item.composers);
+                Item::new(item.work.work_id, name)
+            })
+            .collect(),
+        Kind::Recording => library
+            .list_recordings()?
+            .into_iter()
+            .map(|item| {
+                let mut name = with_composers(item.work_name.to_string(), &item.composers);
+                if !item.performers.is_empty() {
+                    name = format!("{name} — {}", join(&item.performers));
+                }
+                Item::new(item.recording.recording_id, name)
+            })
+            .collect(),
+        Kind::Album => library
+            .list_albums()?
+            .into_iter()
+            .map(|album| Item::new(album.album_id, album.name))
+            .collect(),
+        Kind::Tag => library
+            .list_tags()?
+            .into_iter()
+            .map(|tag| Item::new(tag.tag_id, tag.name))
+            .collect::<Vec<_>>(),
+    };
+
+    if json {
+        print_json(&items)?;
+    } else {
+        print_items(&items);
+    }
+
+    Ok(Outcome::Done)
+}
+
+fn with_composers(name: String, composers: &[impl ToString]) -> String {
+    if composers.is_empty() {
+        name
+    } else {
+        format!("{}: {name}", join(composers))
+    }
+}
+
+fn join(names: &[impl ToString]) -> String {
+    names
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn print_items(items: &[Item]) {
+    for item in items {
+        println!("{}\t{}", item.id, item.name);
+    }
+}
+
+pub fn show(library: &Library, kind: Kind, id: &str) -> Result<Outcome> {
+    let context = || format!("Failed to load the {} {id}", kind_name(kind));
+
+    match kind {
+        Kind::Person => print_json(&library.load_person(id).with_context(context)?)?,
+        Kind::Role => print_json(&library.load_role(id).with_context(context)?)?,
+        Kind::Instrument => print_json(&library.load_instrument(id).with_context(context)?)?,
+        Kind::Ensemble => print_json(&library.load_ensemble(id).with_context(context)?)?,
+        Kind::Work => print_json(&library.load_work(id).with_context(context)?)?,
+        Kind::Recording => print_json(&library.load_recording(id).with_context(context)?)?,
+        Kind::Album => print_json(&library.load_album(id).with_context(context)?)?,
+        Kind::Tag => print_json(&library.load_tag(id).with_context(context)?)?,
+    }
+
+    Ok(Outcome::Done)
+}
+
+pub fn import_tracks(library: &Library, recording_id: &str, files: &[PathBuf]) -> Result<Outcome> {
+    // Fail before copying anything if the recording does not exist.
+    library
+        .load_recording(recording_id)
+        .with_context(|| format!("Failed to load the recording {recording_id}"))?;
+
+    let existing = library.tracks_for_recording(recording_id)?.len();
+
+    for (offset, file) in files.iter().enumerate() {
+        let index = i32::try_from(existing + offset)?;
+
+        library
+            .import_track(file, recording_id, index, Vec::new())
+            .with_context(|| format!("Failed to import {}", file.display()))?;
+
+        eprintln!("Imported {}", file.display());
+    }
+
+    Ok(Outcome::Done)
+}
+
+pub fn export(library: &Library, archive: &Path, backup: bool) -> Result<Outcome> {
+    let handle = if backup {
+        library.backup_library_to_zip(archive)?
+    } else {
+        library.export_library_to_zip(archive)?
+    };
+
+    let warnings = process::wait(handle)?;
+    Ok(Outcome::from_warnings(warnings))
+}
+
+pub fn import(library: &Library, archive: &Path) -> Result<Outcome> {
+    let warnings = process::wait(library.import_library_from_zip(archive, Source::Import)?)?;
+    Ok(Outcome::from_warnings(warnings))
+}
+
+pub fn reorganize(library: &Library, dry_run: bool) -> Result<Outcome> {
+    if !dry_run {
+        let warnings = process::wait(library.reorganize_files()?)?;
+        return Ok(Outcome::from_warnings(warnings));
+    }
+
+    let (handle, plan) = library.preview_reorganization()?;
+    let warnings = process::wait(handle)?;
+    let plan = plan.recv_blocking()?;
+
+    for rename in &plan.renames {
+        println!("{} -> {}", rename.from.display(), rename.to.display());
+    }
+
+    for file in &plan.tag_changes {
+        println!("{}", file.path.display());
+
+        for change in &file.changes {
+            println!(
+                "\t{}: {} -> {}",
+                change.field,
+                change.old.as_deref().unwrap_or("(none)"),
+                change.new.as_deref().unwrap_or("(none)")
+            );
+        }
+    }
+
+    Ok(Outcome::from_warnings(warnings))
+}
+
+pub fn merge(library: &Library, kind: Kind, from: &str, into: &str) -> Result<Outcome> {
+    match kind {
+        Kind::Person => library.merge_persons(from, into)?,
+        Kind::Role => library.merge_roles(from, into)?,
+        Kind::Instrument => library.merge_instruments(from, into)?,
+        Kind::Ensemble => library.merge_ensembles(from, into)?,
+        Kind::Work => library.merge_works(from, into)?,
+        Kind::Tag => library.merge_tags(from, into)?,
+        Kind::Recording | Kind::Album => bail!("{}s cannot be merged", kind_name(kind)),
+    }
+
+    Ok(Outcome::Done)
+}
+
+pub fn add_tag(
+    library: &Library,
+    tag_id: &str,
+    value: Option<&str>,
+    targets: &Targets,
+) -> Result<Outcome> {
+    let tag = library
+        .load_tag(tag_id)
+        .with_context(|| format!("Failed to load the tag {tag_id}"))?;
+
+    if value.is_some() && !tag.takes_value {
+        bail!("The tag {} does not take a value", tag.name);
+    }
+
+    let changed = if targets.works.is_empty() {
+        library.add_tag_to_recordings(&ids(&targets.recordings), &tag, value)?
+    } else {
+        library.add_tag_to_works(&ids(&targets.works), &tag, value)?
+    };
+
+    eprintln!("Tagged {changed} item(s)");
+    Ok(Outcome::Done)
+}
+
+pub fn remove_tag(library: &Library, tag_id: &str, targets: &Targets) -> Result<Outcome> {
+    let changed = if targets.works.is_empty() {
+        library.remove_tag_from_recordings(&ids(&targets.recordings), tag_id)?
+    } else {
+        library.remove_tag_from_works(&ids(&targets.works), tag_id)?
+    };
+
+    eprintln!("Untagged {changed} item(s)");
+    Ok(Outcome::Done)
+}
+
+fn ids(ids: &[String]) -> Vec<&str> {
+    ids.iter().map(String::as_str).collect()
+}
+
+pub fn check(library: &Library, json: bool) -> Result<Outcome> {
+    let report = library.check_integrity()?;
+
+    if json {
+        print_json(&report)?;
+    } else {
+        for problem in &report.database {
+            println!("database: {problem}");
+        }
+
+        for reference in &report.references {
+            match reference.rowid {
+                Some(rowid) => println!(
+                    "broken reference: row {rowid} of {} refers to a missing row of {}",
+                    reference.table, reference.parent
+                ),
+                None => println!(
+                    "broken reference: a row of {} refers to a missing row of {}",
+                    reference.table, reference.parent
+                ),
+            }
+        }
+
+        for track in &report.missing_tracks {
+            println!(
+                "missing track: {} ({})",
+                track.path.display(),
+                track.track_id
+            );
+        }
+
+        for path in &report.missing_cover_art {
+            println!("missing cover art: {}", path.display());
+        }
+
+        if report.is_ok() {
+            eprintln!("No problems found");
+        }
+    }
+
+    Ok(if report.is_ok() {
+        Outcome::Done
+    } else {
+        Outcome::NeedsAttention
+    })
+}
+
+pub fn update_metadata(
+    library: &Library,
+    url: Option<&str>,
+    file: Option<&Path>,
+) -> Result<Outcome> {
+    let handle = match file {
+        Some(file) => library.import_metadata_from_path(file)?,
+        None => {
+            let url = match url {
+                Some(url) => url.to_owned(),
+                None => configured_metadata_url(library)?,
+            };
+
+            eprintln!("Downloading metadata from {url}");
+            library.import_metadata_from_url(&url)?
+        }
+    };
+
+    let warnings = process::wait(handle)?;
+    Ok(Outcome::from_warnings(warnings))
+}
+
+/// The metadata URL the app would use for `library`.
+fn configured_metadata_url(library: &Library) -> Result<String> {
+    let use_custom = library.setting("use-custom-metadata-url")?.as_deref() == Some("true");
+
+    if use_custom {
+        if let Some(url) = library
+            .setting("custom-metadata-url")?
+            .filter(|url| !url.is_empty())
+        {
+            return Ok(url);
+        }
+    }
+
+    Ok(DEFAULT_METADATA_URL.to_owned())
+}
+
+fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Person => "person",
+        Kind::Role => "role",
+        Kind::Instrument => "instrument",
+        Kind::Ensemble => "ensemble",
+        Kind::Work => "work",
+        Kind::Recording => "recording",
+        Kind::Album => "album",
+        Kind::Tag => "tag",
+    }
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,304 @@
+//! Command-line access to a Musicus library, for scripts and servers.
+//!
+//! Everything goes through `musicus-library`, the same code the app uses, so a
+//! library changed from here is indistinguishable from one edited in the app.
+//! What a command produces goes to stdout; progress, messages and warnings go
+//! to stderr.
+
+mod commands;
+mod process;
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use musicus_library::Library;
+
+use crate::commands::Outcome;
+
+/// The exit code of a command that failed.
+const EXIT_FAILURE: u8 = 1;
+
+/// The exit code of a command that finished, but found something that needs
+/// attention: an operation warned about individual items, or the integrity
+/// check found a problem.
+///
+/// Invalid arguments exit with 2, like every program using clap.
+const EXIT_ATTENTION: u8 = 3;
+
+/// The folder of the metadata cache of the app, so that both share downloads.
+const CACHE_DIR_NAME: &str = "de.johrpan.Musicus";
+
+/// Work with a Musicus library from the command line.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// The library folder.
+    #[arg(short, long, env = "MUSICUS_LIBRARY")]
+    library: PathBuf,
+
+    /// Where downloaded metadata is cached [default: the cache of the app]
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Search the library the way the search field of the app does.
+    Search {
+        text: String,
+
+        /// Print the results as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List every item of a kind with its ID.
+    List {
+        kind: Kind,
+
+        /// Print the items as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print an item with everything it refers to as JSON.
+    Show { kind: Kind, id: String },
+
+    /// Import audio files as the next tracks of a recording.
+    ImportTracks {
+        recording_id: String,
+
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+
+    /// Export the library to a .muslib archive.
+    Export {
+        archive: PathBuf,
+
+        /// Back up the library for yourself, including the listening history
+        /// and the private tags.
+        #[arg(long)]
+        backup: bool,
+    },
+
+    /// Import a .muslib archive into the library.
+    Import { archive: PathBuf },
+
+    /// Rename and retag the track files according to the patterns.
+    Reorganize {
+        /// Only print what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Merge an item into another one of the same kind.
+    Merge {
+        kind: Kind,
+        from: String,
+        into: String,
+    },
+
+    /// Add a tag to works or recordings, or remove it from them.
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+
+    /// Check the database and the files of the library.
+    Check {
+        /// Print the report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Update the metadata from the metadata server or from a file.
+    UpdateMetadata {
+        /// Download the metadata from this URL instead of the one configured
+        /// for the library.
+        #[arg(long, conflicts_with = "file")]
+        url: Option<String>,
+
+        /// Read the metadata from a .musdb file or a folder containing one.
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TagAction {
+    Add {
+        tag_id: String,
+
+        /// The value, for tags that take one.
+        #[arg(long)]
+        value: Option<String>,
+
+        #[command(flatten)]
+        targets: Targets,
+    },
+
+    Remove {
+        tag_id: String,
+
+        #[command(flatten)]
+        targets: Targets,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct Targets {
+    /// The IDs of the works.
+    #[arg(long, num_args = 1..)]
+    works: Vec<String>,
+
+    /// The IDs of the recordings.
+    #[arg(long, num_args = 1..)]
+    recordings: Vec<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    #[value(alias = "persons")]
+    Person,
+    #[value(alias = "roles")]
+    Role,
+    #[value(alias = "instruments")]
+    Instrument,
+    #[value(alias = "ensembles")]
+    Ensemble,
+    #[value(alias = "works")]
+    Work,
+    #[value(alias = "recordings")]
+    Recording,
+    #[value(alias = "albums")]
+    Album,
+    #[value(alias = "tags")]
+    Tag,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(Outcome::Done) => ExitCode::SUCCESS,
+        Ok(Outcome::NeedsAttention) => ExitCode::from(EXIT_ATTENTION),
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<Outcome> {
+    // Opening a folder creates a library in it, which is never what a typo in
+    // the path is meant to do.
+    if !args.library.join("musicus.musdb").is_file() {
+        bail!("There is no music library in {}", args.library.display());
+    }
+
+    let cache_dir = match args.cache_dir {
+        Some(cache_dir) => cache_dir,
+        None => default_cache_dir()?,
+    };
+
+    let library = Library::new(&args.library, cache_dir)?;
+
+    match args.command {
+        Command::Search { text, json } => commands::search(&library, &text, json),
+        Command::List { kind, json } => commands::list(&library, kind, json),
+        Command::Show { kind, id } => commands::show(&library, kind, &id),
+        Command::ImportTracks {
+            recording_id,
+            files,
+        } => commands::import_tracks(&library, &recording_id, &files),
+        Command::Export { archive, backup } => commands::export(&library, &archive, backup),
+        Command::Import { archive } => commands::import(&library, &archive),
+        Command::Reorganize { dry_run } => commands::reorganize(&library, dry_run),
+        Command::Merge { kind, from, into } => commands::merge(&library, kind, &from, &into),
+        Command::Tag { action } => match action {
+            TagAction::Add {
+                tag_id,
+                value,
+                targets,
+            } => commands::add_tag(&library, &tag_id, value.as_deref(), &targets),
+            TagAction::Remove { tag_id, targets } => {
+                commands::remove_tag(&library, &tag_id, &targets)
+            }
+        },
+        Command::Check { json } => commands::check(&library, json),
+        Command::UpdateMetadata { url, file } => {
+            commands::update_metadata(&library, url.as_deref(), file.as_deref())
+        }
+    }
+}
+
+/// The metadata cache of the app, following the XDG base directories.
+fn default_cache_dir() -> Result<PathBuf> {
+    let cache_home = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".cache"),
+            None => bail!("Neither XDG_CACHE_HOME nor HOME is set; pass --cache-dir"),
+        },
+    };
+
+    Ok(cache_home.join(CACHE_DIR_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn the_arguments_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn tags_go_to_either_works_or_recordings() {
+        let args = Args::try_parse_from([
+            "musicus-cli",
+            "--library",
+            "lib",
+            "tag",
+            "add",
+            "tag-id",
+            "--works",
+            "a",
+            "b",
+        ])
+        .unwrap();
+
+        match args.command {
+            Command::Tag {
+                action: TagAction::Add { targets, .. },
+            } => {
+                assert_eq!(targets.works, ["a", "b"]);
+                assert!(targets.recordings.is_empty());
+            }
+            command => panic!("unexpected command {command:?}"),
+        }
+
+        assert!(Args::try_parse_from([
+            "musicus-cli",
+            "--library",
+            "lib",
+            "tag",
+            "remove",
+            "tag-id",
+            "--works",
+            "a",
+            "--recordings",
+            "b",
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Running the background processes of the library in the foreground.
+
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::{bail, Result};
+use musicus_library::library::process::{ProcessHandle, ProcessMsg};
+
+/// Wait for the process behind `handle` to finish, reporting its progress on
+/// stderr. Returns the number of warnings it reported.
+///
+/// Progress is rewritten in place on a terminal and left out otherwise, so
+/// that a log of a scripted run only contains the messages and warnings.
+pub fn wait(handle: ProcessHandle) -> Result<usize> {
+    let interactive = io::stderr().is_terminal();
+    let mut warnings = 0;
+    let mut progress_shown = false;
+
+    while let Ok(msg) = handle.receiver.recv_blocking() {
+        match msg {
+            ProcessMsg::Message(message) => {
+                end_progress(&mut progress_shown);
+                eprintln!("{message}");
+            }
+            ProcessMsg::Warning(warning) => {
+                end_progress(&mut progress_shown);
+                eprintln!("warning: {warning}");
+                warnings += 1;
+            }
+            ProcessMsg::Progress(fraction) => {
+                if interactive {
+                    eprint!("\r{:>3.0} %", fraction * 100.0);
+                    let _ = io::stderr().flush();
+                    progress_shown = true;
+                }
+            }
+            ProcessMsg::Result(result) => {
+                end_progress(&mut progress_shown);
+                result?;
+                return Ok(warnings);
+            }
+            ProcessMsg::Cancelled => {
+                end_progress(&mut progress_shown);
+                bail!("The process was cancelled");
+            }
+        }
+    }
+
+    bail!("The process ended without a result")
+}
+
+/// End the progress line, if there is one, before anything else is printed.
+fn end_progress(progress_shown: &mut bool) {
+    if std::mem::take(progress_shown) {
+        eprintln!();
+    }
+}
@@ -0,0 +1,124 @@
+//! The commands run the way a script runs them, against a generated library.
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+use musicus_testgen::generate::{self, Counts};
+use serde_json::Value;
+use tempfile::TempDir;
+
+const WORKS: usize = 6;
+
+/// A small generated library.
+fn library() -> TempDir {
+    let folder = TempDir::new().unwrap();
+
+    let counts = Counts {
+        instruments: 4,
+        roles: 2,
+        persons: 8,
+        ensembles: 2,
+        works: WORKS,
+        recordings: 8,
+        tracks_per_recording: 1,
+    };
+
+    generate::generate(folder.path(), 3, counts, |_, _| {}).unwrap();
+
+    folder
+}
+
+/// Run the CLI on the library at `library` with `args`.
+fn run(library: &Path, args: &[&str]) -> Output {
+    let cache_dir = TempDir::new().unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_musicus-cli"))
+        .arg("--library")
+        .arg(library)
+        .arg("--cache-dir")
+        .arg(cache_dir.path())
+        .args(args)
+        .env_remove("MUSICUS_LIBRARY")
+        .output()
+        .unwrap()
+}
+
+/// Run the CLI, expecting it to succeed with JSON on stdout.
+fn run_json(library: &Path, args: &[&str]) -> Value {
+    let output = run(library, args);
+
+    assert!(
+        output.status.success(),
+        "{args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn every_item_of_a_kind_is_listed() {
+    let library = library();
+
+    let works = run_json(library.path(), &["list", "works", "--json"]);
+
+    assert_eq!(works.as_array().unwrap().len(), WORKS);
+}
+
+#[test]
+fn a_listed_item_can_be_shown() {
+    let library = library();
+
+    let recordings = run_json(library.path(), &["list", "recordings", "--json"]);
+    let id = recordings[0]["id"].as_str().unwrap();
+
+    let recording = run_json(library.path(), &["show", "recording", id]);
+
+    assert_eq!(recording["recording_id"], id);
+}
+
+#[test]
+fn a_listed_person_can_be_found() {
+    let library = library();
+
+    let persons = run_json(library.path(), &["list", "persons", "--json"]);
+    let name = persons[0]["name"].as_str().unwrap();
+
+    let output = run(library.path(), &["search", name]);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(name));
+}
+
+#[test]
+fn a_generated_library_passes_the_check() {
+    let library = library();
+
+    let output = run(library.path(), &["check"]);
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+/// Opening a folder creates a library in it, so a folder without one must be
+/// refused rather than opened.
+#[test]
+fn a_folder_without_a_library_is_refused() {
+    let folder = TempDir::new().unwrap();
+
+    for library in [folder.path().to_owned(), folder.path().join("missing")] {
+        let output = run(&library, &["list", "works"]);
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("no music library"));
+    }
+
+    assert!(fs::read_dir(folder.path()).unwrap().next().is_none());
+}
@@ -5,12 +5,13 @@ use std::{collections::HashSet, fmt::Display, path::PathBuf};
 
 use anyhow::Result;
 use diesel::prelude::*;
+use serde::Serialize;
 // Re-exports for tables that don't need additional information.
 pub use tables::{Instrument, Person, Role, Tag};
 
 use super::{schema::*, tables, TranslatedString};
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Work {
     pub work_id: String,
     pub name: TranslatedString,
@@ -26,19 +27,19 @@ pub struct Work {
 ///
 /// `value` is set exactly when the tag's `takes_value` is true; a plain label
 /// carries no value.
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct TagValue {
     pub tag: Tag,
     pub value: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Composer {
     pub person: Person,
     pub role: Option<Role>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Ensemble {
     pub ensemble_id: String,
     pub name: TranslatedString,
@@ -46,7 +47,7 @@ pub struct Ensemble {
     pub enable_updates: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Recording {
     pub recording_id: String,
     pub work: Work,
@@ -54,34 +55,38 @@ pub struct Recording {
     pub ensembles: Vec<EnsemblePerformer>,
     pub tags: Vec<TagValue>,
     pub comment: Option<String>,
+    /// The cover image, relative to the library folder.
+    pub cover_art: Option<String>,
     pub enable_updates: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Performer {
     pub person: Person,
     pub role: Option<Role>,
     pub instrument: Option<Instrument>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct EnsemblePerformer {
     pub ensemble: Ensemble,
     pub role: Option<Role>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Track {
     pub track_id: String,
     pub path: PathBuf,
     pub works: Vec<Work>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Album {
     pub album_id: String,
     pub name: TranslatedString,
     pub recordings: Vec<Recording>,
+    /// The cover image, relative to the library folder.
+    pub cover_art: Option<String>,
     pub enable_updates: bool,
 }
 
@@ -391,6 +396,7 @@ impl Recording {
             ensembles,
             tags,
             comment: data.comment,
+            cover_art: data.cover_art,
             enable_updates: data.enable_updates,
         })
     }
@@ -568,10 +574,21 @@ impl Album {
             album_id: data.album_id,
             name: data.name,
             recordings,
+            cover_art: data.cover_art,
             enable_updates: data.enable_updates,
         })
     }
 
+    /// The cover image of the album, or else that of the first of its
+    /// recordings that has one, relative to the library folder.
+    pub fn cover_art(&self) -> Option<&str> {
+        self.cover_art.as_deref().or_else(|| {
+            self.recordings
+                .iter()
+                .find_map(|recording| recording.cover_art.as_deref())
+        })
+    }
+
     pub fn performers_string(&self) -> String {
         let mut performers = HashSet::new();
         let mut ensembles = HashSet::new();
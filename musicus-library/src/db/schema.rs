@@ -17,6 +17,17 @@ diesel::table! {
         created_at -> Timestamp,
         edited_at -> Timestamp,
         last_used_at -> Timestamp,
+        cover_art -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    change_log (entity_kind, entity_id) {
+        entity_kind -> Text,
+        entity_id -> Text,
+        change -> Text,
+        merged_into -> Nullable<Text>,
+        changed_at -> Timestamp,
     }
 }
 
@@ -42,6 +53,33 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    history (change_id) {
+        change_id -> BigInt,
+        action -> Text,
+        entity_kind -> Text,
+        entity_id -> Text,
+        entity_name -> Text,
+        target_name -> Nullable<Text>,
+        changed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    history_state (id) {
+        id -> Integer,
+        change_id -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    history_steps (step_id) {
+        step_id -> BigInt,
+        change_id -> BigInt,
+        undo_sql -> Text,
+    }
+}
+
 diesel::table! {
     instruments (instrument_id) {
         instrument_id -> Text,
@@ -54,12 +92,36 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    merge_references (reference_id) {
+        reference_id -> BigInt,
+        merge_id -> BigInt,
+        reference_kind -> Text,
+        owner_id -> Text,
+        sequence_number -> Nullable<Integer>,
+        value -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    merges (merge_id) {
+        merge_id -> BigInt,
+        entity_kind -> Text,
+        from_id -> Text,
+        from_name -> Text,
+        into_id -> Text,
+        snapshot -> Text,
+        merged_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     meta (id) {
         id -> Integer,
         schema_version -> Integer,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        library_id -> Text,
     }
 }
 
@@ -81,6 +143,7 @@ diesel::table! {
         track_id -> Nullable<Text>,
         recording_id -> Text,
         played_at -> Timestamp,
+        duration_ms -> Nullable<BigInt>,
     }
 }
 
@@ -122,6 +185,7 @@ diesel::table! {
         edited_at -> Timestamp,
         last_used_at -> Timestamp,
         comment -> Nullable<Text>,
+        cover_art -> Nullable<Text>,
     }
 }
 
@@ -137,6 +201,45 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    settings (key) {
+        key -> Text,
+        value -> Text,
+    }
+}
+
+diesel::table! {
+    skips (skip_id) {
+        skip_id -> Text,
+        track_id -> Nullable<Text>,
+        recording_id -> Text,
+        position_ms -> BigInt,
+        skipped_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    stale_tracks (track_id) {
+        track_id -> Text,
+        marked_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sync_conflicts (library_id, entity_kind, entity_id) {
+        library_id -> Text,
+        entity_kind -> Text,
+        entity_id -> Text,
+    }
+}
+
+diesel::table! {
+    sync_peers (library_id) {
+        library_id -> Text,
+        last_synced_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     tags (tag_id) {
         tag_id -> Text,
@@ -218,6 +321,8 @@ diesel::joinable!(ensemble_persons -> ensembles (ensemble_id));
 diesel::joinable!(ensemble_persons -> instruments (instrument_id));
 diesel::joinable!(ensemble_persons -> persons (person_id));
 diesel::joinable!(ensemble_persons -> roles (role_id));
+diesel::joinable!(history_steps -> history (change_id));
+diesel::joinable!(merge_references -> merges (merge_id));
 diesel::joinable!(plays -> recordings (recording_id));
 diesel::joinable!(plays -> tracks (track_id));
 diesel::joinable!(recording_ensembles -> ensembles (ensemble_id));
@@ -232,6 +337,10 @@ diesel::joinable!(recording_tags -> tags (tag_id));
 diesel::joinable!(recordings -> works (work_id));
 diesel::joinable!(track_works -> tracks (track_id));
 diesel::joinable!(track_works -> works (work_id));
+diesel::joinable!(skips -> recordings (recording_id));
+diesel::joinable!(skips -> tracks (track_id));
+diesel::joinable!(stale_tracks -> tracks (track_id));
+diesel::joinable!(sync_conflicts -> sync_peers (library_id));
 diesel::joinable!(tracks -> recordings (recording_id));
 diesel::joinable!(work_instruments -> instruments (instrument_id));
 diesel::joinable!(work_instruments -> works (work_id));
@@ -244,9 +353,15 @@ diesel::joinable!(work_tags -> works (work_id));
 diesel::allow_tables_to_appear_in_same_query!(
     album_recordings,
     albums,
+    change_log,
     ensemble_persons,
     ensembles,
+    history,
+    history_state,
+    history_steps,
     instruments,
+    merge_references,
+    merges,
     meta,
     persons,
     plays,
@@ -255,6 +370,11 @@ diesel::allow_tables_to_appear_in_same_query!(
     recording_tags,
     recordings,
     roles,
+    settings,
+    skips,
+    stale_tracks,
+    sync_conflicts,
+    sync_peers,
     tags,
     track_works,
     tracks,
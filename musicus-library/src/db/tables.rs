@@ -15,8 +15,9 @@ use diesel::{
     sql_types::Text,
     sqlite::Sqlite,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct Person {
     pub person_id: String,
@@ -28,7 +29,7 @@ pub struct Person {
     pub last_used_at: NaiveDateTime,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct Role {
     pub role_id: String,
@@ -40,7 +41,7 @@ pub struct Role {
     pub last_used_at: NaiveDateTime,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct Instrument {
     pub instrument_id: String,
@@ -62,7 +63,7 @@ pub struct Instrument {
 /// A `private` tag is personal to this library: it works like any other tag
 /// locally, but neither it nor the assignments referring to it leave the
 /// library in an export.
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct Tag {
     pub tag_id: String,
@@ -76,7 +77,7 @@ pub struct Tag {
     pub private: bool,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct WorkTag {
     pub work_id: String,
@@ -85,7 +86,7 @@ pub struct WorkTag {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct RecordingTag {
     pub recording_id: String,
@@ -94,8 +95,9 @@ pub struct RecordingTag {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
+#[diesel(treat_none_as_null = true)]
 pub struct Work {
     pub work_id: String,
     pub parent_work_id: Option<String>,
@@ -109,7 +111,7 @@ pub struct Work {
     pub relates_to: Option<String>,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct WorkPerson {
     pub work_id: String,
@@ -118,7 +120,7 @@ pub struct WorkPerson {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct WorkInstrument {
     pub work_id: String,
@@ -126,7 +128,7 @@ pub struct WorkInstrument {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct Ensemble {
     pub ensemble_id: String,
@@ -138,7 +140,7 @@ pub struct Ensemble {
     pub last_used_at: NaiveDateTime,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct EnsemblePerson {
     pub ensemble_id: String,
@@ -148,8 +150,9 @@ pub struct EnsemblePerson {
     pub role_id: Option<String>,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
+#[diesel(treat_none_as_null = true)]
 pub struct Recording {
     pub recording_id: String,
     pub work_id: String,
@@ -159,9 +162,11 @@ pub struct Recording {
     pub edited_at: NaiveDateTime,
     pub last_used_at: NaiveDateTime,
     pub comment: Option<String>,
+    /// The cover image, relative to the library folder.
+    pub cover_art: Option<String>,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct RecordingPerson {
     pub recording_id: String,
@@ -171,7 +176,7 @@ pub struct RecordingPerson {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct RecordingEnsemble {
     pub recording_id: String,
@@ -180,7 +185,7 @@ pub struct RecordingEnsemble {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct Track {
     pub track_id: String,
@@ -199,6 +204,17 @@ pub struct Play {
     pub track_id: Option<String>,
     pub recording_id: String,
     pub played_at: NaiveDateTime,
+    pub duration_ms: Option<i64>,
+}
+
+#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[diesel(check_for_backend(Sqlite))]
+pub struct Skip {
+    pub skip_id: String,
+    pub track_id: Option<String>,
+    pub recording_id: String,
+    pub position_ms: i64,
+    pub skipped_at: NaiveDateTime,
 }
 
 #[derive(Insertable, Queryable, Selectable, Clone, Debug)]
@@ -209,8 +225,9 @@ pub struct TrackWork {
     pub sequence_number: i32,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, AsChangeset, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
+#[diesel(treat_none_as_null = true)]
 pub struct Album {
     pub album_id: String,
     pub name: TranslatedString,
@@ -219,9 +236,11 @@ pub struct Album {
     pub created_at: NaiveDateTime,
     pub edited_at: NaiveDateTime,
     pub last_used_at: NaiveDateTime,
+    /// The cover image, relative to the library folder.
+    pub cover_art: Option<String>,
 }
 
-#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone, Debug)]
 #[diesel(check_for_backend(Sqlite))]
 pub struct AlbumRecording {
     pub album_id: String,
@@ -229,6 +248,25 @@ pub struct AlbumRecording {
     pub sequence_number: i32,
 }
 
+/// What happened to an entity that no longer exists.
+#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[diesel(check_for_backend(Sqlite))]
+#[diesel(table_name = change_log)]
+pub struct ChangeLogEntry {
+    pub entity_kind: String,
+    pub entity_id: String,
+    pub change: String,
+    pub merged_into: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Queryable, Selectable, Clone, Debug)]
+#[diesel(check_for_backend(Sqlite))]
+pub struct SyncPeer {
+    pub library_id: String,
+    pub last_synced_at: NaiveDateTime,
+}
+
 #[derive(AsExpression, FromSqlRow, Clone, Debug)]
 #[diesel(sql_type = Text)]
 pub struct PathBufWrapper(pub PathBuf);
@@ -286,8 +324,9 @@ impl AsRef<Path> for PathBufWrapper {
 }
 
 /// Where an item came from.
-#[derive(AsExpression, FromSqlRow, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
 #[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
 pub enum Source {
     Metadata,
     User,
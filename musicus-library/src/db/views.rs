@@ -110,9 +110,15 @@ macro_rules! allow_view_everywhere {
             $view;
             album_recordings,
             albums,
+            change_log,
             ensemble_persons,
             ensembles,
+            history,
+            history_state,
+            history_steps,
             instruments,
+            merge_references,
+            merges,
             meta,
             persons,
             plays,
@@ -121,6 +127,8 @@ macro_rules! allow_view_everywhere {
             recording_tags,
             recordings,
             roles,
+            sync_conflicts,
+            sync_peers,
             tags,
             track_works,
             tracks,
@@ -34,6 +34,26 @@ impl Display for EntityKind {
     }
 }
 
+impl std::str::FromStr for EntityKind {
+    type Err = anyhow::Error;
+
+    /// Parse the name an entity kind is displayed and stored with.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "person" => EntityKind::Person,
+            "role" => EntityKind::Role,
+            "instrument" => EntityKind::Instrument,
+            "tag" => EntityKind::Tag,
+            "work" => EntityKind::Work,
+            "ensemble" => EntityKind::Ensemble,
+            "recording" => EntityKind::Recording,
+            "album" => EntityKind::Album,
+            "track" => EntityKind::Track,
+            _ => anyhow::bail!("Unknown entity kind {s}"),
+        })
+    }
+}
+
 /// Whether a Diesel error is SQLite refusing to break a foreign key.
 ///
 /// Diesel's SQLite backend does not classify these as
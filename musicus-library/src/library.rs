@@ -1,5 +1,5 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     path::{Path, PathBuf},
     sync::{Arc, Mutex, MutexGuard},
     time::SystemTime,
@@ -9,16 +9,28 @@ use anyhow::{anyhow, Context, Result};
 use diesel::{prelude::*, SqliteConnection};
 
 use crate::db::{self, schema::*, tables};
+pub use duplicates::{Duplicate, DuplicateEntity};
 pub use edit::TrackUpdate;
 pub use list::{EnsembleListItem, RecordingListItem, WorkListItem};
 pub use merge::EntityUsage;
-pub use naming::pattern::Patterns;
+pub use naming::{
+    audio_tags::TagChange,
+    pattern::{Patterns, PreservedTags},
+};
 pub use program::GenerateRecordingParams;
 pub use query::{Facet, LibraryQuery};
+pub use reorganize::{PlannedRename, PlannedTagChanges, ReorganizationPlan, ReorganizationScope};
 pub use search::SearchItem;
+pub mod cover_art;
+pub mod duplicates;
 pub mod edit;
 pub mod exchange;
+pub mod history;
+pub mod integrity;
 pub mod list;
+pub mod listening;
+pub mod listens;
+pub mod maintenance;
 pub mod merge;
 pub mod metadata;
 pub mod naming;
@@ -27,6 +39,9 @@ pub mod program;
 pub mod query;
 pub mod reorganize;
 pub mod search;
+pub mod settings;
+pub mod sync;
+pub mod unmerge;
 
 /// An open metadata database remembered together with the modification time of
 /// the file it came from.
@@ -42,6 +57,8 @@ pub struct Library {
     metadata_cache_dir: PathBuf,
     changed_senders: RefCell<Vec<async_channel::Sender<()>>>,
     patterns: RefCell<Patterns>,
+    /// Whether edits mark the tracks they affect as stale, see [`maintenance`].
+    file_maintenance: Cell<bool>,
 }
 
 impl Library {
@@ -71,6 +88,7 @@ impl Library {
             metadata_cache_dir: metadata_cache_dir.into(),
             changed_senders: RefCell::new(Vec::new()),
             patterns: RefCell::new(Patterns::default()),
+            file_maintenance: Cell::new(false),
         })
     }
 
@@ -79,22 +97,31 @@ impl Library {
     }
 
     /// The patterns used to name and tag the files of newly imported tracks.
+    ///
+    /// These are the ones stored in the library's [`settings`], where it
+    /// stores them, and the ones set with [`Library::set_patterns`] otherwise.
     pub fn patterns(&self) -> Patterns {
-        self.patterns.borrow().clone()
+        let fallback = self.patterns.borrow().clone();
+
+        settings::patterns(&mut self.conn(), fallback.clone()).unwrap_or_else(|err| {
+            log::warn!("Failed to read the patterns stored in the library: {err:?}");
+            fallback
+        })
     }
 
     /// Set the patterns used to name and tag the files of newly imported
-    /// tracks.
+    /// tracks, unless the library stores its own.
     pub fn set_patterns(&self, patterns: &Patterns) {
         self.patterns.replace(patterns.clone());
     }
 
     /// The pattern used to name the files of newly imported tracks.
     pub fn filename_pattern(&self) -> String {
-        self.patterns.borrow().filename.clone()
+        self.patterns().filename
     }
 
-    /// Set the pattern used to name the files of newly imported tracks.
+    /// Set the pattern used to name the files of newly imported tracks, unless
+    /// the library stores its own.
     pub fn set_filename_pattern(&self, pattern: &str) {
         self.patterns.borrow_mut().filename = pattern.to_owned();
     }
@@ -0,0 +1,664 @@
+//! Cover images of recordings and albums.
+//!
+//! Like track files, cover images are files in the library folder that the
+//! database refers to by their path relative to it. They live in a directory of
+//! their own and are named by identifiers only, because unlike track files
+//! nobody browses them by name.
+//!
+//! A recording without a cover of its own takes the one of the first album it
+//! appears on, and an album without one takes the one of its first recording
+//! that has one. Importing a track gives its recording the cover embedded in the
+//! file, if the recording has none yet.
+//!
+//! Replacing a cover does not remove the old file right away, because undoing
+//! the change would bring it back. Files that neither the library nor its undo
+//! history refer to anymore are removed by a reorganization. Only files named
+//! like a stored cover are considered, and track files are kept out of the
+//! directory, so that nothing else that ends up there is ever removed.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, SqliteConnection};
+use lofty::{
+    file::TaggedFileExt,
+    picture::{MimeType, Picture, PictureType},
+};
+
+use super::{
+    exchange,
+    history::{self, ChangeAction},
+    naming::filenames,
+    Library, ReorganizationScope,
+};
+use crate::{
+    db::{
+        self,
+        models::{Album, Recording},
+        schema::*,
+    },
+    error::EntityKind,
+};
+
+/// The directory within the library folder that holds the cover images.
+pub const COVER_ART_DIRECTORY: &str = "covers";
+
+/// The image formats covers are stored in, as named by [`MimeType::ext`].
+const MIME_TYPES: &[MimeType] = &[
+    MimeType::Jpeg,
+    MimeType::Png,
+    MimeType::Tiff,
+    MimeType::Bmp,
+    MimeType::Gif,
+];
+
+impl Library {
+    /// The cover image to show for `recording`, if there is one.
+    ///
+    /// This is the recording's own cover or else the one of the first album it
+    /// appears on. A cover whose file is missing counts as none.
+    pub fn recording_cover_art(&self, recording: &Recording) -> Option<PathBuf> {
+        let cover_art = match &recording.cover_art {
+            Some(cover_art) => Some(cover_art.to_owned()),
+            None => match album_cover_art(&recording.recording_id, &mut self.conn()) {
+                Ok(cover_art) => cover_art,
+                Err(err) => {
+                    log::warn!("Failed to load the cover of a recording's album: {err:?}");
+                    None
+                }
+            },
+        };
+
+        self.existing_cover_art(cover_art.as_deref())
+    }
+
+    /// The cover image to show for `album`, if there is one. See
+    /// [`Album::cover_art`].
+    pub fn album_cover_art(&self, album: &Album) -> Option<PathBuf> {
+        self.existing_cover_art(album.cover_art())
+    }
+
+    /// Give `recording_id` a copy of the image at `image` as its cover, or
+    /// remove its cover if `image` is `None`.
+    pub fn set_recording_cover_art(&self, recording_id: &str, image: Option<&Path>) -> Result<()> {
+        let cover_art = self.store_cover_art(image)?;
+        let connection = &mut *self.conn();
+
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Recording,
+            recording_id,
+            None,
+            |connection| -> Result<()> {
+                diesel::update(recordings::table)
+                    .filter(recordings::recording_id.eq(recording_id))
+                    .set((
+                        recordings::cover_art.eq(cover_art),
+                        recordings::edited_at.eq(db::now()),
+                    ))
+                    .execute(connection)?;
+
+                Ok(())
+            },
+        )?;
+
+        // The cover is embedded into the track files.
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                recording_ids: vec![recording_id.to_string()],
+                ..Default::default()
+            },
+        );
+
+        self.changed();
+
+        Ok(())
+    }
+
+    /// Give `album_id` a copy of the image at `image` as its cover, or remove
+    /// its cover if `image` is `None`.
+    pub fn set_album_cover_art(&self, album_id: &str, image: Option<&Path>) -> Result<()> {
+        let cover_art = self.store_cover_art(image)?;
+        let connection = &mut *self.conn();
+
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Album,
+            album_id,
+            None,
+            |connection| -> Result<()> {
+                diesel::update(albums::table)
+                    .filter(albums::album_id.eq(album_id))
+                    .set((
+                        albums::cover_art.eq(cover_art),
+                        albums::edited_at.eq(db::now()),
+                    ))
+                    .execute(connection)?;
+
+                Ok(())
+            },
+        )?;
+
+        // The cover is embedded into the track files.
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                album_ids: vec![album_id.to_string()],
+                ..Default::default()
+            },
+        );
+
+        self.changed();
+
+        Ok(())
+    }
+
+    fn store_cover_art(&self, image: Option<&Path>) -> Result<Option<String>> {
+        let Some(image) = image else {
+            return Ok(None);
+        };
+
+        let picture = Picture::from_reader(&mut fs::File::open(image)?)
+            .map_err(|err| anyhow!("The file is not a supported image: {err}"))?;
+
+        Ok(Some(store(Path::new(self.folder()), &picture)?))
+    }
+
+    fn existing_cover_art(&self, cover_art: Option<&str>) -> Option<PathBuf> {
+        cover_art
+            .map(|cover_art| Path::new(self.folder()).join(cover_art))
+            .filter(|path| path.is_file())
+    }
+}
+
+/// The cover image embedded in the audio file at `path`, if it has one.
+///
+/// The front cover is preferred, but any picture is better than none.
+pub(crate) fn read_embedded(path: &Path) -> Result<Option<Picture>> {
+    let file = lofty::read_from_path(path)?;
+    let pictures = file
+        .tags()
+        .iter()
+        .flat_map(|tag| tag.pictures())
+        .collect::<Vec<&Picture>>();
+
+    Ok(pictures
+        .iter()
+        .find(|picture| picture.pic_type() == PictureType::CoverFront)
+        .or(pictures.first())
+        .map(|picture| (*picture).clone()))
+}
+
+/// Copy `picture` into the library `folder` and return its path relative to it.
+pub(crate) fn store(folder: &Path, picture: &Picture) -> Result<String> {
+    let extension = picture
+        .mime_type()
+        .and_then(|mime_type| mime_type.ext())
+        .ok_or_else(|| anyhow!("The image is in an unsupported format"))?;
+
+    let cover_art = format!("{COVER_ART_DIRECTORY}/{}.{extension}", db::generate_id());
+    let path = folder.join(&cover_art);
+    let mut part_path = path.clone();
+    part_path.as_mut_os_string().push(".part");
+
+    fs::create_dir_all(folder.join(COVER_ART_DIRECTORY))?;
+
+    let result = exchange::copy_to_file(Cursor::new(picture.data()), &part_path)
+        .and_then(|()| Ok(fs::rename(&part_path, &path)?));
+
+    if let Err(err) = result {
+        let _ = fs::remove_file(&part_path);
+        return Err(err);
+    }
+
+    Ok(cover_art)
+}
+
+/// Read the cover image at `cover_art`, relative to `folder`, for embedding it
+/// into an audio file.
+pub(crate) fn load(folder: &Path, cover_art: &str) -> Result<Picture> {
+    let mut picture = Picture::from_reader(&mut fs::File::open(folder.join(cover_art))?)?;
+    picture.set_pic_type(PictureType::CoverFront);
+    Ok(picture)
+}
+
+/// The cover of the first album `recording_id` appears on that has one.
+pub(crate) fn album_cover_art(
+    recording_id: &str,
+    connection: &mut SqliteConnection,
+) -> Result<Option<String>> {
+    Ok(album_recordings::table
+        .inner_join(albums::table)
+        .filter(album_recordings::recording_id.eq(recording_id))
+        .filter(albums::cover_art.is_not_null())
+        .order((albums::album_id, album_recordings::sequence_number))
+        .select(albums::cover_art)
+        .first::<Option<String>>(connection)
+        .optional()?
+        .flatten())
+}
+
+/// The cover to embed into the files of `recording_id`: its own, or else the one
+/// of the first album it appears on.
+pub(crate) fn for_recording(
+    recording_id: &str,
+    connection: &mut SqliteConnection,
+) -> Result<Option<String>> {
+    let own = recordings::table
+        .filter(recordings::recording_id.eq(recording_id))
+        .select(recordings::cover_art)
+        .first::<Option<String>>(connection)?;
+
+    match own {
+        Some(cover_art) => Ok(Some(cover_art)),
+        None => album_cover_art(recording_id, connection),
+    }
+}
+
+/// Every cover image the library refers to, relative to its folder.
+pub(crate) fn referenced(connection: &mut SqliteConnection) -> Result<Vec<String>> {
+    let mut cover_art = recordings::table
+        .filter(recordings::cover_art.is_not_null())
+        .select(recordings::cover_art)
+        .load::<Option<String>>(connection)?;
+
+    cover_art.extend(
+        albums::table
+            .filter(albums::cover_art.is_not_null())
+            .select(albums::cover_art)
+            .load::<Option<String>>(connection)?,
+    );
+
+    Ok(cover_art.into_iter().flatten().collect())
+}
+
+/// Whether `file_name` is a name [`store`] gives a cover: a generated ID with
+/// the extension of one of the supported image formats.
+fn is_stored_name(file_name: &str) -> bool {
+    let Some((id, extension)) = file_name.split_once('.') else {
+        return false;
+    };
+
+    id.len() == 32
+        && id
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+        && MIME_TYPES
+            .iter()
+            .any(|mime_type| mime_type.ext() == Some(extension))
+}
+
+/// `stem` with its first directory renamed if it would be the directory of the
+/// cover images, the same way a directory whose name is taken is renamed when
+/// files are reorganized.
+pub(crate) fn outside_cover_art_directory(stem: &str) -> String {
+    match stem.split_once(filenames::DIRECTORY_SEPARATOR) {
+        Some((directory, rest)) if directory.to_lowercase() == COVER_ART_DIRECTORY => {
+            format!("{directory}_1{}{rest}", filenames::DIRECTORY_SEPARATOR)
+        }
+        _ => stem.to_owned(),
+    }
+}
+
+/// Remove the cover images in `folder` that neither the library nor a change
+/// that can still be undone refers to. Returns how many were removed.
+///
+/// Files that are not named like a stored cover were put there by someone
+/// else and are left alone, and so is anything a track refers to.
+pub(crate) fn remove_unused(folder: &Path, connection: &mut SqliteConnection) -> Result<usize> {
+    let directory = folder.join(COVER_ART_DIRECTORY);
+
+    if !directory.is_dir() {
+        return Ok(0);
+    }
+
+    let referenced = referenced(connection)?;
+    let track_paths = tracks::table
+        .select(tracks::path)
+        .load::<String>(connection)?
+        .into_iter()
+        .collect::<HashSet<String>>();
+    let mut n_removed = 0;
+
+    for entry in fs::read_dir(&directory)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+
+        if !is_stored_name(&file_name) {
+            continue;
+        }
+
+        let cover_art = format!("{COVER_ART_DIRECTORY}/{file_name}");
+
+        if referenced.contains(&cover_art) || track_paths.contains(&cover_art) {
+            continue;
+        }
+
+        // Undoing a change restores the statements' values verbatim, so a
+        // cover that is mentioned in one of them may come back.
+        let in_history = history_steps::table
+            .filter(history_steps::undo_sql.like(format!("%{cover_art}%")))
+            .count()
+            .get_result::<i64>(connection)?
+            > 0;
+
+        if in_history {
+            continue;
+        }
+
+        match fs::remove_file(entry.path()) {
+            Ok(()) => n_removed += 1,
+            Err(err) => log::warn!(
+                "Failed to remove unused cover image {}: {err}",
+                entry.path().display()
+            ),
+        }
+    }
+
+    Ok(n_removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use lofty::{
+        config::WriteOptions,
+        picture::MimeType,
+        tag::{Tag, TagExt, TagType},
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::{models::*, tables, TranslatedString},
+        library::{naming::audio_tags::minimal_wav, process::ProcessMsg},
+    };
+
+    /// The smallest file that is recognized as a PNG image.
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\0";
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    fn recording(library: &Library) -> Recording {
+        let person = library.create_person(translated("Bach"), true).unwrap();
+        let work = library
+            .create_work(
+                translated("Partita No. 2"),
+                Vec::new(),
+                vec![Composer { person, role: None }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        library
+            .create_recording(work, Vec::new(), Vec::new(), Vec::new(), None, true)
+            .unwrap()
+    }
+
+    /// Write a track to `path`, embedding `cover` if there is one.
+    fn track(path: &Path, cover: Option<&[u8]>) {
+        fs::write(path, minimal_wav()).unwrap();
+
+        if let Some(cover) = cover {
+            let mut tag = Tag::new(TagType::Id3v2);
+            tag.push_picture(
+                Picture::unchecked(cover.to_vec())
+                    .pic_type(PictureType::CoverFront)
+                    .mime_type(MimeType::Png)
+                    .build(),
+            );
+            tag.save_to_path(path, WriteOptions::new()).unwrap();
+        }
+    }
+
+    fn stored_cover_art(library: &Library, recording_id: &str) -> Option<String> {
+        recordings::table
+            .filter(recordings::recording_id.eq(recording_id))
+            .select(recordings::cover_art)
+            .first::<Option<String>>(&mut *library.conn())
+            .unwrap()
+    }
+
+    fn reorganize(library: &Library) {
+        let handle = library.reorganize_files().unwrap();
+
+        while let Ok(msg) = handle.receiver.recv_blocking() {
+            match msg {
+                ProcessMsg::Result(result) => result.unwrap(),
+                ProcessMsg::Cancelled => panic!("the reorganization was cancelled"),
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn an_imported_track_brings_its_cover_along() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        let recording = recording(&library);
+
+        let mut other = PNG.to_vec();
+        other.push(1);
+
+        for (index, cover) in [PNG, &other].into_iter().enumerate() {
+            let source = source_dir.path().join(format!("{index}.wav"));
+            track(&source, Some(cover));
+            library
+                .import_track(&source, &recording.recording_id, index as i32, Vec::new())
+                .unwrap();
+        }
+
+        let cover_art = stored_cover_art(&library, &recording.recording_id)
+            .expect("the recording should have taken over the cover");
+        assert!(cover_art.starts_with(COVER_ART_DIRECTORY));
+
+        // The second track does not replace the cover of the first one.
+        assert_eq!(fs::read(dir.path().join(&cover_art)).unwrap(), PNG);
+        assert_eq!(
+            fs::read_dir(dir.path().join(COVER_ART_DIRECTORY))
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_new_cover_is_embedded_by_a_reorganization() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        let recording = recording(&library);
+
+        // Neither an import nor a reorganization puts a track there.
+        library.set_filename_pattern(&format!("{COVER_ART_DIRECTORY}/{{work}}"));
+
+        let source = source_dir.path().join("track.wav");
+        track(&source, None);
+        library
+            .import_track(&source, &recording.recording_id, 0, Vec::new())
+            .unwrap();
+
+        let image = source_dir.path().join("cover.png");
+        fs::write(&image, PNG).unwrap();
+        library
+            .set_recording_cover_art(&recording.recording_id, Some(&image))
+            .unwrap();
+
+        reorganize(&library);
+
+        let tracks = library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap();
+        let embedded = read_embedded(&dir.path().join(&tracks[0].path))
+            .unwrap()
+            .expect("the cover should have been embedded");
+        assert_eq!(embedded.data(), PNG);
+    }
+
+    #[test]
+    fn an_album_and_its_recordings_share_their_covers() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        let recording = recording(&library);
+
+        let image = dir.path().join("cover.png");
+        fs::write(&image, PNG).unwrap();
+        library
+            .set_recording_cover_art(&recording.recording_id, Some(&image))
+            .unwrap();
+
+        let recording = {
+            let connection = &mut *library.conn();
+            let data = recordings::table
+                .filter(recordings::recording_id.eq(&recording.recording_id))
+                .first::<tables::Recording>(connection)
+                .unwrap();
+            Recording::from_table(data, connection).unwrap()
+        };
+
+        let album = library
+            .create_album(translated("Partitas"), vec![recording.clone()], true)
+            .unwrap();
+        let recording_cover_art = library.recording_cover_art(&recording).unwrap();
+        assert_eq!(
+            library.album_cover_art(&album),
+            Some(recording_cover_art.clone())
+        );
+
+        // Without a cover of its own, a recording shows the one of its album.
+        library
+            .set_album_cover_art(&album.album_id, Some(&image))
+            .unwrap();
+        library
+            .set_recording_cover_art(&recording.recording_id, None)
+            .unwrap();
+
+        let mut recording = recording;
+        recording.cover_art = None;
+        let album_cover_art = library.recording_cover_art(&recording).unwrap();
+        assert_ne!(album_cover_art, recording_cover_art);
+        assert_eq!(fs::read(album_cover_art).unwrap(), PNG);
+    }
+
+    #[test]
+    fn unused_covers_are_removed_unless_an_undo_brings_them_back() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        let recording = recording(&library);
+
+        let image = dir.path().join("cover.png");
+        fs::write(&image, PNG).unwrap();
+        library
+            .set_recording_cover_art(&recording.recording_id, Some(&image))
+            .unwrap();
+        let replaced = stored_cover_art(&library, &recording.recording_id).unwrap();
+        library
+            .set_recording_cover_art(&recording.recording_id, Some(&image))
+            .unwrap();
+        let current = stored_cover_art(&library, &recording.recording_id).unwrap();
+
+        let stray = format!("{COVER_ART_DIRECTORY}/{}.png", db::generate_id());
+        fs::write(dir.path().join(&stray), PNG).unwrap();
+
+        // Somebody else's image, which is no stored cover.
+        let own = format!("{COVER_ART_DIRECTORY}/own.png");
+        fs::write(dir.path().join(&own), PNG).unwrap();
+
+        reorganize(&library);
+
+        assert!(dir.path().join(&current).is_file());
+        assert!(dir.path().join(&replaced).is_file());
+        assert!(!dir.path().join(&stray).exists());
+        assert!(dir.path().join(&own).is_file());
+
+        library.undo_last_change().unwrap();
+        assert_eq!(
+            stored_cover_art(&library, &recording.recording_id),
+            Some(replaced)
+        );
+    }
+
+    #[test]
+    fn tracks_are_kept_out_of_the_cover_directory_and_never_removed_from_it() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        let recording = recording(&library);
+
+        // Neither an import nor a reorganization puts a track there.
+        library.set_filename_pattern(&format!("{COVER_ART_DIRECTORY}/{{work}}"));
+
+        let source = source_dir.path().join("track.wav");
+        track(&source, None);
+        library
+            .import_track(
+                &source,
+                &recording.recording_id,
+                0,
+                vec![recording.work.clone()],
+            )
+            .unwrap();
+
+        for n_reorganizations in 0..3 {
+            if n_reorganizations > 0 {
+                reorganize(&library);
+            }
+
+            let tracks = library
+                .tracks_for_recording(&recording.recording_id)
+                .unwrap();
+            assert_eq!(tracks[0].path, PathBuf::from("covers_1/Partita No. 2.wav"));
+            assert!(dir.path().join(&tracks[0].path).is_file());
+        }
+
+        // A track that was put there before, under a name like that of a
+        // stored cover.
+        let track_id = library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()[0]
+            .track_id
+            .clone();
+        let path = format!("{COVER_ART_DIRECTORY}/{}.png", db::generate_id());
+        fs::create_dir_all(dir.path().join(COVER_ART_DIRECTORY)).unwrap();
+        fs::rename(
+            dir.path().join("covers_1/Partita No. 2.wav"),
+            dir.path().join(&path),
+        )
+        .unwrap();
+        diesel::update(tracks::table.filter(tracks::track_id.eq(&track_id)))
+            .set(tracks::path.eq(&path))
+            .execute(&mut *library.conn())
+            .unwrap();
+
+        let n_removed = remove_unused(dir.path(), &mut library.conn()).unwrap();
+        assert_eq!(n_removed, 0);
+        assert!(dir.path().join(&path).is_file());
+    }
+}
@@ -0,0 +1,600 @@
+//! Finding likely duplicate entities.
+//!
+//! Duplicates usually come from imports and metadata sources that spell the
+//! same thing differently. Names are compared after transliterating them to
+//! ASCII, so that "Dvořák" and "Dvorak" are the same. Persons also match when
+//! their names are written in another order or with initials, as in
+//! "Bach, J.S." and "Johann Sebastian Bach". Works only match works by the same
+//! composers, and ensembles also match when they have the same members.
+//!
+//! Every candidate pair comes with a confidence between 0 and 1. Nothing is
+//! merged automatically; that is up to the user.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{bail, Result};
+use diesel::{prelude::*, SqliteConnection};
+
+use super::{EntityUsage, Library};
+use crate::{
+    db::{schema::*, TranslatedString},
+    error::EntityKind,
+};
+
+/// Name suffixes that tell apart persons who otherwise share a name.
+const NAME_SUFFIXES: [&str; 6] = ["i", "ii", "iii", "iv", "jr", "sr"];
+
+/// How similar two names without matching words have to be to count.
+const MIN_SIMILARITY: f64 = 0.85;
+
+/// A pair of entities that probably describe the same thing.
+#[derive(Clone, Debug)]
+pub struct Duplicate {
+    pub first: DuplicateEntity,
+    pub second: DuplicateEntity,
+    /// How likely the two are the same, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// One side of a [`Duplicate`].
+#[derive(Clone, Debug)]
+pub struct DuplicateEntity {
+    pub id: String,
+    pub name: String,
+    pub usage: EntityUsage,
+}
+
+impl Library {
+    /// Likely duplicates among the entities of `kind`, most likely first.
+    pub fn find_duplicates(&self, kind: EntityKind) -> Result<Vec<Duplicate>> {
+        let (names, mut pairs) = {
+            let connection = &mut *self.conn();
+
+            let candidates = match kind {
+                EntityKind::Person => load_names(
+                    persons::table
+                        .select((persons::person_id, persons::name))
+                        .load(connection)?,
+                ),
+                EntityKind::Role => load_names(
+                    roles::table
+                        .select((roles::role_id, roles::name))
+                        .load(connection)?,
+                ),
+                EntityKind::Instrument => load_names(
+                    instruments::table
+                        .select((instruments::instrument_id, instruments::name))
+                        .load(connection)?,
+                ),
+                EntityKind::Tag => load_names(
+                    tags::table
+                        .select((tags::tag_id, tags::name))
+                        .load(connection)?,
+                ),
+                EntityKind::Ensemble => load_names(
+                    ensembles::table
+                        .select((ensembles::ensemble_id, ensembles::name))
+                        .load(connection)?,
+                ),
+                // Parts are only ever duplicates along with their parent.
+                EntityKind::Work => load_names(
+                    works::table
+                        .filter(works::parent_work_id.is_null())
+                        .select((works::work_id, works::name))
+                        .load(connection)?,
+                ),
+                EntityKind::Recording | EntityKind::Album | EntityKind::Track => {
+                    bail!("cannot merge a {kind}")
+                }
+            };
+
+            let pairs = match kind {
+                EntityKind::Person => person_pairs(&candidates),
+                EntityKind::Ensemble => ensemble_pairs(connection, &candidates)?,
+                EntityKind::Work => work_pairs(connection, &candidates)?,
+                _ => name_pairs(&candidates),
+            };
+
+            let names: HashMap<String, String> = candidates
+                .into_iter()
+                .map(|candidate| (candidate.id, candidate.display_name))
+                .collect();
+
+            (names, pairs)
+        };
+
+        pairs.sort_by(|(a1, a2, a), (b1, b2, b)| {
+            b.total_cmp(a)
+                .then_with(|| names[a1].cmp(&names[b1]))
+                .then_with(|| names[a2].cmp(&names[b2]))
+        });
+
+        pairs
+            .into_iter()
+            .map(|(first, second, confidence)| {
+                Ok(Duplicate {
+                    first: DuplicateEntity {
+                        usage: self.usage_of(kind, &first)?,
+                        name: names[&first].clone(),
+                        id: first,
+                    },
+                    second: DuplicateEntity {
+                        usage: self.usage_of(kind, &second)?,
+                        name: names[&second].clone(),
+                        id: second,
+                    },
+                    confidence,
+                })
+            })
+            .collect()
+    }
+}
+
+/// An entity with every translation of its name.
+struct Candidate {
+    id: String,
+    display_name: String,
+    names: Vec<String>,
+}
+
+fn load_names(rows: Vec<(String, TranslatedString)>) -> Vec<Candidate> {
+    rows.into_iter()
+        .map(|(id, name)| {
+            let mut names: Vec<String> = name.0.values().cloned().collect();
+            names.sort();
+            names.dedup();
+
+            Candidate {
+                id,
+                display_name: name.get().to_owned(),
+                names,
+            }
+        })
+        .collect()
+}
+
+/// Candidate pairs by ID, each with its confidence. Pairs found more than once
+/// keep the highest confidence.
+#[derive(Default)]
+struct Pairs(HashMap<(String, String), f64>);
+
+impl Pairs {
+    fn add(&mut self, a: &str, b: &str, confidence: f64) {
+        if a == b || confidence <= 0.0 {
+            return;
+        }
+
+        let key = if a < b {
+            (a.to_owned(), b.to_owned())
+        } else {
+            (b.to_owned(), a.to_owned())
+        };
+
+        let entry = self.0.entry(key).or_insert(0.0);
+        *entry = entry.max(confidence);
+    }
+
+    fn into_vec(self) -> Vec<(String, String, f64)> {
+        self.0.into_iter().map(|((a, b), c)| (a, b, c)).collect()
+    }
+}
+
+/// Lowercase ASCII words of `name`.
+fn words(name: &str) -> Vec<String> {
+    deunicode::deunicode(name)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// How alike two names are, from 0 to 1.
+///
+/// Names that differ in a number never match, because "Symphony No. 5" and
+/// "Symphony No. 6" are close but not the same.
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    if a == b {
+        return 1.0;
+    }
+
+    let numbers = |words: &[String]| -> Vec<String> {
+        words
+            .iter()
+            .filter(|word| word.chars().any(|c| c.is_ascii_digit()))
+            .cloned()
+            .collect()
+    };
+
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+
+    if numbers(&a) == numbers(&b) {
+        let (joined_a, joined_b) = (a.join(" "), b.join(" "));
+        let length = joined_a.chars().count().max(joined_b.chars().count());
+        let similarity = 1.0 - levenshtein(&joined_a, &joined_b) as f64 / length as f64;
+
+        if similarity >= MIN_SIMILARITY {
+            return 0.9 * similarity;
+        }
+    }
+
+    // One name may just be more complete, like a work title without its
+    // catalogue number.
+    if short.len() >= 2 && short.iter().all(|word| long.contains(word)) {
+        return 0.6;
+    }
+
+    0.0
+}
+
+fn best_similarity(a: &Candidate, b: &Candidate) -> f64 {
+    let mut best: f64 = 0.0;
+
+    for name_a in &a.names {
+        for name_b in &b.names {
+            best = best.max(name_similarity(name_a, name_b));
+        }
+    }
+
+    best
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, character_a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, character_b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(character_a != *character_b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Roles, instruments and tags only have their names to go by.
+fn name_pairs(candidates: &[Candidate]) -> Vec<(String, String, f64)> {
+    let mut pairs = Pairs::default();
+
+    for (index, a) in candidates.iter().enumerate() {
+        for b in &candidates[index + 1..] {
+            pairs.add(&a.id, &b.id, best_similarity(a, b));
+        }
+    }
+
+    pairs.into_vec()
+}
+
+/// A person's name split into its parts, in "given names, surname" order.
+#[derive(Debug, PartialEq, Eq)]
+struct PersonName {
+    given: Vec<String>,
+    surname: String,
+    suffix: Option<String>,
+}
+
+impl PersonName {
+    fn parse(name: &str) -> Option<Self> {
+        // "Bach, Johann Sebastian" lists the surname first.
+        let ordered = match name.split_once(',') {
+            Some((surname, given)) => format!("{given} {surname}"),
+            None => name.to_owned(),
+        };
+
+        let mut suffix = None;
+        let mut parts = Vec::new();
+
+        for (index, word) in words(&ordered).into_iter().enumerate() {
+            if index > 0 && NAME_SUFFIXES.contains(&word.as_str()) {
+                suffix = Some(word);
+            } else {
+                parts.push(word);
+            }
+        }
+
+        let surname = parts.pop()?;
+
+        Some(Self {
+            given: parts,
+            surname,
+            suffix,
+        })
+    }
+
+    /// All words, regardless of their order.
+    fn word_set(&self) -> BTreeSet<&str> {
+        self.given
+            .iter()
+            .map(String::as_str)
+            .chain([self.surname.as_str()])
+            .chain(self.suffix.as_deref())
+            .collect()
+    }
+
+    /// How likely two names with the same surname belong to the same person.
+    fn given_names_match(&self, other: &PersonName) -> f64 {
+        if self.suffix != other.suffix {
+            return 0.0;
+        }
+
+        if self.given == other.given {
+            return 1.0;
+        }
+
+        let (short, long) = if self.given.len() <= other.given.len() {
+            (&self.given, &other.given)
+        } else {
+            (&other.given, &self.given)
+        };
+
+        // Every given name of the shorter name has to appear in the longer one,
+        // in the same order, either in full or as an initial.
+        let mut remaining = long.iter();
+        let mut initials_only = false;
+
+        for word in short {
+            let matched = remaining.by_ref().find(|candidate| {
+                if candidate == &word {
+                    return true;
+                }
+
+                let is_initial =
+                    |initial: &str, full: &str| initial.len() == 1 && full.starts_with(initial);
+
+                if is_initial(word, candidate) || is_initial(candidate, word) {
+                    initials_only = true;
+                    return true;
+                }
+
+                false
+            });
+
+            if matched.is_none() {
+                return 0.0;
+            }
+        }
+
+        match (short.len() == long.len(), short.is_empty()) {
+            (true, _) => 0.8,
+            (false, true) => 0.4,
+            (false, false) if initials_only => 0.5,
+            (false, false) => 0.6,
+        }
+    }
+}
+
+fn person_pairs(candidates: &[Candidate]) -> Vec<(String, String, f64)> {
+    let mut by_surname: HashMap<String, Vec<(&str, PersonName)>> = HashMap::new();
+    let mut by_words: HashMap<Vec<String>, Vec<&str>> = HashMap::new();
+
+    for candidate in candidates {
+        for name in &candidate.names {
+            let Some(parsed) = PersonName::parse(name) else {
+                continue;
+            };
+
+            let word_set = parsed.word_set().into_iter().map(str::to_owned).collect();
+            by_words.entry(word_set).or_default().push(&candidate.id);
+
+            by_surname
+                .entry(parsed.surname.clone())
+                .or_default()
+                .push((&candidate.id, parsed));
+        }
+    }
+
+    let mut pairs = Pairs::default();
+
+    for names in by_surname.values() {
+        for (index, (id_a, a)) in names.iter().enumerate() {
+            for (id_b, b) in &names[index + 1..] {
+                pairs.add(id_a, id_b, a.given_names_match(b));
+            }
+        }
+    }
+
+    // The same words in another order, without a comma to tell which one is
+    // the surname.
+    for ids in by_words.values() {
+        for (index, id_a) in ids.iter().enumerate() {
+            for id_b in &ids[index + 1..] {
+                pairs.add(id_a, id_b, 0.9);
+            }
+        }
+    }
+
+    pairs.into_vec()
+}
+
+fn ensemble_pairs(
+    connection: &mut SqliteConnection,
+    candidates: &[Candidate],
+) -> Result<Vec<(String, String, f64)>> {
+    let mut members: HashMap<String, BTreeSet<String>> = HashMap::new();
+
+    for (ensemble_id, person_id) in ensemble_persons::table
+        .select((ensemble_persons::ensemble_id, ensemble_persons::person_id))
+        .load::<(String, String)>(connection)?
+    {
+        members.entry(ensemble_id).or_default().insert(person_id);
+    }
+
+    let mut pairs = Pairs::default();
+
+    for (index, a) in candidates.iter().enumerate() {
+        for b in &candidates[index + 1..] {
+            let members_a = members.get(&a.id);
+            let members_b = members.get(&b.id);
+            let same_members = members_a.is_some() && members_a == members_b;
+            let similarity = best_similarity(a, b);
+
+            let confidence = if same_members {
+                0.7 + 0.3 * similarity
+            } else if members_a.is_some() && members_b.is_some() {
+                // Line-ups change over time, so different members only make
+                // it a little less likely.
+                0.9 * similarity
+            } else {
+                similarity
+            };
+
+            pairs.add(&a.id, &b.id, confidence);
+        }
+    }
+
+    Ok(pairs.into_vec())
+}
+
+fn work_pairs(
+    connection: &mut SqliteConnection,
+    candidates: &[Candidate],
+) -> Result<Vec<(String, String, f64)>> {
+    let mut composers: HashMap<String, BTreeSet<String>> = HashMap::new();
+
+    for (work_id, person_id) in work_persons::table
+        .select((work_persons::work_id, work_persons::person_id))
+        .load::<(String, String)>(connection)?
+    {
+        composers.entry(work_id).or_default().insert(person_id);
+    }
+
+    let mut by_composers: HashMap<&BTreeSet<String>, Vec<&Candidate>> = HashMap::new();
+
+    for candidate in candidates {
+        if let Some(work_composers) = composers.get(&candidate.id) {
+            by_composers
+                .entry(work_composers)
+                .or_default()
+                .push(candidate);
+        }
+    }
+
+    let mut pairs = Pairs::default();
+
+    for works in by_composers.values() {
+        for (index, a) in works.iter().enumerate() {
+            for b in &works[index + 1..] {
+                pairs.add(&a.id, &b.id, best_similarity(a, b));
+            }
+        }
+    }
+
+    Ok(pairs.into_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::db::models::Composer;
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    fn person_match(a: &str, b: &str) -> f64 {
+        let (a, b) = (PersonName::parse(a).unwrap(), PersonName::parse(b).unwrap());
+
+        if a.surname == b.surname {
+            a.given_names_match(&b)
+        } else {
+            0.0
+        }
+    }
+
+    #[test]
+    fn person_names_match_across_orders_initials_and_accents() {
+        assert_eq!(person_match("Antonín Dvořák", "Antonin Dvorak"), 1.0);
+        assert_eq!(
+            person_match("Bach, Johann Sebastian", "Johann Sebastian Bach"),
+            1.0
+        );
+        assert!(person_match("Bach, J.S.", "Johann Sebastian Bach") > 0.5);
+        assert!(person_match("Beethoven", "Ludwig van Beethoven") > 0.0);
+
+        assert_eq!(
+            person_match("Carl Philipp Emanuel Bach", "Johann Sebastian Bach"),
+            0.0
+        );
+        assert_eq!(person_match("Johann Strauss I", "Johann Strauss II"), 0.0);
+    }
+
+    #[test]
+    fn work_names_differing_in_a_number_do_not_match() {
+        assert_eq!(name_similarity("Symphony No. 5", "Symphony No. 6"), 0.0);
+        assert!(name_similarity("Symphony No. 5", "Symphonie No. 5") > MIN_SIMILARITY * 0.9);
+        assert!(name_similarity("Symphony No. 5", "Symphony No. 5 in C minor, Op. 67") > 0.0);
+    }
+
+    #[test]
+    fn duplicates_are_found_with_their_usage() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let bach = library
+            .create_person(translated("Johann Sebastian Bach"), true)
+            .unwrap();
+        let duplicate = library
+            .create_person(translated("Bach, J.S."), true)
+            .unwrap();
+        library
+            .create_person(translated("Carl Philipp Emanuel Bach"), true)
+            .unwrap();
+
+        for name in ["Mass in B minor", "Mass in B Minor"] {
+            library
+                .create_work(
+                    translated(name),
+                    Vec::new(),
+                    vec![Composer {
+                        person: bach.clone(),
+                        role: None,
+                    }],
+                    Vec::new(),
+                    Vec::new(),
+                    None,
+                    true,
+                )
+                .unwrap();
+        }
+
+        let persons = library.find_duplicates(EntityKind::Person).unwrap();
+        assert_eq!(persons.len(), 1);
+
+        let mut ids = [persons[0].first.id.clone(), persons[0].second.id.clone()];
+        ids.sort();
+        let mut expected = [bach.person_id.clone(), duplicate.person_id.clone()];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        let usage = if persons[0].first.id == bach.person_id {
+            persons[0].first.usage
+        } else {
+            persons[0].second.usage
+        };
+        assert_eq!(usage.works, 2);
+
+        let works = library.find_duplicates(EntityKind::Work).unwrap();
+        assert_eq!(works.len(), 1);
+        assert_eq!(works[0].confidence, 1.0);
+    }
+}
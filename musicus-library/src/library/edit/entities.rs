@@ -11,7 +11,11 @@ use crate::db::{
     TranslatedString,
 };
 use crate::error::{EntityKind, LibraryError};
-use crate::library::Library;
+use crate::library::{
+    history::{self, ChangeAction},
+    reorganize::ReorganizationScope,
+    Library,
+};
 
 impl Library {
     pub fn create_person(&self, name: TranslatedString, enable_updates: bool) -> Result<Person> {
@@ -46,17 +50,36 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        let now = db::now();
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Person,
+            id,
+            None,
+            |connection| -> Result<()> {
+                let now = db::now();
+
+                diesel::update(persons::table)
+                    .filter(persons::person_id.eq(id))
+                    .set((
+                        persons::name.eq(name),
+                        persons::edited_at.eq(now),
+                        persons::last_used_at.eq(now),
+                        persons::enable_updates.eq(enable_updates),
+                    ))
+                    .execute(connection)?;
 
-        diesel::update(persons::table)
-            .filter(persons::person_id.eq(id))
-            .set((
-                persons::name.eq(name),
-                persons::edited_at.eq(now),
-                persons::last_used_at.eq(now),
-                persons::enable_updates.eq(enable_updates),
-            ))
-            .execute(connection)?;
+                Ok(())
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                person_ids: vec![id.to_string()],
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -66,10 +89,21 @@ impl Library {
     pub fn delete_person(&self, person_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(persons::table)
-            .filter(persons::person_id.eq(person_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Person, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Person,
+            person_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(persons::table)
+                    .filter(persons::person_id.eq(person_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Person, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -112,17 +146,36 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        let now = db::now();
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Instrument,
+            id,
+            None,
+            |connection| -> Result<()> {
+                let now = db::now();
+
+                diesel::update(instruments::table)
+                    .filter(instruments::instrument_id.eq(id))
+                    .set((
+                        instruments::name.eq(name),
+                        instruments::edited_at.eq(now),
+                        instruments::last_used_at.eq(now),
+                        instruments::enable_updates.eq(enable_updates),
+                    ))
+                    .execute(connection)?;
 
-        diesel::update(instruments::table)
-            .filter(instruments::instrument_id.eq(id))
-            .set((
-                instruments::name.eq(name),
-                instruments::edited_at.eq(now),
-                instruments::last_used_at.eq(now),
-                instruments::enable_updates.eq(enable_updates),
-            ))
-            .execute(connection)?;
+                Ok(())
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                instrument_ids: vec![id.to_string()],
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -132,10 +185,21 @@ impl Library {
     pub fn delete_instrument(&self, instrument_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(instruments::table)
-            .filter(instruments::instrument_id.eq(instrument_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Instrument, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Instrument,
+            instrument_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(instruments::table)
+                    .filter(instruments::instrument_id.eq(instrument_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Instrument, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -174,17 +238,36 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        let now = db::now();
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Role,
+            id,
+            None,
+            |connection| -> Result<()> {
+                let now = db::now();
+
+                diesel::update(roles::table)
+                    .filter(roles::role_id.eq(id))
+                    .set((
+                        roles::name.eq(name),
+                        roles::edited_at.eq(now),
+                        roles::last_used_at.eq(now),
+                        roles::enable_updates.eq(enable_updates),
+                    ))
+                    .execute(connection)?;
 
-        diesel::update(roles::table)
-            .filter(roles::role_id.eq(id))
-            .set((
-                roles::name.eq(name),
-                roles::edited_at.eq(now),
-                roles::last_used_at.eq(now),
-                roles::enable_updates.eq(enable_updates),
-            ))
-            .execute(connection)?;
+                Ok(())
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                role_ids: vec![id.to_string()],
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -194,10 +277,21 @@ impl Library {
     pub fn delete_role(&self, role_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(roles::table)
-            .filter(roles::role_id.eq(role_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Role, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Role,
+            role_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(roles::table)
+                    .filter(roles::role_id.eq(role_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Role, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -331,21 +425,36 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        connection.transaction::<(), Error, _>(|connection| {
-            Self::update_work_priv(
-                connection,
-                work_id,
-                name,
-                parts,
-                persons,
-                instruments,
-                tags,
-                None,
-                None,
-                relates_to.map(|w| w.work_id),
-                enable_updates,
-            )
-        })?;
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Work,
+            work_id,
+            None,
+            |connection| -> Result<()> {
+                Self::update_work_priv(
+                    connection,
+                    work_id,
+                    name,
+                    parts,
+                    persons,
+                    instruments,
+                    tags,
+                    None,
+                    None,
+                    relates_to.map(|w| w.work_id),
+                    enable_updates,
+                )
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                work_ids: vec![work_id.to_string()],
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -470,10 +579,21 @@ impl Library {
     pub fn delete_work(&self, work_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(works::table)
-            .filter(works::work_id.eq(work_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Work, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Work,
+            work_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(works::table)
+                    .filter(works::work_id.eq(work_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Work, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -536,39 +656,54 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        connection.transaction::<(), Error, _>(|connection| {
-            let now = db::now();
-
-            diesel::update(ensembles::table)
-                .filter(ensembles::ensemble_id.eq(id))
-                .set((
-                    ensembles::name.eq(name),
-                    ensembles::edited_at.eq(now),
-                    ensembles::last_used_at.eq(now),
-                    ensembles::enable_updates.eq(enable_updates),
-                ))
-                .execute(connection)?;
-
-            diesel::delete(ensemble_persons::table)
-                .filter(ensemble_persons::ensemble_id.eq(id))
-                .execute(connection)?;
-
-            for (index, member) in persons.into_iter().enumerate() {
-                let ensemble_person_data = tables::EnsemblePerson {
-                    ensemble_id: id.to_string(),
-                    person_id: member.person.person_id,
-                    role_id: member.role.map(|r| r.role_id),
-                    instrument_id: member.instrument.map(|i| i.instrument_id),
-                    sequence_number: index as i32,
-                };
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Ensemble,
+            id,
+            None,
+            |connection| -> Result<()> {
+                let now = db::now();
+
+                diesel::update(ensembles::table)
+                    .filter(ensembles::ensemble_id.eq(id))
+                    .set((
+                        ensembles::name.eq(name),
+                        ensembles::edited_at.eq(now),
+                        ensembles::last_used_at.eq(now),
+                        ensembles::enable_updates.eq(enable_updates),
+                    ))
+                    .execute(connection)?;
 
-                diesel::insert_into(ensemble_persons::table)
-                    .values(&ensemble_person_data)
+                diesel::delete(ensemble_persons::table)
+                    .filter(ensemble_persons::ensemble_id.eq(id))
                     .execute(connection)?;
-            }
 
-            Ok(())
-        })?;
+                for (index, member) in persons.into_iter().enumerate() {
+                    let ensemble_person_data = tables::EnsemblePerson {
+                        ensemble_id: id.to_string(),
+                        person_id: member.person.person_id,
+                        role_id: member.role.map(|r| r.role_id),
+                        instrument_id: member.instrument.map(|i| i.instrument_id),
+                        sequence_number: index as i32,
+                    };
+
+                    diesel::insert_into(ensemble_persons::table)
+                        .values(&ensemble_person_data)
+                        .execute(connection)?;
+                }
+
+                Ok(())
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                ensemble_ids: vec![id.to_string()],
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -578,10 +713,21 @@ impl Library {
     pub fn delete_ensemble(&self, ensemble_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(ensembles::table)
-            .filter(ensembles::ensemble_id.eq(ensemble_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Ensemble, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Ensemble,
+            ensemble_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(ensembles::table)
+                    .filter(ensembles::ensemble_id.eq(ensemble_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Ensemble, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -611,6 +757,7 @@ impl Library {
                 edited_at: now,
                 last_used_at: now,
                 comment,
+                cover_art: None,
                 enable_updates,
             };
 
@@ -667,59 +814,74 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        connection.transaction::<(), Error, _>(|connection| {
-            let now = db::now();
-
-            diesel::update(recordings::table)
-                .filter(recordings::recording_id.eq(recording_id))
-                .set((
-                    recordings::work_id.eq(work.work_id),
-                    recordings::edited_at.eq(now),
-                    recordings::last_used_at.eq(now),
-                    recordings::comment.eq(comment),
-                    recordings::enable_updates.eq(enable_updates),
-                ))
-                .execute(connection)?;
-
-            diesel::delete(recording_persons::table)
-                .filter(recording_persons::recording_id.eq(recording_id))
-                .execute(connection)?;
-
-            for (index, performer) in performers.into_iter().enumerate() {
-                let recording_person_data = tables::RecordingPerson {
-                    recording_id: recording_id.to_string(),
-                    person_id: performer.person.person_id,
-                    role_id: performer.role.map(|r| r.role_id),
-                    instrument_id: performer.instrument.map(|i| i.instrument_id),
-                    sequence_number: index as i32,
-                };
-
-                diesel::insert_into(recording_persons::table)
-                    .values(&recording_person_data)
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Recording,
+            recording_id,
+            None,
+            |connection| -> Result<()> {
+                let now = db::now();
+
+                diesel::update(recordings::table)
+                    .filter(recordings::recording_id.eq(recording_id))
+                    .set((
+                        recordings::work_id.eq(work.work_id),
+                        recordings::edited_at.eq(now),
+                        recordings::last_used_at.eq(now),
+                        recordings::comment.eq(comment),
+                        recordings::enable_updates.eq(enable_updates),
+                    ))
                     .execute(connection)?;
-            }
-
-            diesel::delete(recording_ensembles::table)
-                .filter(recording_ensembles::recording_id.eq(recording_id))
-                .execute(connection)?;
-
-            for (index, ensemble) in ensembles.into_iter().enumerate() {
-                let recording_ensemble_data = tables::RecordingEnsemble {
-                    recording_id: recording_id.to_string(),
-                    ensemble_id: ensemble.ensemble.ensemble_id,
-                    role_id: ensemble.role.map(|r| r.role_id),
-                    sequence_number: index as i32,
-                };
 
-                diesel::insert_into(recording_ensembles::table)
-                    .values(&recording_ensemble_data)
+                diesel::delete(recording_persons::table)
+                    .filter(recording_persons::recording_id.eq(recording_id))
                     .execute(connection)?;
-            }
 
-            Self::set_recording_tags(connection, recording_id, tags)?;
+                for (index, performer) in performers.into_iter().enumerate() {
+                    let recording_person_data = tables::RecordingPerson {
+                        recording_id: recording_id.to_string(),
+                        person_id: performer.person.person_id,
+                        role_id: performer.role.map(|r| r.role_id),
+                        instrument_id: performer.instrument.map(|i| i.instrument_id),
+                        sequence_number: index as i32,
+                    };
+
+                    diesel::insert_into(recording_persons::table)
+                        .values(&recording_person_data)
+                        .execute(connection)?;
+                }
+
+                diesel::delete(recording_ensembles::table)
+                    .filter(recording_ensembles::recording_id.eq(recording_id))
+                    .execute(connection)?;
 
-            Ok(())
-        })?;
+                for (index, ensemble) in ensembles.into_iter().enumerate() {
+                    let recording_ensemble_data = tables::RecordingEnsemble {
+                        recording_id: recording_id.to_string(),
+                        ensemble_id: ensemble.ensemble.ensemble_id,
+                        role_id: ensemble.role.map(|r| r.role_id),
+                        sequence_number: index as i32,
+                    };
+
+                    diesel::insert_into(recording_ensembles::table)
+                        .values(&recording_ensemble_data)
+                        .execute(connection)?;
+                }
+
+                Self::set_recording_tags(connection, recording_id, tags)?;
+
+                Ok(())
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                recording_ids: vec![recording_id.to_string()],
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -729,10 +891,21 @@ impl Library {
     pub fn delete_recording(&self, recording_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(recordings::table)
-            .filter(recordings::recording_id.eq(recording_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Recording, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Recording,
+            recording_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(recordings::table)
+                    .filter(recordings::recording_id.eq(recording_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Recording, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -759,6 +932,7 @@ impl Library {
                 created_at: now,
                 edited_at: now,
                 last_used_at: now,
+                cover_art: None,
             };
 
             diesel::insert_into(albums::table)
@@ -794,37 +968,59 @@ impl Library {
     ) -> Result<()> {
         let connection = &mut *self.conn();
 
-        connection.transaction::<(), Error, _>(|connection| {
-            let now = db::now();
-
-            diesel::update(albums::table)
-                .filter(albums::album_id.eq(album_id))
-                .set((
-                    albums::name.eq(name),
-                    albums::enable_updates.eq(enable_updates),
-                    albums::edited_at.eq(now),
-                    albums::last_used_at.eq(now),
-                ))
-                .execute(connection)?;
-
-            diesel::delete(album_recordings::table)
-                .filter(album_recordings::album_id.eq(album_id))
-                .execute(connection)?;
-
-            for (index, recording) in recordings.into_iter().enumerate() {
-                let album_recording_data = tables::AlbumRecording {
-                    album_id: album_id.to_owned(),
-                    recording_id: recording.recording_id,
-                    sequence_number: index as i32,
-                };
+        // The recordings that are removed from the album lose its name.
+        let old_recording_ids = album_recordings::table
+            .filter(album_recordings::album_id.eq(album_id))
+            .select(album_recordings::recording_id)
+            .load::<String>(connection)?;
+
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Album,
+            album_id,
+            None,
+            |connection| -> Result<()> {
+                let now = db::now();
+
+                diesel::update(albums::table)
+                    .filter(albums::album_id.eq(album_id))
+                    .set((
+                        albums::name.eq(name),
+                        albums::enable_updates.eq(enable_updates),
+                        albums::edited_at.eq(now),
+                        albums::last_used_at.eq(now),
+                    ))
+                    .execute(connection)?;
 
-                diesel::insert_into(album_recordings::table)
-                    .values(&album_recording_data)
+                diesel::delete(album_recordings::table)
+                    .filter(album_recordings::album_id.eq(album_id))
                     .execute(connection)?;
-            }
 
-            Ok(())
-        })?;
+                for (index, recording) in recordings.into_iter().enumerate() {
+                    let album_recording_data = tables::AlbumRecording {
+                        album_id: album_id.to_owned(),
+                        recording_id: recording.recording_id,
+                        sequence_number: index as i32,
+                    };
+
+                    diesel::insert_into(album_recordings::table)
+                        .values(&album_recording_data)
+                        .execute(connection)?;
+                }
+
+                Ok(())
+            },
+        )?;
+
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                album_ids: vec![album_id.to_string()],
+                recording_ids: old_recording_ids,
+                ..Default::default()
+            },
+        );
 
         self.changed();
 
@@ -834,10 +1030,21 @@ impl Library {
     pub fn delete_album(&self, album_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(albums::table)
-            .filter(albums::album_id.eq(album_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Album, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Album,
+            album_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(albums::table)
+                    .filter(albums::album_id.eq(album_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Album, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
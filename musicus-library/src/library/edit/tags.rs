@@ -1,7 +1,7 @@
 //! Editing tags: creating, updating, deleting and (bulk) assigning tags to
 //! works and recordings.
 
-use anyhow::{Error, Result};
+use anyhow::Result;
 use diesel::{dsl::exists, prelude::*, QueryDsl, SqliteConnection};
 
 use crate::db::{
@@ -12,7 +12,10 @@ use crate::db::{
     TranslatedString,
 };
 use crate::error::{EntityKind, LibraryError};
-use crate::library::Library;
+use crate::library::{
+    history::{self, ChangeAction},
+    Library,
+};
 
 impl Library {
     pub fn create_tag(
@@ -86,30 +89,41 @@ impl Library {
     ) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        let previous = tags::table
-            .filter(tags::tag_id.eq(id))
-            .select(tags::takes_value)
-            .first::<bool>(connection)
-            .map_err(anyhow::Error::from)?;
+        history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Tag,
+            id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                let previous = tags::table
+                    .filter(tags::tag_id.eq(id))
+                    .select(tags::takes_value)
+                    .first::<bool>(connection)
+                    .map_err(anyhow::Error::from)?;
+
+                if previous != takes_value && Self::tag_is_in_use_priv(connection, id)? {
+                    return Err(LibraryError::StillReferenced(EntityKind::Tag));
+                }
 
-        if previous != takes_value && Self::tag_is_in_use_priv(connection, id)? {
-            return Err(LibraryError::StillReferenced(EntityKind::Tag));
-        }
+                let now = db::now();
 
-        let now = db::now();
+                diesel::update(tags::table)
+                    .filter(tags::tag_id.eq(id))
+                    .set((
+                        tags::name.eq(name),
+                        tags::takes_value.eq(takes_value),
+                        tags::private.eq(private),
+                        tags::edited_at.eq(now),
+                        tags::last_used_at.eq(now),
+                        tags::enable_updates.eq(enable_updates),
+                    ))
+                    .execute(connection)
+                    .map_err(anyhow::Error::from)?;
 
-        diesel::update(tags::table)
-            .filter(tags::tag_id.eq(id))
-            .set((
-                tags::name.eq(name),
-                tags::takes_value.eq(takes_value),
-                tags::private.eq(private),
-                tags::edited_at.eq(now),
-                tags::last_used_at.eq(now),
-                tags::enable_updates.eq(enable_updates),
-            ))
-            .execute(connection)
-            .map_err(anyhow::Error::from)?;
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -119,10 +133,21 @@ impl Library {
     pub fn delete_tag(&self, tag_id: &str) -> Result<(), LibraryError> {
         let connection = &mut *self.conn();
 
-        diesel::delete(tags::table)
-            .filter(tags::tag_id.eq(tag_id))
-            .execute(connection)
-            .map_err(|err| LibraryError::from_delete(EntityKind::Tag, err))?;
+        history::record(
+            connection,
+            ChangeAction::Delete,
+            EntityKind::Tag,
+            tag_id,
+            None,
+            |connection| -> Result<(), LibraryError> {
+                diesel::delete(tags::table)
+                    .filter(tags::tag_id.eq(tag_id))
+                    .execute(connection)
+                    .map_err(|err| LibraryError::from_delete(EntityKind::Tag, err))?;
+
+                Ok(())
+            },
+        )?;
 
         self.changed();
 
@@ -199,52 +224,59 @@ impl Library {
         let value = value.filter(|_| tag.takes_value).map(str::to_owned);
         let now = db::now();
 
-        let changed = connection.transaction::<usize, Error, _>(|connection| {
-            let mut changed = 0usize;
-
-            for &work_id in work_ids {
-                let existing = work_tags::table
-                    .filter(work_tags::work_id.eq(work_id))
-                    .select((
-                        work_tags::tag_id,
-                        work_tags::value,
-                        work_tags::sequence_number,
-                    ))
-                    .load::<(String, Option<String>, i32)>(connection)?;
-
-                let already_tagged = existing.iter().any(|(tag_id, existing_value, _)| {
-                    tag_id == &tag.tag_id && existing_value == &value
-                });
-
-                if already_tagged {
-                    continue;
+        let changed = history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Tag,
+            &tag.tag_id,
+            None,
+            |connection| -> Result<usize> {
+                let mut changed = 0usize;
+
+                for &work_id in work_ids {
+                    let existing = work_tags::table
+                        .filter(work_tags::work_id.eq(work_id))
+                        .select((
+                            work_tags::tag_id,
+                            work_tags::value,
+                            work_tags::sequence_number,
+                        ))
+                        .load::<(String, Option<String>, i32)>(connection)?;
+
+                    let already_tagged = existing.iter().any(|(tag_id, existing_value, _)| {
+                        tag_id == &tag.tag_id && existing_value == &value
+                    });
+
+                    if already_tagged {
+                        continue;
+                    }
+
+                    let next_sequence = existing
+                        .iter()
+                        .map(|(_, _, sequence_number)| *sequence_number)
+                        .max()
+                        .map_or(0, |n| n + 1);
+
+                    diesel::insert_into(work_tags::table)
+                        .values(tables::WorkTag {
+                            work_id: work_id.to_string(),
+                            tag_id: tag.tag_id.clone(),
+                            value: value.clone(),
+                            sequence_number: next_sequence,
+                        })
+                        .execute(connection)?;
+
+                    diesel::update(works::table)
+                        .filter(works::work_id.eq(work_id))
+                        .set((works::edited_at.eq(now), works::last_used_at.eq(now)))
+                        .execute(connection)?;
+
+                    changed += 1;
                 }
 
-                let next_sequence = existing
-                    .iter()
-                    .map(|(_, _, sequence_number)| *sequence_number)
-                    .max()
-                    .map_or(0, |n| n + 1);
-
-                diesel::insert_into(work_tags::table)
-                    .values(tables::WorkTag {
-                        work_id: work_id.to_string(),
-                        tag_id: tag.tag_id.clone(),
-                        value: value.clone(),
-                        sequence_number: next_sequence,
-                    })
-                    .execute(connection)?;
-
-                diesel::update(works::table)
-                    .filter(works::work_id.eq(work_id))
-                    .set((works::edited_at.eq(now), works::last_used_at.eq(now)))
-                    .execute(connection)?;
-
-                changed += 1;
-            }
-
-            Ok(changed)
-        })?;
+                Ok(changed)
+            },
+        )?;
 
         self.changed();
 
@@ -265,55 +297,62 @@ impl Library {
         let value = value.filter(|_| tag.takes_value).map(str::to_owned);
         let now = db::now();
 
-        let changed = connection.transaction::<usize, Error, _>(|connection| {
-            let mut changed = 0usize;
-
-            for &recording_id in recording_ids {
-                let existing = recording_tags::table
-                    .filter(recording_tags::recording_id.eq(recording_id))
-                    .select((
-                        recording_tags::tag_id,
-                        recording_tags::value,
-                        recording_tags::sequence_number,
-                    ))
-                    .load::<(String, Option<String>, i32)>(connection)?;
-
-                let already_tagged = existing.iter().any(|(tag_id, existing_value, _)| {
-                    tag_id == &tag.tag_id && existing_value == &value
-                });
-
-                if already_tagged {
-                    continue;
+        let changed = history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Tag,
+            &tag.tag_id,
+            None,
+            |connection| -> Result<usize> {
+                let mut changed = 0usize;
+
+                for &recording_id in recording_ids {
+                    let existing = recording_tags::table
+                        .filter(recording_tags::recording_id.eq(recording_id))
+                        .select((
+                            recording_tags::tag_id,
+                            recording_tags::value,
+                            recording_tags::sequence_number,
+                        ))
+                        .load::<(String, Option<String>, i32)>(connection)?;
+
+                    let already_tagged = existing.iter().any(|(tag_id, existing_value, _)| {
+                        tag_id == &tag.tag_id && existing_value == &value
+                    });
+
+                    if already_tagged {
+                        continue;
+                    }
+
+                    let next_sequence = existing
+                        .iter()
+                        .map(|(_, _, sequence_number)| *sequence_number)
+                        .max()
+                        .map_or(0, |n| n + 1);
+
+                    diesel::insert_into(recording_tags::table)
+                        .values(tables::RecordingTag {
+                            recording_id: recording_id.to_string(),
+                            tag_id: tag.tag_id.clone(),
+                            value: value.clone(),
+                            sequence_number: next_sequence,
+                        })
+                        .execute(connection)?;
+
+                    diesel::update(recordings::table)
+                        .filter(recordings::recording_id.eq(recording_id))
+                        .set((
+                            recordings::edited_at.eq(now),
+                            recordings::last_used_at.eq(now),
+                        ))
+                        .execute(connection)?;
+
+                    changed += 1;
                 }
 
-                let next_sequence = existing
-                    .iter()
-                    .map(|(_, _, sequence_number)| *sequence_number)
-                    .max()
-                    .map_or(0, |n| n + 1);
-
-                diesel::insert_into(recording_tags::table)
-                    .values(tables::RecordingTag {
-                        recording_id: recording_id.to_string(),
-                        tag_id: tag.tag_id.clone(),
-                        value: value.clone(),
-                        sequence_number: next_sequence,
-                    })
-                    .execute(connection)?;
-
-                diesel::update(recordings::table)
-                    .filter(recordings::recording_id.eq(recording_id))
-                    .set((
-                        recordings::edited_at.eq(now),
-                        recordings::last_used_at.eq(now),
-                    ))
-                    .execute(connection)?;
-
-                changed += 1;
-            }
-
-            Ok(changed)
-        })?;
+                Ok(changed)
+            },
+        )?;
 
         self.changed();
 
@@ -327,31 +366,38 @@ impl Library {
         let connection = &mut *self.conn();
         let now = db::now();
 
-        let changed = connection.transaction::<usize, Error, _>(|connection| {
-            let mut changed = 0usize;
-
-            for &work_id in work_ids {
-                let deleted = diesel::delete(
-                    work_tags::table
-                        .filter(work_tags::work_id.eq(work_id))
-                        .filter(work_tags::tag_id.eq(tag_id)),
-                )
-                .execute(connection)?;
+        let changed = history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Tag,
+            tag_id,
+            None,
+            |connection| -> Result<usize> {
+                let mut changed = 0usize;
+
+                for &work_id in work_ids {
+                    let deleted = diesel::delete(
+                        work_tags::table
+                            .filter(work_tags::work_id.eq(work_id))
+                            .filter(work_tags::tag_id.eq(tag_id)),
+                    )
+                    .execute(connection)?;
 
-                if deleted == 0 {
-                    continue;
-                }
+                    if deleted == 0 {
+                        continue;
+                    }
 
-                diesel::update(works::table)
-                    .filter(works::work_id.eq(work_id))
-                    .set((works::edited_at.eq(now), works::last_used_at.eq(now)))
-                    .execute(connection)?;
+                    diesel::update(works::table)
+                        .filter(works::work_id.eq(work_id))
+                        .set((works::edited_at.eq(now), works::last_used_at.eq(now)))
+                        .execute(connection)?;
 
-                changed += 1;
-            }
+                    changed += 1;
+                }
 
-            Ok(changed)
-        })?;
+                Ok(changed)
+            },
+        )?;
 
         self.changed();
 
@@ -369,34 +415,41 @@ impl Library {
         let connection = &mut *self.conn();
         let now = db::now();
 
-        let changed = connection.transaction::<usize, Error, _>(|connection| {
-            let mut changed = 0usize;
+        let changed = history::record(
+            connection,
+            ChangeAction::Update,
+            EntityKind::Tag,
+            tag_id,
+            None,
+            |connection| -> Result<usize> {
+                let mut changed = 0usize;
+
+                for &recording_id in recording_ids {
+                    let deleted = diesel::delete(
+                        recording_tags::table
+                            .filter(recording_tags::recording_id.eq(recording_id))
+                            .filter(recording_tags::tag_id.eq(tag_id)),
+                    )
+                    .execute(connection)?;
 
-            for &recording_id in recording_ids {
-                let deleted = diesel::delete(
-                    recording_tags::table
-                        .filter(recording_tags::recording_id.eq(recording_id))
-                        .filter(recording_tags::tag_id.eq(tag_id)),
-                )
-                .execute(connection)?;
+                    if deleted == 0 {
+                        continue;
+                    }
 
-                if deleted == 0 {
-                    continue;
-                }
+                    diesel::update(recordings::table)
+                        .filter(recordings::recording_id.eq(recording_id))
+                        .set((
+                            recordings::edited_at.eq(now),
+                            recordings::last_used_at.eq(now),
+                        ))
+                        .execute(connection)?;
 
-                diesel::update(recordings::table)
-                    .filter(recordings::recording_id.eq(recording_id))
-                    .set((
-                        recordings::edited_at.eq(now),
-                        recordings::last_used_at.eq(now),
-                    ))
-                    .execute(connection)?;
-
-                changed += 1;
-            }
+                    changed += 1;
+                }
 
-            Ok(changed)
-        })?;
+                Ok(changed)
+            },
+        )?;
 
         self.changed();
 
@@ -8,11 +8,14 @@ use std::{
 
 use anyhow::{bail, Error, Result};
 use diesel::prelude::*;
+use lofty::picture::{Picture, PictureType};
 
 use crate::db::{self, models::*, schema::*, tables};
 use crate::library::{
+    cover_art,
     naming::{audio_tags, filenames, pattern},
-    Library,
+    reorganize::{load_album_names, load_cover_art, remove_empty_directories},
+    Library, ReorganizationScope,
 };
 
 impl Library {
@@ -55,6 +58,8 @@ impl Library {
             if let Err(err) = fs::remove_file(&path) {
                 log::warn!("Failed to remove track file {}: {err}", path.display());
             }
+
+            remove_empty_directories(&library_path, &path);
         }
 
         self.changed();
@@ -144,6 +149,7 @@ impl Library {
         // library folder.
         let mut staged = Vec::new();
         let mut prepared = Vec::with_capacity(tracks.len());
+        let mut existing_track_ids = Vec::new();
 
         // Naming and tagging the file of a new track needs the metadata of the
         // recording it belongs to. It is loaded once for the whole batch and
@@ -165,25 +171,44 @@ impl Library {
                 }
             });
 
+        let albums = match &recording {
+            Some(recording) => load_album_names(&recording.recording_id, &mut self.conn())
+                .unwrap_or_else(|err| {
+                    log::warn!("Failed to load the albums of a recording for naming: {err:?}");
+                    Vec::new()
+                }),
+            None => Vec::new(),
+        };
+
+        // A recording without a cover of its own takes the first one embedded
+        // in its new files. Like the tags, a cover that cannot be taken over
+        // does not fail the import.
+        let mut cover_art = recording.as_ref().and_then(|recording| {
+            load_cover_art(&folder, &recording.recording_id, &mut self.conn())
+        });
+        let mut new_cover_art = None;
+
         for (recording_index, track) in tracks {
             match track {
                 TrackUpdate::Existing { track_id, works } => {
+                    existing_track_ids.push(track_id.clone());
                     prepared.push((recording_index, PreparedTrack::Existing { track_id, works }));
                 }
                 TrackUpdate::New { path, works } => {
                     let Some(recording_id) = recording_id else {
-                        clean_up_staged(&staged, 0);
+                        clean_up_staged(&folder, &staged, 0);
                         bail!("Cannot import a track without the recording it belongs to");
                     };
 
                     let data = recording.as_ref().map(|recording| {
-                        pattern::TrackData::new(recording, recording_index, &works)
+                        pattern::TrackData::new(recording, recording_index, &works, &albums)
                     });
 
                     let stem = data
                         .as_ref()
                         .and_then(|data| filenames::render(&patterns.filename, data))
                         .unwrap_or_else(|| filenames::fallback_stem(recording_id, recording_index));
+                    let stem = cover_art::outside_cover_art_directory(&stem);
 
                     let library_path = unused_track_path(&folder, &stem, path.extension(), &staged);
 
@@ -191,8 +216,16 @@ impl Library {
                     let mut tmp_path = to_path.clone();
                     tmp_path.as_mut_os_string().push(".part");
 
-                    if let Err(err) = fs::copy(&path, &tmp_path) {
-                        clean_up_staged(&staged, 0);
+                    // The pattern can put the file into directories that do not
+                    // exist yet.
+                    let result = tmp_path
+                        .parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|()| fs::copy(&path, &tmp_path));
+
+                    if let Err(err) = result {
+                        remove_empty_directories(&folder, &tmp_path);
+                        clean_up_staged(&folder, &staged, 0);
                         return Err(err.into());
                     }
 
@@ -202,8 +235,31 @@ impl Library {
                     // rename below. Like naming, tagging may not fail an import:
                     // the file the user chose stays untouched either way, and a
                     // reorganization brings the tags in line later.
+                    if recording
+                        .as_ref()
+                        .is_some_and(|recording| recording.cover_art.is_none())
+                        && new_cover_art.is_none()
+                    {
+                        match take_over_cover_art(&folder, &path) {
+                            Ok(Some((path, picture))) => {
+                                new_cover_art = Some(path);
+                                cover_art = Some(picture);
+                            }
+                            Ok(None) => (),
+                            Err(err) => {
+                                log::warn!(
+                                    "Failed to take over the cover of {}: {err:?}",
+                                    path.display()
+                                );
+                            }
+                        }
+                    }
+
                     if let Some(data) = &data {
-                        let tags = audio_tags::AudioTags::render(&patterns, data, recording_index);
+                        let tags = audio_tags::AudioTags {
+                            cover_art: cover_art.clone(),
+                            ..audio_tags::AudioTags::render(&patterns, data, recording_index)
+                        };
 
                         if let Err(err) = audio_tags::write(&tmp_path, &tags) {
                             log::warn!("Failed to tag {}: {err:?}", tmp_path.display());
@@ -295,6 +351,17 @@ impl Library {
                 }
             }
 
+            if let (Some(recording_id), Some(new_cover_art)) = (recording_id, &new_cover_art) {
+                diesel::update(recordings::table)
+                    .filter(recordings::recording_id.eq(recording_id))
+                    .filter(recordings::cover_art.is_null())
+                    .set((
+                        recordings::cover_art.eq(new_cover_art),
+                        recordings::edited_at.eq(now),
+                    ))
+                    .execute(connection)?;
+            }
+
             // Moving the files into place is the last fallible step before the
             // commit, so that a failure here rolls the database back instead of
             // leaving track rows pointing at missing files.
@@ -307,7 +374,12 @@ impl Library {
         });
 
         if let Err(err) = result {
-            clean_up_staged(&staged, renamed);
+            clean_up_staged(&folder, &staged, renamed);
+
+            if let Some(new_cover_art) = &new_cover_art {
+                let _ = fs::remove_file(folder.join(new_cover_art));
+            }
+
             return Err(err);
         }
 
@@ -320,8 +392,26 @@ impl Library {
             if let Err(err) = fs::remove_file(&path) {
                 log::warn!("Failed to remove track file {}: {err}", path.display());
             }
+
+            remove_empty_directories(&folder, &path);
         }
 
+        // The files of existing tracks are named after their position and
+        // works, and a cover taken over from a new file goes into every file of
+        // the recording.
+        self.mark_stale(
+            connection,
+            ReorganizationScope {
+                track_ids: existing_track_ids,
+                recording_ids: recording_id
+                    .filter(|_| new_cover_art.is_some())
+                    .map(str::to_owned)
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+        );
+
         self.changed();
 
         Ok(())
@@ -361,11 +451,27 @@ struct StagedFile {
     pub to_path: PathBuf,
 }
 
+/// Copy the cover embedded in the audio file at `path` into the library
+/// `folder`, if it has one.
+///
+/// Returns the path of the copy relative to `folder`, along with the picture
+/// to embed into the imported files.
+fn take_over_cover_art(folder: &Path, path: &Path) -> Result<Option<(String, Picture)>> {
+    let Some(mut picture) = cover_art::read_embedded(path)? else {
+        return Ok(None);
+    };
+
+    let cover_art = cover_art::store(folder, &picture)?;
+    picture.set_pic_type(PictureType::CoverFront);
+
+    Ok(Some((cover_art, picture)))
+}
+
 /// Remove the files that a failed batch left behind.
 ///
 /// The first `renamed` files had already been moved to their destination when
 /// the batch failed, the remaining ones are still waiting next to it.
-fn clean_up_staged(staged: &[StagedFile], renamed: usize) {
+fn clean_up_staged(folder: &Path, staged: &[StagedFile], renamed: usize) {
     for (index, file) in staged.iter().enumerate() {
         let path = if index < renamed {
             &file.to_path
@@ -379,6 +485,8 @@ fn clean_up_staged(staged: &[StagedFile], renamed: usize) {
                 path.display()
             );
         }
+
+        remove_empty_directories(folder, path);
     }
 }
 
@@ -894,7 +1002,7 @@ mod tests {
         fs::write(&staged[0].to_path, b"renamed").unwrap();
         fs::write(&staged[1].tmp_path, b"still staged").unwrap();
 
-        clean_up_staged(&staged, 1);
+        clean_up_staged(dir.path(), &staged, 1);
 
         assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
     }
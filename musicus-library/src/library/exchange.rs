@@ -1,4 +1,5 @@
 use std::{
+    collections::{HashMap, HashSet},
     fs::{self, File},
     io::{BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
@@ -13,13 +14,14 @@ use tempfile::{NamedTempFile, TempDir};
 use tokio::io::AsyncWriteExt;
 use zip::{write::SimpleFileOptions, ZipWriter};
 
-use super::Library;
+use super::{cover_art, settings, sync, Library};
 use crate::{
     db::{
         self,
         schema::*,
         tables::{self, Source},
     },
+    error::EntityKind,
     format_translated,
     library::process::{spawn_process, Cancellation, ProcessHandle, ProcessMsg},
 };
@@ -31,7 +33,21 @@ const MANIFEST_NAME: &str = "manifest.json";
 ///
 /// Bump when the archive gains, loses or renames entries. Archives with a
 /// higher version are refused rather than partially understood.
-const ARCHIVE_FORMAT_VERSION: u32 = 1;
+///
+/// Version 2 added the cover images, version 3 the kind of archive.
+const ARCHIVE_FORMAT_VERSION: u32 = 3;
+
+/// What an archive is meant for, which decides what it contains.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ArchiveKind {
+    /// A library to hand to someone else, without anything personal.
+    #[default]
+    Export,
+    /// A copy of the library for its owner, including the listening history
+    /// and the private tags.
+    Backup,
+}
 
 /// Describes a `.muslib` archive.
 ///
@@ -45,6 +61,9 @@ struct ArchiveManifest {
     schema_version: i32,
     created_at: String,
     n_tracks: usize,
+    /// Archives written before version 3 are all exports.
+    #[serde(default)]
+    kind: ArchiveKind,
 }
 
 /// Read and check the manifest of an opened archive.
@@ -122,28 +141,50 @@ impl Library {
     /// will be overwritten. The work will be done in a background thread.
     ///
     /// Private tags are personal to this library and are left out, together
-    /// with every assignment referring to one.
+    /// with every assignment referring to one. So is the listening history.
     pub fn export_library_to_zip(&self, path: impl AsRef<Path>) -> Result<ProcessHandle> {
         log::info!(
             "Exporting library to ZIP at {}",
             path.as_ref().to_string_lossy()
         );
+
+        self.write_archive(path, ArchiveKind::Export)
+    }
+
+    /// Back up the whole music library to a ZIP archive at `path`, like
+    /// [`Library::export_library_to_zip`] but including the listening history
+    /// and the private tags.
+    ///
+    /// Importing the backup merges its listening history into the library it
+    /// is imported into.
+    pub fn backup_library_to_zip(&self, path: impl AsRef<Path>) -> Result<ProcessHandle> {
+        log::info!(
+            "Backing up library to ZIP at {}",
+            path.as_ref().to_string_lossy()
+        );
+
+        self.write_archive(path, ArchiveKind::Backup)
+    }
+
+    fn write_archive(&self, path: impl AsRef<Path>, kind: ArchiveKind) -> Result<ProcessHandle> {
         let connection = &mut *self.conn();
 
         let path = path.as_ref().to_owned();
         let library_folder = PathBuf::from(&self.folder());
         let tracks = tracks::table.load::<tables::Track>(connection)?;
+        let cover_art = cover_art::referenced(connection)?;
         let this_connection = self.connection.clone();
 
+        let export = ArchiveExport {
+            path,
+            kind,
+            library_folder,
+            tracks,
+            cover_art,
+        };
+
         Ok(spawn_process(move |sender, cancellation| {
-            export_library_to_zip_priv(
-                path,
-                library_folder,
-                this_connection,
-                tracks,
-                sender,
-                cancellation,
-            )
+            export_library_to_zip_priv(export, this_connection, sender, cancellation)
         }))
     }
 
@@ -169,6 +210,52 @@ impl Library {
         }))
     }
 
+    /// Update metadata from a local database instead of downloading it, for
+    /// machines without network access.
+    ///
+    /// `path` is either a `.musdb` file or a directory containing one. The
+    /// update works exactly like [`Library::import_metadata_from_url`]: the
+    /// file becomes the metadata catalogue and existing entities that allow
+    /// updates take over its metadata.
+    pub fn import_metadata_from_path(&self, path: impl AsRef<Path>) -> Result<ProcessHandle> {
+        let path = metadata_source_file(path.as_ref())?;
+        log::info!("Importing metadata from {}", path.display());
+
+        let this_connection = self.connection.clone();
+        let cache_dir = self.metadata_cache_dir.clone();
+
+        Ok(spawn_process(move |sender, _| {
+            import_metadata_from_path_priv(path, cache_dir, this_connection, sender)
+        }))
+    }
+
+    /// Work out what [`Library::import_metadata_from_path`] would change,
+    /// without changing anything.
+    ///
+    /// The report is sent on the returned receiver once the process has
+    /// finished successfully.
+    pub fn preview_metadata_from_path(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(ProcessHandle, async_channel::Receiver<MetadataUpdateReport>)> {
+        let path = metadata_source_file(path.as_ref())?;
+        let this_connection = self.connection.clone();
+
+        let (report_sender, report_receiver) = async_channel::bounded(1);
+
+        let handle = spawn_process(move |_, _| {
+            // Opening the database runs migrations, so work on a copy.
+            let tmp_file = NamedTempFile::new()?;
+            copy_to_file(File::open(&path)?, tmp_file.path())?;
+
+            let report = update_metadata_from_file(tmp_file.path(), this_connection, true, None)?;
+            let _ = report_sender.send_blocking(report);
+            Ok(())
+        });
+
+        Ok((handle, report_receiver))
+    }
+
     /// Import from metadata from a database file at `url`.
     pub fn import_metadata_from_url(&self, url: &str) -> Result<ProcessHandle> {
         log::info!("Importing metadata from URL {url}");
@@ -194,7 +281,7 @@ fn import_library_from_zip_priv(
     let mut archive = zip::ZipArchive::new(BufReader::new(fs::File::open(zip_path)?))?;
 
     // Refuse an archive this build cannot read before extracting anything.
-    read_manifest(&mut archive)?;
+    let manifest = read_manifest(&mut archive)?;
 
     let archive_db_file = archive.by_name("musicus.musdb")?;
     let tmp_db_file = NamedTempFile::new()?;
@@ -205,8 +292,79 @@ fn import_library_from_zip_priv(
 
     cancellation.check()?;
 
+    let archive_connection = &mut db::connect(&tmp_db_file.path().to_string_lossy())?;
+
+    // Which tracks the plays refer to has to be decided before the tracks of
+    // the archive are added.
+    let track_ids = {
+        let connection = &mut *db::lock_connection(&this_connection);
+        local_track_ids(archive_connection, connection)?
+    };
+
     // Import metadata.
-    let tracks = import_metadata_from_file(tmp_db_file.path(), source, this_connection, false)?;
+    let tracks = import_metadata_from_file(
+        tmp_db_file.path(),
+        source,
+        Arc::clone(&this_connection),
+        false,
+    )?;
+
+    if manifest.is_some_and(|manifest| manifest.kind == ArchiveKind::Backup) {
+        let _ = sender.send_blocking(ProcessMsg::Message(gettext("Importing listening history")));
+    }
+
+    // Exports have no listening history, but backups and archives from before
+    // exports were stripped do.
+    merge_listening_history(
+        archive_connection,
+        &mut db::lock_connection(&this_connection),
+        &track_ids,
+    )?;
+
+    settings::adopt(
+        archive_connection,
+        &mut db::lock_connection(&this_connection),
+    )?;
+
+    // Import cover images. They are few and small compared to the audio files,
+    // so they are not part of the progress.
+    let covers = cover_art::referenced(archive_connection)?;
+
+    for cover in covers {
+        cancellation.check()?;
+
+        let library_cover_path = library_folder.as_ref().join(&cover);
+
+        if library_cover_path.exists() {
+            continue;
+        }
+
+        // An archive whose library was missing a cover simply lacks it, which
+        // leaves the recording or album without one.
+        let archive_cover_file = match archive.by_name(&path_to_zip(&cover)?) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => {
+                log::warn!("The archive is missing the cover image {cover}");
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some(parent) = library_cover_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut part_path = library_cover_path.clone();
+        part_path.as_mut_os_string().push(".part");
+
+        let result = copy_to_file(archive_cover_file, &part_path)
+            .and_then(|()| Ok(fs::rename(&part_path, &library_cover_path)?));
+
+        if let Err(err) = result {
+            let _ = fs::remove_file(&part_path);
+            return Err(err);
+        }
+    }
 
     // Import audio files.
 
@@ -267,12 +425,134 @@ fn import_library_from_zip_priv(
     Ok(())
 }
 
+/// Map the IDs of the tracks in `archive_connection` to the tracks of this
+/// library they correspond to.
+///
+/// A track this library already has under the same ID is the same track. One
+/// that it has under another ID, because the file was imported again, is the
+/// one taking the same place in the same recording. Tracks this library does
+/// not have yet keep their IDs, because the import adds them as they are,
+/// unless it refuses them.
+fn local_track_ids(
+    archive_connection: &mut SqliteConnection,
+    connection: &mut SqliteConnection,
+) -> Result<HashMap<String, String>> {
+    let archive_tracks = tracks::table.load::<tables::Track>(archive_connection)?;
+
+    let local_tracks = tracks::table.load::<tables::Track>(connection)?;
+    let local_ids = local_tracks
+        .iter()
+        .map(|track| track.track_id.as_str())
+        .collect::<HashSet<_>>();
+    let by_position = local_tracks
+        .iter()
+        .map(|track| {
+            (
+                (track.recording_id.as_str(), track.recording_index),
+                track.track_id.as_str(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    Ok(archive_tracks
+        .iter()
+        .map(|track| {
+            let local_id = if local_ids.contains(track.track_id.as_str()) {
+                &track.track_id
+            } else {
+                by_position
+                    .get(&(track.recording_id.as_str(), track.recording_index))
+                    .copied()
+                    .unwrap_or(&track.track_id)
+            };
+
+            (track.track_id.clone(), local_id.to_owned())
+        })
+        .collect())
+}
+
+/// Add the plays and skips of `archive_connection` that this library does not
+/// have yet.
+///
+/// Both are identified by their ID, so importing the same archive twice or
+/// archives of libraries that share a history does not count anything twice.
+/// `track_ids` maps the tracks of the archive to those of this library. A
+/// track the import did not add after all is forgotten, like one that was
+/// deleted.
+fn merge_listening_history(
+    archive_connection: &mut SqliteConnection,
+    connection: &mut SqliteConnection,
+    track_ids: &HashMap<String, String>,
+) -> Result<()> {
+    let plays = plays::table.load::<tables::Play>(archive_connection)?;
+    let skips = skips::table.load::<tables::Skip>(archive_connection)?;
+
+    if plays.is_empty() && skips.is_empty() {
+        return Ok(());
+    }
+
+    connection.transaction::<(), Error, _>(|connection| {
+        let local_tracks = tracks::table
+            .select(tracks::track_id)
+            .load::<String>(connection)?
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        let local_track_id = |track_id: Option<String>| {
+            track_id
+                .and_then(|track_id| track_ids.get(&track_id))
+                .filter(|track_id| local_tracks.contains(*track_id))
+                .cloned()
+        };
+
+        for mut play in plays {
+            // The recording was imported along with the plays, unless this
+            // library refused it. A play of nothing cannot be counted.
+            if !recording_exists(connection, &play.recording_id)? {
+                continue;
+            }
+
+            play.track_id = local_track_id(play.track_id);
+
+            diesel::insert_into(plays::table)
+                .values(play)
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+        }
+
+        for mut skip in skips {
+            if !recording_exists(connection, &skip.recording_id)? {
+                continue;
+            }
+
+            skip.track_id = local_track_id(skip.track_id);
+
+            diesel::insert_into(skips::table)
+                .values(skip)
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn recording_exists(connection: &mut SqliteConnection, recording_id: &str) -> QueryResult<bool> {
+    Ok(recordings::table
+        .filter(recordings::recording_id.eq(recording_id))
+        .count()
+        .get_result::<i64>(connection)?
+        > 0)
+}
+
 /// Write all of `source` to a new file at `path` and make sure it reached the
 /// disk before returning.
 ///
 /// The caller renames the result into place, which is only safe once the bytes
 /// are durable.
-fn copy_to_file(source: impl Read, path: impl AsRef<Path>) -> Result<()> {
+pub(super) fn copy_to_file(source: impl Read, path: impl AsRef<Path>) -> Result<()> {
     let file = File::create(path)?;
 
     let mut writer = BufWriter::new(&file);
@@ -286,8 +566,8 @@ fn copy_to_file(source: impl Read, path: impl AsRef<Path>) -> Result<()> {
 }
 
 /// Copy the library database into a temporary directory, without the listening
-/// history and the privatetags, along with the assignments referring to those
-/// tags.
+/// history, the undo history, the records of past merges and the private tags,
+/// along with the assignments referring to those tags.
 ///
 /// An export is meant to be handed to someone else, so neither must be in the
 /// archive at all — not even as a row nothing points at. The copy is made with
@@ -299,9 +579,13 @@ fn copy_to_file(source: impl Read, path: impl AsRef<Path>) -> Result<()> {
 /// have gaps. Only their order matters, and the editors rewrite a work's or
 /// recording's tags as a whole anyway.
 ///
+/// A backup keeps the listening history and the private tags, which are its
+/// owner's own. Everything else is left out all the same: none of it means
+/// anything to another library.
+///
 /// The returned directory owns the copy and deletes it when dropped, so it has
 /// to outlive the export.
-fn database_for_export(connection: &mut SqliteConnection) -> Result<TempDir> {
+fn database_for_export(connection: &mut SqliteConnection, kind: ArchiveKind) -> Result<TempDir> {
     let dir = TempDir::new()?;
     let path = dir.path().join("musicus.musdb");
     let path = path
@@ -315,7 +599,23 @@ fn database_for_export(connection: &mut SqliteConnection) -> Result<TempDir> {
     let copy = &mut SqliteConnection::establish(path)?;
 
     copy.transaction::<_, Error, _>(|copy| {
+        diesel::delete(stale_tracks::table).execute(copy)?;
+
+        // The undo history holds earlier versions of rows, including removed
+        // plays and private tags.
+        diesel::delete(history_steps::table).execute(copy)?;
+        diesel::delete(history::table).execute(copy)?;
+
+        // So do the snapshots kept for splitting merges again.
+        diesel::delete(merge_references::table).execute(copy)?;
+        diesel::delete(merges::table).execute(copy)?;
+
+        if kind == ArchiveKind::Backup {
+            return Ok(());
+        }
+
         diesel::delete(plays::table).execute(copy)?;
+        diesel::delete(skips::table).execute(copy)?;
 
         let private_tag_ids = tags::table
             .filter(tags::private.eq(true))
@@ -340,23 +640,41 @@ fn database_for_export(connection: &mut SqliteConnection) -> Result<TempDir> {
     Ok(dir)
 }
 
+/// What goes into an archive, gathered before the export moves to the
+/// background.
+struct ArchiveExport {
+    path: PathBuf,
+    kind: ArchiveKind,
+    library_folder: PathBuf,
+    tracks: Vec<tables::Track>,
+    /// The cover images, relative to the library folder.
+    cover_art: Vec<String>,
+}
+
 fn export_library_to_zip_priv(
-    zip_path: impl AsRef<Path>,
-    library_folder: impl AsRef<Path>,
+    export: ArchiveExport,
     this_connection: Arc<Mutex<SqliteConnection>>,
-    tracks: Vec<tables::Track>,
     sender: &async_channel::Sender<ProcessMsg>,
     cancellation: &Cancellation,
 ) -> Result<()> {
+    let ArchiveExport {
+        path: zip_path,
+        kind,
+        library_folder,
+        tracks,
+        cover_art,
+    } = export;
+
     // Every export ships the sanitized copy rather than the file on disk. Every
     // library has a listening history to strip, so there is no case left where
     // shipping the original would be correct, and skipping the copy on a library
     // that happens to have no private tags would silently reintroduce the leak.
+    // Backups go through the same copy, which still drops the undo history.
     // The copy happens here rather than in the caller, so that the work does not
     // block the thread that started the export.
     let database = {
         let connection = &mut *db::lock_connection(&this_connection);
-        database_for_export(connection)?
+        database_for_export(connection, kind)?
     };
 
     cancellation.check()?;
@@ -370,6 +688,7 @@ fn export_library_to_zip_priv(
         schema_version: db::SCHEMA_VERSION,
         created_at: db::now().and_utc().to_rfc3339(),
         n_tracks: tracks.len(),
+        kind,
     };
 
     zip.start_file(MANIFEST_NAME, SimpleFileOptions::default())?;
@@ -383,6 +702,13 @@ fn export_library_to_zip_priv(
         bail!("The library database is missing");
     }
 
+    // A cover that is missing only costs the picture, so unlike a track file
+    // it is not worth a warning.
+    for cover in cover_art {
+        cancellation.check()?;
+        add_file_to_zip(&mut zip, library_folder.join(&cover), &cover)?;
+    }
+
     // avoid div by 0
     let n_tracks = tracks.len().max(1);
     let mut n_missing = 0;
@@ -393,7 +719,7 @@ fn export_library_to_zip_priv(
 
         if !add_file_to_zip(
             &mut zip,
-            library_folder.as_ref().join(&track.path),
+            library_folder.join(&track.path),
             &path_to_zip(&track.path)?,
         )? {
             n_missing += 1;
@@ -473,7 +799,9 @@ fn import_metadata_from_url_priv(
 
     let _ = sender.send_blocking(ProcessMsg::Message(gettext("Importing downloaded library")));
 
-    update_metadata_from_file(&db_path, this_connection)
+    update_metadata_from_file(&db_path, this_connection, false, Some(sender))?;
+
+    Ok(())
 }
 
 fn import_library_from_url_priv(
@@ -508,11 +836,124 @@ fn import_library_from_url_priv(
     )
 }
 
+/// What a metadata update changes, or would change in a dry run.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataUpdateReport {
+    /// Entities of this library whose metadata is replaced.
+    pub updated: Vec<MetadataUpdateEntry>,
+    /// Entities only the metadata database has. They are not added to the
+    /// library, but become available to pick from the metadata catalogue.
+    pub added: Vec<MetadataUpdateEntry>,
+}
+
+/// One entity in a [`MetadataUpdateReport`].
+#[derive(Clone, Debug)]
+pub struct MetadataUpdateEntry {
+    pub entity_kind: EntityKind,
+    pub entity_id: String,
+    /// The name before the update, or the new name for added entities.
+    pub name: String,
+}
+
+impl MetadataUpdateEntry {
+    fn new(connection: &mut SqliteConnection, entity_kind: EntityKind, entity_id: &str) -> Self {
+        Self {
+            entity_kind,
+            entity_id: entity_id.to_owned(),
+            name: sync::describe(connection, entity_kind, entity_id),
+        }
+    }
+}
+
+/// Reports the progress of a metadata update in whole percents.
+struct UpdateProgress<'a> {
+    sender: Option<&'a async_channel::Sender<ProcessMsg>>,
+    done: usize,
+    total: usize,
+}
+
+impl UpdateProgress<'_> {
+    fn step(&mut self) {
+        let percent = |done: usize| done * 100 / self.total.max(1);
+        let before = percent(self.done);
+        self.done += 1;
+
+        if let Some(sender) = self.sender {
+            if percent(self.done) != before {
+                let _ = sender
+                    .send_blocking(ProcessMsg::Progress(self.done as f64 / self.total as f64));
+            }
+        }
+    }
+}
+
+/// Whether the rows `new` differ from `current`, regardless of their order.
+fn rows_differ<T: serde::Serialize>(current: &[T], new: &[&T]) -> Result<bool> {
+    fn serialize<'a, T: serde::Serialize + 'a>(
+        rows: impl Iterator<Item = &'a T>,
+    ) -> Result<Vec<String>> {
+        let mut rows = rows
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<String>, _>>()?;
+        rows.sort();
+        Ok(rows)
+    }
+
+    Ok(serialize(current.iter())? != serialize(new.iter().copied())?)
+}
+
+/// Resolve a local metadata source to a database file. A directory has to
+/// contain either a library database or a downloaded metadata database.
+fn metadata_source_file(path: &Path) -> Result<PathBuf> {
+    if !path.is_dir() {
+        return Ok(path.to_owned());
+    }
+
+    [path.join("musicus.musdb"), metadata_file_path(path)]
+        .into_iter()
+        .find(|file| file.is_file())
+        .ok_or_else(|| anyhow!("{} contains no metadata database", path.display()))
+}
+
+fn import_metadata_from_path_priv(
+    path: PathBuf,
+    cache_dir: PathBuf,
+    this_connection: Arc<Mutex<SqliteConnection>>,
+    sender: &async_channel::Sender<ProcessMsg>,
+) -> Result<()> {
+    let _ = sender.send_blocking(ProcessMsg::Message(format_translated!(
+        gettext("Reading {}"),
+        path.to_string_lossy()
+    )));
+
+    // The file takes the place of a download, so that the catalogue offers
+    // the same entities afterwards. Opening it runs migrations, which must not
+    // touch the original.
+    let db_path = metadata_file_path(&cache_dir);
+    fs::create_dir_all(&cache_dir)?;
+
+    let tmp_file = NamedTempFile::new_in(&cache_dir)?;
+    copy_to_file(File::open(&path)?, tmp_file.path())?;
+    tmp_file.persist(&db_path)?;
+
+    let _ = sender.send_blocking(ProcessMsg::Message(gettext("Updating metadata")));
+
+    update_metadata_from_file(&db_path, this_connection, false, Some(sender))?;
+
+    Ok(())
+}
+
 /// Update metadata from the database file at `path`.
+///
+/// Only entities that this library already has and that allow updates are
+/// changed. With `dry_run`, nothing is written and the report tells what would
+/// have changed.
 fn update_metadata_from_file(
     path: impl AsRef<Path>,
     this_connection: Arc<Mutex<SqliteConnection>>,
-) -> Result<()> {
+    dry_run: bool,
+    sender: Option<&async_channel::Sender<ProcessMsg>>,
+) -> Result<MetadataUpdateReport> {
     let mut other_connection = db::connect(path.as_ref().to_str().unwrap())?;
 
     // Load all metadata from the archive.
@@ -539,239 +980,490 @@ fn update_metadata_from_file(
     let recording_tags =
         recording_tags::table.load::<tables::RecordingTag>(&mut other_connection)?;
 
+    let mut progress = UpdateProgress {
+        sender,
+        done: 0,
+        total: persons.len()
+            + roles.len()
+            + tags.len()
+            + instruments.len()
+            + works.len()
+            + ensembles.len()
+            + recordings.len()
+            + albums.len(),
+    };
+
+    let mut report = MetadataUpdateReport::default();
     let mut this_connection = db::lock_connection(&this_connection);
 
     this_connection.transaction::<(), Error, _>(|connection| {
         for person in persons {
-            let enable_updates = persons::table
-                .filter(persons::person_id.eq(&person.person_id))
-                .select(persons::enable_updates)
-                .first(connection)
-                .optional()?;
+            progress.step();
 
-            if enable_updates == Some(true) {
-                diesel::update(persons::table.filter(persons::person_id.eq(&person.person_id)))
-                    .set(persons::name.eq(person.name))
-                    .execute(connection)?;
+            let Some(current) = persons::table
+                .filter(persons::person_id.eq(&person.person_id))
+                .first::<tables::Person>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Person,
+                    &person.person_id,
+                ));
+                continue;
+            };
+
+            if current.enable_updates && current.name.0 != person.name.0 {
+                report.updated.push(MetadataUpdateEntry::new(
+                    connection,
+                    EntityKind::Person,
+                    &person.person_id,
+                ));
+
+                if !dry_run {
+                    diesel::update(persons::table.filter(persons::person_id.eq(&person.person_id)))
+                        .set(persons::name.eq(person.name))
+                        .execute(connection)?;
+                }
             }
         }
 
         for role in roles {
-            let enable_updates = roles::table
-                .filter(roles::role_id.eq(&role.role_id))
-                .select(roles::enable_updates)
-                .first(connection)
-                .optional()?;
+            progress.step();
 
-            if enable_updates == Some(true) {
-                diesel::update(roles::table.filter(roles::role_id.eq(&role.role_id)))
-                    .set(roles::name.eq(role.name))
-                    .execute(connection)?;
+            let Some(current) = roles::table
+                .filter(roles::role_id.eq(&role.role_id))
+                .first::<tables::Role>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Role,
+                    &role.role_id,
+                ));
+                continue;
+            };
+
+            if current.enable_updates && current.name.0 != role.name.0 {
+                report.updated.push(MetadataUpdateEntry::new(
+                    connection,
+                    EntityKind::Role,
+                    &role.role_id,
+                ));
+
+                if !dry_run {
+                    diesel::update(roles::table.filter(roles::role_id.eq(&role.role_id)))
+                        .set(roles::name.eq(role.name))
+                        .execute(connection)?;
+                }
             }
         }
 
         for tag in tags {
-            let enable_updates = tags::table
+            progress.step();
+
+            let Some(current) = tags::table
                 .filter(tags::tag_id.eq(&tag.tag_id))
-                .select(tags::enable_updates)
-                .first(connection)
-                .optional()?;
-
-            if enable_updates == Some(true) {
-                // Only the name is merged. Whether a tag takes a value decides
-                // what the assignments already in this library mean, so an
-                // update from the catalogue must not change it underneath them.
-                diesel::update(tags::table.filter(tags::tag_id.eq(&tag.tag_id)))
-                    .set(tags::name.eq(tag.name))
-                    .execute(connection)?;
+                .first::<tables::Tag>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Tag,
+                    &tag.tag_id,
+                ));
+                continue;
+            };
+
+            // Only the name is merged. Whether a tag takes a value decides
+            // what the assignments already in this library mean, so an
+            // update from the catalogue must not change it underneath them.
+            if current.enable_updates && current.name.0 != tag.name.0 {
+                report.updated.push(MetadataUpdateEntry::new(
+                    connection,
+                    EntityKind::Tag,
+                    &tag.tag_id,
+                ));
+
+                if !dry_run {
+                    diesel::update(tags::table.filter(tags::tag_id.eq(&tag.tag_id)))
+                        .set(tags::name.eq(tag.name))
+                        .execute(connection)?;
+                }
             }
         }
 
         for instrument in instruments {
-            let enable_updates = instruments::table
+            progress.step();
+
+            let Some(current) = instruments::table
                 .filter(instruments::instrument_id.eq(&instrument.instrument_id))
-                .select(instruments::enable_updates)
-                .first(connection)
-                .optional()?;
-
-            if enable_updates == Some(true) {
-                diesel::update(
-                    instruments::table
-                        .filter(instruments::instrument_id.eq(&instrument.instrument_id)),
-                )
-                .set(instruments::name.eq(instrument.name))
-                .execute(connection)?;
+                .first::<tables::Instrument>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Instrument,
+                    &instrument.instrument_id,
+                ));
+                continue;
+            };
+
+            if current.enable_updates && current.name.0 != instrument.name.0 {
+                report.updated.push(MetadataUpdateEntry::new(
+                    connection,
+                    EntityKind::Instrument,
+                    &instrument.instrument_id,
+                ));
+
+                if !dry_run {
+                    diesel::update(
+                        instruments::table
+                            .filter(instruments::instrument_id.eq(&instrument.instrument_id)),
+                    )
+                    .set(instruments::name.eq(instrument.name))
+                    .execute(connection)?;
+                }
             }
         }
 
         for work in works {
-            let enable_updates = works::table
+            progress.step();
+
+            let Some(current) = works::table
                 .filter(works::work_id.eq(&work.work_id))
-                .select(works::enable_updates)
-                .first(connection)
-                .optional()?;
+                .first::<tables::Work>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Work,
+                    &work.work_id,
+                ));
+                continue;
+            };
+
+            if !current.enable_updates {
+                continue;
+            }
 
-            if enable_updates == Some(true) {
-                diesel::update(works::table.filter(works::work_id.eq(&work.work_id)))
-                    .set(works::name.eq(work.name.clone()))
-                    .execute(connection)?;
+            let new_persons: Vec<&tables::WorkPerson> = work_persons
+                .iter()
+                .filter(|work_person| work_person.work_id == work.work_id)
+                .collect();
+            let new_instruments: Vec<&tables::WorkInstrument> = work_instruments
+                .iter()
+                .filter(|work_instrument| work_instrument.work_id == work.work_id)
+                .collect();
+            let new_tags: Vec<&tables::WorkTag> = work_tags
+                .iter()
+                .filter(|work_tag| work_tag.work_id == work.work_id)
+                .collect();
+
+            let changed = current.name.0 != work.name.0
+                || rows_differ(
+                    &work_persons::table
+                        .filter(work_persons::work_id.eq(&work.work_id))
+                        .load::<tables::WorkPerson>(connection)?,
+                    &new_persons,
+                )?
+                || rows_differ(
+                    &work_instruments::table
+                        .filter(work_instruments::work_id.eq(&work.work_id))
+                        .load::<tables::WorkInstrument>(connection)?,
+                    &new_instruments,
+                )?
+                || rows_differ(
+                    &work_tags::table
+                        .filter(work_tags::work_id.eq(&work.work_id))
+                        .load::<tables::WorkTag>(connection)?,
+                    &new_tags,
+                )?;
+
+            if !changed {
+                continue;
+            }
 
-                diesel::delete(work_persons::table.filter(work_persons::work_id.eq(&work.work_id)))
-                    .execute(connection)?;
+            report.updated.push(MetadataUpdateEntry::new(
+                connection,
+                EntityKind::Work,
+                &work.work_id,
+            ));
 
-                for work_person in work_persons
-                    .iter()
-                    .filter(|work_person| work_person.work_id == work.work_id)
-                {
-                    diesel::insert_into(work_persons::table)
-                        .values(work_person)
-                        .execute(connection)?;
-                }
+            if dry_run {
+                continue;
+            }
 
-                diesel::delete(
-                    work_instruments::table.filter(work_instruments::work_id.eq(&work.work_id)),
-                )
+            diesel::update(works::table.filter(works::work_id.eq(&work.work_id)))
+                .set(works::name.eq(work.name.clone()))
                 .execute(connection)?;
 
-                for work_instrument in work_instruments
-                    .iter()
-                    .filter(|work_instrument| work_instrument.work_id == work.work_id)
-                {
-                    diesel::insert_into(work_instruments::table)
-                        .values(work_instrument)
-                        .execute(connection)?;
-                }
+            diesel::delete(work_persons::table.filter(work_persons::work_id.eq(&work.work_id)))
+                .execute(connection)?;
 
-                diesel::delete(work_tags::table.filter(work_tags::work_id.eq(&work.work_id)))
+            for work_person in new_persons {
+                diesel::insert_into(work_persons::table)
+                    .values(work_person)
                     .execute(connection)?;
+            }
 
-                for work_tag in work_tags
-                    .iter()
-                    .filter(|work_tag| work_tag.work_id == work.work_id)
-                {
-                    diesel::insert_into(work_tags::table)
-                        .values(work_tag)
-                        .execute(connection)?;
-                }
+            diesel::delete(
+                work_instruments::table.filter(work_instruments::work_id.eq(&work.work_id)),
+            )
+            .execute(connection)?;
+
+            for work_instrument in new_instruments {
+                diesel::insert_into(work_instruments::table)
+                    .values(work_instrument)
+                    .execute(connection)?;
+            }
+
+            diesel::delete(work_tags::table.filter(work_tags::work_id.eq(&work.work_id)))
+                .execute(connection)?;
+
+            for work_tag in new_tags {
+                diesel::insert_into(work_tags::table)
+                    .values(work_tag)
+                    .execute(connection)?;
             }
         }
 
         for ensemble in ensembles {
-            let enable_updates = ensembles::table
+            progress.step();
+
+            let Some(current) = ensembles::table
                 .filter(ensembles::ensemble_id.eq(&ensemble.ensemble_id))
-                .select(ensembles::enable_updates)
-                .first(connection)
-                .optional()?;
-
-            if enable_updates == Some(true) {
-                diesel::update(
-                    ensembles::table.filter(ensembles::ensemble_id.eq(&ensemble.ensemble_id)),
-                )
-                .set(ensembles::name.eq(ensemble.name.clone()))
-                .execute(connection)?;
+                .first::<tables::Ensemble>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Ensemble,
+                    &ensemble.ensemble_id,
+                ));
+                continue;
+            };
+
+            if !current.enable_updates {
+                continue;
+            }
 
-                diesel::delete(
-                    ensemble_persons::table
-                        .filter(ensemble_persons::ensemble_id.eq(&ensemble.ensemble_id)),
-                )
-                .execute(connection)?;
+            let new_persons: Vec<&tables::EnsemblePerson> = ensemble_persons
+                .iter()
+                .filter(|ensemble_person| ensemble_person.ensemble_id == ensemble.ensemble_id)
+                .collect();
+
+            let changed = current.name.0 != ensemble.name.0
+                || rows_differ(
+                    &ensemble_persons::table
+                        .filter(ensemble_persons::ensemble_id.eq(&ensemble.ensemble_id))
+                        .load::<tables::EnsemblePerson>(connection)?,
+                    &new_persons,
+                )?;
+
+            if !changed {
+                continue;
+            }
 
-                for ensemble_person in ensemble_persons
-                    .iter()
-                    .filter(|ensemble_person| ensemble_person.ensemble_id == ensemble.ensemble_id)
-                {
-                    diesel::insert_into(ensemble_persons::table)
-                        .values(ensemble_person)
-                        .execute(connection)?;
-                }
+            report.updated.push(MetadataUpdateEntry::new(
+                connection,
+                EntityKind::Ensemble,
+                &ensemble.ensemble_id,
+            ));
+
+            if dry_run {
+                continue;
+            }
+
+            diesel::update(
+                ensembles::table.filter(ensembles::ensemble_id.eq(&ensemble.ensemble_id)),
+            )
+            .set(ensembles::name.eq(ensemble.name.clone()))
+            .execute(connection)?;
+
+            diesel::delete(
+                ensemble_persons::table
+                    .filter(ensemble_persons::ensemble_id.eq(&ensemble.ensemble_id)),
+            )
+            .execute(connection)?;
+
+            for ensemble_person in new_persons {
+                diesel::insert_into(ensemble_persons::table)
+                    .values(ensemble_person)
+                    .execute(connection)?;
             }
         }
 
         for recording in recordings {
-            let enable_updates = recordings::table
+            progress.step();
+
+            let Some(current) = recordings::table
                 .filter(recordings::recording_id.eq(&recording.recording_id))
-                .select(recordings::enable_updates)
-                .first(connection)
-                .optional()?;
-
-            if enable_updates == Some(true) {
-                diesel::delete(
-                    recording_tags::table
-                        .filter(recording_tags::recording_id.eq(&recording.recording_id)),
-                )
-                .execute(connection)?;
+                .first::<tables::Recording>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Recording,
+                    &recording.recording_id,
+                ));
+                continue;
+            };
+
+            if !current.enable_updates {
+                continue;
+            }
 
-                for recording_tag in recording_tags
-                    .iter()
-                    .filter(|recording_tag| recording_tag.recording_id == recording.recording_id)
-                {
-                    diesel::insert_into(recording_tags::table)
-                        .values(recording_tag)
-                        .execute(connection)?;
-                }
+            let new_tags: Vec<&tables::RecordingTag> = recording_tags
+                .iter()
+                .filter(|recording_tag| recording_tag.recording_id == recording.recording_id)
+                .collect();
+            let new_persons: Vec<&tables::RecordingPerson> = recording_persons
+                .iter()
+                .filter(|recording_person| recording_person.recording_id == recording.recording_id)
+                .collect();
+            let new_ensembles: Vec<&tables::RecordingEnsemble> = recording_ensembles
+                .iter()
+                .filter(|recording_ensemble| {
+                    recording_ensemble.recording_id == recording.recording_id
+                })
+                .collect();
+
+            let changed = rows_differ(
+                &recording_tags::table
+                    .filter(recording_tags::recording_id.eq(&recording.recording_id))
+                    .load::<tables::RecordingTag>(connection)?,
+                &new_tags,
+            )? || rows_differ(
+                &recording_persons::table
+                    .filter(recording_persons::recording_id.eq(&recording.recording_id))
+                    .load::<tables::RecordingPerson>(connection)?,
+                &new_persons,
+            )? || rows_differ(
+                &recording_ensembles::table
+                    .filter(recording_ensembles::recording_id.eq(&recording.recording_id))
+                    .load::<tables::RecordingEnsemble>(connection)?,
+                &new_ensembles,
+            )?;
+
+            if !changed {
+                continue;
+            }
 
-                diesel::delete(
-                    recording_persons::table
-                        .filter(recording_persons::recording_id.eq(&recording.recording_id)),
-                )
-                .execute(connection)?;
+            report.updated.push(MetadataUpdateEntry::new(
+                connection,
+                EntityKind::Recording,
+                &recording.recording_id,
+            ));
 
-                for recording_person in recording_persons.iter().filter(|recording_person| {
-                    recording_person.recording_id == recording.recording_id
-                }) {
-                    diesel::insert_into(recording_persons::table)
-                        .values(recording_person)
-                        .execute(connection)?;
-                }
+            if dry_run {
+                continue;
+            }
 
-                diesel::delete(
-                    recording_ensembles::table
-                        .filter(recording_ensembles::recording_id.eq(&recording.recording_id)),
-                )
-                .execute(connection)?;
+            diesel::delete(
+                recording_tags::table
+                    .filter(recording_tags::recording_id.eq(&recording.recording_id)),
+            )
+            .execute(connection)?;
 
-                for recording_ensemble in recording_ensembles.iter().filter(|recording_ensemble| {
-                    recording_ensemble.recording_id == recording.recording_id
-                }) {
-                    diesel::insert_into(recording_ensembles::table)
-                        .values(recording_ensemble)
-                        .execute(connection)?;
-                }
+            for recording_tag in new_tags {
+                diesel::insert_into(recording_tags::table)
+                    .values(recording_tag)
+                    .execute(connection)?;
+            }
+
+            diesel::delete(
+                recording_persons::table
+                    .filter(recording_persons::recording_id.eq(&recording.recording_id)),
+            )
+            .execute(connection)?;
+
+            for recording_person in new_persons {
+                diesel::insert_into(recording_persons::table)
+                    .values(recording_person)
+                    .execute(connection)?;
+            }
+
+            diesel::delete(
+                recording_ensembles::table
+                    .filter(recording_ensembles::recording_id.eq(&recording.recording_id)),
+            )
+            .execute(connection)?;
+
+            for recording_ensemble in new_ensembles {
+                diesel::insert_into(recording_ensembles::table)
+                    .values(recording_ensemble)
+                    .execute(connection)?;
             }
         }
 
         for album in albums {
-            let enable_updates = albums::table
+            progress.step();
+
+            let Some(current) = albums::table
                 .filter(albums::album_id.eq(&album.album_id))
-                .select(albums::enable_updates)
-                .first(connection)
-                .optional()?;
+                .first::<tables::Album>(connection)
+                .optional()?
+            else {
+                report.added.push(MetadataUpdateEntry::new(
+                    &mut other_connection,
+                    EntityKind::Album,
+                    &album.album_id,
+                ));
+                continue;
+            };
+
+            if !current.enable_updates {
+                continue;
+            }
 
-            if enable_updates == Some(true) {
-                diesel::update(albums::table.filter(albums::album_id.eq(&album.album_id)))
-                    .set(albums::name.eq(album.name.clone()))
-                    .execute(connection)?;
+            let new_recordings: Vec<&tables::AlbumRecording> = album_recordings
+                .iter()
+                .filter(|album_recording| album_recording.album_id == album.album_id)
+                .collect();
+
+            let changed = current.name.0 != album.name.0
+                || rows_differ(
+                    &album_recordings::table
+                        .filter(album_recordings::album_id.eq(&album.album_id))
+                        .load::<tables::AlbumRecording>(connection)?,
+                    &new_recordings,
+                )?;
+
+            if !changed {
+                continue;
+            }
+
+            report.updated.push(MetadataUpdateEntry::new(
+                connection,
+                EntityKind::Album,
+                &album.album_id,
+            ));
+
+            if dry_run {
+                continue;
+            }
 
-                diesel::delete(
-                    album_recordings::table.filter(album_recordings::album_id.eq(&album.album_id)),
-                )
+            diesel::update(albums::table.filter(albums::album_id.eq(&album.album_id)))
+                .set(albums::name.eq(album.name.clone()))
                 .execute(connection)?;
 
-                for album_recording in album_recordings
-                    .iter()
-                    .filter(|album_recording| album_recording.album_id == album.album_id)
-                {
-                    diesel::insert_into(album_recordings::table)
-                        .values(album_recording)
-                        .execute(connection)?;
-                }
+            diesel::delete(
+                album_recordings::table.filter(album_recordings::album_id.eq(&album.album_id)),
+            )
+            .execute(connection)?;
+
+            for album_recording in new_recordings {
+                diesel::insert_into(album_recordings::table)
+                    .values(album_recording)
+                    .execute(connection)?;
             }
         }
 
         Ok(())
     })?;
 
-    Ok(())
+    Ok(report)
 }
 
 /// Import metadata from the database file at `path`.
@@ -922,6 +1614,12 @@ fn import_metadata_from_file(
 
         for mut recording in recordings {
             recording.source = source;
+
+            // Without the files, there are no covers to refer to.
+            if ignore_tracks {
+                recording.cover_art = None;
+            }
+
             recording.created_at = now;
             recording.edited_at = now;
             recording.last_used_at = now;
@@ -975,6 +1673,11 @@ fn import_metadata_from_file(
 
         for mut album in albums {
             album.source = source;
+
+            if ignore_tracks {
+                album.cover_art = None;
+            }
+
             album.created_at = now;
             album.edited_at = now;
             album.last_used_at = now;
@@ -1131,6 +1834,14 @@ mod tests {
         }
     }
 
+    /// Wait for a metadata preview to finish, returning its report.
+    fn wait_for_preview(
+        (handle, report_receiver): (ProcessHandle, async_channel::Receiver<MetadataUpdateReport>),
+    ) -> MetadataUpdateReport {
+        wait_for_result(handle).unwrap();
+        report_receiver.recv_blocking().unwrap()
+    }
+
     /// Drain a process channel, returning its result together with every
     /// warning it reported on the way.
     fn wait_for_result_and_warnings(handle: ProcessHandle) -> (Result<()>, Vec<String>) {
@@ -1215,7 +1926,8 @@ mod tests {
         let tracks = source
             .tracks_for_recording(&recording.recording_id)
             .unwrap();
-        source.track_played(&tracks[0].track_id).unwrap();
+        source.track_played(&tracks[0].track_id, 60_000).unwrap();
+        source.track_skipped(&tracks[0].track_id, 5_000).unwrap();
 
         let zip_path = source_dir.path().join("export.muslib");
         wait_for_result(source.export_library_to_zip(&zip_path).unwrap()).unwrap();
@@ -1224,6 +1936,8 @@ mod tests {
         let archive = &mut database_in_archive(&zip_path, &archive_dir);
         let plays = plays::table.count().get_result::<i64>(archive).unwrap();
         assert_eq!(plays, 0, "the archive must not carry any plays");
+        let skips = skips::table.count().get_result::<i64>(archive).unwrap();
+        assert_eq!(skips, 0, "the archive must not carry any skips");
 
         // Exporting is not supposed to cost the library its own statistics.
         let plays = plays::table
@@ -1269,6 +1983,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cover_images_travel_with_the_archive() {
+        let source_dir = TempDir::new().unwrap();
+        let source_cache_dir = TempDir::new().unwrap();
+        let source = Library::new(source_dir.path(), source_cache_dir.path()).unwrap();
+
+        let track_source_file = source_dir.path().join("source_track.mp3");
+        fs::write(&track_source_file, b"not actually audio").unwrap();
+        let recording = populate(&source, &track_source_file);
+
+        let image = source_dir.path().join("cover.png");
+        fs::write(&image, b"\x89PNG\r\n\x1a\n\0\0\0\0").unwrap();
+        source
+            .set_recording_cover_art(&recording.recording_id, Some(&image))
+            .unwrap();
+
+        let zip_path = source_dir.path().join("export.muslib");
+        wait_for_result(source.export_library_to_zip(&zip_path).unwrap()).unwrap();
+
+        let dest_dir = TempDir::new().unwrap();
+        let dest_cache_dir = TempDir::new().unwrap();
+        let dest = Library::new(dest_dir.path(), dest_cache_dir.path()).unwrap();
+        wait_for_result(
+            dest.import_library_from_zip(&zip_path, Source::Import)
+                .unwrap(),
+        )
+        .unwrap();
+
+        let cover_art = cover_art::referenced(&mut dest.conn()).unwrap();
+        assert_eq!(cover_art.len(), 1);
+        assert_eq!(
+            fs::read(dest_dir.path().join(&cover_art[0])).unwrap(),
+            fs::read(&image).unwrap()
+        );
+    }
+
+    #[test]
+    fn settings_travel_with_the_archive_without_overriding_existing_ones() {
+        let source_dir = TempDir::new().unwrap();
+        let source_cache_dir = TempDir::new().unwrap();
+        let source = Library::new(source_dir.path(), source_cache_dir.path()).unwrap();
+
+        let track_source_file = source_dir.path().join("source_track.mp3");
+        fs::write(&track_source_file, b"not actually audio").unwrap();
+        populate(&source, &track_source_file);
+
+        source
+            .set_setting(settings::FILENAME_PATTERN, "{work}/{part}")
+            .unwrap();
+        source
+            .set_setting(settings::TITLE_PATTERN, "{part}")
+            .unwrap();
+
+        let zip_path = source_dir.path().join("export.muslib");
+        wait_for_result(source.export_library_to_zip(&zip_path).unwrap()).unwrap();
+
+        let dest_dir = TempDir::new().unwrap();
+        let dest_cache_dir = TempDir::new().unwrap();
+        let dest = Library::new(dest_dir.path(), dest_cache_dir.path()).unwrap();
+        dest.set_setting(settings::TITLE_PATTERN, "{work}").unwrap();
+        wait_for_result(
+            dest.import_library_from_zip(&zip_path, Source::Import)
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            dest.setting(settings::FILENAME_PATTERN).unwrap().as_deref(),
+            Some("{work}/{part}")
+        );
+        assert_eq!(
+            dest.setting(settings::TITLE_PATTERN).unwrap().as_deref(),
+            Some("{work}")
+        );
+    }
+
     /// A private tag is personal to its library: neither the tag nor the
     /// assignment referring to it may end up in an archive.
     #[test]
@@ -1369,6 +2159,7 @@ mod tests {
         assert_eq!(manifest.format_version, ARCHIVE_FORMAT_VERSION);
         assert_eq!(manifest.schema_version, db::SCHEMA_VERSION);
         assert_eq!(manifest.n_tracks, 1);
+        assert_eq!(manifest.kind, ArchiveKind::Export);
     }
 
     /// An archive claiming a format this build does not know must be refused
@@ -1389,6 +2180,7 @@ mod tests {
                 schema_version: db::SCHEMA_VERSION,
                 created_at: "2026-01-01T00:00:00+00:00".to_owned(),
                 n_tracks: 0,
+                kind: ArchiveKind::Export,
             })
             .unwrap()
             .as_bytes(),
@@ -1530,7 +2322,7 @@ mod tests {
             .execute(&mut remote_connection)
             .unwrap();
 
-        update_metadata_from_file(&remote_db_path, dest.connection.clone()).unwrap();
+        update_metadata_from_file(&remote_db_path, dest.connection.clone(), false, None).unwrap();
 
         let after_disabled = dest.search_persons("Original Name").unwrap();
         assert_eq!(after_disabled.len(), 1, "name must stay unchanged");
@@ -1541,12 +2333,89 @@ mod tests {
             .execute(&mut *dest.conn())
             .unwrap();
 
-        update_metadata_from_file(&remote_db_path, dest.connection.clone()).unwrap();
+        update_metadata_from_file(&remote_db_path, dest.connection.clone(), false, None).unwrap();
 
         let after_enabled = dest.search_persons("Renamed").unwrap();
         assert_eq!(after_enabled.len(), 1, "name should now be updated");
     }
 
+    /// A metadata database in `dir` with one person that `library` also has
+    /// under a different name, and one it does not have.
+    fn metadata_database(dir: &TempDir, library: &Library) -> (PathBuf, String) {
+        let person = library.create_person(translated("Old Name"), true).unwrap();
+
+        let path = dir.path().join("musicus.musdb");
+        let mut connection = db::connect(path.to_str().unwrap()).unwrap();
+        let now = db::now();
+
+        for (person_id, name) in [(person.person_id.as_str(), "New Name"), ("other", "Other")] {
+            diesel::insert_into(persons::table)
+                .values(tables::Person {
+                    person_id: person_id.to_owned(),
+                    name: translated(name),
+                    source: Source::Metadata,
+                    enable_updates: true,
+                    created_at: now,
+                    edited_at: now,
+                    last_used_at: now,
+                })
+                .execute(&mut connection)
+                .unwrap();
+        }
+
+        (path, person.person_id)
+    }
+
+    #[test]
+    fn a_metadata_preview_reports_changes_without_making_them() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let metadata_dir = TempDir::new().unwrap();
+        let (path, person_id) = metadata_database(&metadata_dir, &library);
+
+        let preview = library.preview_metadata_from_path(&path).unwrap();
+        let report = wait_for_preview(preview);
+
+        assert_eq!(report.updated.len(), 1);
+        assert_eq!(report.updated[0].entity_id, person_id);
+        assert_eq!(report.updated[0].name, "Old Name");
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.added[0].name, "Other");
+
+        assert_eq!(library.search_persons("Old Name").unwrap().len(), 1);
+        assert!(!metadata_file_path(cache_dir.path()).exists());
+    }
+
+    /// Updating from a directory behaves like a download of its database.
+    #[test]
+    fn metadata_is_updated_from_a_local_directory() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let metadata_dir = TempDir::new().unwrap();
+        metadata_database(&metadata_dir, &library);
+
+        wait_for_result(
+            library
+                .import_metadata_from_path(metadata_dir.path())
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(library.search_persons("New Name").unwrap().len(), 1);
+        assert!(metadata_file_path(cache_dir.path()).exists());
+
+        let report = wait_for_preview(
+            library
+                .preview_metadata_from_path(metadata_dir.path())
+                .unwrap(),
+        );
+        assert!(report.updated.is_empty());
+    }
+
     /// Export an archive of a library populated with one track, returning the
     /// archive path and the library relative path of that track.
     fn export_with_one_track(dir: &TempDir, cache_dir: &TempDir) -> (PathBuf, PathBuf) {
@@ -1668,4 +2537,248 @@ mod tests {
             "the missing track must simply not be in the archive"
         );
     }
+
+    /// A backup is for its owner and keeps what an export leaves out.
+    #[test]
+    fn backup_keeps_the_listening_history_and_private_tags() {
+        let source_dir = TempDir::new().unwrap();
+        let source_cache_dir = TempDir::new().unwrap();
+        let source = Library::new(source_dir.path(), source_cache_dir.path()).unwrap();
+
+        let track_source_file = source_dir.path().join("source_track.mp3");
+        fs::write(&track_source_file, b"not actually audio").unwrap();
+        let recording = populate(&source, &track_source_file);
+
+        let private = source
+            .create_tag(translated("Favourite"), false, true, true)
+            .unwrap();
+
+        source
+            .update_recording(
+                &recording.recording_id,
+                recording.work.clone(),
+                Vec::new(),
+                Vec::new(),
+                vec![crate::db::models::TagValue {
+                    tag: private.clone(),
+                    value: None,
+                }],
+                None,
+                true,
+            )
+            .unwrap();
+
+        let tracks = source
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap();
+        source.track_played(&tracks[0].track_id, 60_000).unwrap();
+        source.track_skipped(&tracks[0].track_id, 5_000).unwrap();
+
+        let zip_path = source_dir.path().join("backup.muslib");
+        wait_for_result(source.backup_library_to_zip(&zip_path).unwrap()).unwrap();
+
+        let mut archive =
+            zip::ZipArchive::new(BufReader::new(File::open(&zip_path).unwrap())).unwrap();
+        let manifest = read_manifest(&mut archive).unwrap().unwrap();
+        assert_eq!(manifest.kind, ArchiveKind::Backup);
+
+        let archive_dir = TempDir::new().unwrap();
+        let archive = &mut database_in_archive(&zip_path, &archive_dir);
+
+        let plays = plays::table.count().get_result::<i64>(archive).unwrap();
+        assert_eq!(plays, 1);
+        let skips = skips::table.count().get_result::<i64>(archive).unwrap();
+        assert_eq!(skips, 1);
+
+        let tag_ids = recording_tags::table
+            .select(recording_tags::tag_id)
+            .load::<String>(archive)
+            .unwrap();
+        assert_eq!(tag_ids, vec![private.tag_id]);
+
+        // The undo history is still nobody's business.
+        let steps = history::table.count().get_result::<i64>(archive).unwrap();
+        assert_eq!(steps, 0);
+    }
+
+    #[test]
+    fn importing_a_backup_twice_counts_each_play_once() {
+        let source_dir = TempDir::new().unwrap();
+        let source_cache_dir = TempDir::new().unwrap();
+        let source = Library::new(source_dir.path(), source_cache_dir.path()).unwrap();
+
+        let track_source_file = source_dir.path().join("source_track.mp3");
+        fs::write(&track_source_file, b"not actually audio").unwrap();
+        let recording = populate(&source, &track_source_file);
+
+        let tracks = source
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap();
+        let first = source.track_played(&tracks[0].track_id, 60_000).unwrap();
+        let second = source.track_played(&tracks[0].track_id, 60_000).unwrap();
+
+        let zip_path = source_dir.path().join("backup.muslib");
+        wait_for_result(source.backup_library_to_zip(&zip_path).unwrap()).unwrap();
+
+        let dest_dir = TempDir::new().unwrap();
+        let dest_cache_dir = TempDir::new().unwrap();
+        let dest = Library::new(dest_dir.path(), dest_cache_dir.path()).unwrap();
+
+        for _ in 0..2 {
+            wait_for_result(
+                dest.import_library_from_zip(&zip_path, Source::Import)
+                    .unwrap(),
+            )
+            .unwrap();
+        }
+
+        let mut play_ids = dest
+            .play_log(0, 10)
+            .unwrap()
+            .into_iter()
+            .map(|play| play.play_id)
+            .collect::<Vec<_>>();
+        play_ids.sort();
+
+        let mut expected = vec![first, second];
+        expected.sort();
+        assert_eq!(play_ids, expected);
+
+        let log = dest.play_log(0, 10).unwrap();
+        assert!(log
+            .iter()
+            .all(|play| play.track.as_ref().unwrap().track_id == tracks[0].track_id));
+    }
+
+    /// A library whose files were imported again has the same tracks under
+    /// other IDs. The plays and skips of a backup belong to those.
+    #[test]
+    fn plays_of_a_backup_follow_re_imported_tracks() {
+        let source_dir = TempDir::new().unwrap();
+        let source_cache_dir = TempDir::new().unwrap();
+        let source = Library::new(source_dir.path(), source_cache_dir.path()).unwrap();
+
+        let track_source_file = source_dir.path().join("source_track.mp3");
+        fs::write(&track_source_file, b"not actually audio").unwrap();
+        let recording = populate(&source, &track_source_file);
+
+        let zip_path = source_dir.path().join("export.muslib");
+        wait_for_result(source.export_library_to_zip(&zip_path).unwrap()).unwrap();
+
+        let dest_dir = TempDir::new().unwrap();
+        let dest_cache_dir = TempDir::new().unwrap();
+        let dest = Library::new(dest_dir.path(), dest_cache_dir.path()).unwrap();
+        wait_for_result(
+            dest.import_library_from_zip(&zip_path, Source::Import)
+                .unwrap(),
+        )
+        .unwrap();
+
+        let dest_tracks = dest.tracks_for_recording(&recording.recording_id).unwrap();
+        dest.delete_track(&dest_tracks[0]).unwrap();
+        dest.import_track(
+            &track_source_file,
+            &recording.recording_id,
+            0,
+            vec![recording.work.clone()],
+        )
+        .unwrap();
+        let re_imported = dest.tracks_for_recording(&recording.recording_id).unwrap()[0]
+            .track_id
+            .clone();
+
+        let source_tracks = source
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap();
+        assert_ne!(source_tracks[0].track_id, re_imported);
+        source
+            .track_played(&source_tracks[0].track_id, 60_000)
+            .unwrap();
+        source
+            .track_skipped(&source_tracks[0].track_id, 5_000)
+            .unwrap();
+
+        let backup_path = source_dir.path().join("backup.muslib");
+        wait_for_result(source.backup_library_to_zip(&backup_path).unwrap()).unwrap();
+        wait_for_result(
+            dest.import_library_from_zip(&backup_path, Source::Import)
+                .unwrap(),
+        )
+        .unwrap();
+
+        let log = dest.play_log(0, 10).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].track.as_ref().unwrap().track_id, re_imported);
+
+        let skipped = skips::table
+            .select(skips::track_id)
+            .load::<Option<String>>(&mut *dest.conn())
+            .unwrap();
+        assert_eq!(skipped, vec![Some(re_imported)]);
+    }
+
+    /// A play of an archive track that never made it into this library still
+    /// counts for its recording, just without the track.
+    #[test]
+    fn plays_of_tracks_the_import_did_not_add_lose_their_track() {
+        let source_dir = TempDir::new().unwrap();
+        let source_cache_dir = TempDir::new().unwrap();
+        let source = Library::new(source_dir.path(), source_cache_dir.path()).unwrap();
+
+        let track_source_file = source_dir.path().join("source_track.mp3");
+        fs::write(&track_source_file, b"not actually audio").unwrap();
+        let recording = populate(&source, &track_source_file);
+
+        let zip_path = source_dir.path().join("export.muslib");
+        wait_for_result(source.export_library_to_zip(&zip_path).unwrap()).unwrap();
+
+        let dest_dir = TempDir::new().unwrap();
+        let dest_cache_dir = TempDir::new().unwrap();
+        let dest = Library::new(dest_dir.path(), dest_cache_dir.path()).unwrap();
+        wait_for_result(
+            dest.import_library_from_zip(&zip_path, Source::Import)
+                .unwrap(),
+        )
+        .unwrap();
+
+        let dest_tracks = dest.tracks_for_recording(&recording.recording_id).unwrap();
+        dest.delete_track(&dest_tracks[0]).unwrap();
+
+        let source_tracks = source
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap();
+        source
+            .track_played(&source_tracks[0].track_id, 60_000)
+            .unwrap();
+        source
+            .track_skipped(&source_tracks[0].track_id, 5_000)
+            .unwrap();
+
+        let backup_path = source_dir.path().join("backup.muslib");
+        wait_for_result(source.backup_library_to_zip(&backup_path).unwrap()).unwrap();
+
+        // Merge without importing, as if the import had refused the track.
+        let archive_dir = TempDir::new().unwrap();
+        let archive = &mut database_in_archive(&backup_path, &archive_dir);
+        let connection = &mut *dest.conn();
+        let track_ids = local_track_ids(archive, connection).unwrap();
+        assert_eq!(
+            track_ids.get(&source_tracks[0].track_id),
+            Some(&source_tracks[0].track_id)
+        );
+
+        merge_listening_history(archive, connection, &track_ids).unwrap();
+
+        let played = plays::table
+            .select((plays::recording_id, plays::track_id))
+            .load::<(String, Option<String>)>(connection)
+            .unwrap();
+        assert_eq!(played, vec![(recording.recording_id.clone(), None)]);
+
+        let skipped = skips::table
+            .select(skips::track_id)
+            .load::<Option<String>>(connection)
+            .unwrap();
+        assert_eq!(skipped, vec![None]);
+    }
 }
@@ -0,0 +1,425 @@
+//! Undoing edits, deletions and merges.
+//!
+//! Every user action on an entity is recorded as one change in the `history`
+//! table. While a change is recorded, triggers on the library tables write a
+//! statement that restores the previous state of each row the action touches,
+//! including the rows removed by cascading deletes. Undoing a change runs those
+//! statements in reverse and forgets the change.
+//!
+//! Only the most recent changes are kept. Imports, metadata updates and
+//! synchronization are not recorded, so a change that predates one of them may
+//! no longer apply cleanly. Undoing it then fails without touching the library.
+
+use anyhow::{bail, Error, Result};
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, result::Error as DieselError, SqliteConnection};
+
+use super::{sync, Library};
+use crate::{db::schema::*, error::EntityKind};
+
+/// How many changes can be undone.
+const HISTORY_LENGTH: i64 = 100;
+
+/// What kind of action a change was.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeAction {
+    Update,
+    Delete,
+    Merge,
+}
+
+impl ChangeAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChangeAction::Update => "update",
+            ChangeAction::Delete => "delete",
+            ChangeAction::Merge => "merge",
+        }
+    }
+
+    fn parse(action: &str) -> Result<Self> {
+        Ok(match action {
+            "update" => ChangeAction::Update,
+            "delete" => ChangeAction::Delete,
+            "merge" => ChangeAction::Merge,
+            _ => bail!("Unknown change action {action}"),
+        })
+    }
+}
+
+/// A recorded user action that can be undone.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub change_id: i64,
+    pub action: ChangeAction,
+    pub entity_kind: EntityKind,
+    pub entity_id: String,
+    /// The name of the entity before the change was made.
+    pub entity_name: String,
+    /// The name of the entity that was merged into, for merges.
+    pub target_name: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+impl Change {
+    fn from_row(
+        row: (
+            i64,
+            String,
+            String,
+            String,
+            String,
+            Option<String>,
+            NaiveDateTime,
+        ),
+    ) -> Result<Self> {
+        let (change_id, action, entity_kind, entity_id, entity_name, target_name, changed_at) = row;
+
+        Ok(Self {
+            change_id,
+            action: ChangeAction::parse(&action)?,
+            entity_kind: entity_kind.parse()?,
+            entity_id,
+            entity_name,
+            target_name,
+            changed_at,
+        })
+    }
+}
+
+impl Library {
+    /// All changes that can still be undone, newest first.
+    pub fn change_history(&self) -> Result<Vec<Change>> {
+        load_changes(&mut self.conn(), None)
+    }
+
+    /// The ID of the most recent change, if there is any.
+    pub fn latest_change_id(&self) -> Result<Option<i64>> {
+        Ok(history::table
+            .select(diesel::dsl::max(history::change_id))
+            .first(&mut *self.conn())?)
+    }
+
+    /// Undo the most recent change and return it, if there is any.
+    pub fn undo_last_change(&self) -> Result<Option<Change>> {
+        let Some(change_id) = self.latest_change_id()? else {
+            return Ok(None);
+        };
+
+        Ok(self.undo_changes_since(change_id)?.pop())
+    }
+
+    /// Undo the change with `change_id` and every change made after it.
+    ///
+    /// Returns the undone changes, newest first. Either all of them are undone
+    /// or none are.
+    pub fn undo_changes_since(&self, change_id: i64) -> Result<Vec<Change>> {
+        let connection = &mut *self.conn();
+
+        let changes = connection.transaction::<_, Error, _>(|connection| {
+            let changes = load_changes(connection, Some(change_id))?;
+
+            if changes.last().map(|c| c.change_id) != Some(change_id) {
+                bail!("The change {change_id} is not in the history");
+            }
+
+            // The steps restore rows in the reverse order of their removal,
+            // which is not always an order that satisfies foreign keys in
+            // between. They only have to hold once everything is restored.
+            diesel::sql_query("PRAGMA defer_foreign_keys = ON").execute(connection)?;
+
+            for change in &changes {
+                let steps = history_steps::table
+                    .filter(history_steps::change_id.eq(change.change_id))
+                    .order(history_steps::step_id.desc())
+                    .select(history_steps::undo_sql)
+                    .load::<String>(connection)?;
+
+                for step in steps {
+                    diesel::sql_query(step).execute(connection)?;
+                }
+            }
+
+            diesel::delete(history::table.filter(history::change_id.ge(change_id)))
+                .execute(connection)?;
+
+            Ok(changes)
+        })?;
+
+        self.changed();
+
+        Ok(changes)
+    }
+}
+
+/// The recorded changes, newest first, optionally only from `since` on.
+fn load_changes(connection: &mut SqliteConnection, since: Option<i64>) -> Result<Vec<Change>> {
+    let mut query = history::table
+        .order(history::change_id.desc())
+        .select((
+            history::change_id,
+            history::action,
+            history::entity_kind,
+            history::entity_id,
+            history::entity_name,
+            history::target_name,
+            history::changed_at,
+        ))
+        .into_boxed();
+
+    if let Some(since) = since {
+        query = query.filter(history::change_id.ge(since));
+    }
+
+    query
+        .load(connection)?
+        .into_iter()
+        .map(Change::from_row)
+        .collect()
+}
+
+/// Run `f` in a transaction and record everything it changes as one change
+/// that can be undone.
+///
+/// `target_id` is the entity merged into, for merges.
+pub(crate) fn record<T, E>(
+    connection: &mut SqliteConnection,
+    action: ChangeAction,
+    kind: EntityKind,
+    entity_id: &str,
+    target_id: Option<&str>,
+    f: impl FnOnce(&mut SqliteConnection) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<DieselError>,
+{
+    connection.transaction(|connection| {
+        // Names are looked up before `f` runs, because it may remove them.
+        let entity_name = sync::describe(connection, kind, entity_id);
+        let target_name = target_id.map(|id| sync::describe(connection, kind, id));
+
+        diesel::insert_into(history::table)
+            .values((
+                history::action.eq(action.as_str()),
+                history::entity_kind.eq(kind.to_string()),
+                history::entity_id.eq(entity_id),
+                history::entity_name.eq(entity_name),
+                history::target_name.eq(target_name),
+            ))
+            .execute(connection)?;
+
+        let change_id = history::table
+            .select(history::change_id)
+            .order(history::change_id.desc())
+            .first::<i64>(connection)?;
+
+        set_recording(connection, Some(change_id))?;
+        let result = f(connection);
+        set_recording(connection, None)?;
+        let value = result?;
+
+        let n_steps: i64 = history_steps::table
+            .filter(history_steps::change_id.eq(change_id))
+            .count()
+            .get_result(connection)?;
+
+        if n_steps == 0 {
+            // Nothing to undo, so nothing worth listing.
+            diesel::delete(history::table.filter(history::change_id.eq(change_id)))
+                .execute(connection)?;
+        } else {
+            diesel::sql_query(
+                "DELETE FROM history WHERE change_id <= \
+                 (SELECT change_id FROM history ORDER BY change_id DESC LIMIT 1 OFFSET ?)",
+            )
+            .bind::<diesel::sql_types::BigInt, _>(HISTORY_LENGTH)
+            .execute(connection)?;
+        }
+
+        Ok(value)
+    })
+}
+
+fn set_recording(connection: &mut SqliteConnection, change_id: Option<i64>) -> QueryResult<()> {
+    diesel::update(history_state::table.filter(history_state::id.eq(1)))
+        .set(history_state::change_id.eq(change_id))
+        .execute(connection)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::db::{models::Composer, TranslatedString};
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    fn library(dir: &TempDir, cache_dir: &TempDir) -> Library {
+        Library::new(dir.path(), cache_dir.path()).unwrap()
+    }
+
+    fn person_names(library: &Library) -> Vec<String> {
+        persons::table
+            .order(persons::name)
+            .select(persons::name)
+            .load::<TranslatedString>(&mut *library.conn())
+            .unwrap()
+            .iter()
+            .map(|name| name.get().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn an_update_is_undone() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let person = library
+            .create_person(translated("Beethoven"), true)
+            .unwrap();
+        library
+            .update_person(&person.person_id, translated("Mozart"), true)
+            .unwrap();
+
+        let change = library.undo_last_change().unwrap().unwrap();
+        assert_eq!(change.action, ChangeAction::Update);
+        assert_eq!(change.entity_kind, EntityKind::Person);
+        assert_eq!(change.entity_name, "Beethoven");
+
+        assert_eq!(person_names(&library), ["Beethoven"]);
+        assert!(library.undo_last_change().unwrap().is_none());
+    }
+
+    /// Deleting a work also deletes its composers, which have to come back
+    /// with it.
+    #[test]
+    fn a_deletion_is_undone_along_with_cascaded_rows() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let person = library
+            .create_person(translated("Beethoven"), true)
+            .unwrap();
+        let work = library
+            .create_work(
+                translated("Symphony No. 5"),
+                Vec::new(),
+                vec![Composer { person, role: None }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        library.delete_work(&work.work_id).unwrap();
+        assert!(library.work(&work.work_id).is_err());
+
+        library.undo_last_change().unwrap();
+        let work = library.work(&work.work_id).unwrap();
+        assert_eq!(work.name.get(), "Symphony No. 5");
+        assert_eq!(work.persons.len(), 1);
+    }
+
+    #[test]
+    fn a_merge_is_undone() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let duplicate = library
+            .create_person(translated("L. v. Beethoven"), true)
+            .unwrap();
+        let person = library
+            .create_person(translated("Beethoven"), true)
+            .unwrap();
+        let work = library
+            .create_work(
+                translated("Symphony No. 5"),
+                Vec::new(),
+                vec![Composer {
+                    person: duplicate.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        library
+            .merge_persons(&duplicate.person_id, &person.person_id)
+            .unwrap();
+
+        let history = library.change_history().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].action, ChangeAction::Merge);
+        assert_eq!(history[0].target_name.as_deref(), Some("Beethoven"));
+
+        library.undo_last_change().unwrap();
+        let work = library.work(&work.work_id).unwrap();
+        assert_eq!(work.persons[0].person.person_id, duplicate.person_id);
+        assert_eq!(person_names(&library).len(), 2);
+    }
+
+    #[test]
+    fn undoing_an_older_change_undoes_everything_after_it() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let a = library.create_person(translated("A"), true).unwrap();
+        let b = library.create_person(translated("B"), true).unwrap();
+        library.delete_person(&a.person_id).unwrap();
+        library.delete_person(&b.person_id).unwrap();
+
+        let history = library.change_history().unwrap();
+        assert_eq!(history.len(), 2);
+
+        let undone = library.undo_changes_since(history[1].change_id).unwrap();
+        assert_eq!(undone.len(), 2);
+        assert_eq!(person_names(&library).len(), 2);
+        assert!(library.change_history().unwrap().is_empty());
+    }
+
+    /// A refused deletion changes nothing, so there is nothing to undo.
+    #[test]
+    fn a_failed_action_is_not_recorded() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let person = library
+            .create_person(translated("Beethoven"), true)
+            .unwrap();
+        library
+            .create_work(
+                translated("Symphony No. 5"),
+                Vec::new(),
+                vec![Composer {
+                    person: person.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        assert!(library.delete_person(&person.person_id).is_err());
+        assert!(library.change_history().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+//! Checking a library for problems that would otherwise only show up once a
+//! track is played or the library is exported.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use diesel::{prelude::*, sql_query, sql_types};
+use serde::Serialize;
+
+use super::{cover_art, Library};
+use crate::db::{schema::*, tables::PathBufWrapper};
+
+/// Everything [`Library::check_integrity`] found.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    /// What SQLite reports about the database file itself.
+    pub database: Vec<String>,
+    /// Rows that refer to a row of another table that does not exist.
+    pub references: Vec<BrokenReference>,
+    /// Tracks whose file is not in the library folder.
+    pub missing_tracks: Vec<MissingTrack>,
+    /// Cover images that are referred to but not in the library folder,
+    /// relative to it.
+    pub missing_cover_art: Vec<PathBuf>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.database.is_empty()
+            && self.references.is_empty()
+            && self.missing_tracks.is_empty()
+            && self.missing_cover_art.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BrokenReference {
+    pub table: String,
+    pub rowid: Option<i64>,
+    /// The table the row refers to.
+    pub parent: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MissingTrack {
+    pub track_id: String,
+    /// Relative to the library folder.
+    pub path: PathBuf,
+}
+
+#[derive(QueryableByName)]
+struct IntegrityCheckRow {
+    #[diesel(sql_type = sql_types::Text)]
+    integrity_check: String,
+}
+
+#[derive(QueryableByName)]
+struct ForeignKeyCheckRow {
+    #[diesel(sql_type = sql_types::Text)]
+    table: String,
+    #[diesel(sql_type = sql_types::Nullable<sql_types::BigInt>)]
+    rowid: Option<i64>,
+    #[diesel(sql_type = sql_types::Text)]
+    parent: String,
+}
+
+impl Library {
+    /// Look for damage to the database and for files the library refers to but
+    /// does not have. Nothing is repaired.
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let connection = &mut *self.conn();
+        let folder = Path::new(self.folder());
+
+        let database = sql_query("PRAGMA integrity_check")
+            .load::<IntegrityCheckRow>(connection)?
+            .into_iter()
+            .map(|row| row.integrity_check)
+            .filter(|message| message != "ok")
+            .collect();
+
+        let references = sql_query("PRAGMA foreign_key_check")
+            .load::<ForeignKeyCheckRow>(connection)?
+            .into_iter()
+            .map(|row| BrokenReference {
+                table: row.table,
+                rowid: row.rowid,
+                parent: row.parent,
+            })
+            .collect();
+
+        let missing_tracks = tracks::table
+            .order(tracks::path)
+            .select((tracks::track_id, tracks::path))
+            .load::<(String, PathBufWrapper)>(connection)?
+            .into_iter()
+            .filter(|(_, path)| !folder.join(path).is_file())
+            .map(|(track_id, path)| MissingTrack {
+                track_id,
+                path: path.into(),
+            })
+            .collect();
+
+        let mut missing_cover_art = cover_art::referenced(connection)?
+            .into_iter()
+            .filter(|path| !folder.join(path).is_file())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        missing_cover_art.sort();
+        missing_cover_art.dedup();
+
+        Ok(IntegrityReport {
+            database,
+            references,
+            missing_tracks,
+            missing_cover_art,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::db::{models::*, TranslatedString};
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    fn recording(library: &Library) -> Recording {
+        let work = library
+            .create_work(
+                translated("Partita No. 2"),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        library
+            .create_recording(work, Vec::new(), Vec::new(), Vec::new(), None, true)
+            .unwrap()
+    }
+
+    #[test]
+    fn a_fresh_library_is_intact() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        recording(&library);
+
+        assert!(library.check_integrity().unwrap().is_ok());
+    }
+
+    #[test]
+    fn files_that_went_missing_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let recording = recording(&library);
+
+        let source = source_dir.path().join("track.mp3");
+        fs::write(&source, b"not actually audio").unwrap();
+        library
+            .import_track(&source, &recording.recording_id, 0, Vec::new())
+            .unwrap();
+
+        let image = source_dir.path().join("cover.png");
+        fs::write(&image, b"\x89PNG\r\n\x1a\n\0\0\0\0").unwrap();
+        library
+            .set_recording_cover_art(&recording.recording_id, Some(&image))
+            .unwrap();
+
+        let track = &library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()[0];
+        let cover_art = library
+            .recording_cover_art(&library.load_recording(&recording.recording_id).unwrap())
+            .unwrap();
+
+        fs::remove_file(dir.path().join(&track.path)).unwrap();
+        fs::remove_file(&cover_art).unwrap();
+
+        let report = library.check_integrity().unwrap();
+
+        assert!(!report.is_ok());
+        assert!(report.database.is_empty());
+        assert!(report.references.is_empty());
+        assert_eq!(report.missing_tracks.len(), 1);
+        assert_eq!(report.missing_tracks[0].track_id, track.track_id);
+        assert_eq!(report.missing_tracks[0].path, track.path);
+        assert_eq!(report.missing_cover_art.len(), 1);
+    }
+}
@@ -0,0 +1,615 @@
+//! The listening history: the log of plays and the statistics drawn from it.
+//!
+//! Every track that was listened to long enough is recorded as a play (see
+//! [`Library::track_played`]). A recording of four movements therefore counts
+//! as four plays, which is also how the `*_last_played` views count them.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime};
+use diesel::{prelude::*, sql_query, sql_types, SqliteConnection};
+
+use super::Library;
+use crate::db::{self, models, schema::*, tables, views::*};
+
+/// How far back statistics look.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Period {
+    Week,
+    #[default]
+    Month,
+    Year,
+    AllTime,
+}
+
+impl Period {
+    /// The earliest play the period includes, given that it ends at `now`.
+    fn since(self, now: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Period::Week => now - Duration::days(7),
+            Period::Month => now - Duration::days(30),
+            Period::Year => now - Duration::days(365),
+            Period::AllTime => DateTime::UNIX_EPOCH.naive_utc(),
+        }
+    }
+}
+
+/// One play in the log.
+#[derive(Clone, Debug)]
+pub struct PlayLogEntry {
+    pub play_id: String,
+    pub played_at: NaiveDateTime,
+    /// The length of the track, if it was recorded.
+    pub duration_ms: Option<i64>,
+    pub recording: models::Recording,
+    /// The track that was played, unless it has been removed since.
+    pub track: Option<models::Track>,
+}
+
+impl PlayLogEntry {
+    fn from_table(play: tables::Play, connection: &mut SqliteConnection) -> Result<Self> {
+        let recording = recordings::table
+            .filter(recordings::recording_id.eq(&play.recording_id))
+            .select(tables::Recording::as_select())
+            .first(connection)?;
+
+        let track = match &play.track_id {
+            Some(track_id) => tracks::table
+                .filter(tracks::track_id.eq(track_id))
+                .select(tables::Track::as_select())
+                .first(connection)
+                .optional()?
+                .map(|track| models::Track::from_table(track, connection))
+                .transpose()?,
+            None => None,
+        };
+
+        Ok(Self {
+            play_id: play.play_id,
+            played_at: play.played_at,
+            duration_ms: play.duration_ms,
+            recording: models::Recording::from_table(recording, connection)?,
+            track,
+        })
+    }
+}
+
+/// An item together with how often it was played within a period.
+#[derive(Clone, Debug)]
+pub struct Ranked<T> {
+    pub item: T,
+    pub play_count: i64,
+}
+
+/// Someone or something performing on a recording.
+#[derive(Clone, Debug)]
+pub enum Performer {
+    Person(tables::Person),
+    Ensemble(tables::Ensemble),
+}
+
+impl Performer {
+    pub fn name(&self) -> &db::TranslatedString {
+        match self {
+            Performer::Person(person) => &person.name,
+            Performer::Ensemble(ensemble) => &ensemble.name,
+        }
+    }
+}
+
+/// How much was listened to in one week.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WeeklyListening {
+    /// The Monday the week starts with.
+    pub week: NaiveDate,
+    /// The summed length of the tracks played. Plays without a recorded length
+    /// only count towards `play_count`.
+    pub duration_ms: i64,
+    pub play_count: i64,
+}
+
+/// A recording that used to be played, but has not been for a long time.
+#[derive(Clone, Debug)]
+pub struct ForgottenRecording {
+    pub recording: models::Recording,
+    pub last_played_at: NaiveDateTime,
+    pub play_count: i64,
+}
+
+#[derive(QueryableByName)]
+struct CountRow {
+    #[diesel(sql_type = sql_types::Text)]
+    id: String,
+    #[diesel(sql_type = sql_types::BigInt)]
+    play_count: i64,
+}
+
+#[derive(QueryableByName)]
+struct PerformerCountRow {
+    #[diesel(sql_type = sql_types::Bool)]
+    is_ensemble: bool,
+    #[diesel(sql_type = sql_types::Text)]
+    id: String,
+    #[diesel(sql_type = sql_types::BigInt)]
+    play_count: i64,
+}
+
+impl Library {
+    /// The plays, the most recent first, skipping the first `offset` ones.
+    pub fn play_log(&self, offset: i64, limit: i64) -> Result<Vec<PlayLogEntry>> {
+        let connection = &mut *self.conn();
+
+        let plays = plays::table
+            .order((plays::played_at.desc(), plays::play_id))
+            .offset(offset)
+            .limit(limit)
+            .select(tables::Play::as_select())
+            .load(connection)?;
+
+        plays
+            .into_iter()
+            .map(|play| PlayLogEntry::from_table(play, connection))
+            .collect()
+    }
+
+    /// A single play of the log.
+    pub fn play(&self, play_id: &str) -> Result<PlayLogEntry> {
+        let connection = &mut *self.conn();
+
+        let play = plays::table
+            .filter(plays::play_id.eq(play_id))
+            .select(tables::Play::as_select())
+            .first(connection)?;
+
+        PlayLogEntry::from_table(play, connection)
+    }
+
+    /// Remove a play from the log. It no longer counts for statistics or for
+    /// choosing what a program plays.
+    pub fn delete_play(&self, play_id: &str) -> Result<()> {
+        let connection = &mut *self.conn();
+        diesel::delete(plays::table.filter(plays::play_id.eq(play_id))).execute(connection)?;
+
+        self.changed();
+        Ok(())
+    }
+
+    /// The composers of the most played works. Everyone credited on a work
+    /// counts, like in `person_last_played`.
+    pub fn top_composers(&self, period: Period, limit: i64) -> Result<Vec<Ranked<tables::Person>>> {
+        let connection = &mut *self.conn();
+
+        let rows = sql_query(
+            "SELECT work_persons.person_id AS id, COUNT(DISTINCT plays.play_id) AS play_count \
+            FROM plays \
+            JOIN recordings ON recordings.recording_id = plays.recording_id \
+            JOIN work_persons ON work_persons.work_id = recordings.work_id \
+            WHERE plays.played_at >= ? \
+            GROUP BY work_persons.person_id \
+            ORDER BY play_count DESC, id \
+            LIMIT ?",
+        )
+        .bind::<sql_types::Timestamp, _>(period.since(db::now()))
+        .bind::<sql_types::BigInt, _>(limit)
+        .load::<CountRow>(connection)?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Ranked {
+                    item: persons::table
+                        .filter(persons::person_id.eq(&row.id))
+                        .select(tables::Person::as_select())
+                        .first(connection)?,
+                    play_count: row.play_count,
+                })
+            })
+            .collect()
+    }
+
+    /// The most played works.
+    pub fn top_works(&self, period: Period, limit: i64) -> Result<Vec<Ranked<models::Work>>> {
+        let connection = &mut *self.conn();
+
+        let rows = sql_query(
+            "SELECT recordings.work_id AS id, COUNT(*) AS play_count \
+            FROM plays \
+            JOIN recordings ON recordings.recording_id = plays.recording_id \
+            WHERE plays.played_at >= ? \
+            GROUP BY recordings.work_id \
+            ORDER BY play_count DESC, id \
+            LIMIT ?",
+        )
+        .bind::<sql_types::Timestamp, _>(period.since(db::now()))
+        .bind::<sql_types::BigInt, _>(limit)
+        .load::<CountRow>(connection)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let work = works::table
+                    .filter(works::work_id.eq(&row.id))
+                    .first::<tables::Work>(connection)?;
+
+                Ok(Ranked {
+                    item: models::Work::from_table(work, connection)?,
+                    play_count: row.play_count,
+                })
+            })
+            .collect()
+    }
+
+    /// The persons and ensembles that performed on the most played recordings.
+    pub fn top_performers(&self, period: Period, limit: i64) -> Result<Vec<Ranked<Performer>>> {
+        let connection = &mut *self.conn();
+        let since = period.since(db::now());
+
+        let rows = sql_query(
+            "SELECT is_ensemble, id, COUNT(DISTINCT play_id) AS play_count \
+            FROM ( \
+                SELECT FALSE AS is_ensemble, recording_persons.person_id AS id, plays.play_id \
+                FROM plays \
+                JOIN recording_persons ON recording_persons.recording_id = plays.recording_id \
+                WHERE plays.played_at >= ? \
+                UNION ALL \
+                SELECT TRUE, recording_ensembles.ensemble_id, plays.play_id \
+                FROM plays \
+                JOIN recording_ensembles ON recording_ensembles.recording_id = plays.recording_id \
+                WHERE plays.played_at >= ? \
+            ) \
+            GROUP BY is_ensemble, id \
+            ORDER BY play_count DESC, id \
+            LIMIT ?",
+        )
+        .bind::<sql_types::Timestamp, _>(since)
+        .bind::<sql_types::Timestamp, _>(since)
+        .bind::<sql_types::BigInt, _>(limit)
+        .load::<PerformerCountRow>(connection)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let item = if row.is_ensemble {
+                    Performer::Ensemble(
+                        ensembles::table
+                            .filter(ensembles::ensemble_id.eq(&row.id))
+                            .select(tables::Ensemble::as_select())
+                            .first(connection)?,
+                    )
+                } else {
+                    Performer::Person(
+                        persons::table
+                            .filter(persons::person_id.eq(&row.id))
+                            .select(tables::Person::as_select())
+                            .first(connection)?,
+                    )
+                };
+
+                Ok(Ranked {
+                    item,
+                    play_count: row.play_count,
+                })
+            })
+            .collect()
+    }
+
+    /// The listening time of the last `weeks` weeks, including the current one,
+    /// the oldest first. Weeks without plays are included.
+    ///
+    /// Plays are stored in UTC, so a play around midnight on a Sunday may fall
+    /// into the neighbouring week of the local calendar.
+    pub fn listening_time_by_week(&self, weeks: u32) -> Result<Vec<WeeklyListening>> {
+        let connection = &mut *self.conn();
+        listening_time_by_week(connection, db::now().date(), weeks)
+    }
+
+    /// Recordings that were last played before `not_since` ago, as suggestions
+    /// to listen to again. Those that were played the most come first, and
+    /// recordings without tracks are left out.
+    pub fn forgotten_recordings(
+        &self,
+        not_since: Duration,
+        limit: i64,
+    ) -> Result<Vec<ForgottenRecording>> {
+        let connection = &mut *self.conn();
+
+        let rows = recordings::table
+            .inner_join(recording_last_played::table)
+            .filter(recording_last_played::last_played_at.lt(db::now() - not_since))
+            .filter(diesel::dsl::exists(
+                tracks::table.filter(tracks::recording_id.eq(recordings::recording_id)),
+            ))
+            .order((
+                recording_last_played::play_count.desc(),
+                recording_last_played::last_played_at,
+            ))
+            .limit(limit)
+            .select((
+                tables::Recording::as_select(),
+                recording_last_played::last_played_at,
+                recording_last_played::play_count,
+            ))
+            .load::<(tables::Recording, NaiveDateTime, i64)>(connection)?;
+
+        rows.into_iter()
+            .map(|(recording, last_played_at, play_count)| {
+                Ok(ForgottenRecording {
+                    recording: models::Recording::from_table(recording, connection)?,
+                    last_played_at,
+                    play_count,
+                })
+            })
+            .collect()
+    }
+}
+
+fn listening_time_by_week(
+    connection: &mut SqliteConnection,
+    today: NaiveDate,
+    weeks: u32,
+) -> Result<Vec<WeeklyListening>> {
+    let Some(last_week) = weeks.checked_sub(1) else {
+        return Ok(Vec::new());
+    };
+
+    let current_week = week_of(today);
+    let first_week = current_week - Duration::weeks(i64::from(last_week));
+
+    let mut totals = (0..weeks)
+        .map(|week| {
+            let week = first_week + Duration::weeks(i64::from(week));
+            (week, (0, 0))
+        })
+        .collect::<BTreeMap<NaiveDate, (i64, i64)>>();
+
+    for (played_at, duration_ms) in plays::table
+        .filter(plays::played_at.ge(first_week.and_hms_opt(0, 0, 0).unwrap()))
+        .select((plays::played_at, plays::duration_ms))
+        .load::<(NaiveDateTime, Option<i64>)>(connection)?
+    {
+        if let Some((total_ms, play_count)) = totals.get_mut(&week_of(played_at.date())) {
+            *total_ms += duration_ms.unwrap_or(0);
+            *play_count += 1;
+        }
+    }
+
+    Ok(totals
+        .into_iter()
+        .map(|(week, (duration_ms, play_count))| WeeklyListening {
+            week,
+            duration_ms,
+            play_count,
+        })
+        .collect())
+}
+
+/// The Monday of the week `date` belongs to.
+fn week_of(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::db::{
+        models::{Composer, Performer as RecordingPerformer},
+        TranslatedString,
+    };
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    /// A recording of a work by `composer` with one track, performed by
+    /// `performer`, together with the ID of the track.
+    fn recording(
+        library: &Library,
+        source_dir: &TempDir,
+        name: &str,
+        composer: &tables::Person,
+        performer: &tables::Person,
+    ) -> (models::Recording, String) {
+        let work = library
+            .create_work(
+                translated(name),
+                Vec::new(),
+                vec![Composer {
+                    person: composer.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        let recording = library
+            .create_recording(
+                work,
+                vec![RecordingPerformer {
+                    person: performer.clone(),
+                    role: None,
+                    instrument: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        let source = source_dir.path().join(format!("{name}.mp3"));
+        fs::write(&source, format!("audio of {name}").as_bytes()).unwrap();
+
+        library
+            .import_track(&source, &recording.recording_id, 0, Vec::new())
+            .unwrap();
+
+        let track_id = library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()[0]
+            .track_id
+            .clone();
+
+        (recording, track_id)
+    }
+
+    /// Move every play of `track_id` back by `age`.
+    fn age_plays(library: &Library, track_id: &str, age: Duration) {
+        let connection = &mut *library.conn();
+
+        let plays = plays::table
+            .filter(plays::track_id.eq(track_id))
+            .select(tables::Play::as_select())
+            .load(connection)
+            .unwrap();
+
+        for play in plays {
+            diesel::update(plays::table.filter(plays::play_id.eq(&play.play_id)))
+                .set(plays::played_at.eq(play.played_at - age))
+                .execute(connection)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn the_log_lists_plays_from_the_most_recent_and_forgets_deleted_ones() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let composer = library.create_person(translated("Composer"), true).unwrap();
+        let performer = library
+            .create_person(translated("Performer"), true)
+            .unwrap();
+        let (old, old_track) = recording(&library, &source_dir, "Old", &composer, &performer);
+        let (new, new_track) = recording(&library, &source_dir, "New", &composer, &performer);
+
+        library.track_played(&old_track, 60_000).unwrap();
+        age_plays(&library, &old_track, Duration::hours(1));
+        library.track_played(&new_track, 90_000).unwrap();
+
+        let log = library.play_log(0, 10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].recording.recording_id, new.recording_id);
+        assert_eq!(log[0].duration_ms, Some(90_000));
+        assert_eq!(log[0].track.as_ref().unwrap().track_id, new_track);
+        assert_eq!(log[1].recording.recording_id, old.recording_id);
+
+        assert_eq!(library.play_log(1, 10).unwrap().len(), 1);
+
+        library.delete_play(&log[0].play_id).unwrap();
+        let log = library.play_log(0, 10).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].recording.recording_id, old.recording_id);
+    }
+
+    #[test]
+    fn rankings_only_count_plays_within_the_period() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let bach = library.create_person(translated("Bach"), true).unwrap();
+        let brahms = library.create_person(translated("Brahms"), true).unwrap();
+        let pianist = library.create_person(translated("Pianist"), true).unwrap();
+        let violinist = library
+            .create_person(translated("Violinist"), true)
+            .unwrap();
+
+        let (_, recent_track) = recording(&library, &source_dir, "Partita", &bach, &violinist);
+        let (_, old_track) = recording(&library, &source_dir, "Intermezzo", &brahms, &pianist);
+
+        library.track_played(&recent_track, 60_000).unwrap();
+        for _ in 0..3 {
+            library.track_played(&old_track, 60_000).unwrap();
+        }
+        age_plays(&library, &old_track, Duration::days(60));
+
+        let composers = library.top_composers(Period::Month, 10).unwrap();
+        assert_eq!(composers.len(), 1);
+        assert_eq!(composers[0].item.person_id, bach.person_id);
+        assert_eq!(composers[0].play_count, 1);
+
+        let composers = library.top_composers(Period::Year, 10).unwrap();
+        assert_eq!(composers[0].item.person_id, brahms.person_id);
+        assert_eq!(composers[0].play_count, 3);
+
+        let works = library.top_works(Period::AllTime, 1).unwrap();
+        assert_eq!(works.len(), 1);
+        assert_eq!(works[0].item.name.get(), "Intermezzo");
+
+        let performers = library.top_performers(Period::Week, 10).unwrap();
+        assert_eq!(performers.len(), 1);
+        assert!(
+            matches!(&performers[0].item, Performer::Person(person) if person.person_id == violinist.person_id)
+        );
+    }
+
+    #[test]
+    fn listening_time_is_summed_per_week() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let composer = library.create_person(translated("Composer"), true).unwrap();
+        let performer = library
+            .create_person(translated("Performer"), true)
+            .unwrap();
+        let (_, track) = recording(&library, &source_dir, "Work", &composer, &performer);
+
+        library.track_played(&track, 60_000).unwrap();
+        library.track_played(&track, 30_000).unwrap();
+
+        let today = db::now().date();
+        let weeks = listening_time_by_week(&mut library.conn(), today, 3).unwrap();
+
+        assert_eq!(weeks.len(), 3);
+        assert_eq!(weeks[2].week, week_of(today));
+        assert_eq!(weeks[2].week.weekday(), chrono::Weekday::Mon);
+        assert_eq!(weeks[2].duration_ms, 90_000);
+        assert_eq!(weeks[2].play_count, 2);
+        assert_eq!(weeks[0].play_count, 0);
+        assert_eq!(weeks[1].week - weeks[0].week, Duration::weeks(1));
+    }
+
+    #[test]
+    fn only_recordings_not_played_recently_are_suggested() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let composer = library.create_person(translated("Composer"), true).unwrap();
+        let performer = library
+            .create_person(translated("Performer"), true)
+            .unwrap();
+        let (forgotten, forgotten_track) =
+            recording(&library, &source_dir, "Forgotten", &composer, &performer);
+        let (_, recent_track) = recording(&library, &source_dir, "Recent", &composer, &performer);
+        recording(&library, &source_dir, "Never played", &composer, &performer);
+
+        library.track_played(&forgotten_track, 60_000).unwrap();
+        age_plays(&library, &forgotten_track, Duration::days(400));
+        library.track_played(&recent_track, 60_000).unwrap();
+
+        let suggestions = library
+            .forgotten_recordings(Duration::days(180), 10)
+            .unwrap();
+
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(
+            suggestions[0].recording.recording_id,
+            forgotten.recording_id
+        );
+        assert_eq!(suggestions[0].play_count, 1);
+    }
+}
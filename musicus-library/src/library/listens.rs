@@ -0,0 +1,613 @@
+//! Plays as listens in the format of ListenBrainz.
+//!
+//! Plays never leave the library on their own: archives strip them. A listen
+//! is what is handed to the outside instead, either by submitting it to a
+//! ListenBrainz compatible server or by exporting the whole history as JSON
+//! lines, the format ListenBrainz itself uses for its exports.
+//!
+//! Submission is opt-in and up to the application. Listens wait in a
+//! [`ListenQueue`] on disk until a submission succeeds, so that nothing is lost
+//! while the computer is offline.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use super::{
+    listening::PlayLogEntry,
+    process::{spawn_process, ProcessHandle},
+    Library,
+};
+
+/// Where ListenBrainz itself accepts listens.
+pub const LISTENBRAINZ_ENDPOINT: &str = "https://api.listenbrainz.org/1/submit-listens";
+
+/// One play in the format ListenBrainz submits and exports listens in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Listen {
+    /// The time of the play in seconds since the Unix epoch.
+    pub listened_at: i64,
+    pub track_metadata: TrackMetadata,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrackMetadata {
+    /// The composers, or the performers for works without one.
+    pub artist_name: String,
+    /// The work, followed by the parts the track contains.
+    pub track_name: String,
+    pub additional_info: AdditionalInfo,
+}
+
+/// The details ListenBrainz keeps without interpreting them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AdditionalInfo {
+    pub submission_client: String,
+    pub composers: Vec<String>,
+    pub work: String,
+    /// The parts of the work the track contains, if it does not contain the
+    /// whole work.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub part: Option<String>,
+    pub performers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub duration_ms: Option<i64>,
+}
+
+impl Listen {
+    pub fn from_play(play: &PlayLogEntry) -> Self {
+        let work = &play.recording.work;
+
+        let composers = work
+            .persons
+            .iter()
+            .map(|composer| composer.person.name.get().to_owned())
+            .collect::<Vec<_>>();
+
+        let performers = play
+            .recording
+            .persons
+            .iter()
+            .map(ToString::to_string)
+            .chain(play.recording.ensembles.iter().map(ToString::to_string))
+            .collect::<Vec<_>>();
+
+        // A track containing the work itself rather than some of its parts
+        // has no part to name.
+        let part = play
+            .track
+            .as_ref()
+            .map(|track| {
+                track
+                    .works
+                    .iter()
+                    .filter(|part| part.work_id != work.work_id)
+                    .map(|part| part.name.get().to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|parts| !parts.is_empty())
+            .map(|parts| parts.join(", "));
+
+        let artist_name = if composers.is_empty() {
+            performers.join(", ")
+        } else {
+            composers.join(", ")
+        };
+
+        let track_name = match &part {
+            Some(part) => format!("{}: {}", work.name.get(), part),
+            None => work.name.get().to_owned(),
+        };
+
+        Self {
+            listened_at: play.played_at.and_utc().timestamp(),
+            track_metadata: TrackMetadata {
+                artist_name,
+                track_name,
+                additional_info: AdditionalInfo {
+                    submission_client: String::from("Musicus"),
+                    composers,
+                    work: work.name.get().to_owned(),
+                    part,
+                    performers,
+                    duration_ms: play.duration_ms,
+                },
+            },
+        }
+    }
+}
+
+impl Library {
+    /// The play `play_id` as a listen.
+    pub fn listen(&self, play_id: &str) -> Result<Listen> {
+        Ok(Listen::from_play(&self.play(play_id)?))
+    }
+
+    /// Write every play to `path` as a listen per line, the most recent first,
+    /// and return how many were written.
+    pub fn export_listens(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let plays = self.play_log(0, i64::MAX)?;
+
+        let parent = path
+            .parent()
+            .ok_or_else(|| anyhow!("Export target {} has no parent directory", path.display()))?;
+
+        // Only replace an earlier export once the new one is complete.
+        let file = NamedTempFile::new_in(parent)?;
+        let mut writer = BufWriter::new(file.as_file());
+
+        for play in &plays {
+            serde_json::to_writer(&mut writer, &Listen::from_play(play))?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        file.persist(path)?;
+
+        Ok(plays.len())
+    }
+}
+
+/// Listens waiting to be submitted, stored as JSON lines in a file.
+///
+/// Listens are only ever appended, and a submission only removes the ones it
+/// sent from the start of the file, so listens queued while a submission is
+/// under way are kept for the next one.
+#[derive(Clone, Debug)]
+pub struct ListenQueue {
+    path: PathBuf,
+    /// Held while the file is read or written.
+    file_lock: Arc<Mutex<()>>,
+    /// Held while a submission is under way.
+    submission_lock: Arc<Mutex<()>>,
+}
+
+impl ListenQueue {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file_lock: Arc::new(Mutex::new(())),
+            submission_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Add a listen to the end of the queue.
+    pub fn push(&self, listen: &Listen) -> Result<()> {
+        let _lock = self.file_lock.lock().unwrap();
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_vec(listen)?;
+        line.push(b'\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)?;
+
+        Ok(())
+    }
+
+    /// The queued listens, the oldest first.
+    pub fn listens(&self) -> Result<Vec<Listen>> {
+        let _lock = self.file_lock.lock().unwrap();
+        self.read()
+    }
+
+    /// Submit the queued listens to `endpoint` on a background thread,
+    /// authenticating with the user `token`.
+    ///
+    /// Each listen is submitted on its own as a single listen, which is what
+    /// ListenBrainz expects of plays as they happen, even when some of them
+    /// were queued while offline. A listen is removed from the queue as soon
+    /// as the server has accepted it. If the server cannot be reached, the
+    /// rest stays queued for the next attempt. Nothing happens while another
+    /// submission is under way.
+    pub fn submit(&self, endpoint: &str, token: &str) -> ProcessHandle {
+        let queue = self.clone();
+        let endpoint = endpoint.to_owned();
+        let token = token.to_owned();
+
+        spawn_process(move |_, _| {
+            let Ok(_submitting) = queue.submission_lock.try_lock() else {
+                return Ok(());
+            };
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+
+            let client = reqwest::Client::builder()
+                .connect_timeout(std::time::Duration::from_secs(10))
+                .timeout(std::time::Duration::from_secs(30))
+                .build()?;
+
+            loop {
+                let Some(listen) = queue.listens()?.into_iter().next() else {
+                    return Ok(());
+                };
+
+                let body = serde_json::to_vec(&Submission {
+                    listen_type: "single",
+                    payload: &[listen],
+                })?;
+
+                runtime.block_on(async {
+                    client
+                        .post(&endpoint)
+                        .header(reqwest::header::AUTHORIZATION, format!("Token {token}"))
+                        .header(reqwest::header::CONTENT_TYPE, "application/json")
+                        .body(body)
+                        .send()
+                        .await?
+                        .error_for_status()?;
+
+                    anyhow::Ok(())
+                })?;
+
+                queue.remove_first(1)?;
+            }
+        })
+    }
+
+    fn read(&self) -> Result<Vec<Listen>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut listens = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // A line cut short by a crash must not hold up the listens after it.
+            match serde_json::from_str(&line) {
+                Ok(listen) => listens.push(listen),
+                Err(err) => log::warn!("Dropping malformed queued listen: {err}"),
+            }
+        }
+
+        Ok(listens)
+    }
+
+    /// Remove the `count` oldest listens.
+    fn remove_first(&self, count: usize) -> Result<()> {
+        let _lock = self.file_lock.lock().unwrap();
+
+        let listens = self.read()?;
+        let parent = self
+            .path
+            .parent()
+            .ok_or_else(|| anyhow!("Queue {} has no parent directory", self.path.display()))?;
+
+        let file = NamedTempFile::new_in(parent)?;
+        let mut writer = BufWriter::new(file.as_file());
+
+        for listen in listens.iter().skip(count) {
+            serde_json::to_writer(&mut writer, listen)?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        file.persist(&self.path)?;
+
+        Ok(())
+    }
+}
+
+/// The request body of a submission.
+#[derive(Serialize)]
+struct Submission<'a> {
+    listen_type: &'a str,
+    payload: &'a [Listen],
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::Read,
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+        thread,
+    };
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::{models, tables::Person, TranslatedString},
+        library::process::ProcessMsg,
+    };
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    /// A recording of a work in two parts, with the first part imported as a
+    /// track.
+    fn recording(
+        library: &Library,
+        source_dir: &TempDir,
+        composer: &Person,
+        performer: &Person,
+    ) -> (models::Recording, String) {
+        let work = library
+            .create_work(
+                translated("Sonata"),
+                vec![
+                    library
+                        .create_work(
+                            translated("Allegro"),
+                            Vec::new(),
+                            Vec::new(),
+                            Vec::new(),
+                            Vec::new(),
+                            None,
+                            true,
+                        )
+                        .unwrap(),
+                    library
+                        .create_work(
+                            translated("Adagio"),
+                            Vec::new(),
+                            Vec::new(),
+                            Vec::new(),
+                            Vec::new(),
+                            None,
+                            true,
+                        )
+                        .unwrap(),
+                ],
+                vec![models::Composer {
+                    person: composer.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        let recording = library
+            .create_recording(
+                work.clone(),
+                vec![models::Performer {
+                    person: performer.clone(),
+                    role: None,
+                    instrument: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        let source = source_dir.path().join("sonata.mp3");
+        fs::write(&source, b"audio of the sonata").unwrap();
+
+        library
+            .import_track(
+                &source,
+                &recording.recording_id,
+                0,
+                vec![work.parts[0].clone()],
+            )
+            .unwrap();
+
+        let track_id = library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()[0]
+            .track_id
+            .clone();
+
+        (recording, track_id)
+    }
+
+    fn listen(at: i64) -> Listen {
+        Listen {
+            listened_at: at,
+            track_metadata: TrackMetadata {
+                artist_name: String::from("Composer"),
+                track_name: String::from("Work"),
+                additional_info: AdditionalInfo {
+                    submission_client: String::from("Musicus"),
+                    composers: vec![String::from("Composer")],
+                    work: String::from("Work"),
+                    part: None,
+                    performers: Vec::new(),
+                    duration_ms: None,
+                },
+            },
+        }
+    }
+
+    /// Wait for the outcome of a background operation.
+    fn finish(handle: ProcessHandle) -> Result<()> {
+        loop {
+            match handle.receiver.recv_blocking().unwrap() {
+                ProcessMsg::Result(result) => return result,
+                ProcessMsg::Cancelled => panic!("submission was cancelled"),
+                _ => (),
+            }
+        }
+    }
+
+    /// A server accepting every request, passing the requests it received on
+    /// to the returned channel.
+    fn stub_server() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/1/submit-listens", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&mut stream);
+
+                if sender.send(request).is_err() {
+                    break;
+                }
+
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 16\r\nConnection: close\r\n\r\n{\"status\": \"ok\"}")
+                    .unwrap();
+            }
+        });
+
+        (endpoint, receiver)
+    }
+
+    /// Read a request with its body, which is sent with a content length.
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+
+        loop {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+
+            let text = String::from_utf8_lossy(&request);
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or_default();
+
+                if request.len() >= header_end + 4 + content_length || read == 0 {
+                    return String::from_utf8(request).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_play_names_the_composer_work_part_and_performers() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let composer = library.create_person(translated("Composer"), true).unwrap();
+        let performer = library.create_person(translated("Pianist"), true).unwrap();
+        let (_, track_id) = recording(&library, &source_dir, &composer, &performer);
+
+        let play_id = library.track_played(&track_id, 120_000).unwrap();
+        let listen = library.listen(&play_id).unwrap();
+
+        assert_eq!(listen.track_metadata.artist_name, "Composer");
+        assert_eq!(listen.track_metadata.track_name, "Sonata: Allegro");
+
+        let info = &listen.track_metadata.additional_info;
+        assert_eq!(info.composers, vec!["Composer"]);
+        assert_eq!(info.work, "Sonata");
+        assert_eq!(info.part.as_deref(), Some("Allegro"));
+        assert_eq!(info.performers, vec!["Pianist"]);
+        assert_eq!(info.duration_ms, Some(120_000));
+    }
+
+    #[test]
+    fn the_export_has_a_listen_per_line() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let export_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let composer = library.create_person(translated("Composer"), true).unwrap();
+        let performer = library.create_person(translated("Pianist"), true).unwrap();
+        let (_, track_id) = recording(&library, &source_dir, &composer, &performer);
+
+        library.track_played(&track_id, 120_000).unwrap();
+        library.track_played(&track_id, 120_000).unwrap();
+
+        let path = export_dir.path().join("listens.jsonl");
+        assert_eq!(library.export_listens(&path).unwrap(), 2);
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        for line in lines {
+            let listen: Listen = serde_json::from_str(line).unwrap();
+            assert_eq!(listen.track_metadata.track_name, "Sonata: Allegro");
+        }
+    }
+
+    #[test]
+    fn submitted_listens_leave_the_queue() {
+        let dir = TempDir::new().unwrap();
+        let queue = ListenQueue::new(dir.path().join("queue").join("listens.jsonl"));
+
+        for at in 0..3 {
+            queue.push(&listen(at)).unwrap();
+        }
+
+        let (endpoint, requests) = stub_server();
+        finish(queue.submit(&endpoint, "secret")).unwrap();
+
+        for at in 0..3 {
+            let request = requests.recv().unwrap();
+            assert!(request.starts_with("POST /1/submit-listens"));
+            assert!(request
+                .lines()
+                .any(|line| line.eq_ignore_ascii_case("authorization: Token secret")));
+
+            let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+            let body: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(body["listen_type"], "single");
+            assert_eq!(body["payload"].as_array().unwrap().len(), 1);
+            assert_eq!(body["payload"][0]["listened_at"], at);
+        }
+
+        assert!(queue.listens().unwrap().is_empty());
+    }
+
+    #[test]
+    fn listens_stay_queued_while_offline() {
+        let dir = TempDir::new().unwrap();
+        let queue = ListenQueue::new(dir.path().join("listens.jsonl"));
+
+        queue.push(&listen(0)).unwrap();
+        queue.push(&listen(1)).unwrap();
+
+        // Nothing listens on a port that was just given up.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/1/submit-listens", listener.local_addr().unwrap());
+        drop(listener);
+
+        assert!(finish(queue.submit(&endpoint, "secret")).is_err());
+        assert_eq!(queue.listens().unwrap(), vec![listen(0), listen(1)]);
+
+        let (endpoint, _requests) = stub_server();
+        finish(queue.submit(&endpoint, "secret")).unwrap();
+        assert!(queue.listens().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+//! Keeping track files in line with the database without being asked to.
+//!
+//! While automatic file maintenance is enabled, every edit of something tracks
+//! are named and tagged after marks the affected tracks as stale. Once the user
+//! has stopped editing for a moment, the application runs
+//! [`Library::maintain_files`], which reorganizes just those tracks.
+//!
+//! The marks only cover edits. Undoing a change, updating metadata or
+//! synchronizing leave them alone, and a full reorganization does not clear
+//! them, which at worst costs a file that is looked at twice.
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use diesel::{prelude::*, SqliteConnection};
+
+use super::{
+    process::{spawn_process, ProcessHandle},
+    reorganize::{self, ReorganizationScope},
+    Library,
+};
+use crate::db::{self, schema::*};
+
+impl Library {
+    /// Turn automatic file maintenance on or off. Edits only mark tracks as
+    /// stale while it is on.
+    pub fn set_file_maintenance(&self, enabled: bool) {
+        self.file_maintenance.set(enabled);
+    }
+
+    /// Whether any track is marked as stale.
+    pub fn has_stale_tracks(&self) -> Result<bool> {
+        Ok(stale_tracks::table
+            .count()
+            .get_result::<i64>(&mut *self.conn())?
+            > 0)
+    }
+
+    /// Reorganize the tracks that are marked as stale, like
+    /// [`Library::reorganize_files_in`] does.
+    ///
+    /// A track stays marked if it is edited again in the meantime or if the
+    /// reorganization fails as a whole. A file that could not be tagged is
+    /// reported as a warning and only tried again after its track is edited
+    /// again, so that it does not come up after every edit.
+    pub fn maintain_files(&self) -> Result<ProcessHandle> {
+        let folder = PathBuf::from(self.folder());
+        let patterns = self.patterns();
+        let connection = Arc::clone(&self.connection);
+
+        reorganize::validate(&patterns)?;
+
+        let started_at = db::now();
+        let scope = ReorganizationScope {
+            track_ids: stale_tracks::table
+                .select(stale_tracks::track_id)
+                .load::<String>(&mut *self.conn())?,
+            ..Default::default()
+        };
+
+        Ok(spawn_process(move |sender, cancellation| {
+            reorganize::reorganize(
+                &folder,
+                &patterns,
+                Some(&scope),
+                &connection,
+                sender,
+                cancellation,
+            )?;
+
+            diesel::delete(
+                stale_tracks::table
+                    .filter(stale_tracks::track_id.eq_any(&scope.track_ids))
+                    .filter(stale_tracks::marked_at.le(started_at)),
+            )
+            .execute(&mut *db::lock_connection(&connection))?;
+
+            Ok(())
+        }))
+    }
+
+    /// Mark the tracks in `scope` as stale, if automatic file maintenance is on.
+    ///
+    /// The edit has already happened at this point, so a failure is only
+    /// logged.
+    pub(crate) fn mark_stale(&self, connection: &mut SqliteConnection, scope: ReorganizationScope) {
+        if !self.file_maintenance.get() {
+            return;
+        }
+
+        let result = reorganize::affected_tracks(&scope, connection).and_then(|track_ids| {
+            let now = db::now();
+
+            let rows = track_ids
+                .into_iter()
+                .map(|track_id| {
+                    (
+                        stale_tracks::track_id.eq(track_id),
+                        stale_tracks::marked_at.eq(now),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            diesel::replace_into(stale_tracks::table)
+                .values(&rows)
+                .execute(connection)?;
+
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            log::warn!("Failed to mark tracks for file maintenance: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::{models::*, TranslatedString},
+        library::{naming::audio_tags::minimal_wav, process::ProcessMsg},
+    };
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    /// A work named `work_name` with a recording of a single track.
+    fn recording_with_track(library: &Library, source_dir: &TempDir, work_name: &str) -> Recording {
+        let person = library
+            .create_person(translated("Beethoven"), true)
+            .unwrap();
+        let work = library
+            .create_work(
+                translated(work_name),
+                Vec::new(),
+                vec![Composer { person, role: None }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+        let recording = library
+            .create_recording(work, Vec::new(), Vec::new(), Vec::new(), None, true)
+            .unwrap();
+
+        let source = source_dir.path().join(format!("{work_name}.wav"));
+        fs::write(&source, minimal_wav()).unwrap();
+        library
+            .import_track(&source, &recording.recording_id, 0, Vec::new())
+            .unwrap();
+
+        recording
+    }
+
+    fn rename_work(library: &Library, recording: &Recording, name: &str) {
+        library
+            .update_work(
+                &recording.work.work_id,
+                translated(name),
+                Vec::new(),
+                recording.work.persons.clone(),
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+    }
+
+    fn track_paths(library: &Library, recording: &Recording) -> Vec<PathBuf> {
+        library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()
+            .into_iter()
+            .map(|track| track.path)
+            .collect()
+    }
+
+    #[test]
+    fn edits_only_mark_tracks_while_maintenance_is_on() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let recording = recording_with_track(&library, &source_dir, "Sonate");
+
+        rename_work(&library, &recording, "Sonata");
+        assert!(!library.has_stale_tracks().unwrap());
+
+        library.set_file_maintenance(true);
+        rename_work(&library, &recording, "Sonate");
+        assert!(library.has_stale_tracks().unwrap());
+    }
+
+    #[test]
+    fn maintenance_fixes_the_stale_tracks_and_clears_the_marks() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        let first = recording_with_track(&library, &source_dir, "Sonate");
+        let second = recording_with_track(&library, &source_dir, "Quartett");
+        let second_before = track_paths(&library, &second);
+
+        // The second recording is edited before maintenance is turned on.
+        rename_work(&library, &second, "Quartet");
+        library.set_file_maintenance(true);
+        rename_work(&library, &first, "Sonata");
+
+        let handle = library.maintain_files().unwrap();
+        while let Ok(msg) = handle.receiver.recv_blocking() {
+            match msg {
+                ProcessMsg::Warning(warning) => panic!("unexpected warning: {warning}"),
+                ProcessMsg::Result(result) => result.unwrap(),
+                ProcessMsg::Cancelled => panic!("the maintenance was cancelled"),
+                _ => (),
+            }
+        }
+
+        assert_eq!(
+            track_paths(&library, &first),
+            vec![PathBuf::from("Beethoven; Sonata; 01.wav")]
+        );
+        assert_eq!(track_paths(&library, &second), second_before);
+        assert!(!library.has_stale_tracks().unwrap());
+    }
+
+    /// Whether any track is marked as stale, clearing the marks.
+    fn take_stale_tracks(library: &Library) -> bool {
+        diesel::delete(stale_tracks::table)
+            .execute(&mut *library.conn())
+            .unwrap()
+            > 0
+    }
+
+    #[test]
+    fn track_album_and_cover_edits_mark_tracks() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        library.set_file_maintenance(true);
+
+        let recording = recording_with_track(&library, &source_dir, "Sonate");
+        let track = library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()[0]
+            .clone();
+        take_stale_tracks(&library);
+
+        library
+            .update_track(&track.track_id, 1, Vec::new())
+            .unwrap();
+        assert!(take_stale_tracks(&library));
+
+        let album = library
+            .create_album(translated("Sonatas"), vec![recording.clone()], true)
+            .unwrap();
+
+        // Removing the recording changes its tracks as well.
+        library
+            .update_album(&album.album_id, translated("Sonatas"), Vec::new(), true)
+            .unwrap();
+        assert!(take_stale_tracks(&library));
+
+        library
+            .set_recording_cover_art(&recording.recording_id, None)
+            .unwrap();
+        assert!(take_stale_tracks(&library));
+    }
+}
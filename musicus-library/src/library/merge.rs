@@ -7,10 +7,13 @@
 use std::collections::HashSet;
 
 use anyhow::{bail, Result};
-use diesel::prelude::*;
+use diesel::{prelude::*, SqliteConnection};
 
-use super::Library;
-use crate::db::schema::*;
+use super::{
+    history::{self, ChangeAction},
+    sync, unmerge, Library,
+};
+use crate::{db::schema::*, error::EntityKind};
 
 /// Reference counts within the library for one entity.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
@@ -31,109 +34,17 @@ impl EntityUsage {
 impl Library {
     /// Merge one person into another.
     pub fn merge_persons(&self, from: &str, into: &str) -> Result<()> {
-        if from == into {
-            bail!("cannot merge a person into itself");
-        }
-
-        let connection = &mut *self.conn();
-
-        connection.transaction::<_, diesel::result::Error, _>(|connection| {
-            diesel::update(work_persons::table.filter(work_persons::person_id.eq(from)))
-                .set(work_persons::person_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(ensemble_persons::table.filter(ensemble_persons::person_id.eq(from)))
-                .set(ensemble_persons::person_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(recording_persons::table.filter(recording_persons::person_id.eq(from)))
-                .set(recording_persons::person_id.eq(into))
-                .execute(connection)?;
-
-            diesel::delete(persons::table.filter(persons::person_id.eq(from)))
-                .execute(connection)?;
-
-            Ok(())
-        })?;
-
-        self.changed();
-
-        Ok(())
+        self.merge(EntityKind::Person, from, into)
     }
 
     /// Merge one role into another.
     pub fn merge_roles(&self, from: &str, into: &str) -> Result<()> {
-        if from == into {
-            bail!("cannot merge a role into itself");
-        }
-
-        let connection = &mut *self.conn();
-
-        connection.transaction::<_, diesel::result::Error, _>(|connection| {
-            diesel::update(work_persons::table.filter(work_persons::role_id.eq(from)))
-                .set(work_persons::role_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(ensemble_persons::table.filter(ensemble_persons::role_id.eq(from)))
-                .set(ensemble_persons::role_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(recording_persons::table.filter(recording_persons::role_id.eq(from)))
-                .set(recording_persons::role_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(
-                recording_ensembles::table.filter(recording_ensembles::role_id.eq(from)),
-            )
-            .set(recording_ensembles::role_id.eq(into))
-            .execute(connection)?;
-
-            diesel::delete(roles::table.filter(roles::role_id.eq(from))).execute(connection)?;
-
-            Ok(())
-        })?;
-
-        self.changed();
-
-        Ok(())
+        self.merge(EntityKind::Role, from, into)
     }
 
     /// Merge one instrument into another.
     pub fn merge_instruments(&self, from: &str, into: &str) -> Result<()> {
-        if from == into {
-            bail!("cannot merge an instrument into itself");
-        }
-
-        let connection = &mut *self.conn();
-
-        connection.transaction::<_, diesel::result::Error, _>(|connection| {
-            diesel::update(
-                work_instruments::table.filter(work_instruments::instrument_id.eq(from)),
-            )
-            .set(work_instruments::instrument_id.eq(into))
-            .execute(connection)?;
-
-            diesel::update(
-                ensemble_persons::table.filter(ensemble_persons::instrument_id.eq(from)),
-            )
-            .set(ensemble_persons::instrument_id.eq(into))
-            .execute(connection)?;
-
-            diesel::update(
-                recording_persons::table.filter(recording_persons::instrument_id.eq(from)),
-            )
-            .set(recording_persons::instrument_id.eq(into))
-            .execute(connection)?;
-
-            diesel::delete(instruments::table.filter(instruments::instrument_id.eq(from)))
-                .execute(connection)?;
-
-            Ok(())
-        })?;
-
-        self.changed();
-
-        Ok(())
+        self.merge(EntityKind::Instrument, from, into)
     }
 
     /// Merge one tag into another.
@@ -141,70 +52,12 @@ impl Library {
     /// Meging a value-taking tag into a plain tag means that all values are
     /// effectively discarded.
     pub fn merge_tags(&self, from: &str, into: &str) -> Result<()> {
-        if from == into {
-            bail!("cannot merge a tag into itself");
-        }
-
-        let connection = &mut *self.conn();
-
-        connection.transaction::<_, diesel::result::Error, _>(|connection| {
-            let takes_value = tags::table
-                .filter(tags::tag_id.eq(into))
-                .select(tags::takes_value)
-                .first::<bool>(connection)?;
-
-            diesel::update(work_tags::table.filter(work_tags::tag_id.eq(from)))
-                .set(work_tags::tag_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(recording_tags::table.filter(recording_tags::tag_id.eq(from)))
-                .set(recording_tags::tag_id.eq(into))
-                .execute(connection)?;
-
-            if !takes_value {
-                diesel::update(work_tags::table.filter(work_tags::tag_id.eq(into)))
-                    .set(work_tags::value.eq(None::<String>))
-                    .execute(connection)?;
-
-                diesel::update(recording_tags::table.filter(recording_tags::tag_id.eq(into)))
-                    .set(recording_tags::value.eq(None::<String>))
-                    .execute(connection)?;
-            }
-
-            diesel::delete(tags::table.filter(tags::tag_id.eq(from))).execute(connection)?;
-
-            Ok(())
-        })?;
-
-        self.changed();
-
-        Ok(())
+        self.merge(EntityKind::Tag, from, into)
     }
 
     /// Merge one ensemble into another.
     pub fn merge_ensembles(&self, from: &str, into: &str) -> Result<()> {
-        if from == into {
-            bail!("cannot merge an ensemble into itself");
-        }
-
-        let connection = &mut *self.conn();
-
-        connection.transaction::<_, diesel::result::Error, _>(|connection| {
-            diesel::update(
-                recording_ensembles::table.filter(recording_ensembles::ensemble_id.eq(from)),
-            )
-            .set(recording_ensembles::ensemble_id.eq(into))
-            .execute(connection)?;
-
-            diesel::delete(ensembles::table.filter(ensembles::ensemble_id.eq(from)))
-                .execute(connection)?;
-
-            Ok(())
-        })?;
-
-        self.changed();
-
-        Ok(())
+        self.merge(EntityKind::Ensemble, from, into)
     }
 
     /// Merge one work into another.
@@ -216,67 +69,41 @@ impl Library {
     /// Merging a work into its own children will fail to avoid circular
     /// references.
     pub fn merge_works(&self, from: &str, into: &str) -> Result<()> {
-        if from == into {
-            bail!("cannot merge a work into itself");
-        }
+        self.merge(EntityKind::Work, from, into)
+    }
 
+    fn merge(&self, kind: EntityKind, from: &str, into: &str) -> Result<()> {
         let connection = &mut *self.conn();
 
-        connection.transaction::<_, anyhow::Error, _>(|connection| {
-            let mut ancestor = works::table
-                .filter(works::work_id.eq(into))
-                .select(works::parent_work_id)
-                .first::<Option<String>>(connection)?;
-            let mut seen = HashSet::new();
-
-            while let Some(id) = ancestor {
-                if id == from {
-                    bail!("cannot merge a work into one of its own descendants");
-                }
-
-                if !seen.insert(id.clone()) {
-                    break;
-                }
-
-                ancestor = works::table
-                    .filter(works::work_id.eq(&id))
-                    .select(works::parent_work_id)
-                    .first::<Option<String>>(connection)?;
-            }
-
-            // Check if `into` has any work parts (children)
-            let into_has_children = works::table
-                .filter(works::parent_work_id.eq(into))
-                .select(works::work_id)
-                .first::<String>(connection)
-                .optional()?
-                .is_some();
-
-            // Only move work parts of `from` to `into` if `into` has no work parts
-            if !into_has_children {
-                diesel::update(works::table.filter(works::parent_work_id.eq(from)))
-                    .set(works::parent_work_id.eq(into))
-                    .execute(connection)?;
-            }
-
-            diesel::update(recordings::table.filter(recordings::work_id.eq(from)))
-                .set(recordings::work_id.eq(into))
-                .execute(connection)?;
-
-            diesel::update(track_works::table.filter(track_works::work_id.eq(from)))
-                .set(track_works::work_id.eq(into))
-                .execute(connection)?;
-
-            diesel::delete(works::table.filter(works::work_id.eq(from))).execute(connection)?;
-
-            Ok(())
-        })?;
+        history::record(
+            connection,
+            ChangeAction::Merge,
+            kind,
+            from,
+            Some(into),
+            |connection| merge_entities(connection, kind, from, into),
+        )?;
 
         self.changed();
 
         Ok(())
     }
 
+    /// How much else in the library refers to an entity of `kind`.
+    pub fn usage_of(&self, kind: EntityKind, id: &str) -> Result<EntityUsage> {
+        match kind {
+            EntityKind::Person => self.usage_of_person(id),
+            EntityKind::Role => self.usage_of_role(id),
+            EntityKind::Instrument => self.usage_of_instrument(id),
+            EntityKind::Tag => self.usage_of_tag(id),
+            EntityKind::Ensemble => self.usage_of_ensemble(id),
+            EntityKind::Work => self.usage_of_work(id),
+            EntityKind::Recording | EntityKind::Album | EntityKind::Track => {
+                bail!("cannot merge a {kind}")
+            }
+        }
+    }
+
     /// How much else in the library refers to this person.
     pub fn usage_of_person(&self, id: &str) -> Result<EntityUsage> {
         let connection = &mut *self.conn();
@@ -427,6 +254,209 @@ impl Library {
     }
 }
 
+/// Merge `from` into `into` within an already open transaction.
+///
+/// The merge is recorded in the change log, so that a library this one is
+/// synchronized with can repeat it instead of only seeing `from` disappear.
+pub(crate) fn merge_entities(
+    connection: &mut SqliteConnection,
+    kind: EntityKind,
+    from: &str,
+    into: &str,
+) -> Result<()> {
+    // The provenance has to be captured while the references still point to
+    // `from`. Merging an entity into itself fails below.
+    if from != into {
+        unmerge::record_merge(connection, kind, from, into)?;
+    }
+
+    match kind {
+        EntityKind::Person => merge_persons(connection, from, into)?,
+        EntityKind::Role => merge_roles(connection, from, into)?,
+        EntityKind::Instrument => merge_instruments(connection, from, into)?,
+        EntityKind::Tag => merge_tags(connection, from, into)?,
+        EntityKind::Ensemble => merge_ensembles(connection, from, into)?,
+        EntityKind::Work => merge_works(connection, from, into)?,
+        EntityKind::Recording | EntityKind::Album | EntityKind::Track => {
+            bail!("cannot merge a {kind}")
+        }
+    }
+
+    sync::record_merge(connection, kind, from, into)
+}
+
+fn merge_persons(connection: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        bail!("cannot merge a person into itself");
+    }
+
+    diesel::update(work_persons::table.filter(work_persons::person_id.eq(from)))
+        .set(work_persons::person_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(ensemble_persons::table.filter(ensemble_persons::person_id.eq(from)))
+        .set(ensemble_persons::person_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(recording_persons::table.filter(recording_persons::person_id.eq(from)))
+        .set(recording_persons::person_id.eq(into))
+        .execute(connection)?;
+
+    diesel::delete(persons::table.filter(persons::person_id.eq(from))).execute(connection)?;
+
+    Ok(())
+}
+
+fn merge_roles(connection: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        bail!("cannot merge a role into itself");
+    }
+
+    diesel::update(work_persons::table.filter(work_persons::role_id.eq(from)))
+        .set(work_persons::role_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(ensemble_persons::table.filter(ensemble_persons::role_id.eq(from)))
+        .set(ensemble_persons::role_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(recording_persons::table.filter(recording_persons::role_id.eq(from)))
+        .set(recording_persons::role_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(recording_ensembles::table.filter(recording_ensembles::role_id.eq(from)))
+        .set(recording_ensembles::role_id.eq(into))
+        .execute(connection)?;
+
+    diesel::delete(roles::table.filter(roles::role_id.eq(from))).execute(connection)?;
+
+    Ok(())
+}
+
+fn merge_instruments(connection: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        bail!("cannot merge an instrument into itself");
+    }
+
+    diesel::update(work_instruments::table.filter(work_instruments::instrument_id.eq(from)))
+        .set(work_instruments::instrument_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(ensemble_persons::table.filter(ensemble_persons::instrument_id.eq(from)))
+        .set(ensemble_persons::instrument_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(recording_persons::table.filter(recording_persons::instrument_id.eq(from)))
+        .set(recording_persons::instrument_id.eq(into))
+        .execute(connection)?;
+
+    diesel::delete(instruments::table.filter(instruments::instrument_id.eq(from)))
+        .execute(connection)?;
+
+    Ok(())
+}
+
+fn merge_tags(connection: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        bail!("cannot merge a tag into itself");
+    }
+
+    let takes_value = tags::table
+        .filter(tags::tag_id.eq(into))
+        .select(tags::takes_value)
+        .first::<bool>(connection)?;
+
+    diesel::update(work_tags::table.filter(work_tags::tag_id.eq(from)))
+        .set(work_tags::tag_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(recording_tags::table.filter(recording_tags::tag_id.eq(from)))
+        .set(recording_tags::tag_id.eq(into))
+        .execute(connection)?;
+
+    if !takes_value {
+        diesel::update(work_tags::table.filter(work_tags::tag_id.eq(into)))
+            .set(work_tags::value.eq(None::<String>))
+            .execute(connection)?;
+
+        diesel::update(recording_tags::table.filter(recording_tags::tag_id.eq(into)))
+            .set(recording_tags::value.eq(None::<String>))
+            .execute(connection)?;
+    }
+
+    diesel::delete(tags::table.filter(tags::tag_id.eq(from))).execute(connection)?;
+
+    Ok(())
+}
+
+fn merge_ensembles(connection: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        bail!("cannot merge an ensemble into itself");
+    }
+
+    diesel::update(recording_ensembles::table.filter(recording_ensembles::ensemble_id.eq(from)))
+        .set(recording_ensembles::ensemble_id.eq(into))
+        .execute(connection)?;
+
+    diesel::delete(ensembles::table.filter(ensembles::ensemble_id.eq(from))).execute(connection)?;
+
+    Ok(())
+}
+
+fn merge_works(connection: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        bail!("cannot merge a work into itself");
+    }
+
+    let mut ancestor = works::table
+        .filter(works::work_id.eq(into))
+        .select(works::parent_work_id)
+        .first::<Option<String>>(connection)?;
+    let mut seen = HashSet::new();
+
+    while let Some(id) = ancestor {
+        if id == from {
+            bail!("cannot merge a work into one of its own descendants");
+        }
+
+        if !seen.insert(id.clone()) {
+            break;
+        }
+
+        ancestor = works::table
+            .filter(works::work_id.eq(&id))
+            .select(works::parent_work_id)
+            .first::<Option<String>>(connection)?;
+    }
+
+    // Check if `into` has any work parts (children)
+    let into_has_children = works::table
+        .filter(works::parent_work_id.eq(into))
+        .select(works::work_id)
+        .first::<String>(connection)
+        .optional()?
+        .is_some();
+
+    // Only move work parts of `from` to `into` if `into` has no work parts
+    if !into_has_children {
+        diesel::update(works::table.filter(works::parent_work_id.eq(from)))
+            .set(works::parent_work_id.eq(into))
+            .execute(connection)?;
+    }
+
+    diesel::update(recordings::table.filter(recordings::work_id.eq(from)))
+        .set(recordings::work_id.eq(into))
+        .execute(connection)?;
+
+    diesel::update(track_works::table.filter(track_works::work_id.eq(from)))
+        .set(track_works::work_id.eq(into))
+        .execute(connection)?;
+
+    diesel::delete(works::table.filter(works::work_id.eq(from))).execute(connection)?;
+
+    Ok(())
+}
+
 fn as_usize(count: std::result::Result<i64, diesel::result::Error>) -> Result<usize> {
     Ok(count?.max(0) as usize)
 }
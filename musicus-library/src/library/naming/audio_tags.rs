@@ -9,17 +9,26 @@
 //! fatal: the database is unaffected, and the files can be brought back in line
 //! at any time with a reorganization.
 
-use std::path::Path;
+use std::{borrow::Cow, fs::File, path::Path};
 
 use anyhow::{anyhow, bail, Context, Result};
+use gettextrs::gettext;
 use lofty::{
     config::{ParseOptions, WriteOptions},
-    file::TaggedFileExt,
+    file::{AudioFile, FileType, TaggedFile, TaggedFileExt},
+    flac::FlacFile,
+    id3::v2::{Frame, FrameId, Id3v2Tag, TextInformationFrame},
+    iff::{aiff::AiffFile, wav::WavFile},
+    mpeg::MpegFile,
+    ogg::{tag::VorbisComments, OpusFile, SpeexFile, VorbisFile},
+    picture::Picture,
     probe::Probe,
-    tag::{Accessor, Tag, TagExt, TagType},
+    tag::{ItemKey, ItemValue, Tag, TagExt, TagItem, TagType},
+    TextEncoding,
 };
 
-use super::pattern::{self, Patterns, TrackData};
+use super::pattern::{self, Patterns, PreservedTags, TrackData};
+use crate::format_translated;
 
 pub use super::pattern::{
     DEFAULT_ALBUM_PATTERN, DEFAULT_ARTIST_PATTERN, DEFAULT_TITLE_PATTERN, PLACEHOLDERS,
@@ -32,6 +41,79 @@ pub use super::pattern::{
 /// like `{composer}: {work}` has to survive a recording without a composer.
 const SEPARATORS: &[char] = &[' ', '_', '-', '.', ';', ',', ':'];
 
+/// Keys that Musicus derives from its database and therefore always writes
+/// itself, whatever the file brought along.
+///
+/// The disc number is not among them: the library does not know how an album
+/// is split into discs. The file's own disc number counts as one of the other
+/// tags and is kept along with them.
+const MANAGED_KEYS: &[ItemKey] = &[
+    ItemKey::AlbumTitle,
+    ItemKey::TrackArtist,
+    ItemKey::TrackTitle,
+    ItemKey::TrackNumber,
+    ItemKey::Composer,
+    ItemKey::Conductor,
+    ItemKey::Performer,
+    ItemKey::Work,
+    ItemKey::Movement,
+    ItemKey::MovementNumber,
+    ItemKey::MovementTotal,
+];
+
+const REPLAY_GAIN_KEYS: &[ItemKey] = &[
+    ItemKey::ReplayGainAlbumGain,
+    ItemKey::ReplayGainAlbumPeak,
+    ItemKey::ReplayGainTrackGain,
+    ItemKey::ReplayGainTrackPeak,
+    ItemKey::R128AlbumGain,
+    ItemKey::R128TrackGain,
+];
+
+const MUSICBRAINZ_KEYS: &[ItemKey] = &[
+    ItemKey::MusicBrainzRecordingId,
+    ItemKey::MusicBrainzTrackId,
+    ItemKey::MusicBrainzReleaseId,
+    ItemKey::MusicBrainzReleaseGroupId,
+    ItemKey::MusicBrainzArtistId,
+    ItemKey::MusicBrainzReleaseArtistId,
+    ItemKey::MusicBrainzWorkId,
+    ItemKey::MusicBrainzReleaseType,
+];
+
+/// The name of the field listing the ensembles of a recording.
+///
+/// Neither this nor the list of performers has a key that lofty could map to
+/// every format, so they are written by name into Vorbis comments and as
+/// user-defined text frames into ID3v2, the two tag formats players read them
+/// from. Other formats go without.
+const ORCHESTRA_FIELD: &str = "ORCHESTRA";
+
+/// The name of the field listing the performers in ID3v2, see
+/// [`ORCHESTRA_FIELD`]. Vorbis comments have a key for them.
+const PERFORMER_FIELD: &str = "PERFORMER";
+
+/// The user-defined text frame holding the work in ID3v2.
+const WORK_FRAME: &str = "WORK";
+
+/// The frame holding the movement number and total in ID3v2.
+const MOVEMENT_FRAME: &str = "MVIN";
+
+/// Whether `preserved` keeps the items of the file for `key`.
+fn keeps(preserved: &PreservedTags, key: &ItemKey) -> bool {
+    if MANAGED_KEYS.contains(key) {
+        false
+    } else if REPLAY_GAIN_KEYS.contains(key) {
+        preserved.replay_gain
+    } else if MUSICBRAINZ_KEYS.contains(key) {
+        preserved.musicbrainz
+    } else {
+        // The Vorbis vendor string shows up as the encoder. It describes the
+        // file rather than the music and is always kept.
+        preserved.other || *key == ItemKey::EncoderSoftware
+    }
+}
+
 /// The tags of one track, ready to be written.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AudioTags {
@@ -43,6 +125,20 @@ pub struct AudioTags {
     /// Derived from the position within the recording rather than from a
     /// pattern, because players sort by it numerically.
     pub track_number: u32,
+    /// The fields players use to describe classical music, taken from the
+    /// database as they are.
+    pub composer: Option<String>,
+    pub conductor: Option<String>,
+    pub orchestra: Option<String>,
+    pub performers: Vec<String>,
+    pub work: Option<String>,
+    pub movement: Option<String>,
+    pub movement_number: Option<(u32, u32)>,
+    /// The cover image from the library. It replaces whatever pictures the file
+    /// carried, even preserved ones. Not part of the pattern data, so it is set
+    /// by the caller.
+    pub cover_art: Option<Picture>,
+    pub preserved: PreservedTags,
 }
 
 impl AudioTags {
@@ -55,6 +151,11 @@ impl AudioTags {
                 .filter(|value| !value.is_empty())
         };
 
+        let field = |value: &str| {
+            let value = tidy(value);
+            (!value.is_empty()).then_some(value)
+        };
+
         Self {
             album: render(&patterns.album),
             artist: render(&patterns.artist),
@@ -68,24 +169,124 @@ impl AudioTags {
                     return None;
                 }
 
-                let work = tidy(&data.work);
-                (!work.is_empty()).then_some(work)
+                field(&data.work)
             }),
             track_number: recording_index.saturating_add(1).max(0) as u32,
+            composer: field(&data.composer),
+            conductor: field(&data.conductor()),
+            orchestra: field(&data.ensemble),
+            performers: data
+                .performer_credits
+                .iter()
+                .filter_map(|performer| field(performer))
+                .collect(),
+            work: field(&data.work),
+            movement: data.movement.and_then(|_| field(&data.part)),
+            movement_number: data.movement,
+            cover_art: None,
+            preserved: patterns.preserved_tags,
+        }
+    }
+
+    /// The tag items this describes, in lofty's format-independent terms.
+    fn items(&self) -> Vec<TagItem> {
+        let text = |key: ItemKey, value: &str| TagItem::new(key, ItemValue::Text(value.to_owned()));
+
+        let mut items = vec![text(ItemKey::TrackNumber, &self.track_number.to_string())];
+
+        for (key, value) in [
+            (ItemKey::AlbumTitle, &self.album),
+            (ItemKey::TrackArtist, &self.artist),
+            (ItemKey::TrackTitle, &self.title),
+            (ItemKey::Composer, &self.composer),
+            (ItemKey::Conductor, &self.conductor),
+            (ItemKey::Work, &self.work),
+            (ItemKey::Movement, &self.movement),
+        ] {
+            if let Some(value) = value {
+                items.push(text(key, value));
+            }
         }
+
+        for performer in &self.performers {
+            items.push(text(ItemKey::Performer, performer));
+        }
+
+        if let Some((number, total)) = self.movement_number {
+            items.push(text(ItemKey::MovementNumber, &number.to_string()));
+            items.push(text(ItemKey::MovementTotal, &total.to_string()));
+        }
+
+        items
     }
 
-    /// The number of tag items this describes.
-    fn len(&self) -> usize {
-        // The track number is always written.
-        1 + [&self.album, &self.artist, &self.title]
+    /// The fields of a `tag_type` tag that lofty has no key for, in the order
+    /// of [`custom_field_names`].
+    fn custom_fields(&self, tag_type: TagType) -> Vec<(&'static str, String)> {
+        custom_field_names(tag_type)
             .iter()
-            .filter(|value| value.is_some())
-            .count()
+            .filter_map(|name| {
+                let value = match *name {
+                    ORCHESTRA_FIELD => self.orchestra.clone(),
+                    _ => (!self.performers.is_empty()).then(|| self.performers.join(", ")),
+                };
+
+                Some((*name, value?))
+            })
+            .collect()
+    }
+
+    /// The complete tag to write into a file whose current tags are `existing`.
+    fn build(&self, existing: &[Tag], tag_type: TagType) -> Tag {
+        let mut tag = Tag::new(tag_type);
+
+        for item in self.items() {
+            // A key the format has no field for is left out.
+            tag.push(item);
+        }
+
+        if let Some(cover_art) = &self.cover_art {
+            tag.push_picture(cover_art.clone());
+        }
+
+        // The primary tag comes first, so where several tags of the file have a
+        // value for the same key, its value wins.
+        let mut primary_first = existing.iter().collect::<Vec<&Tag>>();
+        primary_first.sort_by_key(|existing| existing.tag_type() != tag_type);
+
+        let mut preserved_keys = Vec::new();
+
+        for existing in primary_first {
+            let mut keys = Vec::new();
+
+            for item in existing.items() {
+                let key = item.key();
+
+                if keeps(&self.preserved, &key)
+                    && !preserved_keys.contains(&key)
+                    && tag.push(item.clone())
+                {
+                    keys.push(key);
+                }
+            }
+
+            preserved_keys.extend(keys);
+
+            if self.preserved.cover_art && tag.pictures().is_empty() {
+                for picture in existing.pictures() {
+                    tag.push_picture(picture.clone());
+                }
+            }
+        }
+
+        tag
     }
 }
 
-/// Replace every tag of the file at `path` with `tags`.
+/// Write `tags` into the file at `path`, replacing its primary tag.
+///
+/// Of the tags the file carried before, only those chosen by
+/// [`AudioTags::preserved`] are kept.
 ///
 /// Returns `false` if the file already carried exactly these tags and was
 /// therefore left untouched.
@@ -93,16 +294,154 @@ impl AudioTags {
 /// Fails if the file is not in an audio format lofty recognizes, if that format
 /// cannot store a writable tag, or if the write itself fails.
 pub fn write(path: &Path, tags: &AudioTags) -> Result<bool> {
+    let (file_type, existing) = read(path)?;
+    let tag_type = file_type.primary_tag_type();
+
+    let tag = tags.build(existing.tags(), tag_type);
+    let custom_fields = tags.custom_fields(tag_type);
+    let unknown_fields = read_unknown_fields(path, file_type)?;
+
+    if is_up_to_date(&existing, &tag)
+        && read_custom_fields(path, file_type, &custom_fields)? == custom_fields
+        && (tags.preserved.other || unknown_fields.is_empty())
+    {
+        return Ok(false);
+    }
+
+    let result = match tag_type {
+        TagType::VorbisComments => {
+            let mut comments = VorbisComments::from(tag);
+            for (name, value) in custom_fields {
+                comments.push(name.to_owned(), value);
+            }
+            if tags.preserved.other {
+                for (name, value) in unknown_fields {
+                    comments.push(name, value);
+                }
+            }
+            comments.save_to_path(path, WriteOptions::new())
+        }
+        TagType::Id3v2 => {
+            let mut id3v2 = to_id3v2(tag);
+            for (name, value) in custom_fields {
+                id3v2.insert_user_text(name.to_owned(), value);
+            }
+            id3v2.save_to_path(path, WriteOptions::new())
+        }
+        _ => tag.save_to_path(path, WriteOptions::new()),
+    };
+
+    result.with_context(|| format!("Failed to write the tags of {}", path.display()))?;
+
+    // Whatever was kept of the other tags is now part of the primary one.
+    // Players that prefer one of them would otherwise show stale values.
+    for other in existing.tags() {
+        if other.tag_type() == tag_type {
+            continue;
+        }
+
+        // Like `read`, this identifies the format by the content of the file.
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        other
+            .tag_type()
+            .remove_from(&mut file, WriteOptions::new())
+            .with_context(|| format!("Failed to remove old tags from {}", path.display()))?;
+    }
+
+    Ok(true)
+}
+
+/// One field of a file's primary tag that [`write`] would change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TagChange {
+    /// The name of the field, meant to be shown to the user.
+    pub field: String,
+    /// `None` if the file has no value for the field yet.
+    pub old: Option<String>,
+    /// `None` if the field is removed.
+    pub new: Option<String>,
+}
+
+/// What writing `tags` into the file at `path` would change, without touching
+/// the file.
+///
+/// A field with several values is compared as a whole. The result is empty if
+/// the file already carries these values, even if [`write`] would still rewrite
+/// it to bring their order in line.
+pub fn changes(path: &Path, tags: &AudioTags) -> Result<Vec<TagChange>> {
+    let (file_type, existing) = read(path)?;
+    let tag_type = file_type.primary_tag_type();
+
+    let tag = tags.build(existing.tags(), tag_type);
+    let empty = Tag::new(tag_type);
+    let current = existing.tag(tag_type).unwrap_or(&empty);
+
+    let mut keys = Vec::new();
+
+    for item in tag.items().chain(current.items()) {
+        if !keys.contains(&item.key()) {
+            keys.push(item.key());
+        }
+    }
+
+    let mut changes = keys
+        .into_iter()
+        .map(|key| TagChange {
+            field: field_name(&key),
+            old: values(current, &key),
+            new: values(&tag, &key),
+        })
+        .collect::<Vec<TagChange>>();
+
+    let custom_fields = tags.custom_fields(tag_type);
+    let current_custom_fields = read_custom_fields(path, file_type, &custom_fields)?;
+
+    for name in custom_field_names(tag_type) {
+        let value = |fields: &[(&'static str, String)]| {
+            let values = fields
+                .iter()
+                .filter(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+                .collect::<Vec<&str>>();
+
+            (!values.is_empty()).then(|| values.join("; "))
+        };
+
+        changes.push(TagChange {
+            field: custom_field_label(name),
+            old: value(&current_custom_fields),
+            new: value(&custom_fields),
+        });
+    }
+
+    changes.push(TagChange {
+        field: gettext("Cover art"),
+        old: describe_pictures(current.pictures()),
+        new: describe_pictures(tag.pictures()),
+    });
+
+    changes.retain(|change| change.old != change.new);
+
+    Ok(changes)
+}
+
+/// Open the file at `path` for reading its tags, making sure that they can be
+/// written afterwards.
+///
+/// Fails if the file is not in an audio format lofty recognizes or if that
+/// format cannot store a writable tag.
+fn read(path: &Path) -> Result<(FileType, TaggedFile)> {
     // The format has to be determined from the content: while a track is being
     // imported its file is still called `<name>.<extension>.part`, so the
     // extension says nothing.
     let probe = Probe::open(path)
         .with_context(|| format!("Failed to open {}", path.display()))?
-        .options(
-            ParseOptions::new()
-                .read_properties(false)
-                .read_cover_art(false),
-        )
+        .options(ParseOptions::new().read_properties(false))
         .guess_file_type()
         .with_context(|| format!("Failed to read {}", path.display()))?;
 
@@ -120,33 +459,116 @@ pub fn write(path: &Path, tags: &AudioTags) -> Result<bool> {
         .read()
         .with_context(|| format!("Failed to read {}", path.display()))?;
 
-    if is_up_to_date(&existing, tags, tag_type) {
-        return Ok(false);
-    }
+    Ok((file_type, existing))
+}
+
+/// The values `tag` has for `key`, joined for display.
+fn values(tag: &Tag, key: &ItemKey) -> Option<String> {
+    let values = tag
+        .items()
+        .filter(|item| item.key() == *key)
+        .map(|item| match item.value() {
+            ItemValue::Text(text) | ItemValue::Locator(text) => text.to_owned(),
+            ItemValue::Binary(data) => format_translated!(gettext("{} bytes"), data.len()),
+        })
+        .collect::<Vec<String>>();
+
+    (!values.is_empty()).then(|| values.join("; "))
+}
+
+fn describe_pictures(pictures: &[Picture]) -> Option<String> {
+    let descriptions = pictures
+        .iter()
+        .map(|picture| {
+            let mime_type = picture
+                .mime_type()
+                .map(|mime_type| mime_type.as_str().to_owned())
+                .unwrap_or_else(|| gettext("unknown format"));
+
+            format!(
+                "{mime_type}, {}",
+                format_translated!(gettext("{} bytes"), picture.data().len())
+            )
+        })
+        .collect::<Vec<String>>();
 
-    let mut tag = Tag::new(tag_type);
+    (!descriptions.is_empty()).then(|| descriptions.join("; "))
+}
 
-    if let Some(album) = &tags.album {
-        tag.set_album(album.to_owned());
+/// The name of the field behind `key`, for showing it to the user.
+///
+/// The fields Musicus writes get a proper name. Everything else is only ever
+/// kept or dropped and goes by lofty's name for it.
+fn field_name(key: &ItemKey) -> String {
+    match key {
+        ItemKey::AlbumTitle => gettext("Album"),
+        ItemKey::TrackArtist => gettext("Artist"),
+        ItemKey::TrackTitle => gettext("Title"),
+        ItemKey::TrackNumber => gettext("Track number"),
+        ItemKey::Composer => gettext("Composer"),
+        ItemKey::Conductor => gettext("Conductor"),
+        ItemKey::Performer => gettext("Performers"),
+        ItemKey::Work => gettext("Work"),
+        ItemKey::Movement => gettext("Movement"),
+        ItemKey::MovementNumber => gettext("Movement number"),
+        ItemKey::MovementTotal => gettext("Number of movements"),
+        key => format!("{key:?}"),
     }
+}
+
+fn custom_field_label(name: &str) -> String {
+    match name {
+        ORCHESTRA_FIELD => gettext("Orchestra"),
+        _ => gettext("Performers"),
+    }
+}
+
+/// Convert `tag` into an ID3v2 tag.
+///
+/// lofty reads the work and the movement number from ID3v2, but cannot write
+/// them: it would put the work into a frame whose name ID3v2 does not allow and
+/// drop the movement number. Both are therefore written by hand, into the
+/// frames lofty reads them back from.
+fn to_id3v2(mut tag: Tag) -> Id3v2Tag {
+    let work = tag.take_strings(ItemKey::Work).next();
+    let number = tag.take_strings(ItemKey::MovementNumber).next();
+    let total = tag.take_strings(ItemKey::MovementTotal).next();
+
+    // MusicBrainz identifiers are user-defined text frames that the conversion
+    // would drop for their descriptions, which are no valid frame IDs.
+    let musicbrainz = MUSICBRAINZ_KEYS
+        .iter()
+        .filter_map(|key| {
+            let description = key.map_key(TagType::Id3v2)?;
+            let value = tag.take_strings(*key).next()?;
+            Some((description.to_owned(), value))
+        })
+        .collect::<Vec<(String, String)>>();
+
+    let mut id3v2 = Id3v2Tag::from(tag);
 
-    if let Some(artist) = &tags.artist {
-        tag.set_artist(artist.to_owned());
+    for (description, value) in musicbrainz {
+        id3v2.insert_user_text(description, value);
     }
 
-    if let Some(title) = &tags.title {
-        tag.set_title(title.to_owned());
+    if let Some(work) = work {
+        id3v2.insert_user_text(WORK_FRAME.to_owned(), work);
     }
 
-    tag.set_track(tags.track_number);
+    if let Some(number) = number {
+        let value = match total {
+            Some(total) => format!("{number}/{total}"),
+            None => number,
+        };
 
-    // Writing a freshly built tag with `remove_others` is what makes the file
-    // carry exactly the tags Musicus manages: anything the source file brought
-    // along, in this or in any other tag format, is dropped.
-    tag.save_to_path(path, WriteOptions::new().remove_others(true))
-        .with_context(|| format!("Failed to write the tags of {}", path.display()))?;
+        id3v2.insert(Frame::Text(TextInformationFrame::new(
+            FrameId::Valid(Cow::Borrowed(MOVEMENT_FRAME)),
+            TextEncoding::UTF8,
+            value,
+        )));
+    }
 
-    Ok(true)
+    id3v2
 }
 
 /// Whether the user turned this tag off by emptying its pattern.
@@ -200,23 +622,150 @@ fn tidy(value: &str) -> String {
         .to_owned()
 }
 
-/// Whether the file already carries exactly `tags` and nothing else.
+/// Whether `tag` already is the only tag of `file`.
 ///
 /// Rewriting a file that is already correct would change its modification time
 /// for nothing, which matters because a reorganization walks the whole library.
-fn is_up_to_date(file: &lofty::file::TaggedFile, tags: &AudioTags, tag_type: TagType) -> bool {
-    // A second tag in another format would have to be removed, whatever the
-    // first one contains.
-    let [tag] = file.tags() else {
+fn is_up_to_date(file: &lofty::file::TaggedFile, tag: &Tag) -> bool {
+    // A tag in another format would have to be removed, whatever the primary
+    // one contains.
+    let [existing] = file.tags() else {
         return false;
     };
 
-    tag.tag_type() == tag_type
-        && tag.len() == tags.len()
-        && tag.album().as_deref() == tags.album.as_deref()
-        && tag.artist().as_deref() == tags.artist.as_deref()
-        && tag.title().as_deref() == tags.title.as_deref()
-        && tag.track() == Some(tags.track_number)
+    if existing.tag_type() != tag.tag_type() {
+        return false;
+    }
+
+    // Neither the order of the items nor that of the pictures matters.
+    let same_items = existing.item_count() == tag.item_count()
+        && tag
+            .items()
+            .all(|item| existing.items().any(|other| other == item));
+
+    let same_pictures = existing.picture_count() == tag.picture_count()
+        && tag
+            .pictures()
+            .iter()
+            .all(|picture| existing.pictures().contains(picture));
+
+    same_items && same_pictures
+}
+
+/// The fields Musicus writes by name into a `tag_type` tag.
+fn custom_field_names(tag_type: TagType) -> &'static [&'static str] {
+    match tag_type {
+        TagType::VorbisComments => &[ORCHESTRA_FIELD],
+        TagType::Id3v2 => &[ORCHESTRA_FIELD, PERFORMER_FIELD],
+        _ => &[],
+    }
+}
+
+/// The values the file at `path` has for the custom fields of its primary tag.
+///
+/// The custom fields are invisible in lofty's format-independent tag, so they
+/// are read from the concrete tag of the file. A value that is missing is
+/// left out of the result, which then differs from `expected`.
+fn read_custom_fields(
+    path: &Path,
+    file_type: FileType,
+    expected: &[(&'static str, String)],
+) -> Result<Vec<(&'static str, String)>> {
+    let names = custom_field_names(file_type.primary_tag_type());
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let options = ParseOptions::new()
+        .read_properties(false)
+        .read_cover_art(false);
+
+    let vorbis_fields = |comments: Option<&VorbisComments>| {
+        comments.map(|comments| {
+            names
+                .iter()
+                .flat_map(|name| {
+                    comments
+                        .get_all(name)
+                        .map(|value| (*name, value.to_owned()))
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let id3v2_fields = |id3v2: Option<&Id3v2Tag>| {
+        id3v2.map(|id3v2| {
+            names
+                .iter()
+                .filter_map(|name| Some((*name, id3v2.get_user_text(name)?.to_owned())))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let fields = match file_type {
+        FileType::Flac => vorbis_fields(FlacFile::read_from(&mut file, options)?.vorbis_comments()),
+        FileType::Opus => vorbis_fields(Some(
+            OpusFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        FileType::Vorbis => vorbis_fields(Some(
+            VorbisFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        FileType::Speex => vorbis_fields(Some(
+            SpeexFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        FileType::Mpeg => id3v2_fields(MpegFile::read_from(&mut file, options)?.id3v2()),
+        FileType::Wav => id3v2_fields(WavFile::read_from(&mut file, options)?.id3v2()),
+        FileType::Aiff => id3v2_fields(AiffFile::read_from(&mut file, options)?.id3v2()),
+        // No custom fields are written into other formats.
+        _ => return Ok(expected.to_vec()),
+    };
+
+    Ok(fields.unwrap_or_default())
+}
+
+/// The Vorbis comments of the file at `path` that lofty has no key for, like
+/// the fields only another player knows about.
+///
+/// Like the custom fields, they are invisible in lofty's format-independent tag
+/// and are read from the concrete tag of the file. The custom fields are
+/// Musicus' own and therefore not part of the result.
+fn read_unknown_fields(path: &Path, file_type: FileType) -> Result<Vec<(String, String)>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let options = ParseOptions::new()
+        .read_properties(false)
+        .read_cover_art(false);
+
+    let unknown_fields = |comments: Option<&VorbisComments>| {
+        let names = custom_field_names(TagType::VorbisComments);
+
+        comments
+            .map(|comments| {
+                comments
+                    .items()
+                    .filter(|(key, _)| {
+                        ItemKey::from_key(TagType::VorbisComments, key).is_none()
+                            && !names.iter().any(|name| name.eq_ignore_ascii_case(key))
+                    })
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    Ok(match file_type {
+        FileType::Flac => {
+            unknown_fields(FlacFile::read_from(&mut file, options)?.vorbis_comments())
+        }
+        FileType::Opus => unknown_fields(Some(
+            OpusFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        FileType::Vorbis => unknown_fields(Some(
+            VorbisFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        FileType::Speex => unknown_fields(Some(
+            SpeexFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        _ => Vec::new(),
+    })
 }
 
 /// The smallest file that lofty accepts as audio.
@@ -257,6 +806,11 @@ mod tests {
     use lofty::probe::read_from_path;
     use tempfile::TempDir;
 
+    use lofty::{
+        picture::{MimeType, Picture, PictureType},
+        tag::Accessor,
+    };
+
     use super::*;
 
     fn data() -> TrackData {
@@ -266,6 +820,21 @@ mod tests {
             part: "Largo".to_owned(),
             performers: "Berliner Philharmoniker".to_owned(),
             index: "02".to_owned(),
+            ..TrackData::default()
+        }
+    }
+
+    /// Data for a track with everything a classical tag set can describe.
+    fn full_data() -> TrackData {
+        TrackData {
+            ensemble: "Berliner Philharmoniker".to_owned(),
+            performer_credits: vec!["Anne-Sophie Mutter (Violin)".to_owned()],
+            roles: vec![pattern::NamedValue {
+                names: vec!["Conductor".to_owned()],
+                value: "Herbert von Karajan".to_owned(),
+            }],
+            movement: Some((2, 4)),
+            ..data()
         }
     }
 
@@ -369,6 +938,183 @@ mod tests {
         assert_eq!(tag.track(), Some(2));
     }
 
+    #[test]
+    fn the_classical_fields_are_written() {
+        let dir = TempDir::new().unwrap();
+        let path = wav(&dir, "track.wav");
+        let tags = AudioTags::render(&Patterns::default(), &full_data(), 1);
+
+        assert!(write(&path, &tags).unwrap());
+
+        let file = read_from_path(&path).unwrap();
+        let tag = file.primary_tag().unwrap();
+
+        assert_eq!(tag.get_string(ItemKey::Composer), Some("Antonín Dvořák"));
+        assert_eq!(
+            tag.get_string(ItemKey::Conductor),
+            Some("Herbert von Karajan")
+        );
+        assert_eq!(tag.get_string(ItemKey::Movement), Some("Largo"));
+        assert_eq!(tag.get_string(ItemKey::MovementNumber), Some("2"));
+        assert_eq!(tag.get_string(ItemKey::MovementTotal), Some("4"));
+        assert_eq!(tag.disk(), None);
+
+        // The fields without a format-independent key.
+        let mut file = File::open(&path).unwrap();
+        let wav = WavFile::read_from(&mut file, ParseOptions::new()).unwrap();
+        let id3v2 = wav.id3v2().unwrap();
+
+        assert_eq!(id3v2.get_user_text(WORK_FRAME), Some("Symfonie č. 9"));
+        assert_eq!(
+            id3v2.get_user_text(ORCHESTRA_FIELD),
+            Some("Berliner Philharmoniker")
+        );
+        assert_eq!(
+            id3v2.get_user_text(PERFORMER_FIELD),
+            Some("Anne-Sophie Mutter (Violin)")
+        );
+    }
+
+    #[test]
+    fn a_track_that_is_no_movement_has_no_movement_fields() {
+        let tags = AudioTags::render(&Patterns::default(), &data(), 0);
+
+        assert_eq!(tags.movement, None);
+        assert_eq!(tags.movement_number, None);
+        assert_eq!(tags.work.as_deref(), Some("Symfonie č. 9"));
+    }
+
+    /// A file with what a ripper typically adds on top of the basic tags.
+    fn ripped_wav(dir: &TempDir) -> std::path::PathBuf {
+        let path = wav(dir, "track.wav");
+
+        let mut previous = Tag::new(TagType::Id3v2);
+        previous.set_genre("Rock".to_owned());
+        previous.insert_text(ItemKey::ReplayGainTrackGain, "-6.5 dB".to_owned());
+        previous.push_picture(
+            Picture::unchecked(vec![0x89, b'P', b'N', b'G', 0, 0, 0, 0])
+                .pic_type(PictureType::CoverFront)
+                .mime_type(MimeType::Png)
+                .build(),
+        );
+
+        // lofty cannot write a MusicBrainz ID into ID3v2 by its key.
+        let mut previous = Id3v2Tag::from(previous);
+        previous.insert_user_text(
+            "MusicBrainz Album Id".to_owned(),
+            "5d1d3f8e-0000-4000-8000-000000000000".to_owned(),
+        );
+        previous.save_to_path(&path, WriteOptions::new()).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn cover_art_replay_gain_and_musicbrainz_ids_survive_by_default() {
+        let dir = TempDir::new().unwrap();
+        let path = ripped_wav(&dir);
+        let tags = AudioTags::render(&Patterns::default(), &data(), 0);
+
+        assert!(write(&path, &tags).unwrap());
+
+        let file = read_from_path(&path).unwrap();
+        let tag = file.primary_tag().unwrap();
+
+        assert_eq!(tag.pictures().len(), 1);
+        assert_eq!(
+            tag.get_string(ItemKey::ReplayGainTrackGain),
+            Some("-6.5 dB")
+        );
+        assert!(tag.get_string(ItemKey::MusicBrainzReleaseId).is_some());
+        assert_eq!(tag.genre(), None);
+
+        // Keeping them does not make the file look outdated.
+        assert!(!write(&path, &tags).unwrap());
+    }
+
+    #[test]
+    fn the_preserved_tags_can_be_chosen() {
+        let dir = TempDir::new().unwrap();
+        let path = ripped_wav(&dir);
+
+        let patterns = Patterns {
+            preserved_tags: PreservedTags {
+                cover_art: false,
+                replay_gain: false,
+                musicbrainz: true,
+                other: true,
+            },
+            ..Patterns::default()
+        };
+        let tags = AudioTags::render(&patterns, &data(), 0);
+
+        assert!(write(&path, &tags).unwrap());
+
+        let file = read_from_path(&path).unwrap();
+        let tag = file.primary_tag().unwrap();
+
+        assert!(tag.pictures().is_empty());
+        assert_eq!(tag.get_string(ItemKey::ReplayGainTrackGain), None);
+        assert!(tag.get_string(ItemKey::MusicBrainzReleaseId).is_some());
+        assert_eq!(tag.genre().as_deref(), Some("Rock"));
+    }
+
+    /// Tags that Musicus writes itself are replaced even if everything else is
+    /// kept.
+    #[test]
+    fn managed_tags_are_never_preserved() {
+        let dir = TempDir::new().unwrap();
+        let path = wav(&dir, "track.wav");
+
+        let mut previous = Tag::new(TagType::Id3v2);
+        previous.set_album("Greatest Hits".to_owned());
+        previous.insert_text(ItemKey::Composer, "Somebody".to_owned());
+        previous.save_to_path(&path, WriteOptions::new()).unwrap();
+
+        let patterns = Patterns {
+            album: String::new(),
+            preserved_tags: PreservedTags {
+                other: true,
+                ..PreservedTags::default()
+            },
+            ..Patterns::default()
+        };
+
+        let mut data = data();
+        data.composer = String::new();
+
+        assert!(write(&path, &AudioTags::render(&patterns, &data, 0)).unwrap());
+
+        let file = read_from_path(&path).unwrap();
+        let tag = file.primary_tag().unwrap();
+
+        assert_eq!(tag.album(), None);
+        assert_eq!(tag.get_string(ItemKey::Composer), None);
+    }
+
+    #[test]
+    fn the_disc_number_of_the_file_is_kept_with_the_other_tags() {
+        let dir = TempDir::new().unwrap();
+        let path = wav(&dir, "track.wav");
+
+        let mut previous = Tag::new(TagType::Id3v2);
+        previous.set_disk(2);
+        previous.save_to_path(&path, WriteOptions::new()).unwrap();
+
+        let patterns = Patterns {
+            preserved_tags: PreservedTags {
+                other: true,
+                ..PreservedTags::default()
+            },
+            ..Patterns::default()
+        };
+
+        assert!(write(&path, &AudioTags::render(&patterns, &data(), 0)).unwrap());
+
+        let file = read_from_path(&path).unwrap();
+        assert_eq!(file.primary_tag().unwrap().disk(), Some(2));
+    }
+
     #[test]
     fn tags_of_the_source_file_do_not_survive() {
         let dir = TempDir::new().unwrap();
@@ -392,11 +1138,103 @@ mod tests {
         assert_eq!(file.tags().len(), 1);
     }
 
+    #[test]
+    fn tags_in_other_formats_are_removed() {
+        let dir = TempDir::new().unwrap();
+        let path = wav(&dir, "track.wav");
+
+        let mut previous = Tag::new(TagType::RiffInfo);
+        previous.set_title("Track 1".to_owned());
+        previous.set_genre("Rock".to_owned());
+        previous.save_to_path(&path, WriteOptions::new()).unwrap();
+
+        let patterns = Patterns {
+            preserved_tags: PreservedTags {
+                other: true,
+                ..PreservedTags::default()
+            },
+            ..Patterns::default()
+        };
+        let tags = AudioTags::render(&patterns, &data(), 0);
+
+        assert!(write(&path, &tags).unwrap());
+
+        let file = read_from_path(&path).unwrap();
+        assert_eq!(file.tags().len(), 1);
+
+        let tag = file.primary_tag().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Symfonie č. 9: Largo"));
+        assert_eq!(tag.genre().as_deref(), Some("Rock"));
+
+        assert!(!write(&path, &tags).unwrap());
+    }
+
+    /// A FLAC file without any audio frames, which is enough for tagging.
+    fn flac(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("track.flac");
+
+        let mut file = b"fLaC".to_vec();
+        // The only metadata block is the stream info, 34 bytes long.
+        file.extend_from_slice(&[0x80, 0, 0, 34]);
+        file.extend_from_slice(&4096u16.to_be_bytes()); // Minimum block size.
+        file.extend_from_slice(&4096u16.to_be_bytes()); // Maximum block size.
+        file.extend_from_slice(&[0; 6]); // Unknown frame sizes.
+                                         // 44100 Hz, two channels, 16 bits per sample and no samples.
+        file.extend_from_slice(&((44100u64 << 44) | (1 << 41) | (15 << 36)).to_be_bytes());
+        file.extend_from_slice(&[0; 16]); // No MD5 signature.
+
+        fs::write(&path, file).unwrap();
+        path
+    }
+
+    #[test]
+    fn fields_only_other_players_know_are_other_tags() {
+        let dir = TempDir::new().unwrap();
+        let path = flac(&dir);
+
+        let mut previous = VorbisComments::new();
+        previous.push("LABEL_CATALOG_NOTE".to_owned(), "First pressing".to_owned());
+        previous.save_to_path(&path, WriteOptions::new()).unwrap();
+
+        let mut patterns = Patterns {
+            preserved_tags: PreservedTags {
+                other: true,
+                ..PreservedTags::default()
+            },
+            ..Patterns::default()
+        };
+        let tags = AudioTags::render(&patterns, &full_data(), 0);
+
+        assert!(write(&path, &tags).unwrap());
+        assert!(!write(&path, &tags).unwrap());
+
+        let read_note = || {
+            let mut file = File::open(&path).unwrap();
+            let flac = FlacFile::read_from(&mut file, ParseOptions::new()).unwrap();
+            let comments = flac.vorbis_comments().unwrap();
+
+            assert_eq!(
+                comments.get(ORCHESTRA_FIELD),
+                Some("Berliner Philharmoniker")
+            );
+
+            comments.get("LABEL_CATALOG_NOTE").map(str::to_owned)
+        };
+
+        assert_eq!(read_note().as_deref(), Some("First pressing"));
+
+        patterns.preserved_tags.other = false;
+        let tags = AudioTags::render(&patterns, &full_data(), 0);
+
+        assert!(write(&path, &tags).unwrap());
+        assert_eq!(read_note(), None);
+    }
+
     #[test]
     fn a_file_that_is_already_correct_is_not_rewritten() {
         let dir = TempDir::new().unwrap();
         let path = wav(&dir, "track.wav");
-        let tags = AudioTags::render(&Patterns::default(), &data(), 0);
+        let tags = AudioTags::render(&Patterns::default(), &full_data(), 0);
 
         assert!(write(&path, &tags).unwrap());
 
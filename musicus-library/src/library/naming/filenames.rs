@@ -6,6 +6,11 @@
 //! path of every track, and a name that cannot be built falls back to the
 //! track's identifiers.
 //!
+//! A `/` in the pattern starts a subdirectory, so that a large library does not
+//! end up as one flat folder. Each directory name is made safe on its own, and a
+//! `/` within a value like "C/D" or within a placeholder like `{tag:AC/DC}` is
+//! never taken for one.
+//!
 //! The pattern vocabulary itself lives in [`pattern`](super::pattern); this
 //! module only turns rendered text into something a file system accepts.
 
@@ -15,13 +20,16 @@ use super::pattern;
 
 pub use super::pattern::{TrackData as TrackNameData, DEFAULT_FILENAME_PATTERN, PLACEHOLDERS};
 
-/// The longest file stem that will be generated.
+/// The longest file stem or directory name that will be generated.
 ///
 /// Every relevant file system allows at least 255 bytes per file name. The
 /// remaining room is for the deduplication suffix, the file extension and the
 /// `.part` suffix a track file carries while it is being imported.
 const MAX_STEM_LENGTH: usize = 180;
 
+/// What separates directories in patterns and in the names built from them.
+pub const DIRECTORY_SEPARATOR: char = '/';
+
 /// Characters that are not allowed in a file name on Windows, and that would
 /// either separate path components or confuse shell users elsewhere.
 const ILLEGAL_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
@@ -38,18 +46,60 @@ const RESERVED_STEMS: &[&str] = &[
 
 /// Build the file stem for `data` from `pattern`.
 ///
-/// Returns `None` if the pattern is unusable or describes a name that is empty
-/// once it has been made safe to use as a file name. Callers are expected to
-/// fall back to a name that is always available in that case, so that no import
-/// can fail because of naming.
+/// The stem is relative to the library folder and may start with directories,
+/// separated by [`DIRECTORY_SEPARATOR`]. A directory whose name renders empty
+/// is left out.
+///
+/// Returns `None` if the pattern is unusable or describes a file name that is
+/// empty once it has been made safe to use. Callers are expected to fall back to
+/// a name that is always available in that case, so that no import can fail
+/// because of naming.
 pub fn render(pattern: &str, data: &TrackNameData) -> Option<String> {
-    let stem = sanitize(&pattern::render(pattern, data).ok()?);
+    render_path(pattern, data).ok()
+}
+
+fn render_path(pattern: &str, data: &TrackNameData) -> Result<String> {
+    let mut components = split_directories(pattern)
+        .into_iter()
+        .map(|component| Ok(sanitize(&pattern::render(component, data)?)))
+        .collect::<Result<Vec<String>>>()?;
+
+    let stem = components.pop().unwrap_or_default();
 
     if stem.is_empty() {
-        None
-    } else {
-        Some(stem)
+        return Err(anyhow!("The pattern does not describe a file name."));
     }
+
+    components.retain(|directory| !directory.is_empty());
+    components.push(stem);
+
+    Ok(components.join(&DIRECTORY_SEPARATOR.to_string()))
+}
+
+/// Split `pattern` at every directory separator outside of a placeholder.
+fn split_directories(pattern: &str) -> Vec<&str> {
+    let mut components = Vec::new();
+    let mut start = 0;
+    let mut in_placeholder = false;
+    let mut characters = pattern.char_indices().peekable();
+
+    while let Some((index, character)) = characters.next() {
+        match character {
+            // A doubled brace is literal text and does not open a placeholder.
+            '{' if !in_placeholder => {
+                in_placeholder = characters.next_if(|(_, next)| *next == '{').is_none();
+            }
+            '}' if in_placeholder => in_placeholder = false,
+            DIRECTORY_SEPARATOR if !in_placeholder => {
+                components.push(&pattern[start..index]);
+                start = index + DIRECTORY_SEPARATOR.len_utf8();
+            }
+            _ => (),
+        }
+    }
+
+    components.push(&pattern[start..]);
+    components
 }
 
 /// The file stem for a track whose name cannot be built from the pattern.
@@ -73,16 +123,11 @@ pub fn validate(pattern: &str) -> Result<()> {
 /// This doubles as validation: a pattern that cannot be rendered for the
 /// example cannot be rendered for a real track either.
 pub fn preview(pattern: &str) -> Result<String> {
-    let stem = sanitize(&pattern::render(pattern, &TrackNameData::example())?);
-
-    if stem.is_empty() {
-        return Err(anyhow!("The pattern does not describe a file name."));
-    }
-
+    let stem = render_path(pattern, &TrackNameData::example())?;
     Ok(format!("{stem}.flac"))
 }
 
-/// Reduce `name` to a portable ASCII file stem.
+/// Reduce `name` to a portable ASCII file stem or directory name.
 fn sanitize(name: &str) -> String {
     // Transliterating first is what makes the result portable, but it can
     // introduce illegal characters of its own ("½" becomes "1/2"), so the
@@ -135,9 +180,27 @@ mod tests {
             part: "Largo".to_owned(),
             performers: "Berliner Philharmoniker".to_owned(),
             index: "02".to_owned(),
+            ..TrackNameData::default()
         }
     }
 
+    #[test]
+    fn modifiers_and_lookups_are_sanitized_like_any_value() {
+        let data = TrackNameData {
+            composer_surname: "Dvořák".to_owned(),
+            ..data()
+        };
+
+        assert_eq!(
+            render(
+                "{composer_surname|upper}/{tag:Catalogue|default:B. 178}",
+                &data
+            )
+            .unwrap(),
+            "DVORAK/B. 178"
+        );
+    }
+
     #[test]
     fn default_pattern_renders_a_readable_name() {
         assert_eq!(
@@ -222,7 +285,7 @@ mod tests {
         data.work = "///".to_owned();
 
         assert!(render("{work}", &data).is_none());
-        assert!(validate("///").is_err());
+        assert!(validate("***").is_err());
     }
 
     #[test]
@@ -231,6 +294,73 @@ mod tests {
         assert!(render("{bogus}", &data()).is_none());
     }
 
+    #[test]
+    fn slashes_in_the_pattern_create_directories() {
+        assert_eq!(
+            render("{composer}/{work}/{index} {part}", &data()).unwrap(),
+            "Antonin Dvorak/Symfonie c. 9/02 Largo"
+        );
+    }
+
+    /// Only the pattern decides about directories, never a value.
+    #[test]
+    fn slashes_in_values_do_not_create_directories() {
+        let mut data = data();
+        data.work = "½ Sonate in C/D".to_owned();
+
+        assert_eq!(
+            render("{composer}/{work}", &data).unwrap(),
+            "Antonin Dvorak/1 2 Sonate in C D"
+        );
+    }
+
+    #[test]
+    fn slashes_within_placeholders_do_not_create_directories() {
+        let mut data = data();
+        data.work = String::new();
+
+        assert_eq!(
+            render("{composer}/{work|default:N/A}", &data).unwrap(),
+            "Antonin Dvorak/N A"
+        );
+        assert_eq!(
+            render("{{composer}}/{tag:AC/DC|default:x}/{part}", &data).unwrap(),
+            "{composer}/x/Largo"
+        );
+    }
+
+    #[test]
+    fn empty_directories_are_left_out() {
+        let mut data = data();
+        data.composer = String::new();
+
+        assert_eq!(
+            render("{composer}/{work}/{part}", &data).unwrap(),
+            "Symfonie c. 9/Largo"
+        );
+        assert_eq!(render("/{part}", &data).unwrap(), "Largo");
+    }
+
+    /// Directory names are sanitized like file names, so that neither ".." nor
+    /// a hidden directory can come out of a pattern.
+    #[test]
+    fn directory_names_cannot_escape_the_library_folder() {
+        let mut data = data();
+        data.composer = "..".to_owned();
+
+        assert_eq!(render("{composer}/{part}", &data).unwrap(), "Largo");
+        assert_eq!(render("../.hidden/{part}", &data).unwrap(), "hidden/Largo");
+    }
+
+    #[test]
+    fn a_pattern_ending_in_a_directory_is_rejected() {
+        assert!(validate("{composer}/").is_err());
+        assert_eq!(
+            preview("{composer}/{work}/{index} {part}").unwrap(),
+            "Ludwig van Beethoven/Symphony No. 5 in C minor, Op. 67/01 Allegro con brio.flac"
+        );
+    }
+
     #[test]
     fn the_default_pattern_is_valid() {
         assert_eq!(
@@ -1,18 +1,26 @@
 //! The placeholder patterns that describe how a track file is named and tagged.
 //!
-//! The same handful of placeholders drives the file name and every tag, so that
-//! the user only has to learn one vocabulary and a pattern can be moved from one
-//! setting to another. Everything specific to one target — making a name safe
-//! for a file system, deciding what an empty tag means — lives in [`filenames`]
-//! and [`audio_tags`] respectively.
+//! The same placeholders drive the file name and every tag, so that the user
+//! only has to learn one vocabulary and a pattern can be moved from one setting
+//! to another. Everything specific to one target — making a name safe for a
+//! file system, deciding what an empty tag means — lives in [`filenames`] and
+//! [`audio_tags`] respectively.
+//!
+//! A placeholder is written as `{name}`. Some of them look a value up by name,
+//! like `{tag:Catalogue}`, and every placeholder can be followed by modifiers
+//! separated by `|`, like `{composer_surname|upper}` or `{album|default:Misc}`.
+//! Braces that are meant literally are doubled.
 //!
 //! [`filenames`]: super::filenames
 //! [`audio_tags`]: super::audio_tags
 
-use anyhow::{anyhow, Result};
-use formatx::Template;
+use anyhow::{anyhow, bail, Result};
 
-use crate::db::models::{Recording, Work};
+use crate::db::{
+    models::{Recording, TagValue, Work},
+    tables::Role,
+    TranslatedString,
+};
 
 /// The file name pattern used unless the user configured another one.
 ///
@@ -42,18 +50,48 @@ pub const DEFAULT_ARTIST_PATTERN: &str = "{performers}";
 pub const DEFAULT_TITLE_PATTERN: &str = "{work}: {part}";
 
 /// The placeholders a pattern may use.
-pub const PLACEHOLDERS: &[&str] = &["composer", "work", "part", "performers", "index"];
+pub const PLACEHOLDERS: &[&str] = &[
+    "composer",
+    "composer_surname",
+    "composer_sort",
+    "work",
+    "part",
+    "performers",
+    "ensemble",
+    "conductor",
+    "instrument",
+    "album",
+    "year",
+    "date",
+    "comment",
+    "index",
+];
+
+/// The placeholders that look a value up by the name following them, like
+/// `{tag:Catalogue}` or `{role:Soloist}`.
+pub const LOOKUP_PLACEHOLDERS: &[&str] = &["tag", "role"];
+
+/// The modifiers that can follow a placeholder, like `{work|max:40}`.
+pub const MODIFIERS: &[&str] = &["upper", "lower", "max", "default"];
+
+/// The name of the role whose performers `{conductor}` stands for.
+const CONDUCTOR_ROLE: &str = "Conductor";
+
+/// The names of the tags `{date}` and `{year}` are read from, in order.
+const DATE_TAGS: &[&str] = &["Date", "Year"];
 
 /// Every pattern that describes a track file.
 ///
 /// They are configured together by the user and pushed into the library as a
-/// whole, so that they cannot drift apart.
+/// whole, so that they cannot drift apart. The choice of which tags of a file
+/// survive a rewrite travels along, because it is part of how a file is tagged.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Patterns {
     pub filename: String,
     pub album: String,
     pub artist: String,
     pub title: String,
+    pub preserved_tags: PreservedTags,
 }
 
 impl Default for Patterns {
@@ -63,25 +101,178 @@ impl Default for Patterns {
             album: DEFAULT_ALBUM_PATTERN.to_owned(),
             artist: DEFAULT_ARTIST_PATTERN.to_owned(),
             title: DEFAULT_TITLE_PATTERN.to_owned(),
+            preserved_tags: PreservedTags::default(),
+        }
+    }
+}
+
+/// Which of the tags a file brought along survive when Musicus writes its own.
+///
+/// Musicus rewrites the whole tag, so anything not kept here is dropped. Tags
+/// that Musicus writes itself are never kept, even if it has no value for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PreservedTags {
+    pub cover_art: bool,
+    pub replay_gain: bool,
+    pub musicbrainz: bool,
+    /// Everything else, like the genre, a comment or a field only another
+    /// player knows about.
+    pub other: bool,
+}
+
+impl Default for PreservedTags {
+    /// Keep what cannot be derived from the database and is costly to get back,
+    /// but nothing that would contradict it.
+    fn default() -> Self {
+        Self {
+            cover_art: true,
+            replay_gain: true,
+            musicbrainz: true,
+            other: false,
         }
     }
 }
 
 /// The values a pattern can refer to.
+#[derive(Clone, Debug, Default)]
 pub struct TrackData {
     pub composer: String,
+    pub composer_surname: String,
+    pub composer_sort: String,
     pub work: String,
     pub part: String,
     pub performers: String,
+    pub ensemble: String,
+    pub instrument: String,
+    pub album: String,
+    pub comment: String,
     pub index: String,
+    /// The tags of the recording followed by those of the work.
+    pub tags: Vec<NamedValue>,
+    /// The performers of the recording by their role.
+    pub roles: Vec<NamedValue>,
+    /// Every performer that is not an ensemble or a conductor, with what they
+    /// play. This is written as a tag of its own rather than used in patterns.
+    pub performer_credits: Vec<String>,
+    /// The number of the first part the track covers and the number of parts
+    /// of the work, if the track covers a part at all.
+    pub movement: Option<(u32, u32)>,
+}
+
+/// A value that a pattern looks up by name.
+#[derive(Clone, Debug)]
+pub struct NamedValue {
+    /// Every translation of the name, any of which the pattern may use.
+    pub names: Vec<String>,
+    pub value: String,
+}
+
+impl NamedValue {
+    fn new(name: &TranslatedString, value: String) -> Self {
+        Self {
+            names: name.0.values().cloned().collect(),
+            value,
+        }
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.names
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(name))
+    }
 }
 
 impl TrackData {
     /// Collect the values describing the track at `recording_index` of
-    /// `recording` that covers `works`.
-    pub fn new(recording: &Recording, recording_index: i32, works: &[Work]) -> Self {
+    /// `recording` that covers `works` and appears on `albums`.
+    pub fn new(
+        recording: &Recording,
+        recording_index: i32,
+        works: &[Work],
+        albums: &[String],
+    ) -> Self {
+        let composers = recording
+            .work
+            .persons
+            .iter()
+            .map(|composer| split_person_name(composer.person.name.get()))
+            .collect::<Vec<(&str, &str)>>();
+
+        // The instrumentation belongs to the work. Only a work that does not
+        // state it falls back to what the performers play.
+        let mut instruments = recording
+            .work
+            .instruments
+            .iter()
+            .map(|instrument| instrument.name.get().to_owned())
+            .collect::<Vec<String>>();
+
+        if instruments.is_empty() {
+            for instrument in recording
+                .persons
+                .iter()
+                .filter_map(|p| p.instrument.as_ref())
+            {
+                let name = instrument.name.get().to_owned();
+                if !instruments.contains(&name) {
+                    instruments.push(name);
+                }
+            }
+        }
+
+        let roles = recording
+            .persons
+            .iter()
+            .filter_map(|performer| {
+                let role = performer.role.as_ref()?;
+                Some(NamedValue::new(
+                    &role.name,
+                    performer.person.name.get().to_owned(),
+                ))
+            })
+            .chain(recording.ensembles.iter().filter_map(|performer| {
+                let role = performer.role.as_ref()?;
+                Some(NamedValue::new(
+                    &role.name,
+                    performer.ensemble.name.get().to_owned(),
+                ))
+            }))
+            .collect();
+
+        let performer_credits = recording
+            .persons
+            .iter()
+            .filter(|performer| !performer.role.as_ref().is_some_and(is_conductor))
+            .map(|performer| match &performer.instrument {
+                Some(instrument) => format!(
+                    "{} ({})",
+                    performer.person.name.get(),
+                    instrument.name.get()
+                ),
+                None => performer.person.name.get().to_owned(),
+            })
+            .collect();
+
+        // A track that covers several parts is numbered after the first one.
+        let parts = &recording.work.parts;
+        let movement = works.first().and_then(|work| {
+            let position = parts.iter().position(|part| part.work_id == work.work_id)?;
+            Some((position as u32 + 1, parts.len() as u32))
+        });
+
         Self {
             composer: recording.work.composers_string().unwrap_or_default(),
+            composer_surname: composers
+                .iter()
+                .map(|(_, surname)| *surname)
+                .collect::<Vec<&str>>()
+                .join(", "),
+            // A sort name contains a comma of its own.
+            composer_sort: composers
+                .iter()
+                .map(|(given, surname)| sort_name(given, surname))
+                .collect::<Vec<String>>()
+                .join("; "),
             work: recording.work.name.get().to_owned(),
             part: works
                 .iter()
@@ -89,7 +280,25 @@ impl TrackData {
                 .collect::<Vec<String>>()
                 .join(", "),
             performers: recording.performers_string(),
+            ensemble: recording
+                .ensembles
+                .iter()
+                .map(|performer| performer.ensemble.name.get().to_owned())
+                .collect::<Vec<String>>()
+                .join(", "),
+            instrument: instruments.join(", "),
+            album: albums.join(", "),
+            comment: recording.comment.clone().unwrap_or_default(),
             index: format!("{:02}", recording_index + 1),
+            tags: recording
+                .tags
+                .iter()
+                .chain(&recording.work.tags)
+                .map(tag_value)
+                .collect(),
+            roles,
+            performer_credits,
+            movement,
         }
     }
 
@@ -98,14 +307,66 @@ impl TrackData {
     /// Every preview shown to the user describes the same track, so that the
     /// file name and the tags can be compared against each other.
     pub fn example() -> Self {
+        let named = |name: &str, value: &str| NamedValue {
+            names: vec![name.to_owned()],
+            value: value.to_owned(),
+        };
+
         Self {
             composer: "Ludwig van Beethoven".to_owned(),
+            composer_surname: "Beethoven".to_owned(),
+            composer_sort: "Beethoven, Ludwig van".to_owned(),
             work: "Symphony No. 5 in C minor, Op. 67".to_owned(),
             part: "Allegro con brio".to_owned(),
-            performers: "Wiener Philharmoniker".to_owned(),
+            performers: "Carlos Kleiber, Wiener Philharmoniker".to_owned(),
+            ensemble: "Wiener Philharmoniker".to_owned(),
+            instrument: "Orchestra".to_owned(),
+            album: "Symphonies Nos. 5 & 7".to_owned(),
+            comment: "Recorded in the Musikverein".to_owned(),
             index: "01".to_owned(),
+            tags: vec![named("Date", "1974-03"), named("Catalogue", "Op. 67")],
+            roles: vec![named(CONDUCTOR_ROLE, "Carlos Kleiber")],
+            performer_credits: Vec::new(),
+            movement: Some((1, 4)),
         }
     }
+
+    /// The performers with the role of a conductor.
+    pub fn conductor(&self) -> String {
+        lookup(&self.roles, CONDUCTOR_ROLE)
+    }
+
+    /// The value of the placeholder `name` with `argument`, which has already
+    /// been checked by [`parse`].
+    fn value(&self, name: &str, argument: Option<&str>) -> String {
+        match name {
+            "composer" => self.composer.clone(),
+            "composer_surname" => self.composer_surname.clone(),
+            "composer_sort" => self.composer_sort.clone(),
+            "work" => self.work.clone(),
+            "part" => self.part.clone(),
+            "performers" => self.performers.clone(),
+            "ensemble" => self.ensemble.clone(),
+            "conductor" => self.conductor(),
+            "instrument" => self.instrument.clone(),
+            "album" => self.album.clone(),
+            "year" => year(&self.date()),
+            "date" => self.date(),
+            "comment" => self.comment.clone(),
+            "index" => self.index.clone(),
+            "tag" => lookup(&self.tags, argument.unwrap_or_default()),
+            "role" => lookup(&self.roles, argument.unwrap_or_default()),
+            _ => String::new(),
+        }
+    }
+
+    fn date(&self) -> String {
+        DATE_TAGS
+            .iter()
+            .map(|name| lookup(&self.tags, name))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+    }
 }
 
 /// Substitute the placeholders of `pattern` with the values of `data`.
@@ -113,19 +374,28 @@ impl TrackData {
 /// The result is raw text. Making it usable as a file name or as a tag value is
 /// the caller's job.
 pub fn render(pattern: &str, data: &TrackData) -> Result<String> {
-    let template = Template::new(pattern).map_err(|err| anyhow!("{err}"))?;
-
-    check_placeholders(pattern)?;
-
-    template
-        .render()
-        .named("composer", &data.composer)
-        .named("work", &data.work)
-        .named("part", &data.part)
-        .named("performers", &data.performers)
-        .named("index", &data.index)
-        .finish()
-        .map_err(|err| anyhow!("{err}"))
+    let mut rendered = String::new();
+
+    for segment in parse(pattern)? {
+        match segment {
+            Segment::Text(text) => rendered.push_str(&text),
+            Segment::Placeholder {
+                name,
+                argument,
+                modifiers,
+            } => {
+                let mut value = data.value(name, argument);
+
+                for modifier in modifiers {
+                    value = modifier.apply(value);
+                }
+
+                rendered.push_str(&value);
+            }
+        }
+    }
+
+    Ok(rendered)
 }
 
 /// Whether `pattern` can be rendered at all.
@@ -137,6 +407,30 @@ pub fn validate(pattern: &str) -> Result<()> {
     render(pattern, &TrackData::example()).map(|_| ())
 }
 
+/// An example of every placeholder together with what it renders to for the
+/// example track, for showing the user what is available.
+pub fn examples() -> Vec<(String, String)> {
+    let data = TrackData::example();
+
+    PLACEHOLDERS
+        .iter()
+        .map(|name| format!("{{{name}}}"))
+        .chain([
+            "{tag:Catalogue}".to_owned(),
+            format!("{{role:{CONDUCTOR_ROLE}}}"),
+        ])
+        .chain([
+            "{composer_surname|upper}".to_owned(),
+            "{work|max:12}".to_owned(),
+            "{tag:Opus|default:Unknown}".to_owned(),
+        ])
+        .map(|pattern| {
+            let value = render(&pattern, &data).unwrap_or_default();
+            (pattern, value)
+        })
+        .collect()
+}
+
 /// Drop repetitions of one and the same separator character.
 ///
 /// A placeholder without a value leaves the separators around it behind. This
@@ -158,37 +452,219 @@ pub(super) fn collapse_separators(text: &str, separators: &[char]) -> String {
     collapsed
 }
 
-/// Ensure that every placeholder of `pattern` refers to one of the values a
-/// track provides.
+/// A piece of a parsed pattern.
+enum Segment<'a> {
+    Text(String),
+    Placeholder {
+        name: &'a str,
+        argument: Option<&'a str>,
+        modifiers: Vec<Modifier<'a>>,
+    },
+}
+
+/// A change applied to the value of a placeholder.
+enum Modifier<'a> {
+    Upper,
+    Lower,
+    /// Keep at most this many characters.
+    Max(usize),
+    /// Use this text if the value is empty.
+    Default(&'a str),
+}
+
+impl Modifier<'_> {
+    fn apply(&self, value: String) -> String {
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Max(length) => value
+                .chars()
+                .take(*length)
+                .collect::<String>()
+                .trim_end()
+                .to_owned(),
+            Self::Default(text) => {
+                if value.trim().is_empty() {
+                    (*text).to_owned()
+                } else {
+                    value
+                }
+            }
+        }
+    }
+}
+
+/// Split `pattern` into literal text and placeholders.
 ///
-/// The template itself only rejects placeholders it cannot parse, and renders a
-/// positional one as an empty string, which would silently swallow part of the
-/// user's pattern.
-fn check_placeholders(pattern: &str) -> Result<()> {
+/// Every placeholder has to refer to one of the values a track provides, and
+/// every modifier has to be one that exists, so that a typo is reported instead
+/// of silently swallowing part of the user's pattern.
+fn parse(pattern: &str) -> Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
     let mut rest = pattern;
 
-    while let Some(start) = rest.find('{') {
-        // An escaped brace is literal text and does not open a placeholder.
-        if rest[start..].starts_with("{{") {
-            rest = &rest[start + 2..];
+    while let Some(position) = rest.find(['{', '}']) {
+        text.push_str(&rest[..position]);
+        rest = &rest[position..];
+
+        // A doubled brace is literal text.
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push_str(&rest[..1]);
+            rest = &rest[2..];
             continue;
         }
 
-        let body = &rest[start + 1..];
+        if rest.starts_with('}') {
+            bail!("A closing brace has no placeholder to close. Write }}}} for a literal one.");
+        }
+
+        let body = &rest[1..];
         let end = body
             .find('}')
             .ok_or_else(|| anyhow!("A placeholder is missing its closing brace."))?;
 
-        let name = body[..end].split(':').next().unwrap_or_default();
-
-        if !PLACEHOLDERS.contains(&name) {
-            return Err(anyhow!("Unknown placeholder: {{{name}}}"));
+        if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut text)));
         }
 
+        segments.push(parse_placeholder(&body[..end])?);
         rest = &body[end + 1..];
     }
 
-    Ok(())
+    text.push_str(rest);
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+
+    Ok(segments)
+}
+
+/// Parse the text between the braces of a placeholder.
+fn parse_placeholder(body: &str) -> Result<Segment<'_>> {
+    let mut parts = body.split('|');
+    let placeholder = parts.next().unwrap_or_default();
+
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (placeholder.trim(), None),
+    };
+
+    if name.is_empty() {
+        bail!("A placeholder is missing its name.");
+    }
+
+    if LOOKUP_PLACEHOLDERS.contains(&name) {
+        if argument.is_none_or(str::is_empty) {
+            bail!("The placeholder {{{name}}} needs a name to look up, like {{{name}:Name}}.");
+        }
+    } else if PLACEHOLDERS.contains(&name) {
+        if argument.is_some() {
+            bail!("The placeholder {{{name}}} does not take a name.");
+        }
+    } else {
+        bail!("Unknown placeholder: {{{name}}}");
+    }
+
+    let modifiers = parts
+        .map(parse_modifier)
+        .collect::<Result<Vec<Modifier>>>()?;
+
+    Ok(Segment::Placeholder {
+        name,
+        argument,
+        modifiers,
+    })
+}
+
+fn parse_modifier(text: &str) -> Result<Modifier<'_>> {
+    let (name, argument) = match text.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (text.trim(), None),
+    };
+
+    match (name, argument) {
+        ("upper", None) => Ok(Modifier::Upper),
+        ("lower", None) => Ok(Modifier::Lower),
+        ("max", Some(length)) => length
+            .trim()
+            .parse()
+            .ok()
+            .filter(|length| *length > 0)
+            .map(Modifier::Max)
+            .ok_or_else(|| anyhow!("The modifier max needs a number of characters, like max:20.")),
+        ("max", None) => bail!("The modifier max needs a number of characters, like max:20."),
+        ("default", Some(text)) if !text.is_empty() => Ok(Modifier::Default(text)),
+        ("default", _) => bail!("The modifier default needs a text, like default:Unknown."),
+        ("upper" | "lower", Some(_)) => bail!("The modifier {name} does not take a value."),
+        _ => bail!("Unknown modifier: {name}"),
+    }
+}
+
+/// Split a person's name into given names and surname.
+///
+/// Both "Ludwig van Beethoven" and "Beethoven, Ludwig van" are understood.
+/// Nobility particles like "van" stay with the given names, which is how such a
+/// name is sorted.
+fn split_person_name(name: &str) -> (&str, &str) {
+    if let Some((surname, given)) = name.split_once(',') {
+        return (given.trim(), surname.trim());
+    }
+
+    let name = name.trim();
+
+    match name.rsplit_once(char::is_whitespace) {
+        Some((given, surname)) => (given.trim_end(), surname),
+        None => ("", name),
+    }
+}
+
+fn sort_name(given: &str, surname: &str) -> String {
+    if given.is_empty() {
+        surname.to_owned()
+    } else {
+        format!("{surname}, {given}")
+    }
+}
+
+fn is_conductor(role: &Role) -> bool {
+    role.name
+        .0
+        .values()
+        .any(|name| name.eq_ignore_ascii_case(CONDUCTOR_ROLE))
+}
+
+fn tag_value(tag: &TagValue) -> NamedValue {
+    // A tag without a value can only be told apart by its name.
+    let value = tag
+        .value
+        .clone()
+        .unwrap_or_else(|| tag.tag.name.get().to_owned());
+
+    NamedValue::new(&tag.tag.name, value)
+}
+
+/// The values named `name`, without repetitions.
+fn lookup(values: &[NamedValue], name: &str) -> String {
+    let mut found: Vec<&str> = Vec::new();
+
+    for value in values.iter().filter(|value| value.is_named(name)) {
+        if !found.contains(&value.value.as_str()) {
+            found.push(&value.value);
+        }
+    }
+
+    found.join(", ")
+}
+
+/// The first run of four digits in `date`.
+fn year(date: &str) -> String {
+    date.as_bytes()
+        .windows(4)
+        .position(|window| window.iter().all(u8::is_ascii_digit))
+        .map(|start| date[start..start + 4].to_owned())
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -250,6 +726,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lookups_find_values_by_name() {
+        let data = TrackData::example();
+
+        assert_eq!(render("{tag:Catalogue}", &data).unwrap(), "Op. 67");
+        assert_eq!(render("{tag:catalogue}", &data).unwrap(), "Op. 67");
+        assert_eq!(render("{conductor}", &data).unwrap(), "Carlos Kleiber");
+        assert_eq!(render("{role:Soloist}", &data).unwrap(), "");
+        assert_eq!(render("{year}", &data).unwrap(), "1974");
+        assert_eq!(render("{date}", &data).unwrap(), "1974-03");
+
+        assert!(validate("{tag}").is_err());
+        assert!(validate("{work:Catalogue}").is_err());
+    }
+
+    #[test]
+    fn modifiers_are_applied_in_order() {
+        let data = TrackData::example();
+
+        assert_eq!(
+            render("{composer_surname|upper}", &data).unwrap(),
+            "BEETHOVEN"
+        );
+        assert_eq!(render("{work|max:9}", &data).unwrap(), "Symphony");
+        assert_eq!(
+            render("{tag:Opus|default:Unknown|lower}", &data).unwrap(),
+            "unknown"
+        );
+        assert_eq!(
+            render("{composer | default: Anonymous}", &data).unwrap(),
+            "Ludwig van Beethoven"
+        );
+
+        for pattern in [
+            "{work|max}",
+            "{work|max:0}",
+            "{work|default:}",
+            "{work|upper:x}",
+            "{work|bogus}",
+        ] {
+            assert!(validate(pattern).is_err(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn every_example_renders() {
+        for (pattern, value) in examples() {
+            assert!(validate(&pattern).is_ok(), "{pattern}");
+            assert!(!value.is_empty(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn person_names_are_split_into_surname_and_given_names() {
+        assert_eq!(
+            split_person_name("Ludwig van Beethoven"),
+            ("Ludwig van", "Beethoven")
+        );
+        assert_eq!(
+            split_person_name("Bach, Johann Sebastian"),
+            ("Johann Sebastian", "Bach")
+        );
+        assert_eq!(split_person_name("Pérotin"), ("", "Pérotin"));
+        assert_eq!(sort_name("", "Pérotin"), "Pérotin");
+    }
+
+    #[test]
+    fn stray_closing_braces_are_rejected() {
+        assert!(validate("{work}}").is_err());
+        assert_eq!(
+            render("}}{work|max:3}", &TrackData::example()).unwrap(),
+            "}Sym"
+        );
+    }
+
     #[test]
     fn only_repetitions_of_the_same_separator_are_collapsed() {
         let separators = &[' ', '-'];
@@ -284,10 +835,11 @@ mod tests {
             ensembles: Vec::new(),
             tags: Vec::new(),
             comment: None,
+            cover_art: None,
             enable_updates: false,
         };
 
-        let data = TrackData::new(&recording, 0, std::slice::from_ref(&part));
+        let data = TrackData::new(&recording, 0, std::slice::from_ref(&part), &[]);
 
         assert_eq!(data.work, "Symphony No. 5");
         assert_eq!(data.part, "Allegro");
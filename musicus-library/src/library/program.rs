@@ -1,5 +1,5 @@
 //! Listening history and program generation: recording that a track was
-//! played, and using that history to choose what to play next.
+//! played or skipped, and using that history to choose what to play next.
 //!
 //! Track selection is based on settings within a program. The main concept is
 //! based an exponential function of the form:
@@ -19,7 +19,7 @@ use std::collections::HashMap;
 use anyhow::{anyhow, Result};
 use chrono::{NaiveDateTime, TimeDelta};
 use diesel::{
-    dsl::exists,
+    dsl::{count_star, exists},
     prelude::*,
     sql_types::{self, Bool},
     QueryDsl,
@@ -55,6 +55,8 @@ pub struct GenerateRecordingParams {
     pub avoid_repeated_composers: i32,
     /// For how many **minutes** after hearing an instrument to avoid it. 0 disables it.
     pub avoid_repeated_instruments: i32,
+    /// How much to avoid recordings that are often skipped, from 0.0 to 1.0.
+    pub avoid_skipped: f64,
 }
 
 /// One recording a program allows, with everything needed to weight it.
@@ -76,6 +78,16 @@ struct Repetition {
     instruments: HashMap<String, NaiveDateTime>,
 }
 
+/// The share of a recording's tracks that were skipped rather than played, from
+/// 0.0 to 1.0.
+fn skip_rate(skips: i64, plays: i64) -> f64 {
+    if skips <= 0 {
+        return 0.0;
+    }
+
+    skips as f64 / (skips + plays.max(0)) as f64
+}
+
 /// Rank `values` from 0.0 for the smallest to 1.0 for the largest.
 ///
 /// Equal values share the average of the ranks they span, so that a set of
@@ -162,8 +174,14 @@ fn weight(
     least_recently_played_score: f64,
     recently_created_score: f64,
     repetition: &Repetition,
+    skip_rates: &HashMap<String, f64>,
     now: NaiveDateTime,
 ) -> f64 {
+    let skip_rate = skip_rates
+        .get(&candidate.recording_id)
+        .copied()
+        .unwrap_or(0.0);
+
     weight_preference(
         params.prefer_least_recently_played,
         least_recently_played_score,
@@ -178,6 +196,7 @@ fn weight(
             now,
             params.avoid_repeated_instruments,
         )
+        * weight_preference(params.avoid_skipped, 1.0 - skip_rate)
 }
 
 /// Draw one candidate, with a probability proportional to its weight.
@@ -216,6 +235,7 @@ impl Library {
 
         let candidates = self.candidates(params)?;
         let repetition = self.repetition(params, now)?;
+        let skip_rates = self.skip_rates(params)?;
 
         let least_recently_played_ranks = rank_descending(
             &candidates
@@ -237,6 +257,7 @@ impl Library {
                     least_recently_played_ranks[index],
                     recently_created_ranks[index],
                     &repetition,
+                    &skip_rates,
                     now,
                 )
             })
@@ -458,22 +479,85 @@ impl Library {
         Ok(repetition)
     }
 
-    /// Record that a track was played.
-    pub fn track_played(&self, track_id: &str) -> Result<()> {
+    /// How often each recording was skipped, for those that were skipped at all.
+    ///
+    /// A recording missing from the map was never skipped.
+    fn skip_rates(&self, params: &GenerateRecordingParams) -> Result<HashMap<String, f64>> {
+        if params.avoid_skipped <= 0.0 {
+            return Ok(HashMap::new());
+        }
+
+        let connection = &mut *self.conn();
+
+        let skips = skips::table
+            .group_by(skips::recording_id)
+            .select((skips::recording_id, count_star()))
+            .load::<(String, i64)>(connection)?;
+
+        let plays = plays::table
+            .group_by(plays::recording_id)
+            .select((plays::recording_id, count_star()))
+            .load::<(String, i64)>(connection)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        Ok(skips
+            .into_iter()
+            .map(|(recording_id, skips)| {
+                let plays = plays.get(&recording_id).copied().unwrap_or(0);
+                (recording_id, skip_rate(skips, plays))
+            })
+            .collect())
+    }
+
+    /// Record that a track was played and return the ID of the play.
+    /// `duration_ms` is the length of the track, which counts towards the
+    /// listening time.
+    pub fn track_played(&self, track_id: &str, duration_ms: u64) -> Result<String> {
         let connection = &mut *self.conn();
 
-        connection.transaction::<(), diesel::result::Error, _>(|connection| {
+        let play_id = connection.transaction::<_, diesel::result::Error, _>(|connection| {
             let recording_id = tracks::table
                 .filter(tracks::track_id.eq(track_id))
                 .select(tracks::recording_id)
                 .first::<String>(connection)?;
 
+            let play_id = db::generate_id();
+
             diesel::insert_into(plays::table)
                 .values(tables::Play {
-                    play_id: db::generate_id(),
+                    play_id: play_id.clone(),
                     track_id: Some(track_id.to_owned()),
                     recording_id,
                     played_at: db::now(),
+                    duration_ms: i64::try_from(duration_ms).ok(),
+                })
+                .execute(connection)?;
+
+            Ok(play_id)
+        })?;
+
+        Ok(play_id)
+    }
+
+    /// Record that a track was left before it counted as played.
+    /// `position_ms` is how far into the track playback was at that point.
+    pub fn track_skipped(&self, track_id: &str, position_ms: u64) -> Result<()> {
+        let connection = &mut *self.conn();
+
+        connection.transaction::<_, diesel::result::Error, _>(|connection| {
+            let recording_id = tracks::table
+                .filter(tracks::track_id.eq(track_id))
+                .select(tracks::recording_id)
+                .first::<String>(connection)?;
+
+            diesel::insert_into(skips::table)
+                .values(tables::Skip {
+                    skip_id: db::generate_id(),
+                    track_id: Some(track_id.to_owned()),
+                    recording_id,
+                    position_ms: i64::try_from(position_ms).unwrap_or(i64::MAX),
+                    skipped_at: db::now(),
                 })
                 .execute(connection)?;
 
@@ -609,4 +693,81 @@ mod tests {
         assert!(recording_ids.contains(&arrangement_recording.recording_id));
         assert!(!recording_ids.contains(&unrelated_recording.recording_id));
     }
+
+    fn first_track_id(library: &Library, recording: &Recording) -> String {
+        library
+            .tracks_for_recording(&recording.recording_id)
+            .unwrap()[0]
+            .track_id
+            .clone()
+    }
+
+    #[test]
+    fn the_skip_rate_is_the_share_of_skips_among_plays_and_skips() {
+        assert_eq!(skip_rate(0, 0), 0.0);
+        assert_eq!(skip_rate(0, 5), 0.0);
+        assert_eq!(skip_rate(1, 3), 0.25);
+        assert_eq!(skip_rate(2, 0), 1.0);
+    }
+
+    #[test]
+    fn skips_count_against_a_recording_only_when_the_program_avoids_them() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let (_, skipped) = work_with_recording(&library, &source_dir, "Skipped", None);
+        let (_, mixed) = work_with_recording(&library, &source_dir, "Mixed", None);
+        let (_, played) = work_with_recording(&library, &source_dir, "Played", None);
+
+        let skipped_track = first_track_id(&library, &skipped);
+        let mixed_track = first_track_id(&library, &mixed);
+        let played_track = first_track_id(&library, &played);
+
+        library.track_skipped(&skipped_track, 5_000).unwrap();
+        library.track_skipped(&skipped_track, 12_000).unwrap();
+        library.track_skipped(&mixed_track, 30_000).unwrap();
+        library.track_played(&mixed_track, 60_000).unwrap();
+        library.track_played(&played_track, 60_000).unwrap();
+
+        let rates = library
+            .skip_rates(&GenerateRecordingParams::default())
+            .unwrap();
+        assert!(rates.is_empty());
+
+        let rates = library
+            .skip_rates(&GenerateRecordingParams {
+                avoid_skipped: 1.0,
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(rates.get(&skipped.recording_id), Some(&1.0));
+        assert_eq!(rates.get(&mixed.recording_id), Some(&0.5));
+        assert_eq!(rates.get(&played.recording_id), None);
+    }
+
+    #[test]
+    fn a_skip_keeps_where_in_the_track_it_happened() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let (_, recording) = work_with_recording(&library, &source_dir, "Skipped", None);
+        let track_id = first_track_id(&library, &recording);
+
+        library.track_skipped(&track_id, 42_000).unwrap();
+
+        let skips = skips::table
+            .select(tables::Skip::as_select())
+            .load(&mut *library.conn())
+            .unwrap();
+
+        assert_eq!(skips.len(), 1);
+        assert_eq!(skips[0].track_id.as_deref(), Some(track_id.as_str()));
+        assert_eq!(skips[0].recording_id, recording.recording_id);
+        assert_eq!(skips[0].position_ms, 42_000);
+    }
 }
@@ -4,6 +4,7 @@ use anyhow::Result;
 use diesel::{dsl::sql, prelude::*, sql_types, QueryDsl};
 
 use gettextrs::gettext;
+use serde::Serialize;
 
 use super::Library;
 use crate::{
@@ -148,7 +149,7 @@ impl LibraryQuery {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Default, Debug)]
 pub struct LibraryResults {
     pub composers: Vec<Person>,
     pub performers: Vec<Person>,
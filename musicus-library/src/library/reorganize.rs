@@ -9,9 +9,12 @@ use anyhow::{Context, Error, Result};
 use diesel::{prelude::*, SqliteConnection};
 use gettextrs::gettext;
 
+use lofty::picture::Picture;
+
 use super::{
+    cover_art,
     naming::{
-        audio_tags::{self, AudioTags},
+        audio_tags::{self, AudioTags, TagChange},
         filenames, pattern,
     },
     Library, Patterns,
@@ -29,6 +32,7 @@ use crate::{
 
 /// What a reorganization has to do to the file of one track.
 struct TrackTask {
+    track_id: String,
     /// The name the file ends up under, relative to the library folder.
     path: PathBuf,
     /// `None` if the file is already named after the pattern.
@@ -47,11 +51,124 @@ struct Rename {
     tmp: PathBuf,
 }
 
+/// What a reorganization would do, as worked out by
+/// [`Library::preview_reorganization`].
+#[derive(Clone, Debug, Default)]
+pub struct ReorganizationPlan {
+    pub renames: Vec<PlannedRename>,
+    /// The files whose tags would change, under the name they have now.
+    pub tag_changes: Vec<PlannedTagChanges>,
+}
+
+impl ReorganizationPlan {
+    /// Whether a reorganization would leave every file as it is.
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.tag_changes.is_empty()
+    }
+}
+
+/// A track file a reorganization would rename, with paths relative to the
+/// library folder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedRename {
+    pub track_id: String,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// The tag changes a reorganization would make to one track file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedTagChanges {
+    pub track_id: String,
+    /// The path of the file before any rename, relative to the library folder.
+    pub path: PathBuf,
+    pub changes: Vec<TagChange>,
+}
+
+/// The part of the library a reorganization is limited to.
+///
+/// A track is affected if any of the entities it is named and tagged after is
+/// in the scope.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReorganizationScope {
+    pub track_ids: Vec<String>,
+    pub recording_ids: Vec<String>,
+    /// Includes the parts of the works.
+    pub work_ids: Vec<String>,
+    /// Includes the works the persons composed and the recordings they perform
+    /// on.
+    pub person_ids: Vec<String>,
+    /// Includes the recordings the ensembles perform on.
+    pub ensemble_ids: Vec<String>,
+    /// Includes the recordings on the albums.
+    pub album_ids: Vec<String>,
+    /// Includes the works written for the instruments and the recordings they
+    /// are played on.
+    pub instrument_ids: Vec<String>,
+    /// Includes the recordings with performers in the roles.
+    pub role_ids: Vec<String>,
+}
+
 impl Library {
+    /// Work out what [`Library::reorganize_files`] would do, without touching
+    /// any file or the database.
+    ///
+    /// The plan is sent on the returned receiver once the process has finished
+    /// successfully. Files whose tags cannot be read are reported as warnings
+    /// and left out of it. Reorganizing afterwards plans again, so changes
+    /// made to the library in between are not lost, but they are not in the
+    /// preview either.
+    pub fn preview_reorganization(
+        &self,
+    ) -> Result<(ProcessHandle, async_channel::Receiver<ReorganizationPlan>)> {
+        self.preview_reorganization_of(None)
+    }
+
+    /// Like [`Library::preview_reorganization`], but only for the tracks in
+    /// `scope`.
+    pub fn preview_reorganization_in(
+        &self,
+        scope: &ReorganizationScope,
+    ) -> Result<(ProcessHandle, async_channel::Receiver<ReorganizationPlan>)> {
+        self.preview_reorganization_of(Some(scope.to_owned()))
+    }
+
+    fn preview_reorganization_of(
+        &self,
+        scope: Option<ReorganizationScope>,
+    ) -> Result<(ProcessHandle, async_channel::Receiver<ReorganizationPlan>)> {
+        let folder = PathBuf::from(self.folder());
+        let patterns = self.patterns();
+        let connection = Arc::clone(&self.connection);
+
+        validate(&patterns)?;
+
+        let (plan_sender, plan_receiver) = async_channel::bounded(1);
+
+        let handle = spawn_process(move |sender, cancellation| {
+            let plan = preview(
+                &folder,
+                &patterns,
+                scope.as_ref(),
+                &connection,
+                sender,
+                cancellation,
+            )?;
+            let _ = plan_sender.send_blocking(plan);
+            Ok(())
+        });
+
+        Ok((handle, plan_receiver))
+    }
+
     /// Bring every track file in line with the configured patterns.
     ///
     /// Files that no track refers to are left untouched, and so are tracks
-    /// whose file is missing; both are reported as warnings.
+    /// whose file is missing; both are reported as warnings. Directories the
+    /// pattern asks for are created, and directories the files were moved out
+    /// of are removed once they are empty. The cover of a track's recording is
+    /// embedded into its file, and cover images that nothing refers to anymore
+    /// are removed.
     ///
     /// Renaming and tagging are deliberately not equally safe. The renames are
     /// applied together with the database update, so they either all happen or
@@ -61,26 +178,123 @@ impl Library {
     /// pass idempotent and repeatable, and a file it could not write is reported
     /// rather than rolled back.
     pub fn reorganize_files(&self) -> Result<ProcessHandle> {
+        self.reorganize_files_of(None)
+    }
+
+    /// Whether any track is in `scope`. Reorganizing it changes nothing
+    /// otherwise.
+    pub fn has_tracks_in(&self, scope: &ReorganizationScope) -> Result<bool> {
+        Ok(!affected_tracks(scope, &mut self.conn())?.is_empty())
+    }
+
+    /// Like [`Library::reorganize_files`], but only for the tracks in `scope`.
+    ///
+    /// The files of all other tracks keep their names, so that no affected
+    /// track takes one of them.
+    pub fn reorganize_files_in(&self, scope: &ReorganizationScope) -> Result<ProcessHandle> {
+        self.reorganize_files_of(Some(scope.to_owned()))
+    }
+
+    fn reorganize_files_of(&self, scope: Option<ReorganizationScope>) -> Result<ProcessHandle> {
         let folder = PathBuf::from(self.folder());
         let patterns = self.patterns();
         let connection = Arc::clone(&self.connection);
 
-        // Reject an unusable pattern before starting an operation that could
-        // only rename every file to its fallback name and strip every tag.
-        filenames::validate(&patterns.filename)?;
-        audio_tags::validate(&patterns.album)?;
-        audio_tags::validate(&patterns.artist)?;
-        audio_tags::validate(&patterns.title)?;
+        validate(&patterns)?;
 
         Ok(spawn_process(move |sender, cancellation| {
-            reorganize(&folder, &patterns, &connection, sender, cancellation)
+            reorganize(
+                &folder,
+                &patterns,
+                scope.as_ref(),
+                &connection,
+                sender,
+                cancellation,
+            )
         }))
     }
 }
 
-fn reorganize(
+/// Reject an unusable pattern before starting an operation that could only
+/// rename every file to its fallback name and strip every tag.
+pub(crate) fn validate(patterns: &Patterns) -> Result<()> {
+    filenames::validate(&patterns.filename)?;
+    audio_tags::validate(&patterns.album)?;
+    audio_tags::validate(&patterns.artist)?;
+    audio_tags::validate(&patterns.title)?;
+
+    Ok(())
+}
+
+fn preview(
+    folder: &Path,
+    patterns: &Patterns,
+    scope: Option<&ReorganizationScope>,
+    connection: &Arc<Mutex<SqliteConnection>>,
+    sender: &async_channel::Sender<ProcessMsg>,
+    cancellation: &Cancellation,
+) -> Result<ReorganizationPlan> {
+    // As when reorganizing, the database is not needed for reading the files.
+    let tasks = plan(
+        folder,
+        patterns,
+        scope,
+        &mut db::lock_connection(connection),
+        sender,
+        cancellation,
+    )?;
+
+    let mut plan = ReorganizationPlan::default();
+    let n_tasks = tasks.len().max(1);
+
+    for (index, task) in tasks.into_iter().enumerate() {
+        cancellation.check()?;
+
+        // The tags are compared with the file as it is now, under its old name.
+        let path = match task.rename {
+            Some(rename) => {
+                plan.renames.push(PlannedRename {
+                    track_id: rename.track_id,
+                    from: rename.from.clone(),
+                    to: rename.to,
+                });
+
+                rename.from
+            }
+            None => task.path,
+        };
+
+        if let Some(tags) = &task.tags {
+            match audio_tags::changes(&folder.join(&path), tags) {
+                Ok(changes) if changes.is_empty() => (),
+                Ok(changes) => plan.tag_changes.push(PlannedTagChanges {
+                    track_id: task.track_id,
+                    path,
+                    changes,
+                }),
+                Err(err) => {
+                    log::warn!("Failed to read the tags of {}: {err:?}", path.display());
+
+                    let _ = sender.send_blocking(ProcessMsg::Warning(format_translated!(
+                        gettext("The tags of a file could not be read: {}"),
+                        path.display()
+                    )));
+                }
+            }
+        }
+
+        let _ = sender.send_blocking(ProcessMsg::Progress(
+            0.5 + 0.5 * (index + 1) as f64 / n_tasks as f64,
+        ));
+    }
+
+    Ok(plan)
+}
+
+pub(crate) fn reorganize(
     folder: &Path,
     patterns: &Patterns,
+    scope: Option<&ReorganizationScope>,
     connection: &Arc<Mutex<SqliteConnection>>,
     sender: &async_channel::Sender<ProcessMsg>,
     cancellation: &Cancellation,
@@ -88,10 +302,20 @@ fn reorganize(
     // The database is only needed to plan and to record the renames. It is
     // unlocked again before the files are tagged, which is by far the longest
     // part and does not touch it.
-    let (tasks, n_renames) = rename_files(folder, patterns, connection, sender, cancellation)?;
+    let (tasks, n_renames) =
+        rename_files(folder, patterns, scope, connection, sender, cancellation)?;
 
     let n_tagged = tag_files(folder, &tasks, n_renames, sender, cancellation)?;
 
+    // Replaced covers stay until nothing can bring them back, which is checked
+    // here rather than whenever a cover changes.
+    let removed = cover_art::remove_unused(folder, &mut db::lock_connection(connection));
+
+    match removed {
+        Ok(n_removed) => log::info!("Removed {n_removed} unused cover images"),
+        Err(err) => log::warn!("Failed to remove unused cover images: {err:?}"),
+    }
+
     let _ = sender.send_blocking(ProcessMsg::Message(format_translated!(
         gettext("Renamed {} files and updated the tags of {} files."),
         n_renames,
@@ -108,12 +332,122 @@ fn reorganize(
 fn rename_files(
     folder: &Path,
     patterns: &Patterns,
+    scope: Option<&ReorganizationScope>,
     connection: &Arc<Mutex<SqliteConnection>>,
     sender: &async_channel::Sender<ProcessMsg>,
     cancellation: &Cancellation,
 ) -> Result<(Vec<TrackTask>, usize)> {
     let connection = &mut *db::lock_connection(connection);
+    let tasks = plan(folder, patterns, scope, connection, sender, cancellation)?;
+
+    let renames = tasks
+        .iter()
+        .filter_map(|task| task.rename.as_ref())
+        .collect::<Vec<&Rename>>();
+
+    if renames.is_empty() {
+        return Ok((tasks, 0));
+    }
+
+    cancellation.check()?;
+
+    // Park every file that moves under a name nothing else uses first. Two
+    // tracks can trade names, and moving them one after the other would
+    // overwrite one of the two files.
+    let mut parked = 0;
+
+    for rename in &renames {
+        if let Err(err) = fs::rename(folder.join(&rename.from), folder.join(&rename.tmp)) {
+            unpark(folder, &renames[..parked]);
+
+            return Err(Error::from(err).context(format!(
+                "Failed to move {} out of the way",
+                rename.from.display()
+            )));
+        }
+
+        parked += 1;
+    }
+
+    let n_renames = renames.len();
+    let n_remaining = n_renames + tasks.iter().filter(|task| task.tags.is_some()).count();
+    let now = db::now();
+    let mut moved = 0;
+    let mut created_directories = Vec::new();
+
+    let result = connection.transaction::<(), Error, _>(|connection| {
+        for rename in &renames {
+            diesel::update(tracks::table)
+                .filter(tracks::track_id.eq(&rename.track_id))
+                .set((
+                    tracks::path.eq(tables::PathBufWrapper(rename.to.clone())),
+                    tracks::edited_at.eq(now),
+                ))
+                .execute(connection)?;
+        }
+
+        // Only once the database is consistent are the files moved into place,
+        // so that a failure here still rolls the whole operation back.
+        for rename in &renames {
+            create_directories(folder, &rename.to, &mut created_directories).with_context(
+                || format!("Failed to create the directory of {}", rename.to.display()),
+            )?;
+
+            fs::rename(folder.join(&rename.tmp), folder.join(&rename.to)).with_context(|| {
+                format!("Failed to rename a track file to {}", rename.to.display())
+            })?;
 
+            moved += 1;
+
+            let _ = sender.send_blocking(ProcessMsg::Progress(
+                0.5 + 0.5 * moved as f64 / n_remaining as f64,
+            ));
+        }
+
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        // The files that already reached their destination go back to their
+        // parked name, from where everything returns to where it started.
+        for rename in &renames[..moved] {
+            if let Err(err) = fs::rename(folder.join(&rename.to), folder.join(&rename.tmp)) {
+                log::error!("Failed to undo a rename: {err:?}");
+            }
+        }
+
+        unpark(folder, &renames);
+
+        for directory in created_directories.iter().rev() {
+            let _ = fs::remove_dir(directory);
+        }
+
+        return Err(err);
+    }
+
+    // Directories that only held files that moved elsewhere are no longer
+    // needed.
+    for rename in &renames {
+        remove_empty_directories(folder, &folder.join(&rename.from));
+    }
+
+    Ok((tasks, n_renames))
+}
+
+/// Work out the name and the tags of every track file without touching any of
+/// them.
+///
+/// Only the tracks in `scope` are planned for, or all of them if there is none.
+/// Tracks whose file is missing are reported and left out. Reports the first
+/// half of the progress.
+fn plan(
+    folder: &Path,
+    patterns: &Patterns,
+    scope: Option<&ReorganizationScope>,
+    connection: &mut SqliteConnection,
+    sender: &async_channel::Sender<ProcessMsg>,
+    cancellation: &Cancellation,
+) -> Result<Vec<TrackTask>> {
     let rows = tracks::table
         .order((tracks::recording_id, tracks::recording_index))
         .select(tables::Track::as_select())
@@ -121,9 +455,29 @@ fn rename_files(
 
     // Everything in the folder that is not a track file keeps its name, so no
     // track may be renamed onto it.
-    let mut taken = unrelated_file_names(folder, &rows)?;
+    let mut taken = unrelated_names(folder, &rows)?;
+
+    // The same goes for the files of the tracks outside of the scope, which have
+    // to be known before the first track is renamed.
+    let rows = match scope {
+        Some(scope) => {
+            let affected = affected_tracks(scope, connection)?;
+
+            let (rows, others) = rows
+                .into_iter()
+                .partition::<Vec<tables::Track>, _>(|row| affected.contains(&row.track_id));
+
+            for row in others {
+                taken.files.insert(name_key(&row.path.0));
+            }
+
+            rows
+        }
+        None => rows,
+    };
 
     let mut recordings: HashMap<String, Option<Recording>> = HashMap::new();
+    let mut covers: HashMap<String, Option<Picture>> = HashMap::new();
     let mut tasks = Vec::with_capacity(rows.len());
     let n_rows = rows.len();
 
@@ -140,7 +494,7 @@ fn rename_files(
 
             // The name stays reserved: the file may come back, and taking its
             // name for another track would then lose one of the two.
-            taken.insert(name_key(&from));
+            taken.files.insert(name_key(&from));
             continue;
         }
 
@@ -161,7 +515,8 @@ fn rename_files(
         // collected once.
         let data = recording.as_ref().map(|recording| {
             let works = load_track_works(&row.track_id, connection).unwrap_or_default();
-            pattern::TrackData::new(recording, row.recording_index, &works)
+            let albums = load_album_names(&row.recording_id, connection).unwrap_or_default();
+            pattern::TrackData::new(recording, row.recording_index, &works, &albums)
         });
 
         let stem = data
@@ -169,15 +524,19 @@ fn rename_files(
             .and_then(|data| filenames::render(&patterns.filename, data))
             .unwrap_or_else(|| filenames::fallback_stem(&row.recording_id, row.recording_index));
 
-        let tags = data
-            .as_ref()
-            .map(|data| AudioTags::render(patterns, data, row.recording_index));
+        let tags = data.as_ref().map(|data| AudioTags {
+            cover_art: covers
+                .entry(row.recording_id.clone())
+                .or_insert_with(|| load_cover_art(folder, &row.recording_id, connection))
+                .clone(),
+            ..AudioTags::render(patterns, data, row.recording_index)
+        });
 
         let extension = from
             .extension()
             .map(|extension| extension.to_string_lossy().into_owned());
 
-        let to = PathBuf::from(unused_name(&stem, extension.as_deref(), &mut taken));
+        let to = PathBuf::from(taken.reserve(&stem, extension.as_deref()));
 
         let rename = (to != from).then(|| Rename {
             track_id: row.track_id.clone(),
@@ -187,6 +546,7 @@ fn rename_files(
         });
 
         tasks.push(TrackTask {
+            track_id: row.track_id.clone(),
             path: to,
             rename,
             tags,
@@ -199,83 +559,48 @@ fn rename_files(
         ));
     }
 
-    let renames = tasks
-        .iter()
-        .filter_map(|task| task.rename.as_ref())
-        .collect::<Vec<&Rename>>();
+    Ok(tasks)
+}
 
-    if renames.is_empty() {
-        return Ok((tasks, 0));
-    }
+/// Create the directories `path` is in, relative to `folder`, and remember the
+/// ones that did not exist yet.
+fn create_directories(folder: &Path, path: &Path, created: &mut Vec<PathBuf>) -> Result<()> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
 
-    cancellation.check()?;
+    let mut directory = folder.to_owned();
 
-    // Park every file that moves under a name nothing else uses first. Two
-    // tracks can trade names, and moving them one after the other would
-    // overwrite one of the two files.
-    let mut parked = 0;
-
-    for rename in &renames {
-        if let Err(err) = fs::rename(folder.join(&rename.from), folder.join(&rename.tmp)) {
-            unpark(folder, &renames[..parked]);
+    for component in parent.components() {
+        directory.push(component);
 
-            return Err(Error::from(err).context(format!(
-                "Failed to move {} out of the way",
-                rename.from.display()
-            )));
+        if !directory.is_dir() {
+            fs::create_dir(&directory)?;
+            created.push(directory.clone());
         }
-
-        parked += 1;
     }
 
-    let n_renames = renames.len();
-    let n_remaining = n_renames + tasks.iter().filter(|task| task.tags.is_some()).count();
-    let now = db::now();
-    let mut moved = 0;
-
-    let result = connection.transaction::<(), Error, _>(|connection| {
-        for rename in &renames {
-            diesel::update(tracks::table)
-                .filter(tracks::track_id.eq(&rename.track_id))
-                .set((
-                    tracks::path.eq(tables::PathBufWrapper(rename.to.clone())),
-                    tracks::edited_at.eq(now),
-                ))
-                .execute(connection)?;
-        }
-
-        // Only once the database is consistent are the files moved into place,
-        // so that a failure here still rolls the whole operation back.
-        for rename in &renames {
-            fs::rename(folder.join(&rename.tmp), folder.join(&rename.to)).with_context(|| {
-                format!("Failed to rename a track file to {}", rename.to.display())
-            })?;
+    Ok(())
+}
 
-            moved += 1;
+/// Remove the directories above `path` up to `folder` that are empty, from the
+/// innermost one outwards.
+pub(crate) fn remove_empty_directories(folder: &Path, path: &Path) {
+    let mut directory = path.parent();
 
-            let _ = sender.send_blocking(ProcessMsg::Progress(
-                0.5 + 0.5 * moved as f64 / n_remaining as f64,
-            ));
+    while let Some(current) = directory {
+        if current == folder || !current.starts_with(folder) {
+            break;
         }
 
-        Ok(())
-    });
-
-    if let Err(err) = result {
-        // The files that already reached their destination go back to their
-        // parked name, from where everything returns to where it started.
-        for rename in &renames[..moved] {
-            if let Err(err) = fs::rename(folder.join(&rename.to), folder.join(&rename.tmp)) {
-                log::error!("Failed to undo a rename: {err:?}");
-            }
+        // Fails for a directory that still has something in it, which is where
+        // the removal stops.
+        if fs::remove_dir(current).is_err() {
+            break;
         }
 
-        unpark(folder, &renames);
-
-        return Err(err);
+        directory = current.parent();
     }
-
-    Ok((tasks, n_renames))
 }
 
 /// Write the tags of every task whose metadata could be loaded.
@@ -338,20 +663,105 @@ fn unpark(folder: &Path, renames: &[&Rename]) {
     }
 }
 
-/// The names of the files in `folder` that none of `rows` refers to.
-fn unrelated_file_names(folder: &Path, rows: &[tables::Track]) -> Result<HashSet<String>> {
+/// Names within the library folder that a track file cannot be given.
+#[derive(Default)]
+struct TakenNames {
+    /// The relative paths of files, as compared by [`name_key`].
+    files: HashSet<String>,
+    /// The relative paths of directories, as compared by [`name_key`].
+    directories: HashSet<String>,
+}
+
+impl TakenNames {
+    /// Build a relative file name from `stem` that is not taken yet, and
+    /// reserve it along with its directories.
+    ///
+    /// A file can neither take the name of a directory nor be put into a
+    /// directory whose name a file already has. A directory is renamed in that
+    /// case, like a file would be.
+    fn reserve(&mut self, stem: &str, extension: Option<&str>) -> String {
+        let mut components = stem
+            .split(filenames::DIRECTORY_SEPARATOR)
+            .collect::<Vec<&str>>();
+        let file_stem = components.pop().unwrap_or_default();
+
+        let mut directory = String::new();
+
+        for component in components {
+            directory = (0..)
+                .map(|suffix| join(&directory, &with_suffix(component, suffix, None)))
+                .find(|candidate| !self.files.contains(&candidate.to_lowercase()))
+                .unwrap_or_default();
+
+            self.directories.insert(directory.to_lowercase());
+        }
+
+        (0..)
+            .map(|suffix| join(&directory, &with_suffix(file_stem, suffix, extension)))
+            .find(|candidate| {
+                let key = candidate.to_lowercase();
+                !self.directories.contains(&key) && self.files.insert(key)
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn with_suffix(stem: &str, suffix: usize, extension: Option<&str>) -> String {
+    let mut name = stem.to_owned();
+
+    if suffix > 0 {
+        name.push_str(&format!("_{suffix}"));
+    }
+
+    if let Some(extension) = extension {
+        name.push('.');
+        name.push_str(extension);
+    }
+
+    name
+}
+
+fn join(directory: &str, name: &str) -> String {
+    if directory.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{directory}{}{name}", filenames::DIRECTORY_SEPARATOR)
+    }
+}
+
+/// The names in `folder` and its subdirectories that none of `rows` refers to,
+/// along with the directory of the cover images.
+fn unrelated_names(folder: &Path, rows: &[tables::Track]) -> Result<TakenNames> {
     let track_names = rows
         .iter()
         .map(|row| name_key(&row.path.0))
         .collect::<HashSet<String>>();
 
-    let mut names = HashSet::new();
-
-    for entry in fs::read_dir(folder)? {
-        let name = name_key(&PathBuf::from(entry?.file_name()));
-
-        if !track_names.contains(&name) {
-            names.insert(name);
+    let mut names = TakenNames::default();
+
+    // The cover images have a directory of their own, which must not receive
+    // track files even before it exists. Taking it like a file moves a
+    // directory of that name aside.
+    names
+        .files
+        .insert(name_key(Path::new(cover_art::COVER_ART_DIRECTORY)));
+
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(folder.join(&directory))? {
+            let entry = entry?;
+            let path = directory.join(entry.file_name());
+            let name = name_key(&path);
+
+            // Symbolic links are not followed, so that a link cannot make the
+            // walk leave the library folder or run in circles.
+            if entry.file_type()?.is_dir() {
+                names.directories.insert(name);
+                pending.push(path);
+            } else if !track_names.contains(&name) {
+                names.files.insert(name);
+            }
         }
     }
 
@@ -366,29 +776,106 @@ fn name_key(path: &Path) -> String {
     path.to_string_lossy().to_lowercase()
 }
 
-/// Build a file name from `stem` that nothing in `taken` uses yet, and reserve
-/// it.
-fn unused_name(stem: &str, extension: Option<&str>, taken: &mut HashSet<String>) -> String {
-    let mut suffix = 0;
-
-    loop {
-        let mut name = stem.to_owned();
-
-        if suffix > 0 {
-            name.push_str(&format!("_{suffix}"));
-        }
-
-        if let Some(extension) = extension {
-            name.push('.');
-            name.push_str(extension);
-        }
-
-        if taken.insert(name.to_lowercase()) {
-            return name;
-        }
-
-        suffix += 1;
+/// The IDs of the tracks in `scope`.
+pub(crate) fn affected_tracks(
+    scope: &ReorganizationScope,
+    connection: &mut SqliteConnection,
+) -> Result<HashSet<String>> {
+    let mut work_ids = scope.work_ids.iter().cloned().collect::<HashSet<String>>();
+
+    work_ids.extend(
+        work_persons::table
+            .filter(work_persons::person_id.eq_any(&scope.person_ids))
+            .select(work_persons::work_id)
+            .load::<String>(connection)?,
+    );
+
+    work_ids.extend(
+        work_instruments::table
+            .filter(work_instruments::instrument_id.eq_any(&scope.instrument_ids))
+            .select(work_instruments::work_id)
+            .load::<String>(connection)?,
+    );
+
+    // Parts can have parts of their own.
+    let mut parents = work_ids.iter().cloned().collect::<Vec<String>>();
+
+    while !parents.is_empty() {
+        parents = works::table
+            .filter(works::parent_work_id.eq_any(&parents))
+            .select(works::work_id)
+            .load::<String>(connection)?
+            .into_iter()
+            .filter(|work_id| work_ids.insert(work_id.clone()))
+            .collect();
     }
+
+    let work_ids = work_ids.into_iter().collect::<Vec<String>>();
+    let mut recording_ids = scope.recording_ids.clone();
+
+    recording_ids.extend(
+        recordings::table
+            .filter(recordings::work_id.eq_any(&work_ids))
+            .select(recordings::recording_id)
+            .load::<String>(connection)?,
+    );
+
+    recording_ids.extend(
+        recording_ensembles::table
+            .filter(recording_ensembles::ensemble_id.eq_any(&scope.ensemble_ids))
+            .select(recording_ensembles::recording_id)
+            .load::<String>(connection)?,
+    );
+
+    recording_ids.extend(
+        recording_persons::table
+            .filter(recording_persons::person_id.eq_any(&scope.person_ids))
+            .select(recording_persons::recording_id)
+            .load::<String>(connection)?,
+    );
+
+    recording_ids.extend(
+        recording_persons::table
+            .filter(
+                recording_persons::instrument_id
+                    .eq_any(&scope.instrument_ids)
+                    .or(recording_persons::role_id.eq_any(&scope.role_ids)),
+            )
+            .select(recording_persons::recording_id)
+            .load::<String>(connection)?,
+    );
+
+    recording_ids.extend(
+        recording_ensembles::table
+            .filter(recording_ensembles::role_id.eq_any(&scope.role_ids))
+            .select(recording_ensembles::recording_id)
+            .load::<String>(connection)?,
+    );
+
+    recording_ids.extend(
+        album_recordings::table
+            .filter(album_recordings::album_id.eq_any(&scope.album_ids))
+            .select(album_recordings::recording_id)
+            .load::<String>(connection)?,
+    );
+
+    let mut track_ids = scope.track_ids.iter().cloned().collect::<HashSet<String>>();
+
+    track_ids.extend(
+        tracks::table
+            .filter(tracks::recording_id.eq_any(&recording_ids))
+            .select(tracks::track_id)
+            .load::<String>(connection)?,
+    );
+
+    track_ids.extend(
+        track_works::table
+            .filter(track_works::work_id.eq_any(&work_ids))
+            .select(track_works::track_id)
+            .load::<String>(connection)?,
+    );
+
+    Ok(track_ids)
 }
 
 fn load_recording(recording_id: &str, connection: &mut SqliteConnection) -> Result<Recording> {
@@ -415,6 +902,46 @@ fn load_track_works(
         .collect()
 }
 
+/// The cover image to embed into the files of `recording_id`.
+///
+/// A cover that cannot be read only costs the picture, not the tags: the file
+/// keeps whatever pictures it has.
+pub(crate) fn load_cover_art(
+    folder: &Path,
+    recording_id: &str,
+    connection: &mut SqliteConnection,
+) -> Option<Picture> {
+    let result = cover_art::for_recording(recording_id, connection).and_then(|cover_art| {
+        cover_art
+            .map(|cover_art| cover_art::load(folder, &cover_art))
+            .transpose()
+    });
+
+    match result {
+        Ok(picture) => picture,
+        Err(err) => {
+            log::warn!("Failed to load the cover of recording {recording_id}: {err:?}");
+            None
+        }
+    }
+}
+
+/// The names of the albums `recording_id` appears on, for naming its tracks.
+pub(crate) fn load_album_names(
+    recording_id: &str,
+    connection: &mut SqliteConnection,
+) -> Result<Vec<String>> {
+    Ok(albums::table
+        .inner_join(album_recordings::table)
+        .filter(album_recordings::recording_id.eq(recording_id))
+        .select(albums::name)
+        .distinct()
+        .load::<crate::db::TranslatedString>(connection)?
+        .into_iter()
+        .map(|name| name.get().to_owned())
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -505,6 +1032,21 @@ mod tests {
         warnings
     }
 
+    /// Work out a reorganization to completion and return its plan.
+    fn preview(library: &Library) -> ReorganizationPlan {
+        let (handle, plan) = library.preview_reorganization().unwrap();
+
+        while let Ok(msg) = handle.receiver.recv_blocking() {
+            match msg {
+                ProcessMsg::Result(result) => result.unwrap(),
+                ProcessMsg::Cancelled => panic!("the preview was cancelled"),
+                _ => (),
+            }
+        }
+
+        plan.recv_blocking().unwrap()
+    }
+
     /// The paths of all tracks of `recording`, in order.
     fn track_paths(library: &Library, recording: &Recording) -> Vec<PathBuf> {
         library
@@ -711,6 +1253,81 @@ mod tests {
         assert!(dir.path().join("musicus.musdb").exists());
     }
 
+    #[test]
+    fn tracks_move_into_directories_and_leave_none_behind() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let recording = recording_with_tracks(&library, &source_dir, "Symphony", &["Allegro"]);
+
+        library.set_filename_pattern("{composer}/{work}/{part}");
+        assert!(run(&library).is_empty());
+
+        assert_eq!(
+            track_paths(&library, &recording),
+            vec![PathBuf::from("Beethoven/Symphony/Allegro.wav")]
+        );
+        assert!(dir.path().join("Beethoven/Symphony/Allegro.wav").exists());
+
+        // Moving the tracks out again removes the directories that are left
+        // empty.
+        library.set_filename_pattern("{composer}/{part}");
+        run(&library);
+
+        assert_eq!(
+            track_paths(&library, &recording),
+            vec![PathBuf::from("Beethoven/Allegro.wav")]
+        );
+        assert!(!dir.path().join("Beethoven/Symphony").exists());
+
+        library.set_filename_pattern("{part}");
+        run(&library);
+
+        assert_eq!(
+            track_paths(&library, &recording),
+            vec![PathBuf::from("Allegro.wav")]
+        );
+        assert!(!dir.path().join("Beethoven").exists());
+    }
+
+    #[test]
+    fn a_directory_does_not_clash_with_an_unrelated_file() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let recording = recording_with_tracks(&library, &source_dir, "Symphony", &["Allegro"]);
+
+        // A file where the directory would go and a directory where the file
+        // would go.
+        fs::write(dir.path().join("Beethoven"), b"not a directory").unwrap();
+        fs::create_dir(dir.path().join("Allegro.wav")).unwrap();
+
+        library.set_filename_pattern("{composer}/{part}");
+        run(&library);
+
+        assert_eq!(
+            track_paths(&library, &recording),
+            vec![PathBuf::from("Beethoven_1/Allegro.wav")]
+        );
+
+        library.set_filename_pattern("{part}");
+        run(&library);
+
+        assert_eq!(
+            track_paths(&library, &recording),
+            vec![PathBuf::from("Allegro_1.wav")]
+        );
+        assert_eq!(
+            fs::read(dir.path().join("Beethoven")).unwrap(),
+            b"not a directory"
+        );
+        assert!(dir.path().join("Allegro.wav").is_dir());
+    }
+
     #[test]
     fn a_missing_file_is_reported_and_skipped() {
         let dir = TempDir::new().unwrap();
@@ -818,4 +1435,238 @@ mod tests {
         assert!(library.reorganize_files().is_err());
         assert_eq!(track_paths(&library, &recording), before);
     }
+
+    #[test]
+    fn a_preview_lists_the_changes_without_making_them() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let recording =
+            recording_with_tracks(&library, &source_dir, "Symphonie No. 5", &["Allegro"]);
+
+        library
+            .update_work(
+                &recording.work.work_id,
+                translated("Symphony No. 5"),
+                Vec::new(),
+                recording.work.persons.clone(),
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        let before = track_paths(&library, &recording);
+        let modified_before = modified(&dir.path().join(&before[0]));
+
+        let plan = preview(&library);
+
+        assert_eq!(
+            plan.renames,
+            vec![PlannedRename {
+                track_id: plan.renames[0].track_id.clone(),
+                from: before[0].clone(),
+                to: PathBuf::from("Beethoven; Symphony No. 5; 01 Allegro.wav"),
+            }]
+        );
+
+        assert_eq!(plan.tag_changes.len(), 1);
+        assert_eq!(plan.tag_changes[0].path, before[0]);
+        assert!(plan.tag_changes[0].changes.contains(&TagChange {
+            field: "Title".to_owned(),
+            old: Some("Symphonie No. 5: Allegro".to_owned()),
+            new: Some("Symphony No. 5: Allegro".to_owned()),
+        }));
+
+        assert_eq!(track_paths(&library, &recording), before);
+        assert_eq!(modified(&dir.path().join(&before[0])), modified_before);
+
+        assert!(run(&library).is_empty());
+        assert!(preview(&library).is_empty());
+    }
+
+    /// Run a reorganization of `scope` to completion.
+    fn run_in(library: &Library, scope: &ReorganizationScope) {
+        let handle = library.reorganize_files_in(scope).unwrap();
+
+        while let Ok(msg) = handle.receiver.recv_blocking() {
+            match msg {
+                ProcessMsg::Result(result) => result.unwrap(),
+                ProcessMsg::Cancelled => panic!("the reorganization was cancelled"),
+                _ => (),
+            }
+        }
+    }
+
+    fn rename_work(library: &Library, recording: &Recording, name: &str) {
+        library
+            .update_work(
+                &recording.work.work_id,
+                translated(name),
+                Vec::new(),
+                recording.work.persons.clone(),
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn a_scoped_reorganization_leaves_other_tracks_alone() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let first = recording_with_tracks(&library, &source_dir, "Sonate", &["Adagio"]);
+        let second = recording_with_tracks(&library, &source_dir, "Quartett", &["Largo"]);
+        let second_before = track_paths(&library, &second);
+        let second_modified = modified(&dir.path().join(&second_before[0]));
+
+        rename_work(&library, &first, "Sonata");
+        rename_work(&library, &second, "Quartet");
+
+        let scope = ReorganizationScope {
+            work_ids: vec![first.work.work_id.clone()],
+            ..Default::default()
+        };
+
+        let (handle, plan) = library.preview_reorganization_in(&scope).unwrap();
+        while handle.receiver.recv_blocking().is_ok() {}
+        let plan = plan.recv_blocking().unwrap();
+        assert_eq!(plan.renames.len(), 1);
+        assert_eq!(plan.tag_changes.len(), 1);
+
+        run_in(&library, &scope);
+
+        assert_eq!(
+            track_paths(&library, &first),
+            vec![PathBuf::from("Beethoven; Sonata; 01 Adagio.wav")]
+        );
+        assert_eq!(track_paths(&library, &second), second_before);
+        assert_eq!(
+            modified(&dir.path().join(&second_before[0])),
+            second_modified
+        );
+    }
+
+    #[test]
+    fn a_person_scope_covers_the_works_they_composed() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let recording = recording_with_tracks(&library, &source_dir, "Sonata", &["Adagio"]);
+        let composer = &recording.work.persons[0].person;
+
+        library
+            .update_person(&composer.person_id, translated("Brahms"), true)
+            .unwrap();
+
+        run_in(
+            &library,
+            &ReorganizationScope {
+                person_ids: vec![composer.person_id.clone()],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            track_paths(&library, &recording),
+            vec![PathBuf::from("Brahms; Sonata; 01 Adagio.wav")]
+        );
+    }
+
+    #[test]
+    fn album_instrument_and_role_scopes_cover_their_recordings() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let recording = recording_with_tracks(&library, &source_dir, "Sonata", &["Adagio"]);
+        let album = library
+            .create_album(translated("Sonatas"), vec![recording.clone()], true)
+            .unwrap();
+        let instrument = library
+            .create_instrument(translated("Piano"), true)
+            .unwrap();
+        let role = library.create_role(translated("Soloist"), true).unwrap();
+
+        let covers = |scope: ReorganizationScope| library.has_tracks_in(&scope).unwrap();
+
+        assert!(covers(ReorganizationScope {
+            album_ids: vec![album.album_id.clone()],
+            ..Default::default()
+        }));
+        assert!(!covers(ReorganizationScope {
+            instrument_ids: vec![instrument.instrument_id.clone()],
+            role_ids: vec![role.role_id.clone()],
+            ..Default::default()
+        }));
+
+        let performed = library
+            .create_recording(
+                recording.work.clone(),
+                vec![Performer {
+                    person: recording.work.persons[0].person.clone(),
+                    role: Some(role.clone()),
+                    instrument: Some(instrument.clone()),
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        let source = source_dir.path().join("performed.wav");
+        fs::write(&source, minimal_wav()).unwrap();
+        library
+            .import_track(&source, &performed.recording_id, 0, Vec::new())
+            .unwrap();
+
+        assert!(covers(ReorganizationScope {
+            instrument_ids: vec![instrument.instrument_id],
+            ..Default::default()
+        }));
+        assert!(covers(ReorganizationScope {
+            role_ids: vec![role.role_id],
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn a_scoped_reorganization_does_not_take_the_name_of_another_track() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let first = recording_with_tracks(&library, &source_dir, "Sonata", &["Adagio"]);
+        let second = recording_with_tracks(&library, &source_dir, "Sonate", &["Adagio"]);
+        let first_before = track_paths(&library, &first);
+
+        rename_work(&library, &second, "Sonata");
+
+        run_in(
+            &library,
+            &ReorganizationScope {
+                recording_ids: vec![second.recording_id.clone()],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(track_paths(&library, &first), first_before);
+        assert_eq!(
+            track_paths(&library, &second),
+            vec![PathBuf::from("Beethoven; Sonata; 01 Adagio_1.wav")]
+        );
+    }
 }
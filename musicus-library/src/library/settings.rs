@@ -0,0 +1,170 @@
+//! Settings that belong to a library rather than to the computer it is opened
+//! on.
+//!
+//! A library keeps them in its own database, so that it names its files the
+//! same way wherever it is opened and takes them along when it is exported.
+//! The keys are the names of the application settings they correspond to and
+//! the values are stored as text. The library itself only interprets the ones
+//! describing its patterns; every setting it does not store falls back to what
+//! the application configured.
+
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, SqliteConnection};
+
+use super::{naming::pattern::Patterns, Library};
+use crate::db::schema::*;
+
+pub const FILENAME_PATTERN: &str = "track-filename-pattern";
+pub const ALBUM_PATTERN: &str = "track-tag-album-pattern";
+pub const ARTIST_PATTERN: &str = "track-tag-artist-pattern";
+pub const TITLE_PATTERN: &str = "track-tag-title-pattern";
+pub const PRESERVE_COVER_ART: &str = "track-tag-preserve-cover-art";
+pub const PRESERVE_REPLAY_GAIN: &str = "track-tag-preserve-replay-gain";
+pub const PRESERVE_MUSICBRAINZ: &str = "track-tag-preserve-musicbrainz";
+pub const PRESERVE_OTHER: &str = "track-tag-preserve-other";
+
+/// The settings describing how a track file is named and tagged.
+pub const PATTERN_KEYS: &[&str] = &[
+    FILENAME_PATTERN,
+    ALBUM_PATTERN,
+    ARTIST_PATTERN,
+    TITLE_PATTERN,
+    PRESERVE_COVER_ART,
+    PRESERVE_REPLAY_GAIN,
+    PRESERVE_MUSICBRAINZ,
+    PRESERVE_OTHER,
+];
+
+impl Library {
+    /// The value this library stores for the setting `key`, if any.
+    pub fn setting(&self, key: &str) -> Result<Option<String>> {
+        get(&mut self.conn(), key)
+    }
+
+    /// Store `value` for the setting `key` in this library.
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        diesel::replace_into(settings::table)
+            .values((settings::key.eq(key), settings::value.eq(value)))
+            .execute(&mut *self.conn())?;
+
+        Ok(())
+    }
+
+    /// Forget the value this library stores for the setting `key`, so that
+    /// the one configured by the application applies again.
+    pub fn reset_setting(&self, key: &str) -> Result<()> {
+        diesel::delete(settings::table.filter(settings::key.eq(key))).execute(&mut *self.conn())?;
+        Ok(())
+    }
+}
+
+fn get(connection: &mut SqliteConnection, key: &str) -> Result<Option<String>> {
+    Ok(settings::table
+        .filter(settings::key.eq(key))
+        .select(settings::value)
+        .first::<String>(connection)
+        .optional()?)
+}
+
+/// The patterns stored in the library, with `fallback` for those it does not
+/// store.
+pub(super) fn patterns(connection: &mut SqliteConnection, fallback: Patterns) -> Result<Patterns> {
+    let mut patterns = fallback;
+
+    let strings = [
+        (FILENAME_PATTERN, &mut patterns.filename),
+        (ALBUM_PATTERN, &mut patterns.album),
+        (ARTIST_PATTERN, &mut patterns.artist),
+        (TITLE_PATTERN, &mut patterns.title),
+    ];
+
+    for (key, pattern) in strings {
+        if let Some(value) = get(connection, key)? {
+            *pattern = value;
+        }
+    }
+
+    let preserved_tags = &mut patterns.preserved_tags;
+    let flags = [
+        (PRESERVE_COVER_ART, &mut preserved_tags.cover_art),
+        (PRESERVE_REPLAY_GAIN, &mut preserved_tags.replay_gain),
+        (PRESERVE_MUSICBRAINZ, &mut preserved_tags.musicbrainz),
+        (PRESERVE_OTHER, &mut preserved_tags.other),
+    ];
+
+    for (key, flag) in flags {
+        if let Some(value) = get(connection, key)? {
+            *flag = value
+                .parse()
+                .map_err(|_| anyhow!("Invalid value {value:?} for the setting {key}"))?;
+        }
+    }
+
+    Ok(patterns)
+}
+
+/// Take over the settings of the library behind `other` that this library
+/// does not store yet.
+///
+/// This is how an imported archive brings its settings into a new library
+/// without overriding what the user already configured for an existing one.
+pub(super) fn adopt(other: &mut SqliteConnection, connection: &mut SqliteConnection) -> Result<()> {
+    let rows = settings::table
+        .select((settings::key, settings::value))
+        .load::<(String, String)>(other)?;
+
+    for (key, value) in rows {
+        diesel::insert_or_ignore_into(settings::table)
+            .values((settings::key.eq(key), settings::value.eq(value)))
+            .execute(connection)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn stored_patterns_take_precedence_over_the_configured_ones() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+
+        {
+            let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+            library.set_setting(FILENAME_PATTERN, "{work}").unwrap();
+            library.set_setting(PRESERVE_OTHER, "true").unwrap();
+        }
+
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+        library.set_patterns(&Patterns {
+            filename: "{part}".to_owned(),
+            title: "{part}".to_owned(),
+            ..Default::default()
+        });
+
+        let patterns = library.patterns();
+        assert_eq!(patterns.filename, "{work}");
+        assert_eq!(patterns.title, "{part}");
+        assert!(patterns.preserved_tags.other);
+
+        library.reset_setting(FILENAME_PATTERN).unwrap();
+        assert_eq!(library.filename_pattern(), "{part}");
+        assert_eq!(library.setting(FILENAME_PATTERN).unwrap(), None);
+    }
+
+    #[test]
+    fn an_invalid_stored_value_falls_back_to_the_configured_patterns() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = Library::new(dir.path(), cache_dir.path()).unwrap();
+
+        library.set_setting(FILENAME_PATTERN, "{work}").unwrap();
+        library.set_setting(PRESERVE_COVER_ART, "maybe").unwrap();
+
+        assert_eq!(library.patterns(), Patterns::default());
+    }
+}
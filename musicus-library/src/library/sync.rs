@@ -0,0 +1,1655 @@
+//! Synchronizing two libraries with each other.
+//!
+//! Both libraries end up with every entity either of them has, in the version
+//! that was edited last. What decides that is `edited_at`: an entity that was
+//! only edited on one side since the two libraries were last synchronized is
+//! copied over the other side's version. An entity that was edited on both
+//! sides in that time is a conflict. It is reported and left alone in both
+//! libraries until one of them is edited again, because there is no way to tell
+//! which of the two edits should win.
+//!
+//! A deleted entity leaves nothing behind to compare, so deletions and merges
+//! are recorded in the `change_log` table and repeated on the other side, unless
+//! the entity was edited there after it was deleted. In that case the edit wins
+//! and the entity is restored.
+//!
+//! The metadata of both libraries is synchronized in one transaction per
+//! library. Track files are copied afterwards, best effort: a file that could
+//! not be copied is reported, and copied by the next synchronization.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Error, Result};
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, SqliteConnection};
+use gettextrs::gettext;
+
+use super::{cover_art, exchange, merge, Library};
+use crate::{
+    db::{self, schema::*, tables},
+    error::EntityKind,
+    format_translated,
+    library::process::{spawn_process, Cancellation, ProcessHandle, ProcessMsg},
+};
+
+impl Library {
+    /// The identifier that tells this library apart from others, including
+    /// from copies of its folder that have been synchronized with it.
+    pub fn library_id(&self) -> Result<String> {
+        library_id(&mut self.conn())
+    }
+
+    /// Synchronize this library with the one in `other_folder`, in both
+    /// directions. See the [module documentation](self) for how edits are
+    /// merged.
+    ///
+    /// Conflicts and files that could not be copied are reported as warnings.
+    pub fn sync_with(&self, other_folder: impl AsRef<Path>) -> Result<ProcessHandle> {
+        let this_folder = PathBuf::from(self.folder());
+        let other_folder = other_folder.as_ref().to_owned();
+        let this_connection = Arc::clone(&self.connection);
+
+        if !other_folder.join("musicus.musdb").exists() {
+            bail!("There is no music library in {}", other_folder.display());
+        }
+
+        if fs::canonicalize(&this_folder)? == fs::canonicalize(&other_folder)? {
+            bail!("A library cannot be synchronized with itself");
+        }
+
+        Ok(spawn_process(move |sender, cancellation| {
+            sync(
+                &this_folder,
+                &this_connection,
+                &other_folder,
+                sender,
+                cancellation,
+            )
+        }))
+    }
+}
+
+/// Record that `from` was merged into `into`, rather than just deleted.
+///
+/// Must be called after `from` was deleted, whose trigger created the entry.
+pub(crate) fn record_merge(
+    connection: &mut SqliteConnection,
+    kind: EntityKind,
+    from: &str,
+    into: &str,
+) -> Result<()> {
+    diesel::update(
+        change_log::table
+            .filter(change_log::entity_kind.eq(kind.to_string()))
+            .filter(change_log::entity_id.eq(from)),
+    )
+    .set((
+        change_log::change.eq("merge"),
+        change_log::merged_into.eq(into),
+    ))
+    .execute(connection)?;
+
+    Ok(())
+}
+
+fn library_id(connection: &mut SqliteConnection) -> Result<String> {
+    Ok(meta::table
+        .filter(meta::id.eq(1))
+        .select(meta::library_id)
+        .first(connection)?)
+}
+
+/// One side's view of the previous synchronization with the other side.
+struct SyncState {
+    last_synced_at: Option<NaiveDateTime>,
+    /// The entities that were left alone as conflicts last time, by kind and
+    /// ID.
+    conflicts: HashSet<(String, String)>,
+}
+
+impl SyncState {
+    fn load(connection: &mut SqliteConnection, peer_id: &str) -> Result<Self> {
+        let last_synced_at = sync_peers::table
+            .filter(sync_peers::library_id.eq(peer_id))
+            .select(sync_peers::last_synced_at)
+            .first(connection)
+            .optional()?;
+
+        let conflicts = sync_conflicts::table
+            .filter(sync_conflicts::library_id.eq(peer_id))
+            .select((sync_conflicts::entity_kind, sync_conflicts::entity_id))
+            .load::<(String, String)>(connection)?
+            .into_iter()
+            .collect();
+
+        Ok(Self {
+            last_synced_at,
+            conflicts,
+        })
+    }
+
+    /// Whether an entity whose two versions differ has to be left alone.
+    fn is_conflict(
+        &self,
+        kind: EntityKind,
+        id: &str,
+        this_edited_at: NaiveDateTime,
+        other_edited_at: NaiveDateTime,
+    ) -> bool {
+        let Some(last_synced_at) = self.last_synced_at else {
+            // Without a previous synchronization there is nothing to tell
+            // concurrent edits apart, so the newer version wins.
+            return false;
+        };
+
+        let this_edited = this_edited_at > last_synced_at;
+        let other_edited = other_edited_at > last_synced_at;
+
+        (this_edited && other_edited)
+            || (!this_edited
+                && !other_edited
+                && self.conflicts.contains(&(kind.to_string(), id.to_owned())))
+    }
+
+    fn save(
+        connection: &mut SqliteConnection,
+        peer_id: &str,
+        synced_at: NaiveDateTime,
+        conflicts: &[Conflict],
+    ) -> Result<()> {
+        diesel::insert_into(sync_peers::table)
+            .values(tables::SyncPeer {
+                library_id: peer_id.to_owned(),
+                last_synced_at: synced_at,
+            })
+            .on_conflict(sync_peers::library_id)
+            .do_update()
+            .set(sync_peers::last_synced_at.eq(synced_at))
+            .execute(connection)?;
+
+        diesel::delete(sync_conflicts::table.filter(sync_conflicts::library_id.eq(peer_id)))
+            .execute(connection)?;
+
+        for conflict in conflicts {
+            diesel::insert_into(sync_conflicts::table)
+                .values((
+                    sync_conflicts::library_id.eq(peer_id),
+                    sync_conflicts::entity_kind.eq(conflict.kind.to_string()),
+                    sync_conflicts::entity_id.eq(&conflict.id),
+                ))
+                .execute(connection)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An entity that was edited in both libraries.
+struct Conflict {
+    kind: EntityKind,
+    id: String,
+    description: String,
+}
+
+/// Which of the two libraries something happens in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    This,
+    Other,
+}
+
+/// A deletion or merge to repeat in one of the libraries.
+struct Removal {
+    side: Side,
+    kind: EntityKind,
+    id: String,
+    merged_into: Option<String>,
+    /// The file of a removed track, relative to its library folder.
+    path: Option<PathBuf>,
+}
+
+/// A track file that has to follow its track to a new name.
+struct Move {
+    side: Side,
+    from: PathBuf,
+    to: PathBuf,
+}
+
+/// What a synchronization did to the metadata, for the summary.
+#[derive(Default)]
+struct Outcome {
+    n_to_this: usize,
+    n_to_other: usize,
+    n_removed: usize,
+    conflicts: Vec<Conflict>,
+    removals: Vec<Removal>,
+    moves: Vec<Move>,
+    /// Problems that did not stop the synchronization.
+    warnings: Vec<String>,
+}
+
+fn sync(
+    this_folder: &Path,
+    this_connection: &Arc<Mutex<SqliteConnection>>,
+    other_folder: &Path,
+    sender: &async_channel::Sender<ProcessMsg>,
+    cancellation: &Cancellation,
+) -> Result<()> {
+    let _ = sender.send_blocking(ProcessMsg::Message(gettext("Comparing the libraries")));
+
+    let other_db_path = other_folder.join("musicus.musdb");
+    let mut other = db::connect(&other_db_path.to_string_lossy())?;
+
+    // Only the metadata pass holds the lock on this library's database. The
+    // files are copied after it was released.
+    let outcome = {
+        let this = &mut *db::lock_connection(this_connection);
+
+        let this_id = library_id(this)?;
+        let mut other_id = library_id(&mut other)?;
+
+        // A library folder that was copied by hand carries the same ID as the
+        // original. From now on, the copy is a library of its own.
+        if other_id == this_id {
+            other_id = db::generate_id();
+            diesel::update(meta::table.filter(meta::id.eq(1)))
+                .set(meta::library_id.eq(&other_id))
+                .execute(&mut other)?;
+        }
+
+        let synced_at = db::now();
+        let this_state = SyncState::load(this, &other_id)?;
+
+        this.transaction::<Outcome, Error, _>(|this| {
+            other.transaction::<Outcome, Error, _>(|other| {
+                let mut outcome = Outcome::default();
+
+                sync_entities::<tables::Person>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<tables::Role>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<tables::Instrument>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<tables::Tag>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<WorkRecord>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<EnsembleRecord>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<RecordingRecord>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<AlbumRecord>(this, other, &this_state, &mut outcome)?;
+                sync_entities::<TrackRecord>(this, other, &this_state, &mut outcome)?;
+
+                cancellation.check()?;
+
+                apply_removals(this, other, &mut outcome)?;
+
+                SyncState::save(this, &other_id, synced_at, &outcome.conflicts)?;
+                SyncState::save(other, &this_id, synced_at, &outcome.conflicts)?;
+
+                Ok(outcome)
+            })
+        })?
+    };
+
+    for conflict in &outcome.conflicts {
+        let _ = sender.send_blocking(ProcessMsg::Warning(format_translated!(
+            gettext("Edited in both libraries, left unchanged: {}"),
+            &conflict.description
+        )));
+    }
+
+    for warning in &outcome.warnings {
+        let _ = sender.send_blocking(ProcessMsg::Warning(warning.clone()));
+    }
+
+    let n_files = sync_files(
+        this_folder,
+        this_connection,
+        other_folder,
+        &mut other,
+        &outcome,
+        sender,
+        cancellation,
+    )?;
+
+    let _ = sender.send_blocking(ProcessMsg::Message(format_translated!(
+        gettext(
+            "Took over {} changes, passed on {} changes, removed {} items and copied {} files. {} conflicts."
+        ),
+        outcome.n_to_this,
+        outcome.n_to_other,
+        outcome.n_removed,
+        n_files,
+        outcome.conflicts.len()
+    )));
+
+    Ok(())
+}
+
+/// An entity together with everything it owns in other tables, as stored.
+trait Entity: Sized {
+    const KIND: EntityKind;
+
+    /// All entities of this kind, in an order in which they can be inserted.
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>>;
+
+    fn id(&self) -> &str;
+
+    fn edited_at(&self) -> NaiveDateTime;
+
+    /// A name for the entity to show the user.
+    fn describe(&self, connection: &mut SqliteConnection) -> String;
+
+    /// Insert the entity, or replace the existing version of it.
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()>;
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()>;
+
+    /// The file that belongs to the entity, relative to the library folder.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Bring the entities of one kind in line in both libraries.
+///
+/// Removals are only collected here. They are applied once all kinds have been
+/// synchronized, in reverse, so that nothing is removed while it is still
+/// referenced by something that would have been updated later.
+fn sync_entities<E: Entity>(
+    this: &mut SqliteConnection,
+    other: &mut SqliteConnection,
+    state: &SyncState,
+    outcome: &mut Outcome,
+) -> Result<()> {
+    let this_entities = E::load_all(this)?;
+    let other_entities = E::load_all(other)?;
+    let this_log = change_log(this, E::KIND)?;
+    let other_log = change_log(other, E::KIND)?;
+
+    let this_by_id: HashMap<&str, &E> = this_entities.iter().map(|e| (e.id(), e)).collect();
+    let other_by_id: HashMap<&str, &E> = other_entities.iter().map(|e| (e.id(), e)).collect();
+
+    // The entities to save, in insertion order, with the side to save them to.
+    let mut saves: Vec<(Side, &E)> = Vec::new();
+
+    for entity in &this_entities {
+        match other_by_id.get(entity.id()) {
+            Some(other_entity) => {
+                if entity.edited_at() == other_entity.edited_at() {
+                    continue;
+                }
+
+                if state.is_conflict(
+                    E::KIND,
+                    entity.id(),
+                    entity.edited_at(),
+                    other_entity.edited_at(),
+                ) {
+                    outcome.conflicts.push(Conflict {
+                        kind: E::KIND,
+                        id: entity.id().to_owned(),
+                        description: entity.describe(this),
+                    });
+                } else if entity.edited_at() > other_entity.edited_at() {
+                    if let (Some(from), Some(to)) = (other_entity.path(), entity.path()) {
+                        if from != to {
+                            outcome.moves.push(Move {
+                                side: Side::Other,
+                                from: from.to_owned(),
+                                to: to.to_owned(),
+                            });
+                        }
+                    }
+
+                    saves.push((Side::Other, entity));
+                } else {
+                    if let (Some(from), Some(to)) = (entity.path(), other_entity.path()) {
+                        if from != to {
+                            outcome.moves.push(Move {
+                                side: Side::This,
+                                from: from.to_owned(),
+                                to: to.to_owned(),
+                            });
+                        }
+                    }
+
+                    saves.push((Side::This, other_entity));
+                }
+            }
+            None => match other_log.get(entity.id()) {
+                Some(entry) if entity.edited_at() <= entry.changed_at => {
+                    outcome.removals.push(Removal {
+                        side: Side::This,
+                        kind: E::KIND,
+                        id: entity.id().to_owned(),
+                        merged_into: entry.merged_into.clone(),
+                        path: entity.path().map(Path::to_owned),
+                    });
+                }
+                _ => saves.push((Side::Other, entity)),
+            },
+        }
+    }
+
+    for entity in &other_entities {
+        if this_by_id.contains_key(entity.id()) {
+            continue;
+        }
+
+        match this_log.get(entity.id()) {
+            Some(entry) if entity.edited_at() <= entry.changed_at => {
+                outcome.removals.push(Removal {
+                    side: Side::Other,
+                    kind: E::KIND,
+                    id: entity.id().to_owned(),
+                    merged_into: entry.merged_into.clone(),
+                    path: entity.path().map(Path::to_owned),
+                });
+            }
+            _ => saves.push((Side::This, entity)),
+        }
+    }
+
+    // Entities may refer to others of the same kind that come later, such as
+    // a work related to another work. Whatever failed is retried for as long
+    // as the previous round got anything saved.
+    loop {
+        let mut failed = Vec::new();
+
+        for (side, entity) in saves.iter().copied() {
+            let connection = match side {
+                Side::This => &mut *this,
+                Side::Other => &mut *other,
+            };
+
+            match connection.transaction(|connection| entity.save(connection)) {
+                Ok(()) => match side {
+                    Side::This => outcome.n_to_this += 1,
+                    Side::Other => outcome.n_to_other += 1,
+                },
+                Err(err) => failed.push((side, entity, err)),
+            }
+        }
+
+        if failed.is_empty() || failed.len() == saves.len() {
+            for (side, entity, err) in failed {
+                log::warn!("Failed to synchronize {} {}: {err}", E::KIND, entity.id());
+
+                let connection = match side {
+                    Side::This => &mut *other,
+                    Side::Other => &mut *this,
+                };
+
+                outcome.warnings.push(format_translated!(
+                    gettext("Could not be synchronized: {}"),
+                    entity.describe(connection)
+                ));
+            }
+
+            break;
+        }
+
+        saves = failed
+            .into_iter()
+            .map(|(side, entity, _)| (side, entity))
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// The deletions and merges of one kind of entity, by ID.
+fn change_log(
+    connection: &mut SqliteConnection,
+    kind: EntityKind,
+) -> Result<HashMap<String, tables::ChangeLogEntry>> {
+    Ok(change_log::table
+        .filter(change_log::entity_kind.eq(kind.to_string()))
+        .load::<tables::ChangeLogEntry>(connection)?
+        .into_iter()
+        .map(|entry| (entry.entity_id.clone(), entry))
+        .collect())
+}
+
+/// Repeat the deletions and merges of one library in the other one.
+///
+/// Tracks go first and persons last, the reverse of the order in which the
+/// entities were synchronized.
+fn apply_removals(
+    this: &mut SqliteConnection,
+    other: &mut SqliteConnection,
+    outcome: &mut Outcome,
+) -> Result<()> {
+    let order = [
+        EntityKind::Track,
+        EntityKind::Album,
+        EntityKind::Recording,
+        EntityKind::Ensemble,
+        EntityKind::Work,
+        EntityKind::Tag,
+        EntityKind::Instrument,
+        EntityKind::Role,
+        EntityKind::Person,
+    ];
+
+    let mut removals = std::mem::take(&mut outcome.removals);
+    removals.sort_by_key(|removal| order.iter().position(|kind| *kind == removal.kind));
+
+    for removal in removals {
+        let connection = match removal.side {
+            Side::This => &mut *this,
+            Side::Other => &mut *other,
+        };
+
+        let result =
+            connection.transaction::<(), Error, _>(|connection| match &removal.merged_into {
+                Some(into) if exists(connection, removal.kind, into)? => {
+                    merge::merge_entities(connection, removal.kind, &removal.id, into)
+                }
+                _ => Ok(delete(connection, removal.kind, &removal.id)?),
+            });
+
+        match result {
+            Ok(()) => {
+                outcome.n_removed += 1;
+                outcome.removals.push(removal);
+            }
+            Err(err) => {
+                log::warn!(
+                    "Failed to remove {} {} while synchronizing: {err:?}",
+                    removal.kind,
+                    removal.id
+                );
+
+                outcome.warnings.push(format_translated!(
+                    gettext("Deleted in the other library, but still used in this one: {}"),
+                    describe(connection, removal.kind, &removal.id)
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn exists(
+    connection: &mut SqliteConnection,
+    kind: EntityKind,
+    id: &str,
+) -> Result<bool> {
+    let count: i64 = match kind {
+        EntityKind::Person => persons::table
+            .filter(persons::person_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Role => roles::table
+            .filter(roles::role_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Instrument => instruments::table
+            .filter(instruments::instrument_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Tag => tags::table
+            .filter(tags::tag_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Work => works::table
+            .filter(works::work_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Ensemble => ensembles::table
+            .filter(ensembles::ensemble_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Recording => recordings::table
+            .filter(recordings::recording_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Album => albums::table
+            .filter(albums::album_id.eq(id))
+            .count()
+            .get_result(connection)?,
+        EntityKind::Track => tracks::table
+            .filter(tracks::track_id.eq(id))
+            .count()
+            .get_result(connection)?,
+    };
+
+    Ok(count > 0)
+}
+
+fn delete(connection: &mut SqliteConnection, kind: EntityKind, id: &str) -> QueryResult<()> {
+    match kind {
+        EntityKind::Person => tables::Person::delete(connection, id),
+        EntityKind::Role => tables::Role::delete(connection, id),
+        EntityKind::Instrument => tables::Instrument::delete(connection, id),
+        EntityKind::Tag => tables::Tag::delete(connection, id),
+        EntityKind::Work => WorkRecord::delete(connection, id),
+        EntityKind::Ensemble => EnsembleRecord::delete(connection, id),
+        EntityKind::Recording => RecordingRecord::delete(connection, id),
+        EntityKind::Album => AlbumRecord::delete(connection, id),
+        EntityKind::Track => TrackRecord::delete(connection, id),
+    }
+}
+
+/// A name for the entity with `id` to show the user, as far as it can be found.
+pub(crate) fn describe(connection: &mut SqliteConnection, kind: EntityKind, id: &str) -> String {
+    let name = match kind {
+        EntityKind::Person => persons::table
+            .filter(persons::person_id.eq(id))
+            .select(persons::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Role => roles::table
+            .filter(roles::role_id.eq(id))
+            .select(roles::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Instrument => instruments::table
+            .filter(instruments::instrument_id.eq(id))
+            .select(instruments::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Tag => tags::table
+            .filter(tags::tag_id.eq(id))
+            .select(tags::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Work => works::table
+            .filter(works::work_id.eq(id))
+            .select(works::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Ensemble => ensembles::table
+            .filter(ensembles::ensemble_id.eq(id))
+            .select(ensembles::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Recording => recordings::table
+            .inner_join(works::table)
+            .filter(recordings::recording_id.eq(id))
+            .select(works::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Album => albums::table
+            .filter(albums::album_id.eq(id))
+            .select(albums::name)
+            .first::<db::TranslatedString>(connection)
+            .ok(),
+        EntityKind::Track => {
+            return tracks::table
+                .filter(tracks::track_id.eq(id))
+                .select(tracks::path)
+                .first::<tables::PathBufWrapper>(connection)
+                .map(|path| path.0.display().to_string())
+                .unwrap_or_else(|_| id.to_owned())
+        }
+    };
+
+    name.map(|name| name.get().to_owned())
+        .unwrap_or_else(|| id.to_owned())
+}
+
+impl Entity for tables::Person {
+    const KIND: EntityKind = EntityKind::Person;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        Ok(persons::table.load(connection)?)
+    }
+
+    fn id(&self) -> &str {
+        &self.person_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        diesel::insert_into(persons::table)
+            .values(self)
+            .on_conflict(persons::person_id)
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(persons::table.filter(persons::person_id.eq(id))).execute(connection)?;
+        Ok(())
+    }
+}
+
+impl Entity for tables::Role {
+    const KIND: EntityKind = EntityKind::Role;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        Ok(roles::table.load(connection)?)
+    }
+
+    fn id(&self) -> &str {
+        &self.role_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        diesel::insert_into(roles::table)
+            .values(self)
+            .on_conflict(roles::role_id)
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(roles::table.filter(roles::role_id.eq(id))).execute(connection)?;
+        Ok(())
+    }
+}
+
+impl Entity for tables::Instrument {
+    const KIND: EntityKind = EntityKind::Instrument;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        Ok(instruments::table.load(connection)?)
+    }
+
+    fn id(&self) -> &str {
+        &self.instrument_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        diesel::insert_into(instruments::table)
+            .values(self)
+            .on_conflict(instruments::instrument_id)
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(instruments::table.filter(instruments::instrument_id.eq(id)))
+            .execute(connection)?;
+        Ok(())
+    }
+}
+
+impl Entity for tables::Tag {
+    const KIND: EntityKind = EntityKind::Tag;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        Ok(tags::table.load(connection)?)
+    }
+
+    fn id(&self) -> &str {
+        &self.tag_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        diesel::insert_into(tags::table)
+            .values(self)
+            .on_conflict(tags::tag_id)
+            .do_update()
+            .set(self)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(tags::table.filter(tags::tag_id.eq(id))).execute(connection)?;
+        Ok(())
+    }
+}
+
+struct WorkRecord {
+    work: tables::Work,
+    persons: Vec<tables::WorkPerson>,
+    instruments: Vec<tables::WorkInstrument>,
+    tags: Vec<tables::WorkTag>,
+}
+
+impl Entity for WorkRecord {
+    const KIND: EntityKind = EntityKind::Work;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        let mut persons = group(
+            work_persons::table
+                .order(work_persons::sequence_number)
+                .load::<tables::WorkPerson>(connection)?,
+            |p| p.work_id.clone(),
+        );
+        let mut instruments = group(
+            work_instruments::table
+                .order(work_instruments::sequence_number)
+                .load::<tables::WorkInstrument>(connection)?,
+            |i| i.work_id.clone(),
+        );
+        let mut tags = group(
+            work_tags::table
+                .order(work_tags::sequence_number)
+                .load::<tables::WorkTag>(connection)?,
+            |t| t.work_id.clone(),
+        );
+
+        let mut works = works::table.load::<tables::Work>(connection)?;
+
+        // Parents have to exist before their parts can be inserted.
+        let parents: HashMap<String, Option<String>> = works
+            .iter()
+            .map(|w| (w.work_id.clone(), w.parent_work_id.clone()))
+            .collect();
+        works.sort_by_cached_key(|w| depth(&parents, &w.work_id));
+
+        Ok(works
+            .into_iter()
+            .map(|work| WorkRecord {
+                persons: persons.remove(&work.work_id).unwrap_or_default(),
+                instruments: instruments.remove(&work.work_id).unwrap_or_default(),
+                tags: tags.remove(&work.work_id).unwrap_or_default(),
+                work,
+            })
+            .collect())
+    }
+
+    fn id(&self) -> &str {
+        &self.work.work_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.work.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.work.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        let work_id = &self.work.work_id;
+
+        diesel::insert_into(works::table)
+            .values(&self.work)
+            .on_conflict(works::work_id)
+            .do_update()
+            .set(&self.work)
+            .execute(connection)?;
+
+        diesel::delete(work_persons::table.filter(work_persons::work_id.eq(work_id)))
+            .execute(connection)?;
+        diesel::insert_into(work_persons::table)
+            .values(&self.persons)
+            .execute(connection)?;
+
+        diesel::delete(work_instruments::table.filter(work_instruments::work_id.eq(work_id)))
+            .execute(connection)?;
+        diesel::insert_into(work_instruments::table)
+            .values(&self.instruments)
+            .execute(connection)?;
+
+        diesel::delete(work_tags::table.filter(work_tags::work_id.eq(work_id)))
+            .execute(connection)?;
+        diesel::insert_into(work_tags::table)
+            .values(&self.tags)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(works::table.filter(works::work_id.eq(id))).execute(connection)?;
+        Ok(())
+    }
+}
+
+/// How many ancestors the work with `work_id` has. A cycle, which the editors
+/// do not allow, ends the count.
+fn depth(parents: &HashMap<String, Option<String>>, work_id: &str) -> usize {
+    let mut seen = HashSet::new();
+    let mut current = parents.get(work_id).cloned().flatten();
+
+    while let Some(parent) = current {
+        if !seen.insert(parent.clone()) {
+            break;
+        }
+
+        current = parents.get(&parent).cloned().flatten();
+    }
+
+    seen.len()
+}
+
+struct EnsembleRecord {
+    ensemble: tables::Ensemble,
+    persons: Vec<tables::EnsemblePerson>,
+}
+
+impl Entity for EnsembleRecord {
+    const KIND: EntityKind = EntityKind::Ensemble;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        let mut persons = group(
+            ensemble_persons::table
+                .order(ensemble_persons::sequence_number)
+                .load::<tables::EnsemblePerson>(connection)?,
+            |p| p.ensemble_id.clone(),
+        );
+
+        Ok(ensembles::table
+            .load::<tables::Ensemble>(connection)?
+            .into_iter()
+            .map(|ensemble| EnsembleRecord {
+                persons: persons.remove(&ensemble.ensemble_id).unwrap_or_default(),
+                ensemble,
+            })
+            .collect())
+    }
+
+    fn id(&self) -> &str {
+        &self.ensemble.ensemble_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.ensemble.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.ensemble.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        let ensemble_id = &self.ensemble.ensemble_id;
+
+        diesel::insert_into(ensembles::table)
+            .values(&self.ensemble)
+            .on_conflict(ensembles::ensemble_id)
+            .do_update()
+            .set(&self.ensemble)
+            .execute(connection)?;
+
+        diesel::delete(
+            ensemble_persons::table.filter(ensemble_persons::ensemble_id.eq(ensemble_id)),
+        )
+        .execute(connection)?;
+        diesel::insert_into(ensemble_persons::table)
+            .values(&self.persons)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(ensembles::table.filter(ensembles::ensemble_id.eq(id)))
+            .execute(connection)?;
+        Ok(())
+    }
+}
+
+struct RecordingRecord {
+    recording: tables::Recording,
+    persons: Vec<tables::RecordingPerson>,
+    ensembles: Vec<tables::RecordingEnsemble>,
+    tags: Vec<tables::RecordingTag>,
+}
+
+impl Entity for RecordingRecord {
+    const KIND: EntityKind = EntityKind::Recording;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        let mut persons = group(
+            recording_persons::table
+                .order(recording_persons::sequence_number)
+                .load::<tables::RecordingPerson>(connection)?,
+            |p| p.recording_id.clone(),
+        );
+        let mut ensembles = group(
+            recording_ensembles::table
+                .order(recording_ensembles::sequence_number)
+                .load::<tables::RecordingEnsemble>(connection)?,
+            |e| e.recording_id.clone(),
+        );
+        let mut tags = group(
+            recording_tags::table
+                .order(recording_tags::sequence_number)
+                .load::<tables::RecordingTag>(connection)?,
+            |t| t.recording_id.clone(),
+        );
+
+        Ok(recordings::table
+            .load::<tables::Recording>(connection)?
+            .into_iter()
+            .map(|recording| RecordingRecord {
+                persons: persons.remove(&recording.recording_id).unwrap_or_default(),
+                ensembles: ensembles
+                    .remove(&recording.recording_id)
+                    .unwrap_or_default(),
+                tags: tags.remove(&recording.recording_id).unwrap_or_default(),
+                recording,
+            })
+            .collect())
+    }
+
+    fn id(&self) -> &str {
+        &self.recording.recording_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.recording.edited_at
+    }
+
+    fn describe(&self, connection: &mut SqliteConnection) -> String {
+        describe(
+            connection,
+            EntityKind::Recording,
+            &self.recording.recording_id,
+        )
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        let recording_id = &self.recording.recording_id;
+
+        diesel::insert_into(recordings::table)
+            .values(&self.recording)
+            .on_conflict(recordings::recording_id)
+            .do_update()
+            .set(&self.recording)
+            .execute(connection)?;
+
+        diesel::delete(
+            recording_persons::table.filter(recording_persons::recording_id.eq(recording_id)),
+        )
+        .execute(connection)?;
+        diesel::insert_into(recording_persons::table)
+            .values(&self.persons)
+            .execute(connection)?;
+
+        diesel::delete(
+            recording_ensembles::table.filter(recording_ensembles::recording_id.eq(recording_id)),
+        )
+        .execute(connection)?;
+        diesel::insert_into(recording_ensembles::table)
+            .values(&self.ensembles)
+            .execute(connection)?;
+
+        diesel::delete(recording_tags::table.filter(recording_tags::recording_id.eq(recording_id)))
+            .execute(connection)?;
+        diesel::insert_into(recording_tags::table)
+            .values(&self.tags)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(recordings::table.filter(recordings::recording_id.eq(id)))
+            .execute(connection)?;
+        Ok(())
+    }
+}
+
+struct AlbumRecord {
+    album: tables::Album,
+    recordings: Vec<tables::AlbumRecording>,
+}
+
+impl Entity for AlbumRecord {
+    const KIND: EntityKind = EntityKind::Album;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        let mut recordings = group(
+            album_recordings::table
+                .order(album_recordings::sequence_number)
+                .load::<tables::AlbumRecording>(connection)?,
+            |r| r.album_id.clone(),
+        );
+
+        Ok(albums::table
+            .load::<tables::Album>(connection)?
+            .into_iter()
+            .map(|album| AlbumRecord {
+                recordings: recordings.remove(&album.album_id).unwrap_or_default(),
+                album,
+            })
+            .collect())
+    }
+
+    fn id(&self) -> &str {
+        &self.album.album_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.album.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.album.name.get().to_owned()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        let album_id = &self.album.album_id;
+
+        diesel::insert_into(albums::table)
+            .values(&self.album)
+            .on_conflict(albums::album_id)
+            .do_update()
+            .set(&self.album)
+            .execute(connection)?;
+
+        diesel::delete(album_recordings::table.filter(album_recordings::album_id.eq(album_id)))
+            .execute(connection)?;
+        diesel::insert_into(album_recordings::table)
+            .values(&self.recordings)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(albums::table.filter(albums::album_id.eq(id))).execute(connection)?;
+        Ok(())
+    }
+}
+
+struct TrackRecord {
+    track: tables::Track,
+    works: Vec<tables::TrackWork>,
+}
+
+impl Entity for TrackRecord {
+    const KIND: EntityKind = EntityKind::Track;
+
+    fn load_all(connection: &mut SqliteConnection) -> Result<Vec<Self>> {
+        let mut works = group(
+            track_works::table
+                .order(track_works::sequence_number)
+                .load::<tables::TrackWork>(connection)?,
+            |w| w.track_id.clone(),
+        );
+
+        Ok(tracks::table
+            .load::<tables::Track>(connection)?
+            .into_iter()
+            .map(|track| TrackRecord {
+                works: works.remove(&track.track_id).unwrap_or_default(),
+                track,
+            })
+            .collect())
+    }
+
+    fn id(&self) -> &str {
+        &self.track.track_id
+    }
+
+    fn edited_at(&self) -> NaiveDateTime {
+        self.track.edited_at
+    }
+
+    fn describe(&self, _: &mut SqliteConnection) -> String {
+        self.track.path.0.display().to_string()
+    }
+
+    fn save(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+        let track_id = &self.track.track_id;
+
+        diesel::insert_into(tracks::table)
+            .values(&self.track)
+            .on_conflict(tracks::track_id)
+            .do_update()
+            .set(&self.track)
+            .execute(connection)?;
+
+        diesel::delete(track_works::table.filter(track_works::track_id.eq(track_id)))
+            .execute(connection)?;
+        diesel::insert_into(track_works::table)
+            .values(&self.works)
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    fn delete(connection: &mut SqliteConnection, id: &str) -> QueryResult<()> {
+        diesel::delete(tracks::table.filter(tracks::track_id.eq(id))).execute(connection)?;
+        Ok(())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.track.path.0)
+    }
+}
+
+fn group<T>(rows: Vec<T>, key: impl Fn(&T) -> String) -> HashMap<String, Vec<T>> {
+    let mut groups: HashMap<String, Vec<T>> = HashMap::new();
+
+    for row in rows {
+        groups.entry(key(&row)).or_default().push(row);
+    }
+
+    groups
+}
+
+/// Bring the track files and cover images in line with the synchronized
+/// metadata.
+///
+/// Files are moved along with renamed tracks, removed along with removed
+/// tracks, and copied from the other library where they are missing. Returns
+/// how many files were copied.
+fn sync_files(
+    this_folder: &Path,
+    this_connection: &Arc<Mutex<SqliteConnection>>,
+    other_folder: &Path,
+    other: &mut SqliteConnection,
+    outcome: &Outcome,
+    sender: &async_channel::Sender<ProcessMsg>,
+    cancellation: &Cancellation,
+) -> Result<usize> {
+    let folder = |side| match side {
+        Side::This => this_folder,
+        Side::Other => other_folder,
+    };
+
+    for removal in &outcome.removals {
+        if let Some(path) = &removal.path {
+            let path = folder(removal.side).join(path);
+            if let Err(err) = fs::remove_file(&path) {
+                log::warn!("Failed to remove track file {}: {err}", path.display());
+            }
+        }
+    }
+
+    for file_move in &outcome.moves {
+        let from = folder(file_move.side).join(&file_move.from);
+        let to = folder(file_move.side).join(&file_move.to);
+
+        // If the file is not where it used to be, it is copied below.
+        if from.exists() && !to.exists() {
+            let result = to
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::rename(&from, &to));
+
+            if let Err(err) = result {
+                log::warn!(
+                    "Failed to move track file {} to {}: {err}",
+                    from.display(),
+                    to.display()
+                );
+            }
+        }
+    }
+
+    // After the metadata pass, both libraries have the same tracks under the
+    // same paths.
+    let tracks = {
+        let this = &mut *db::lock_connection(this_connection);
+        tracks::table.load::<tables::Track>(this)?
+    };
+
+    let other_paths: HashSet<PathBuf> = tracks::table
+        .select(tracks::path)
+        .load::<tables::PathBufWrapper>(other)?
+        .into_iter()
+        .map(|path| path.0)
+        .collect();
+
+    let n_tracks = tracks.len().max(1);
+    let mut n_copied = 0;
+
+    let _ = sender.send_blocking(ProcessMsg::Message(gettext("Copying track files")));
+
+    for (index, track) in tracks.iter().enumerate() {
+        cancellation.check()?;
+
+        let path = &track.path.0;
+        let this_path = this_folder.join(path);
+        let other_path = other_folder.join(path);
+
+        let copy = match (this_path.exists(), other_path.exists()) {
+            (false, true) => Some((&other_path, &this_path)),
+            (true, false) if other_paths.contains(path) => Some((&this_path, &other_path)),
+            (false, false) => {
+                let _ = sender.send_blocking(ProcessMsg::Warning(format_translated!(
+                    gettext("The file of a track is missing in both libraries: {}"),
+                    path.display()
+                )));
+                None
+            }
+            _ => None,
+        };
+
+        if let Some((from, to)) = copy {
+            match copy_file(from, to) {
+                Ok(()) => n_copied += 1,
+                Err(err) => {
+                    log::warn!(
+                        "Failed to copy track file {} to {}: {err:?}",
+                        from.display(),
+                        to.display()
+                    );
+
+                    let _ = sender.send_blocking(ProcessMsg::Warning(format_translated!(
+                        gettext("A track file could not be copied: {}"),
+                        path.display()
+                    )));
+                }
+            }
+        }
+
+        // Ignore if the reveiver has been dropped.
+        let _ = sender.send_blocking(ProcessMsg::Progress((index + 1) as f64 / n_tracks as f64));
+    }
+
+    // Both libraries now refer to the same covers as well. A cover that cannot
+    // be copied only costs the picture, so it is not worth a warning.
+    let covers = {
+        let this = &mut *db::lock_connection(this_connection);
+        cover_art::referenced(this)?
+    };
+
+    for cover in covers {
+        cancellation.check()?;
+
+        let this_path = this_folder.join(&cover);
+        let other_path = other_folder.join(&cover);
+
+        let copy = match (this_path.exists(), other_path.exists()) {
+            (false, true) => Some((&other_path, &this_path)),
+            (true, false) => Some((&this_path, &other_path)),
+            _ => None,
+        };
+
+        if let Some((from, to)) = copy {
+            match copy_file(from, to) {
+                Ok(()) => n_copied += 1,
+                Err(err) => log::warn!(
+                    "Failed to copy cover image {} to {}: {err:?}",
+                    from.display(),
+                    to.display()
+                ),
+            }
+        }
+    }
+
+    Ok(n_copied)
+}
+
+/// Copy a file from one library to the other, through a temporary file
+/// next to the destination, so that an interrupted copy never leaves a
+/// truncated file where the next synchronization would take it for complete.
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut part_path = to.to_owned();
+    part_path.as_mut_os_string().push(".part");
+
+    let result = fs::File::open(from)
+        .map_err(Error::from)
+        .and_then(|file| exchange::copy_to_file(file, &part_path))
+        .and_then(|()| Ok(fs::rename(&part_path, to)?));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&part_path);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::{models::Composer, TranslatedString},
+        library::{naming::audio_tags, process::ProcessMsg, TrackUpdate},
+    };
+
+    fn translated(name: &str) -> TranslatedString {
+        TranslatedString(HashMap::from([("generic".to_owned(), name.to_owned())]))
+    }
+
+    fn library(dir: &TempDir, cache_dir: &TempDir) -> Library {
+        Library::new(dir.path(), cache_dir.path()).unwrap()
+    }
+
+    /// Run a synchronization to its end and return its warnings.
+    fn run(handle: ProcessHandle) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        loop {
+            match handle.receiver.recv_blocking().unwrap() {
+                ProcessMsg::Warning(warning) => warnings.push(warning),
+                ProcessMsg::Result(result) => {
+                    result.unwrap();
+                    return warnings;
+                }
+                ProcessMsg::Cancelled => panic!("the synchronization was cancelled"),
+                _ => (),
+            }
+        }
+    }
+
+    fn person_name(library: &Library, person_id: &str) -> Option<String> {
+        persons::table
+            .filter(persons::person_id.eq(person_id))
+            .select(persons::name)
+            .first::<TranslatedString>(&mut *library.conn())
+            .optional()
+            .unwrap()
+            .map(|name| name.get().to_owned())
+    }
+
+    #[test]
+    fn entities_and_track_files_are_copied_in_both_directions() {
+        let (dir_a, dir_b, cache_dir) = (
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+        );
+        let a = library(&dir_a, &cache_dir);
+        let b = library(&dir_b, &cache_dir);
+
+        let beethoven = a.create_person(translated("Beethoven"), false).unwrap();
+        let mozart = b.create_person(translated("Mozart"), false).unwrap();
+
+        let work = a
+            .create_work(
+                translated("Symphony No. 5"),
+                Vec::new(),
+                vec![Composer {
+                    person: beethoven.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                false,
+            )
+            .unwrap();
+        let recording = a
+            .create_recording(work, Vec::new(), Vec::new(), Vec::new(), None, false)
+            .unwrap();
+
+        let source = TempDir::new().unwrap();
+        let file = source.path().join("track.wav");
+        fs::write(&file, audio_tags::minimal_wav()).unwrap();
+        a.set_recording_tracks(
+            &recording.recording_id,
+            vec![TrackUpdate::New {
+                path: file,
+                works: Vec::new(),
+            }],
+            &[],
+        )
+        .unwrap();
+
+        assert!(run(a.sync_with(dir_b.path()).unwrap()).is_empty());
+
+        assert_eq!(
+            person_name(&a, &mozart.person_id).as_deref(),
+            Some("Mozart")
+        );
+        assert_eq!(
+            person_name(&b, &beethoven.person_id).as_deref(),
+            Some("Beethoven")
+        );
+
+        let tracks = b.tracks_for_recording(&recording.recording_id).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert!(dir_b.path().join(&tracks[0].path).exists());
+    }
+
+    #[test]
+    fn the_newer_edit_wins_and_concurrent_edits_are_conflicts() {
+        let (dir_a, dir_b, cache_dir) = (
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+        );
+        let a = library(&dir_a, &cache_dir);
+        let b = library(&dir_b, &cache_dir);
+
+        let person = a.create_person(translated("Beethoven"), false).unwrap();
+        run(a.sync_with(dir_b.path()).unwrap());
+
+        // Edited on one side only: the edit is taken over.
+        a.update_person(&person.person_id, translated("L. v. Beethoven"), false)
+            .unwrap();
+        run(a.sync_with(dir_b.path()).unwrap());
+        assert_eq!(
+            person_name(&b, &person.person_id).as_deref(),
+            Some("L. v. Beethoven")
+        );
+
+        // Edited on both sides: neither edit is lost.
+        a.update_person(&person.person_id, translated("Ludwig van Beethoven"), false)
+            .unwrap();
+        b.update_person(
+            &person.person_id,
+            translated("Beethoven, Ludwig van"),
+            false,
+        )
+        .unwrap();
+        let warnings = run(a.sync_with(dir_b.path()).unwrap());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            person_name(&a, &person.person_id).as_deref(),
+            Some("Ludwig van Beethoven")
+        );
+        assert_eq!(
+            person_name(&b, &person.person_id).as_deref(),
+            Some("Beethoven, Ludwig van")
+        );
+
+        // The conflict stays until one side is edited again.
+        assert_eq!(run(a.sync_with(dir_b.path()).unwrap()).len(), 1);
+
+        b.update_person(&person.person_id, translated("Beethoven"), false)
+            .unwrap();
+        assert!(run(a.sync_with(dir_b.path()).unwrap()).is_empty());
+        assert_eq!(
+            person_name(&a, &person.person_id).as_deref(),
+            Some("Beethoven")
+        );
+    }
+
+    #[test]
+    fn deletions_and_merges_are_repeated_on_the_other_side() {
+        let (dir_a, dir_b, cache_dir) = (
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+        );
+        let a = library(&dir_a, &cache_dir);
+        let b = library(&dir_b, &cache_dir);
+
+        let unused = a.create_person(translated("Nobody"), false).unwrap();
+        let duplicate = a.create_person(translated("Beethoven, L."), false).unwrap();
+        let person = a.create_person(translated("Beethoven"), false).unwrap();
+        let work = a
+            .create_work(
+                translated("Symphony No. 5"),
+                Vec::new(),
+                vec![Composer {
+                    person: duplicate.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                false,
+            )
+            .unwrap();
+        run(a.sync_with(dir_b.path()).unwrap());
+
+        a.delete_person(&unused.person_id).unwrap();
+        a.merge_persons(&duplicate.person_id, &person.person_id)
+            .unwrap();
+        assert!(run(b.sync_with(dir_a.path()).unwrap()).is_empty());
+
+        assert_eq!(person_name(&b, &unused.person_id), None);
+        assert_eq!(person_name(&b, &duplicate.person_id), None);
+
+        let composers = b.work(&work.work_id).unwrap().persons;
+        assert_eq!(composers.len(), 1);
+        assert_eq!(composers[0].person.person_id, person.person_id);
+    }
+
+    /// A copied library folder has the same ID as the original, which would
+    /// make both sides look like the same library.
+    #[test]
+    fn a_copied_library_gets_an_id_of_its_own() {
+        let (dir_a, dir_b, cache_dir) = (
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+            TempDir::new().unwrap(),
+        );
+        let a = library(&dir_a, &cache_dir);
+        a.create_person(translated("Beethoven"), false).unwrap();
+        fs::copy(
+            dir_a.path().join("musicus.musdb"),
+            dir_b.path().join("musicus.musdb"),
+        )
+        .unwrap();
+
+        run(a.sync_with(dir_b.path()).unwrap());
+
+        let b = library(&dir_b, &cache_dir);
+        assert_ne!(a.library_id().unwrap(), b.library_id().unwrap());
+    }
+}
@@ -0,0 +1,971 @@
+//! Splitting merged entities again.
+//!
+//! Every merge records the entity it discarded and each reference it moved
+//! to the entity it merged into. A merge can then be reverted, which restores
+//! the discarded entity with its original ID and moves all of its references
+//! back, or split partially, which only moves back the references the user
+//! picks. The latter is for entities that were wrongly merged because they
+//! share a name, like the two composers called Johann Strauss.
+//!
+//! References are identified by the row that holds them, so a reference is only
+//! moved back while that row still points to the entity merged into. Edits made
+//! since the merge win.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, sql_types, SqliteConnection};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    history::{self, ChangeAction},
+    sync, Library,
+};
+use crate::{
+    db::{self, schema::*, tables},
+    error::EntityKind,
+};
+
+/// A merge that can still be split again.
+#[derive(Clone, Debug)]
+pub struct PastMerge {
+    pub merge_id: i64,
+    pub entity_kind: EntityKind,
+    pub from_id: String,
+    /// The name of the merged entity when it was merged.
+    pub from_name: String,
+    pub merged_at: NaiveDateTime,
+    /// The references the merge moved that have not been changed since.
+    pub references: Vec<MergedReference>,
+}
+
+/// A reference that a merge moved to the entity merged into.
+#[derive(Clone, Debug)]
+pub struct MergedReference {
+    pub reference_id: i64,
+    /// What holds the reference, such as a work for one of its composers.
+    pub owner_kind: EntityKind,
+    pub owner_id: String,
+    pub owner_name: String,
+}
+
+impl Library {
+    /// The merges whose references are now held by the entity with `id`,
+    /// newest first.
+    pub fn merges_into(&self, kind: EntityKind, id: &str) -> Result<Vec<PastMerge>> {
+        let connection = &mut *self.conn();
+
+        let merges = merges::table
+            .filter(merges::entity_kind.eq(kind.to_string()))
+            .filter(merges::into_id.eq(id))
+            .order(merges::merge_id.desc())
+            .select((
+                merges::merge_id,
+                merges::from_id,
+                merges::from_name,
+                merges::merged_at,
+            ))
+            .load::<(i64, String, String, NaiveDateTime)>(connection)?;
+
+        merges
+            .into_iter()
+            .map(|(merge_id, from_id, from_name, merged_at)| {
+                let mut references = Vec::new();
+
+                for reference in load_references(connection, merge_id)? {
+                    if reference.points_to(connection, id)? {
+                        let owner_kind = reference.kind.location().owner_kind;
+
+                        references.push(MergedReference {
+                            reference_id: reference.reference_id,
+                            owner_kind,
+                            owner_name: sync::describe(connection, owner_kind, &reference.owner_id),
+                            owner_id: reference.owner_id,
+                        });
+                    }
+                }
+
+                Ok(PastMerge {
+                    merge_id,
+                    entity_kind: kind,
+                    from_id,
+                    from_name,
+                    merged_at,
+                    references,
+                })
+            })
+            .collect()
+    }
+
+    /// Revert a merge: restore the merged entity and move all of its references
+    /// back to it.
+    pub fn unmerge(&self, merge_id: i64) -> Result<()> {
+        let reference_ids = merge_references::table
+            .filter(merge_references::merge_id.eq(merge_id))
+            .select(merge_references::reference_id)
+            .load::<i64>(&mut *self.conn())?;
+
+        self.split_merge(merge_id, &reference_ids)
+    }
+
+    /// Restore the entity discarded by a merge, unless that already happened,
+    /// and move the references with `reference_ids` back to it.
+    ///
+    /// The other references stay with the entity merged into. They can be
+    /// moved back by splitting the same merge again later.
+    pub fn split_merge(&self, merge_id: i64, reference_ids: &[i64]) -> Result<()> {
+        let connection = &mut *self.conn();
+
+        let (kind, into_id) = merges::table
+            .filter(merges::merge_id.eq(merge_id))
+            .select((merges::entity_kind, merges::into_id))
+            .first::<(String, String)>(connection)
+            .optional()?
+            .ok_or_else(|| anyhow!("The merge {merge_id} does not exist"))?;
+
+        history::record(
+            connection,
+            ChangeAction::Update,
+            kind.parse()?,
+            &into_id,
+            None,
+            |connection| split(connection, merge_id, reference_ids),
+        )?;
+
+        self.changed();
+
+        Ok(())
+    }
+}
+
+/// Record what merging `from` into `into` is about to do, so that it can be
+/// split again. Must be called before any reference is moved.
+pub(crate) fn record_merge(
+    connection: &mut SqliteConnection,
+    kind: EntityKind,
+    from: &str,
+    into: &str,
+) -> Result<()> {
+    let Some(snapshot) = Snapshot::load(connection, kind, from, into)? else {
+        return Ok(());
+    };
+
+    diesel::insert_into(merges::table)
+        .values((
+            merges::entity_kind.eq(kind.to_string()),
+            merges::from_id.eq(from),
+            merges::from_name.eq(sync::describe(connection, kind, from)),
+            merges::into_id.eq(into),
+            merges::snapshot.eq(serde_json::to_string(&snapshot)?),
+        ))
+        .execute(connection)?;
+
+    let merge_id = merges::table
+        .select(merges::merge_id)
+        .order(merges::merge_id.desc())
+        .first::<i64>(connection)?;
+
+    // Parts only move along if `into` has none of its own. Otherwise they are
+    // discarded and part of the snapshot.
+    let parts_move = !(kind == EntityKind::Work && has_parts(connection, into)?);
+
+    for reference_kind in ReferenceKind::ALL {
+        let location = reference_kind.location();
+
+        if location.target_kind != kind
+            || (reference_kind == ReferenceKind::WorkPart && !parts_move)
+        {
+            continue;
+        }
+
+        diesel::sql_query(format!(
+            "INSERT INTO merge_references \
+                (merge_id, reference_kind, owner_id, sequence_number, value) \
+             SELECT ?, ?, {}, {}, {} FROM {} WHERE {} = ?",
+            location.owner_column,
+            location.sequence_column.unwrap_or("NULL"),
+            location.value_column.unwrap_or("NULL"),
+            location.table,
+            location.column,
+        ))
+        .bind::<sql_types::BigInt, _>(merge_id)
+        .bind::<sql_types::Text, _>(reference_kind.name())
+        .bind::<sql_types::Text, _>(from)
+        .execute(connection)?;
+    }
+
+    // Merges into `from` can still be split from where their references end up.
+    diesel::update(
+        merges::table
+            .filter(merges::entity_kind.eq(kind.to_string()))
+            .filter(merges::into_id.eq(from)),
+    )
+    .set(merges::into_id.eq(into))
+    .execute(connection)?;
+
+    Ok(())
+}
+
+fn split(connection: &mut SqliteConnection, merge_id: i64, reference_ids: &[i64]) -> Result<()> {
+    let (kind, from_id, into_id, snapshot) = merges::table
+        .filter(merges::merge_id.eq(merge_id))
+        .select((
+            merges::entity_kind,
+            merges::from_id,
+            merges::into_id,
+            merges::snapshot,
+        ))
+        .first::<(String, String, String, String)>(connection)?;
+
+    if !sync::exists(connection, kind.parse()?, &from_id)? {
+        serde_json::from_str::<Snapshot>(&snapshot)?.restore(connection)?;
+    }
+
+    let references = load_references(connection, merge_id)?;
+
+    for reference_id in reference_ids {
+        let Some(reference) = references.iter().find(|r| r.reference_id == *reference_id) else {
+            bail!("The reference {reference_id} does not belong to the merge {merge_id}");
+        };
+
+        reference.move_back(connection, &into_id, &from_id)?;
+
+        diesel::delete(
+            merge_references::table.filter(merge_references::reference_id.eq(reference_id)),
+        )
+        .execute(connection)?;
+    }
+
+    let n_remaining: i64 = merge_references::table
+        .filter(merge_references::merge_id.eq(merge_id))
+        .count()
+        .get_result(connection)?;
+
+    if n_remaining == 0 {
+        diesel::delete(merges::table.filter(merges::merge_id.eq(merge_id))).execute(connection)?;
+    }
+
+    Ok(())
+}
+
+fn has_parts(connection: &mut SqliteConnection, work_id: &str) -> Result<bool> {
+    Ok(works::table
+        .filter(works::parent_work_id.eq(work_id))
+        .select(works::work_id)
+        .first::<String>(connection)
+        .optional()?
+        .is_some())
+}
+
+/// The merged entity and the rows that it owned, which are deleted along with
+/// it.
+#[derive(Serialize, Deserialize)]
+enum Snapshot {
+    Person(tables::Person),
+    Role(tables::Role),
+    Instrument(tables::Instrument),
+    Tag(tables::Tag),
+    Ensemble {
+        ensemble: tables::Ensemble,
+        persons: Vec<tables::EnsemblePerson>,
+    },
+    Work {
+        /// The work followed by the parts that were discarded with it, parents
+        /// first.
+        works: Vec<tables::Work>,
+        persons: Vec<tables::WorkPerson>,
+        instruments: Vec<tables::WorkInstrument>,
+        tags: Vec<tables::WorkTag>,
+    },
+}
+
+impl Snapshot {
+    /// Capture `from` before it is merged into `into`. Returns `None` for
+    /// entities that cannot be merged.
+    fn load(
+        connection: &mut SqliteConnection,
+        kind: EntityKind,
+        from: &str,
+        into: &str,
+    ) -> Result<Option<Self>> {
+        let snapshot = match kind {
+            EntityKind::Person => Snapshot::Person(
+                persons::table
+                    .filter(persons::person_id.eq(from))
+                    .first(connection)?,
+            ),
+            EntityKind::Role => Snapshot::Role(
+                roles::table
+                    .filter(roles::role_id.eq(from))
+                    .first(connection)?,
+            ),
+            EntityKind::Instrument => Snapshot::Instrument(
+                instruments::table
+                    .filter(instruments::instrument_id.eq(from))
+                    .first(connection)?,
+            ),
+            EntityKind::Tag => Snapshot::Tag(
+                tags::table
+                    .filter(tags::tag_id.eq(from))
+                    .first(connection)?,
+            ),
+            EntityKind::Ensemble => Snapshot::Ensemble {
+                ensemble: ensembles::table
+                    .filter(ensembles::ensemble_id.eq(from))
+                    .first(connection)?,
+                persons: ensemble_persons::table
+                    .filter(ensemble_persons::ensemble_id.eq(from))
+                    .load(connection)?,
+            },
+            EntityKind::Work => {
+                let mut works = vec![works::table
+                    .filter(works::work_id.eq(from))
+                    .first::<tables::Work>(connection)?];
+
+                if has_parts(connection, into)? {
+                    let mut index = 0;
+
+                    while index < works.len() {
+                        let parts = works::table
+                            .filter(works::parent_work_id.eq(&works[index].work_id))
+                            .load::<tables::Work>(connection)?;
+
+                        works.extend(parts);
+                        index += 1;
+                    }
+                }
+
+                let work_ids: Vec<&str> = works.iter().map(|w| w.work_id.as_str()).collect();
+
+                Snapshot::Work {
+                    persons: work_persons::table
+                        .filter(work_persons::work_id.eq_any(&work_ids))
+                        .load(connection)?,
+                    instruments: work_instruments::table
+                        .filter(work_instruments::work_id.eq_any(&work_ids))
+                        .load(connection)?,
+                    tags: work_tags::table
+                        .filter(work_tags::work_id.eq_any(&work_ids))
+                        .load(connection)?,
+                    works,
+                }
+            }
+            EntityKind::Recording | EntityKind::Album | EntityKind::Track => return Ok(None),
+        };
+
+        Ok(Some(snapshot))
+    }
+
+    /// Insert the captured rows again.
+    ///
+    /// The restored entities count as edited now, so that synchronization
+    /// passes them on instead of repeating the merge.
+    fn restore(self, connection: &mut SqliteConnection) -> Result<()> {
+        let now = db::now();
+
+        match self {
+            Snapshot::Person(mut person) => {
+                person.edited_at = now;
+                diesel::insert_into(persons::table)
+                    .values(person)
+                    .execute(connection)?;
+            }
+            Snapshot::Role(mut role) => {
+                role.edited_at = now;
+                diesel::insert_into(roles::table)
+                    .values(role)
+                    .execute(connection)?;
+            }
+            Snapshot::Instrument(mut instrument) => {
+                instrument.edited_at = now;
+                diesel::insert_into(instruments::table)
+                    .values(instrument)
+                    .execute(connection)?;
+            }
+            Snapshot::Tag(mut tag) => {
+                tag.edited_at = now;
+                diesel::insert_into(tags::table)
+                    .values(tag)
+                    .execute(connection)?;
+            }
+            Snapshot::Ensemble {
+                mut ensemble,
+                persons,
+            } => {
+                ensemble.edited_at = now;
+                diesel::insert_into(ensembles::table)
+                    .values(ensemble)
+                    .execute(connection)?;
+                diesel::insert_into(ensemble_persons::table)
+                    .values(persons)
+                    .execute(connection)?;
+            }
+            Snapshot::Work {
+                works,
+                persons,
+                instruments,
+                tags,
+            } => {
+                for mut work in works {
+                    work.edited_at = now;
+                    diesel::insert_into(works::table)
+                        .values(work)
+                        .execute(connection)?;
+                }
+
+                diesel::insert_into(work_persons::table)
+                    .values(persons)
+                    .execute(connection)?;
+                diesel::insert_into(work_instruments::table)
+                    .values(instruments)
+                    .execute(connection)?;
+                diesel::insert_into(work_tags::table)
+                    .values(tags)
+                    .execute(connection)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A place in the library that refers to an entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReferenceKind {
+    WorkPerson,
+    WorkPersonRole,
+    WorkInstrument,
+    WorkTag,
+    WorkPart,
+    EnsemblePerson,
+    EnsemblePersonRole,
+    EnsemblePersonInstrument,
+    RecordingWork,
+    RecordingPerson,
+    RecordingPersonRole,
+    RecordingPersonInstrument,
+    RecordingEnsemble,
+    RecordingEnsembleRole,
+    RecordingTag,
+    TrackWork,
+}
+
+/// Where a kind of reference is stored.
+struct Location {
+    table: &'static str,
+    /// The column holding the referenced ID.
+    column: &'static str,
+    /// The column holding the ID of the owner, which is an entity of
+    /// `owner_kind`.
+    owner_column: &'static str,
+    owner_kind: EntityKind,
+    /// For ordered relations, the column that tells apart the rows of one
+    /// owner.
+    sequence_column: Option<&'static str>,
+    value_column: Option<&'static str>,
+    target_kind: EntityKind,
+}
+
+impl ReferenceKind {
+    const ALL: [ReferenceKind; 16] = [
+        ReferenceKind::WorkPerson,
+        ReferenceKind::WorkPersonRole,
+        ReferenceKind::WorkInstrument,
+        ReferenceKind::WorkTag,
+        ReferenceKind::WorkPart,
+        ReferenceKind::EnsemblePerson,
+        ReferenceKind::EnsemblePersonRole,
+        ReferenceKind::EnsemblePersonInstrument,
+        ReferenceKind::RecordingWork,
+        ReferenceKind::RecordingPerson,
+        ReferenceKind::RecordingPersonRole,
+        ReferenceKind::RecordingPersonInstrument,
+        ReferenceKind::RecordingEnsemble,
+        ReferenceKind::RecordingEnsembleRole,
+        ReferenceKind::RecordingTag,
+        ReferenceKind::TrackWork,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ReferenceKind::WorkPerson => "work_person",
+            ReferenceKind::WorkPersonRole => "work_person_role",
+            ReferenceKind::WorkInstrument => "work_instrument",
+            ReferenceKind::WorkTag => "work_tag",
+            ReferenceKind::WorkPart => "work_part",
+            ReferenceKind::EnsemblePerson => "ensemble_person",
+            ReferenceKind::EnsemblePersonRole => "ensemble_person_role",
+            ReferenceKind::EnsemblePersonInstrument => "ensemble_person_instrument",
+            ReferenceKind::RecordingWork => "recording_work",
+            ReferenceKind::RecordingPerson => "recording_person",
+            ReferenceKind::RecordingPersonRole => "recording_person_role",
+            ReferenceKind::RecordingPersonInstrument => "recording_person_instrument",
+            ReferenceKind::RecordingEnsemble => "recording_ensemble",
+            ReferenceKind::RecordingEnsembleRole => "recording_ensemble_role",
+            ReferenceKind::RecordingTag => "recording_tag",
+            ReferenceKind::TrackWork => "track_work",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| anyhow!("Unknown reference kind {name}"))
+    }
+
+    fn location(self) -> Location {
+        let (table, column, owner_column, owner_kind, sequence_column, target_kind) = match self {
+            ReferenceKind::WorkPerson => (
+                "work_persons",
+                "person_id",
+                "work_id",
+                EntityKind::Work,
+                Some("sequence_number"),
+                EntityKind::Person,
+            ),
+            ReferenceKind::WorkPersonRole => (
+                "work_persons",
+                "role_id",
+                "work_id",
+                EntityKind::Work,
+                Some("sequence_number"),
+                EntityKind::Role,
+            ),
+            ReferenceKind::WorkInstrument => (
+                "work_instruments",
+                "instrument_id",
+                "work_id",
+                EntityKind::Work,
+                Some("sequence_number"),
+                EntityKind::Instrument,
+            ),
+            ReferenceKind::WorkTag => (
+                "work_tags",
+                "tag_id",
+                "work_id",
+                EntityKind::Work,
+                Some("sequence_number"),
+                EntityKind::Tag,
+            ),
+            ReferenceKind::WorkPart => (
+                "works",
+                "parent_work_id",
+                "work_id",
+                EntityKind::Work,
+                None,
+                EntityKind::Work,
+            ),
+            ReferenceKind::EnsemblePerson => (
+                "ensemble_persons",
+                "person_id",
+                "ensemble_id",
+                EntityKind::Ensemble,
+                Some("sequence_number"),
+                EntityKind::Person,
+            ),
+            ReferenceKind::EnsemblePersonRole => (
+                "ensemble_persons",
+                "role_id",
+                "ensemble_id",
+                EntityKind::Ensemble,
+                Some("sequence_number"),
+                EntityKind::Role,
+            ),
+            ReferenceKind::EnsemblePersonInstrument => (
+                "ensemble_persons",
+                "instrument_id",
+                "ensemble_id",
+                EntityKind::Ensemble,
+                Some("sequence_number"),
+                EntityKind::Instrument,
+            ),
+            ReferenceKind::RecordingWork => (
+                "recordings",
+                "work_id",
+                "recording_id",
+                EntityKind::Recording,
+                None,
+                EntityKind::Work,
+            ),
+            ReferenceKind::RecordingPerson => (
+                "recording_persons",
+                "person_id",
+                "recording_id",
+                EntityKind::Recording,
+                Some("sequence_number"),
+                EntityKind::Person,
+            ),
+            ReferenceKind::RecordingPersonRole => (
+                "recording_persons",
+                "role_id",
+                "recording_id",
+                EntityKind::Recording,
+                Some("sequence_number"),
+                EntityKind::Role,
+            ),
+            ReferenceKind::RecordingPersonInstrument => (
+                "recording_persons",
+                "instrument_id",
+                "recording_id",
+                EntityKind::Recording,
+                Some("sequence_number"),
+                EntityKind::Instrument,
+            ),
+            ReferenceKind::RecordingEnsemble => (
+                "recording_ensembles",
+                "ensemble_id",
+                "recording_id",
+                EntityKind::Recording,
+                Some("sequence_number"),
+                EntityKind::Ensemble,
+            ),
+            ReferenceKind::RecordingEnsembleRole => (
+                "recording_ensembles",
+                "role_id",
+                "recording_id",
+                EntityKind::Recording,
+                Some("sequence_number"),
+                EntityKind::Role,
+            ),
+            ReferenceKind::RecordingTag => (
+                "recording_tags",
+                "tag_id",
+                "recording_id",
+                EntityKind::Recording,
+                Some("sequence_number"),
+                EntityKind::Tag,
+            ),
+            ReferenceKind::TrackWork => (
+                "track_works",
+                "work_id",
+                "track_id",
+                EntityKind::Track,
+                Some("sequence_number"),
+                EntityKind::Work,
+            ),
+        };
+
+        let value_column = match self {
+            ReferenceKind::WorkTag | ReferenceKind::RecordingTag => Some("value"),
+            _ => None,
+        };
+
+        Location {
+            table,
+            column,
+            owner_column,
+            owner_kind,
+            sequence_column,
+            value_column,
+            target_kind,
+        }
+    }
+}
+
+/// A recorded reference, as stored.
+struct Reference {
+    reference_id: i64,
+    kind: ReferenceKind,
+    owner_id: String,
+    sequence_number: Option<i32>,
+    value: Option<String>,
+}
+
+fn load_references(connection: &mut SqliteConnection, merge_id: i64) -> Result<Vec<Reference>> {
+    merge_references::table
+        .filter(merge_references::merge_id.eq(merge_id))
+        .order(merge_references::reference_id)
+        .select((
+            merge_references::reference_id,
+            merge_references::reference_kind,
+            merge_references::owner_id,
+            merge_references::sequence_number,
+            merge_references::value,
+        ))
+        .load::<(i64, String, String, Option<i32>, Option<String>)>(connection)?
+        .into_iter()
+        .map(|(reference_id, kind, owner_id, sequence_number, value)| {
+            Ok(Reference {
+                reference_id,
+                kind: ReferenceKind::parse(&kind)?,
+                owner_id,
+                sequence_number,
+                value,
+            })
+        })
+        .collect()
+}
+
+#[derive(QueryableByName)]
+struct CountRow {
+    #[diesel(sql_type = sql_types::BigInt)]
+    count: i64,
+}
+
+impl Reference {
+    /// The condition that matches the row holding this reference, if it still
+    /// points to `id`.
+    fn condition(&self) -> String {
+        let location = self.kind.location();
+
+        match location.sequence_column {
+            Some(sequence_column) => format!(
+                "{} = ? AND {sequence_column} = ? AND {} = ?",
+                location.owner_column, location.column
+            ),
+            None => format!("{} = ? AND {} = ?", location.owner_column, location.column),
+        }
+    }
+
+    fn points_to(&self, connection: &mut SqliteConnection, id: &str) -> Result<bool> {
+        let location = self.kind.location();
+
+        let mut query = diesel::sql_query(format!(
+            "SELECT COUNT(*) AS count FROM {} WHERE {}",
+            location.table,
+            self.condition()
+        ))
+        .into_boxed()
+        .bind::<sql_types::Text, _>(&self.owner_id);
+
+        if location.sequence_column.is_some() {
+            query = query.bind::<sql_types::Nullable<sql_types::Integer>, _>(self.sequence_number);
+        }
+
+        let count = query
+            .bind::<sql_types::Text, _>(id)
+            .get_result::<CountRow>(connection)?
+            .count;
+
+        Ok(count > 0)
+    }
+
+    /// Point the reference from `into` back to `from`, along with the tag
+    /// value it had, and mark its owner as edited.
+    fn move_back(&self, connection: &mut SqliteConnection, into: &str, from: &str) -> Result<()> {
+        let location = self.kind.location();
+
+        let set = match location.value_column {
+            Some(value_column) => format!("{} = ?, {value_column} = ?", location.column),
+            None => format!("{} = ?", location.column),
+        };
+
+        let mut query = diesel::sql_query(format!(
+            "UPDATE {} SET {set} WHERE {}",
+            location.table,
+            self.condition()
+        ))
+        .into_boxed()
+        .bind::<sql_types::Text, _>(from);
+
+        if location.value_column.is_some() {
+            query = query.bind::<sql_types::Nullable<sql_types::Text>, _>(&self.value);
+        }
+
+        query = query.bind::<sql_types::Text, _>(&self.owner_id);
+
+        if location.sequence_column.is_some() {
+            query = query.bind::<sql_types::Nullable<sql_types::Integer>, _>(self.sequence_number);
+        }
+
+        let n_moved = query.bind::<sql_types::Text, _>(into).execute(connection)?;
+
+        if n_moved > 0 {
+            let (owner_table, owner_id_column) = match location.owner_kind {
+                EntityKind::Work => ("works", "work_id"),
+                EntityKind::Ensemble => ("ensembles", "ensemble_id"),
+                EntityKind::Recording => ("recordings", "recording_id"),
+                EntityKind::Track => ("tracks", "track_id"),
+                kind => bail!("A {kind} does not hold references"),
+            };
+
+            diesel::sql_query(format!(
+                "UPDATE {owner_table} SET edited_at = ? WHERE {owner_id_column} = ?"
+            ))
+            .bind::<sql_types::Timestamp, _>(db::now())
+            .bind::<sql_types::Text, _>(&self.owner_id)
+            .execute(connection)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::db::{
+        models::{Composer, Person, Work},
+        TranslatedString,
+    };
+
+    fn translated(name: &str) -> TranslatedString {
+        let mut translations = HashMap::new();
+        translations.insert("generic".to_string(), name.to_string());
+        TranslatedString(translations)
+    }
+
+    fn library(dir: &TempDir, cache_dir: &TempDir) -> Library {
+        Library::new(dir.path(), cache_dir.path()).unwrap()
+    }
+
+    fn work_by(library: &Library, name: &str, composer: &Person) -> Work {
+        library
+            .create_work(
+                translated(name),
+                Vec::new(),
+                vec![Composer {
+                    person: composer.clone(),
+                    role: None,
+                }],
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap()
+    }
+
+    fn composer_of(library: &Library, work: &Work) -> String {
+        library.work(&work.work_id).unwrap().persons[0]
+            .person
+            .person_id
+            .clone()
+    }
+
+    #[test]
+    fn an_unmerge_restores_the_entity_and_its_references() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let father = library
+            .create_person(translated("Johann Strauss I"), true)
+            .unwrap();
+        let son = library
+            .create_person(translated("Johann Strauss"), true)
+            .unwrap();
+        let march = work_by(&library, "Radetzky March", &father);
+
+        library
+            .merge_persons(&father.person_id, &son.person_id)
+            .unwrap();
+        assert!(!sync::exists(&mut library.conn(), EntityKind::Person, &father.person_id).unwrap());
+
+        let merges = library
+            .merges_into(EntityKind::Person, &son.person_id)
+            .unwrap();
+        assert_eq!(merges.len(), 1);
+        assert_eq!(merges[0].from_name, "Johann Strauss I");
+        assert_eq!(merges[0].references.len(), 1);
+        assert_eq!(merges[0].references[0].owner_name, "Radetzky March");
+
+        library.unmerge(merges[0].merge_id).unwrap();
+
+        assert_eq!(
+            sync::describe(&mut library.conn(), EntityKind::Person, &father.person_id),
+            "Johann Strauss I"
+        );
+        assert_eq!(composer_of(&library, &march), father.person_id);
+        assert!(library
+            .merges_into(EntityKind::Person, &son.person_id)
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Only the chosen works go back to the restored composer. The rest stays
+    /// with the entity merged into and can still be split later.
+    #[test]
+    fn a_split_only_moves_the_chosen_references() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let duplicate = library
+            .create_person(translated("Johann Strauss"), true)
+            .unwrap();
+        let son = library
+            .create_person(translated("Johann Strauss II"), true)
+            .unwrap();
+        let march = work_by(&library, "Radetzky March", &duplicate);
+        let waltz = work_by(&library, "The Blue Danube", &duplicate);
+
+        library
+            .merge_persons(&duplicate.person_id, &son.person_id)
+            .unwrap();
+
+        let merge = library
+            .merges_into(EntityKind::Person, &son.person_id)
+            .unwrap()
+            .remove(0);
+        let march_reference = merge
+            .references
+            .iter()
+            .find(|r| r.owner_id == march.work_id)
+            .unwrap();
+
+        library
+            .split_merge(merge.merge_id, &[march_reference.reference_id])
+            .unwrap();
+
+        assert_eq!(composer_of(&library, &march), duplicate.person_id);
+        assert_eq!(composer_of(&library, &waltz), son.person_id);
+
+        let merges = library
+            .merges_into(EntityKind::Person, &son.person_id)
+            .unwrap();
+        assert_eq!(merges.len(), 1);
+        assert_eq!(merges[0].references.len(), 1);
+        assert_eq!(merges[0].references[0].owner_id, waltz.work_id);
+    }
+
+    /// Merging the entity that was merged into passes the earlier merge on.
+    #[test]
+    fn an_earlier_merge_can_be_split_after_a_chained_merge() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let a = library.create_person(translated("A"), true).unwrap();
+        let b = library.create_person(translated("B"), true).unwrap();
+        let c = library.create_person(translated("C"), true).unwrap();
+        let work = work_by(&library, "Work", &a);
+
+        library.merge_persons(&a.person_id, &b.person_id).unwrap();
+        library.merge_persons(&b.person_id, &c.person_id).unwrap();
+
+        let merges = library
+            .merges_into(EntityKind::Person, &c.person_id)
+            .unwrap();
+        assert_eq!(merges.len(), 2);
+
+        let first = merges.iter().find(|m| m.from_id == a.person_id).unwrap();
+        assert_eq!(first.references.len(), 1);
+
+        library.unmerge(first.merge_id).unwrap();
+        assert_eq!(composer_of(&library, &work), a.person_id);
+    }
+
+    #[test]
+    fn undoing_a_merge_forgets_it() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let library = library(&dir, &cache_dir);
+
+        let a = library.create_person(translated("A"), true).unwrap();
+        let b = library.create_person(translated("B"), true).unwrap();
+        work_by(&library, "Work", &a);
+
+        library.merge_persons(&a.person_id, &b.person_id).unwrap();
+        library.undo_last_change().unwrap();
+
+        assert!(library
+            .merges_into(EntityKind::Person, &b.person_id)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+//! Generating exemplary Musicus libraries.
+//!
+//! Besides the `musicus-testgen` binary, other crates use this to test against
+//! a library of realistic shape.
+
+pub mod generate;
+mod names;
@@ -4,15 +4,11 @@
 //! with procedurally generated metadata, for testing search, browsing and the
 //! other library features at a realistic scale.
 
-mod generate;
-mod names;
-
 use std::{fs, io::Write, path::PathBuf};
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
-
-use crate::generate::Counts;
+use musicus_testgen::generate::{self, Counts};
 
 /// Generate an exemplary Musicus library with realistic looking data.
 ///
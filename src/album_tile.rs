@@ -4,6 +4,8 @@ use gtk::{glib, subclass::prelude::*};
 
 use musicus_library::db::models::Album;
 
+use crate::library::Library;
+
 mod imp {
     use super::*;
 
@@ -12,6 +14,8 @@ mod imp {
     pub struct AlbumTile {
         pub album: OnceCell<Album>,
 
+        #[template_child]
+        pub cover_art_image: TemplateChild<gtk::Image>,
         #[template_child]
         pub title_label: TemplateChild<gtk::Label>,
     }
@@ -43,10 +47,16 @@ glib::wrapper! {
 }
 
 impl AlbumTile {
-    pub fn new(album: &Album) -> Self {
+    pub fn new(library: &Library, album: &Album) -> Self {
         let obj: Self = glib::Object::new();
 
         obj.imp().title_label.set_label(album.name.get());
+
+        if let Some(cover_art) = library.album_cover_art(album) {
+            obj.imp().cover_art_image.set_from_file(Some(cover_art));
+            obj.imp().cover_art_image.set_visible(true);
+        }
+
         obj.imp().album.set(album.clone()).unwrap();
 
         obj
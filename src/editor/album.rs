@@ -8,11 +8,15 @@ use gtk::glib::{self, clone, subclass::Signal, Properties};
 use once_cell::sync::Lazy;
 use recording_row::RecordingRow;
 
-use musicus_library::db::models::{Album, Recording};
+use musicus_library::{
+    db::models::{Album, Recording},
+    library::ReorganizationScope,
+};
 
 use crate::{
     editor::{create, translation::TranslationEditor},
     library::Library,
+    reorganization,
     selector::recording::RecordingSelectorPopover,
 };
 
@@ -29,6 +33,9 @@ mod imp {
         pub library: OnceCell<Library>,
 
         pub album_id: OnceCell<String>,
+        /// The recordings of the album before the edit. Their tracks change
+        /// too if they are removed from it.
+        pub original_recording_ids: OnceCell<Vec<String>>,
         pub recording_rows: RefCell<Vec<RecordingRow>>,
 
         pub recordings_popover: OnceCell<RecordingSelectorPopover>,
@@ -126,6 +133,16 @@ impl AlbumEditor {
         if let Some(album) = album {
             obj.imp().save_row.set_title(&gettext("_Save changes"));
             obj.imp().album_id.set(album.album_id.clone()).unwrap();
+            obj.imp()
+                .original_recording_ids
+                .set(
+                    album
+                        .recordings
+                        .iter()
+                        .map(|recording| recording.recording_id.clone())
+                        .collect(),
+                )
+                .unwrap();
             obj.imp().name_editor.set_translation(&album.name);
 
             for recording in &album.recordings {
@@ -219,6 +236,21 @@ impl AlbumEditor {
             {
                 return;
             }
+
+            reorganization::offer_update(
+                self,
+                library,
+                ReorganizationScope {
+                    album_ids: vec![album_id.to_owned()],
+                    recording_ids: self
+                        .imp()
+                        .original_recording_ids
+                        .get()
+                        .cloned()
+                        .unwrap_or_default(),
+                    ..Default::default()
+                },
+            );
         } else {
             let Some(album) = crate::editor::handle_save(
                 self,
@@ -5,7 +5,10 @@ use gettextrs::gettext;
 use gtk::glib::{self, clone, subclass::Signal};
 use once_cell::sync::Lazy;
 
-use musicus_library::db::models::{Ensemble, Performer, Person};
+use musicus_library::{
+    db::models::{Ensemble, Performer, Person},
+    library::ReorganizationScope,
+};
 
 use crate::{
     editor::{
@@ -13,6 +16,7 @@ use crate::{
         translation::TranslationEditor,
     },
     library::Library,
+    reorganization,
     selector::SelectorPopover,
 };
 
@@ -237,6 +241,15 @@ impl EnsembleEditor {
             {
                 return;
             }
+
+            reorganization::offer_update(
+                self,
+                library,
+                ReorganizationScope {
+                    ensemble_ids: vec![ensemble_id.to_owned()],
+                    ..Default::default()
+                },
+            );
         } else {
             let Some(ensemble) = crate::editor::handle_save(
                 self,
@@ -1,7 +1,10 @@
 use adw::{prelude::*, subclass::prelude::*};
 use gtk::glib;
 
-use crate::{program::Program, slider_row::SliderRow};
+use crate::{
+    program::{Program, ProgramTransition},
+    slider_row::SliderRow,
+};
 
 mod imp {
     use super::*;
@@ -18,7 +21,13 @@ mod imp {
         #[template_child]
         pub avoid_repeated_instruments_adjustment: TemplateChild<gtk::Adjustment>,
         #[template_child]
+        pub avoid_skipped_adjustment: TemplateChild<gtk::Adjustment>,
+        #[template_child]
         pub play_full_recordings_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub transition_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub transition_duration_adjustment: TemplateChild<gtk::Adjustment>,
     }
 
     #[glib::object_subclass]
@@ -64,8 +73,21 @@ impl ProgramSettings {
         imp.avoid_repeated_instruments_adjustment
             .set_value(program.avoid_repeated_instruments() as f64);
 
+        imp.avoid_skipped_adjustment
+            .set_value(program.avoid_skipped() * 100.0);
+
         imp.play_full_recordings_row
             .set_active(program.play_full_recordings());
+
+        if let Some(index) = ProgramTransition::ALL
+            .iter()
+            .position(|transition| *transition == program.transition())
+        {
+            imp.transition_row.set_selected(index as u32);
+        }
+
+        imp.transition_duration_adjustment
+            .set_value(program.transition_duration_s() as f64);
     }
 
     pub fn apply(&self, program: &Program) {
@@ -84,7 +106,18 @@ impl ProgramSettings {
             imp.avoid_repeated_instruments_adjustment.value() as i32
         );
 
+        program.set_avoid_skipped(imp.avoid_skipped_adjustment.value() / 100.0);
+
         program.set_play_full_recordings(imp.play_full_recordings_row.is_active());
+
+        program.set_transition(
+            ProgramTransition::ALL
+                .get(imp.transition_row.selected() as usize)
+                .copied()
+                .unwrap_or_default(),
+        );
+
+        program.set_transition_duration_s(imp.transition_duration_adjustment.value() as u32);
     }
 }
 
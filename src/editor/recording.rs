@@ -1,17 +1,24 @@
 mod ensemble_row;
 
-use std::cell::{OnceCell, RefCell};
+use std::{
+    cell::{OnceCell, RefCell},
+    path::{Path, PathBuf},
+};
 
 use adw::{prelude::*, subclass::prelude::*};
 use ensemble_row::RecordingEditorEnsembleRow;
 use gettextrs::gettext;
-use gtk::glib::{self, clone, subclass::Signal, Properties};
+use gtk::{
+    gio,
+    glib::{self, clone, subclass::Signal, Properties},
+};
 use once_cell::sync::Lazy;
 
 use crate::editor::performer_row::PerformerRow;
 
-use musicus_library::db::models::{
-    Ensemble, EnsemblePerformer, Performer, Person, Recording, Tag, TagValue, Work,
+use musicus_library::{
+    db::models::{Ensemble, EnsemblePerformer, Performer, Person, Recording, Tag, TagValue, Work},
+    library::ReorganizationScope,
 };
 
 use crate::{
@@ -20,6 +27,7 @@ use crate::{
         tag_row::TagRow,
     },
     library::Library,
+    reorganization,
     selector::{work::WorkSelectorPopover, RecordingPrefill, RecordingWork, SelectorPopover},
 };
 
@@ -43,6 +51,10 @@ mod imp {
         pub ensemble_rows: RefCell<Vec<RecordingEditorEnsembleRow>>,
         pub tag_rows: RefCell<Vec<TagRow>>,
 
+        /// A newly chosen cover image, or `Some(None)` if the cover is to be
+        /// removed. The current cover stays as long as this is `None`.
+        pub cover_art: RefCell<Option<Option<PathBuf>>>,
+
         pub work_selector_popover: OnceCell<WorkSelectorPopover>,
         pub persons_popover: OnceCell<SelectorPopover>,
         pub ensembles_popover: OnceCell<SelectorPopover>,
@@ -67,6 +79,10 @@ mod imp {
         #[template_child]
         pub comment_row: TemplateChild<adw::EntryRow>,
         #[template_child]
+        pub cover_art_image: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub remove_cover_art_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub enable_updates_row: TemplateChild<adw::SwitchRow>,
         #[template_child]
         pub save_row: TemplateChild<adw::ButtonRow>,
@@ -257,6 +273,12 @@ impl RecordingEditor {
             if let Some(comment) = &recording.comment {
                 obj.imp().comment_row.set_text(comment);
             }
+
+            // Only the recording's own cover can be edited here, not the one it
+            // shows because of an album.
+            if recording.cover_art.is_some() {
+                obj.show_cover_art(library.recording_cover_art(recording).as_deref());
+            }
         }
 
         obj
@@ -447,6 +469,60 @@ impl RecordingEditor {
         self.imp().tag_rows.borrow_mut().push(row);
     }
 
+    #[template_callback]
+    async fn select_cover_art(&self) {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some(&gettext("Images")));
+        filter.add_pixbuf_formats();
+
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Select cover image"))
+            .modal(true)
+            .filters(&filters)
+            .build();
+
+        let root = self.root();
+        let window = root.as_ref().and_then(|r| r.downcast_ref::<gtk::Window>());
+
+        match dialog.open_future(window).await {
+            Err(err) => {
+                if !err.matches(gtk::DialogError::Dismissed) {
+                    log::error!("File selection failed: {err:?}");
+                }
+            }
+            Ok(file) => match file.path() {
+                Some(path) => {
+                    self.show_cover_art(Some(&path));
+                    self.imp().cover_art.replace(Some(Some(path)));
+                }
+                None => log::warn!("The selected cover image has no local path"),
+            },
+        }
+    }
+
+    #[template_callback]
+    fn remove_cover_art(&self) {
+        self.show_cover_art(None);
+        self.imp().cover_art.replace(Some(None));
+    }
+
+    fn show_cover_art(&self, path: Option<&Path>) {
+        match path {
+            Some(path) => self.imp().cover_art_image.set_from_file(Some(path)),
+            None => self
+                .imp()
+                .cover_art_image
+                .set_icon_name(Some("image-x-generic-symbolic")),
+        }
+
+        self.imp()
+            .remove_cover_art_button
+            .set_visible(path.is_some());
+    }
+
     #[template_callback]
     fn save(&self) {
         if let Some(work) = &*self.imp().work.borrow() {
@@ -484,6 +560,7 @@ impl RecordingEditor {
             };
 
             let enable_updates = self.imp().enable_updates_row.is_active();
+            let cover_art = self.imp().cover_art.take();
 
             if let Some(recording_id) = self.imp().recording_id.get() {
                 if crate::editor::handle_save(
@@ -502,6 +579,26 @@ impl RecordingEditor {
                 {
                     return;
                 }
+
+                if let Some(cover_art) = cover_art {
+                    if crate::editor::handle_save(
+                        self,
+                        library.set_recording_cover_art(recording_id, cover_art.as_deref()),
+                    )
+                    .is_none()
+                    {
+                        return;
+                    }
+                }
+
+                reorganization::offer_update(
+                    self,
+                    library,
+                    ReorganizationScope {
+                        recording_ids: vec![recording_id.to_owned()],
+                        ..Default::default()
+                    },
+                );
             } else {
                 let Some(recording) = crate::editor::handle_save(
                     self,
@@ -517,6 +614,17 @@ impl RecordingEditor {
                     return;
                 };
 
+                if let Some(Some(cover_art)) = cover_art {
+                    if crate::editor::handle_save(
+                        self,
+                        library.set_recording_cover_art(&recording.recording_id, Some(&cover_art)),
+                    )
+                    .is_none()
+                    {
+                        return;
+                    }
+                }
+
                 self.emit_by_name::<()>(
                     "created",
                     &[&glib::BoxedAnyObject::new(recording.clone())],
@@ -9,13 +9,16 @@ use adw::{prelude::*, subclass::prelude::*};
 use anyhow::Result;
 use gettextrs::gettext;
 use gtk::glib::{self, subclass::Signal};
-use musicus_library::db::{
-    models::{Instrument, Person, Role},
-    TranslatedString,
+use musicus_library::{
+    db::{
+        models::{Instrument, Person, Role},
+        TranslatedString,
+    },
+    library::ReorganizationScope,
 };
 use once_cell::sync::Lazy;
 
-use crate::{editor::translation::TranslationEditor, library::Library, util};
+use crate::{editor::translation::TranslationEditor, library::Library, reorganization, util};
 
 /// A type of entity that consists of a translated name and nothing else.
 pub trait SimpleEntityKind: 'static {
@@ -40,6 +43,10 @@ pub trait SimpleEntityKind: 'static {
 
     fn page_title() -> String;
     fn create_label() -> String;
+
+    /// The tracks whose files may have to change after the entity `id` was
+    /// edited.
+    fn reorganization_scope(id: &str) -> ReorganizationScope;
 }
 
 /// The type-erased half of [`SimpleEntityKind`], so that the editor itself does
@@ -63,6 +70,8 @@ pub(crate) trait EntitySource {
         name: TranslatedString,
         enable_updates: bool,
     ) -> Result<()>;
+
+    fn reorganization_scope(&self, id: &str) -> ReorganizationScope;
 }
 
 struct KindSource<K: SimpleEntityKind>(std::marker::PhantomData<K>);
@@ -98,6 +107,10 @@ impl<K: SimpleEntityKind> EntitySource for KindSource<K> {
     ) -> Result<()> {
         K::update(library, id, name, enable_updates)
     }
+
+    fn reorganization_scope(&self, id: &str) -> ReorganizationScope {
+        K::reorganization_scope(id)
+    }
 }
 
 mod imp {
@@ -248,7 +261,9 @@ impl SimpleEntityEditor {
         }
 
         let result = match imp.entity_id.get() {
-            Some(id) => source.update(library, id, name, enable_updates),
+            Some(id) => source.update(library, id, name, enable_updates).map(|()| {
+                reorganization::offer_update(self, library, source.reorganization_scope(id));
+            }),
             None => source
                 .create(library, name, enable_updates)
                 .map(|item| self.emit_by_name::<()>("created", &[&item])),
@@ -306,6 +321,13 @@ impl SimpleEntityKind for PersonKind {
         library.update_person(id, name, enable_updates)
     }
 
+    fn reorganization_scope(id: &str) -> ReorganizationScope {
+        ReorganizationScope {
+            person_ids: vec![id.to_owned()],
+            ..Default::default()
+        }
+    }
+
     fn page_title() -> String {
         gettext("Person")
     }
@@ -349,6 +371,13 @@ impl SimpleEntityKind for InstrumentKind {
         library.update_instrument(id, name, enable_updates)
     }
 
+    fn reorganization_scope(id: &str) -> ReorganizationScope {
+        ReorganizationScope {
+            instrument_ids: vec![id.to_owned()],
+            ..Default::default()
+        }
+    }
+
     fn page_title() -> String {
         gettext("Instrument")
     }
@@ -388,6 +417,13 @@ impl SimpleEntityKind for RoleKind {
         library.update_role(id, name, enable_updates)
     }
 
+    fn reorganization_scope(id: &str) -> ReorganizationScope {
+        ReorganizationScope {
+            role_ids: vec![id.to_owned()],
+            ..Default::default()
+        }
+    }
+
     fn page_title() -> String {
         gettext("Role")
     }
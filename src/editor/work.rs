@@ -11,9 +11,12 @@ use gtk::glib::{self, clone, subclass::Signal, Properties};
 use once_cell::sync::Lazy;
 use part_row::WorkEditorPartRow;
 
-use musicus_library::db::{
-    self,
-    models::{Composer, Instrument, Person, Tag, TagValue, Work},
+use musicus_library::{
+    db::{
+        self,
+        models::{Composer, Instrument, Person, Tag, TagValue, Work},
+    },
+    library::ReorganizationScope,
 };
 
 use crate::{
@@ -22,6 +25,7 @@ use crate::{
         translation::TranslationEditor,
     },
     library::Library,
+    reorganization,
     selector::{work::WorkSelectorPopover, ComposerPrefill, SelectorPopover, WorkPrefill},
 };
 use instrument_row::InstrumentRow;
@@ -591,6 +595,15 @@ impl WorkEditor {
             {
                 return;
             }
+
+            reorganization::offer_update(
+                self,
+                library,
+                ReorganizationScope {
+                    work_ids: vec![work_id.to_owned()],
+                    ..Default::default()
+                },
+            );
         } else {
             let Some(work) = crate::editor::handle_save(
                 self,
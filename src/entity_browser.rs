@@ -15,8 +15,8 @@ use musicus_library::{
         TranslatedString,
     },
     format_translated,
-    library::EntityUsage,
-    LibraryError,
+    library::{unmerge::PastMerge, Duplicate, EntityUsage},
+    EntityKind, LibraryError,
 };
 
 use crate::{
@@ -303,6 +303,19 @@ impl BrowserKind {
         )
     }
 
+    /// The library's kind of entity for this kind, if it can be merged.
+    fn mergeable_kind(self) -> Option<EntityKind> {
+        match self {
+            BrowserKind::Persons => Some(EntityKind::Person),
+            BrowserKind::Roles => Some(EntityKind::Role),
+            BrowserKind::Instruments => Some(EntityKind::Instrument),
+            BrowserKind::Tags => Some(EntityKind::Tag),
+            BrowserKind::Ensembles => Some(EntityKind::Ensemble),
+            BrowserKind::Works => Some(EntityKind::Work),
+            _ => None,
+        }
+    }
+
     /// Merge one item of this kind into another.
     fn merge(self, library: &Library, from: &str, into: &str) -> Result<()> {
         match self {
@@ -363,6 +376,12 @@ impl BrowserKind {
         Ok(())
     }
 
+    /// Whether deleting an item of this kind can be undone. Recordings take
+    /// their files with them, which the library's history cannot bring back.
+    fn can_undo_delete(self) -> bool {
+        self != BrowserKind::Recordings
+    }
+
     fn create(self, navigation: &adw::NavigationView, library: &Library) -> adw::NavigationPage {
         match self {
             BrowserKind::Persons => SimpleEntityEditor::person(navigation, library, None).upcast(),
@@ -498,6 +517,8 @@ mod imp {
         #[template_child]
         pub merge_button: TemplateChild<gtk::Button>,
         #[template_child]
+        pub split_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub add_tag_button: TemplateChild<gtk::Button>,
         #[template_child]
         pub remove_tag_button: TemplateChild<gtk::Button>,
@@ -505,6 +526,8 @@ mod imp {
         pub delete_button: TemplateChild<gtk::Button>,
         #[template_child]
         pub new_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub duplicates_button: TemplateChild<gtk::Button>,
     }
 
     #[glib::object_subclass]
@@ -746,9 +769,11 @@ impl EntityBrowser {
                 gettext("Delete {}?"),
                 ids.len().to_string()
             ))
-            .body(gettext(
-                "This cannot be undone. Items still used elsewhere in the library are kept.",
-            ))
+            .body(if self.kind().can_undo_delete() {
+                gettext("Items still used elsewhere in the library are kept.")
+            } else {
+                gettext("This cannot be undone. The files of the recordings are deleted as well.")
+            })
             .build();
 
         dialog.add_responses(&[
@@ -768,9 +793,22 @@ impl EntityBrowser {
         let mut deleted = 0usize;
         let mut skipped = 0usize;
 
+        let previous_change = library.latest_change_id().ok().flatten();
+        let mut first_change = None;
+
         for id in &ids {
             match kind.delete(&library, id) {
-                Ok(()) => deleted += 1,
+                Ok(()) => {
+                    deleted += 1;
+
+                    if first_change.is_none() {
+                        first_change = library
+                            .latest_change_id()
+                            .ok()
+                            .flatten()
+                            .filter(|change_id| Some(*change_id) != previous_change);
+                    }
+                }
                 Err(err) => match err.downcast_ref::<LibraryError>() {
                     Some(LibraryError::StillReferenced(_)) => skipped += 1,
                     _ => {
@@ -783,19 +821,19 @@ impl EntityBrowser {
 
         self.reload();
 
-        if let Some(toast_overlay) = util::find_toast_overlay(self) {
-            let message = if skipped == 0 {
-                format_translated!(gettext("Deleted {}"), deleted.to_string())
-            } else {
-                format_translated!(
-                    gettext("Deleted {}; kept {}"),
-                    deleted.to_string(),
-                    skipped.to_string()
-                )
-            };
+        let message = if skipped == 0 {
+            format_translated!(gettext("Deleted {}"), deleted.to_string())
+        } else {
+            format_translated!(
+                gettext("Deleted {}; kept {}"),
+                deleted.to_string(),
+                skipped.to_string()
+            )
+        };
 
-            toast_overlay.add_toast(adw::Toast::new(&message));
-        }
+        // Undoing would bring back the entries of deleted recordings, but not
+        // their files.
+        self.show_undo_toast(&message, first_change.filter(|_| kind.can_undo_delete()));
     }
 
     #[template_callback]
@@ -1063,7 +1101,7 @@ impl EntityBrowser {
 
         let dialog = adw::AlertDialog::builder()
             .heading(gettext("Which entity should be kept?"))
-            .body(gettext("All references are moved to the selected entity. The other one is discarded. This cannot be undone."))
+            .body(gettext("All references are moved to the selected entity. The other one is discarded. It can be split off again later."))
             .extra_child(&list)
             .build();
 
@@ -1095,18 +1133,19 @@ impl EntityBrowser {
     #[template_callback]
     async fn merge_selected(&self) {
         match self.merge_selected_priv().await {
-            Ok(_) => {
+            Ok(true) => {
                 self.reload();
 
-                if let Some(toast_overlay) = util::find_toast_overlay(self) {
-                    toast_overlay.add_toast(adw::Toast::new(&gettext("Merged items")));
-                }
+                let change_id = self.library().latest_change_id().ok().flatten();
+                self.show_undo_toast(&gettext("Merged items"), change_id);
             }
+            Ok(false) => (),
             Err(err) => self.report("Failed to merge selected items", err),
         }
     }
 
-    async fn merge_selected_priv(&self) -> Result<()> {
+    /// Returns whether the items were merged, rather than the merge cancelled.
+    async fn merge_selected_priv(&self) -> Result<bool> {
         let items = self.selected_objects();
 
         if items.len() != 2 {
@@ -1122,11 +1161,312 @@ impl EntityBrowser {
         let usage1 = kind.usage(&library, &item1.id())?;
         let usage2 = kind.usage(&library, &item2.id())?;
 
-        if let Some(merge_action) = self.show_merge_dialog(item1, &usage1, item2, &usage2).await {
-            kind.merge(&library, &merge_action.from.id(), &merge_action.into.id())?;
+        match self.show_merge_dialog(item1, &usage1, item2, &usage2).await {
+            Some(merge_action) => {
+                kind.merge(&library, &merge_action.from.id(), &merge_action.into.id())?;
+                Ok(true)
+            }
+            None => Ok(false),
         }
+    }
 
-        Ok(())
+    /// List likely duplicates of the current kind, each with a button to merge
+    /// them.
+    #[template_callback]
+    async fn find_duplicates(&self) {
+        let Some(kind) = self.kind().mergeable_kind() else {
+            return;
+        };
+
+        let duplicates = match self.library().find_duplicates(kind) {
+            Ok(duplicates) => duplicates,
+            Err(err) => {
+                self.report("Failed to look for duplicates", err);
+                return;
+            }
+        };
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Possible duplicates"))
+            .build();
+
+        dialog.add_response("close", &gettext("Close"));
+        dialog.set_close_response("close");
+
+        if duplicates.is_empty() {
+            dialog.set_body(&gettext("No likely duplicates were found."));
+        } else {
+            dialog.set_body(&gettext(
+                "Merging keeps the item that is used more often. Merges can be split again later.",
+            ));
+
+            let list = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .css_classes(["boxed-list"])
+                .build();
+
+            list.set_placeholder(Some(
+                &adw::ActionRow::builder()
+                    .title(gettext("No likely duplicates are left."))
+                    .build(),
+            ));
+
+            self.fill_duplicates(&list, duplicates);
+
+            let scrolled_window = gtk::ScrolledWindow::builder()
+                .child(&list)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .propagate_natural_height(true)
+                .max_content_height(400)
+                .build();
+
+            dialog.set_extra_child(Some(&scrolled_window));
+        }
+
+        dialog.choose_future(Some(self)).await;
+    }
+
+    /// Replace the rows of `list` with `duplicates`.
+    fn fill_duplicates(&self, list: &gtk::ListBox, duplicates: Vec<Duplicate>) {
+        list.remove_all();
+
+        for duplicate in duplicates {
+            list.append(&self.duplicate_row(list, duplicate));
+        }
+    }
+
+    fn duplicate_row(&self, list: &gtk::ListBox, duplicate: Duplicate) -> adw::ActionRow {
+        let usage_label = |usage: &EntityUsage| {
+            format_translated!(gettext("used {} times"), usage.total().to_string())
+        };
+
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(&format!(
+                "{} / {}",
+                duplicate.first.name, duplicate.second.name
+            )))
+            .subtitle(format_translated!(
+                gettext("{} % likely · {}, {}"),
+                ((duplicate.confidence * 100.0).round() as u32).to_string(),
+                usage_label(&duplicate.first.usage),
+                usage_label(&duplicate.second.usage)
+            ))
+            .build();
+
+        let merge_button = gtk::Button::builder()
+            .label(gettext("Merge"))
+            .valign(gtk::Align::Center)
+            .build();
+
+        merge_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            #[weak]
+            list,
+            move |_| {
+                let (from, into) =
+                    if duplicate.first.usage.total() >= duplicate.second.usage.total() {
+                        (&duplicate.second, &duplicate.first)
+                    } else {
+                        (&duplicate.first, &duplicate.second)
+                    };
+
+                match obj.kind().merge(&obj.library(), &from.id, &into.id) {
+                    Ok(()) => {
+                        // Other pairs may have involved the item that is gone
+                        // now, and the usage of the one that is kept changed.
+                        match obj
+                            .kind()
+                            .mergeable_kind()
+                            .map(|kind| obj.library().find_duplicates(kind))
+                        {
+                            Some(Ok(duplicates)) => obj.fill_duplicates(&list, duplicates),
+                            Some(Err(err)) => obj.report("Failed to look for duplicates", err),
+                            None => list.remove_all(),
+                        }
+
+                        obj.reload();
+
+                        let change_id = obj.library().latest_change_id().ok().flatten();
+                        obj.show_undo_toast(&gettext("Merged items"), change_id);
+                    }
+                    Err(err) => obj.report("Failed to merge items", err),
+                }
+            }
+        ));
+
+        row.add_suffix(&merge_button);
+        row
+    }
+
+    /// Split items that were merged into the selected one off again.
+    #[template_callback]
+    async fn split_selected(&self) {
+        match self.split_selected_priv().await {
+            Ok(true) => {
+                self.reload();
+
+                let change_id = self.library().latest_change_id().ok().flatten();
+                self.show_undo_toast(&gettext("Split items"), change_id);
+            }
+            Ok(false) => (),
+            Err(err) => self.report("Failed to split the selected item", err),
+        }
+    }
+
+    /// Returns whether anything was split off, rather than the split cancelled.
+    async fn split_selected_priv(&self) -> Result<bool> {
+        let items = self.selected_objects();
+
+        if items.len() != 1 {
+            bail!("exactly one item has to be selected")
+        }
+
+        let Some(kind) = self.kind().mergeable_kind() else {
+            bail!("splitting not supported for this entity")
+        };
+
+        let library = self.library();
+        let merges = library.merges_into(kind, &items[0].id())?;
+
+        if merges.is_empty() {
+            let dialog = adw::AlertDialog::builder()
+                .heading(gettext("Nothing to split"))
+                .body(gettext("Nothing has been merged into this item."))
+                .build();
+
+            dialog.add_response("close", &gettext("Close"));
+            dialog.choose_future(Some(self)).await;
+
+            return Ok(false);
+        }
+
+        let Some(selection) = self.show_split_dialog(&items[0], &merges).await else {
+            return Ok(false);
+        };
+
+        for (merge_id, reference_ids) in selection {
+            library.split_merge(merge_id, &reference_ids)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Show a dialog to pick the references that go back to the items merged
+    /// into `item`. Returns the chosen reference IDs per merge.
+    async fn show_split_dialog(
+        &self,
+        item: &EntityObject,
+        merges: &[PastMerge],
+    ) -> Option<Vec<(i64, Vec<i64>)>> {
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .build();
+
+        let mut checks = Vec::new();
+
+        for merge in merges {
+            let subtitle = glib::markup_escape_text(&format_translated!(
+                gettext("Back to {}"),
+                &merge.from_name
+            ));
+
+            if merge.references.is_empty() {
+                let check = gtk::CheckButton::new();
+
+                let row = adw::ActionRow::builder()
+                    .title(gettext("Restore without references"))
+                    .subtitle(&subtitle)
+                    .activatable_widget(&check)
+                    .build();
+
+                row.add_prefix(&check);
+                list.append(&row);
+                checks.push((merge.merge_id, None, check));
+            }
+
+            for reference in &merge.references {
+                let check = gtk::CheckButton::new();
+
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&reference.owner_name))
+                    .subtitle(&subtitle)
+                    .activatable_widget(&check)
+                    .build();
+
+                row.add_prefix(&check);
+                list.append(&row);
+                checks.push((merge.merge_id, Some(reference.reference_id), check));
+            }
+        }
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .child(&list)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(400)
+            .build();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(format_translated!(gettext("Split {}"), item.name()))
+            .body(gettext("The selected references are moved back to the item they were merged from, which is restored if necessary."))
+            .extra_child(&scrolled_window)
+            .build();
+
+        dialog.add_responses(&[("cancel", &gettext("Cancel")), ("split", &gettext("Split"))]);
+        dialog.set_close_response("cancel");
+        dialog.set_default_response(Some("cancel"));
+
+        if dialog.choose_future(Some(self)).await != "split" {
+            return None;
+        }
+
+        let mut selection: Vec<(i64, Vec<i64>)> = Vec::new();
+
+        for (merge_id, reference_id, check) in checks {
+            if !check.is_active() {
+                continue;
+            }
+
+            let index = match selection.iter().position(|(id, _)| *id == merge_id) {
+                Some(index) => index,
+                None => {
+                    selection.push((merge_id, Vec::new()));
+                    selection.len() - 1
+                }
+            };
+
+            selection[index].1.extend(reference_id);
+        }
+
+        (!selection.is_empty()).then_some(selection)
+    }
+
+    /// Show a toast that offers to undo the change with `change_id` and all
+    /// changes after it.
+    fn show_undo_toast(&self, message: &str, change_id: Option<i64>) {
+        let Some(toast_overlay) = util::find_toast_overlay(self) else {
+            return;
+        };
+
+        let toast = adw::Toast::new(message);
+
+        if let Some(change_id) = change_id {
+            toast.set_button_label(Some(&gettext("Undo")));
+            toast.connect_button_clicked(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_| {
+                    match obj.library().undo_changes_since(change_id) {
+                        Ok(_) => obj.reload(),
+                        Err(err) => obj.report("Failed to undo changes", err),
+                    }
+                }
+            ));
+        }
+
+        toast_overlay.add_toast(toast);
     }
 
     #[template_callback]
@@ -1185,6 +1525,10 @@ impl EntityBrowser {
             .remove_tag_button
             .set_visible(kind.supports_tagging());
         self.imp().merge_button.set_visible(kind.supports_merging());
+        self.imp().split_button.set_visible(kind.supports_merging());
+        self.imp()
+            .duplicates_button
+            .set_visible(kind.supports_merging());
 
         self.update_view_state();
     }
@@ -1226,6 +1570,8 @@ impl EntityBrowser {
         imp.remove_tag_button.set_sensitive(n_selected > 0);
         imp.merge_button
             .set_sensitive(self.kind().supports_merging() && n_selected == 2);
+        imp.split_button
+            .set_sensitive(self.kind().supports_merging() && n_selected == 1);
     }
 
     pub fn selected_ids(&self) -> Vec<String> {
@@ -0,0 +1,458 @@
+use std::cell::{Cell, OnceCell};
+
+use adw::{prelude::*, subclass::prelude::*};
+use chrono::{Duration, Local, NaiveDateTime, TimeZone, Utc};
+use gettextrs::gettext;
+use gtk::glib::{self, clone};
+use musicus_library::{
+    format_translated,
+    library::listening::{ForgottenRecording, Period, PlayLogEntry},
+};
+
+use crate::{library::Library, player::Player, util};
+
+/// How many entries of the log are loaded at once.
+const LOG_PAGE_SIZE: i64 = 50;
+
+/// How many entries the rankings show.
+const RANKING_SIZE: i64 = 5;
+
+/// How many weeks the listening time goes back.
+const WEEKS: u32 = 8;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(file = "data/ui/history_page.blp")]
+    pub struct HistoryPage {
+        pub library: OnceCell<Library>,
+        pub player: OnceCell<Player>,
+
+        /// How many entries of the log are currently shown.
+        pub log_size: Cell<i64>,
+
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub period_toggle_group: TemplateChild<adw::ToggleGroup>,
+        #[template_child]
+        pub composers_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub works_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub performers_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub weeks_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub forgotten_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub log_list: TemplateChild<gtk::ListBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for HistoryPage {
+        const NAME: &'static str = "MusicusHistoryPage";
+        type Type = super::HistoryPage;
+        type ParentType = adw::NavigationPage;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for HistoryPage {}
+    impl WidgetImpl for HistoryPage {}
+    impl NavigationPageImpl for HistoryPage {}
+}
+
+glib::wrapper! {
+    pub struct HistoryPage(ObjectSubclass<imp::HistoryPage>)
+        @extends adw::NavigationPage, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+#[gtk::template_callbacks]
+impl HistoryPage {
+    pub fn new(library: &Library, player: &Player) -> Self {
+        let obj: Self = glib::Object::new();
+
+        obj.imp().library.set(library.to_owned()).unwrap();
+        obj.imp().player.set(player.to_owned()).unwrap();
+        obj.imp().log_size.set(LOG_PAGE_SIZE);
+
+        // Deleting a play changes the library, as do edits of what was played.
+        library.connect_changed(clone!(
+            #[weak]
+            obj,
+            move |_| obj.reload()
+        ));
+
+        obj.reload();
+        obj
+    }
+
+    #[template_callback]
+    fn period_changed(&self) {
+        self.load_rankings();
+    }
+
+    #[template_callback]
+    async fn export_listens(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Export listening history"))
+            .initial_name("listens.jsonl")
+            .modal(true)
+            .build();
+
+        let root = self.root();
+        let window = root.as_ref().and_then(|r| r.downcast_ref::<gtk::Window>());
+
+        match dialog.save_future(window).await {
+            Err(err) => {
+                if !err.matches(gtk::DialogError::Dismissed) {
+                    log::error!("File selection failed: {err:?}");
+                }
+            }
+            Ok(file) => {
+                if let Some(path) = file.path() {
+                    match self.imp().library.get().unwrap().export_listens(&path) {
+                        Ok(count) => self.imp().toast_overlay.add_toast(adw::Toast::new(
+                            &format_translated!(gettext("Exported {} listens"), count),
+                        )),
+                        Err(err) => self.report("Failed to export the listening history", err),
+                    }
+                }
+            }
+        }
+    }
+
+    fn reload(&self) {
+        self.load_rankings();
+        self.load_weeks();
+        self.load_forgotten();
+        self.load_log();
+    }
+
+    fn period(&self) -> Period {
+        match self.imp().period_toggle_group.active_name().as_deref() {
+            Some("week") => Period::Week,
+            Some("year") => Period::Year,
+            Some("all") => Period::AllTime,
+            _ => Period::Month,
+        }
+    }
+
+    fn load_rankings(&self) {
+        let imp = self.imp();
+        let library = imp.library.get().unwrap();
+        let period = self.period();
+
+        imp.composers_list.remove_all();
+        match library.top_composers(period, RANKING_SIZE) {
+            Ok(composers) => {
+                for ranked in &composers {
+                    imp.composers_list.append(&ranked_row(
+                        &ranked.item.name.get(),
+                        None,
+                        ranked.play_count,
+                    ));
+                }
+
+                if composers.is_empty() {
+                    imp.composers_list.append(&placeholder_row());
+                }
+            }
+            Err(err) => self.report("Failed to load top composers", err),
+        }
+
+        imp.works_list.remove_all();
+        match library.top_works(period, RANKING_SIZE) {
+            Ok(works) => {
+                for ranked in &works {
+                    imp.works_list.append(&ranked_row(
+                        &ranked.item.name.get(),
+                        ranked.item.composers_string().as_deref(),
+                        ranked.play_count,
+                    ));
+                }
+
+                if works.is_empty() {
+                    imp.works_list.append(&placeholder_row());
+                }
+            }
+            Err(err) => self.report("Failed to load top works", err),
+        }
+
+        imp.performers_list.remove_all();
+        match library.top_performers(period, RANKING_SIZE) {
+            Ok(performers) => {
+                for ranked in &performers {
+                    imp.performers_list.append(&ranked_row(
+                        &ranked.item.name().get(),
+                        None,
+                        ranked.play_count,
+                    ));
+                }
+
+                if performers.is_empty() {
+                    imp.performers_list.append(&placeholder_row());
+                }
+            }
+            Err(err) => self.report("Failed to load top performers", err),
+        }
+    }
+
+    fn load_weeks(&self) {
+        let imp = self.imp();
+        imp.weeks_list.remove_all();
+
+        let weeks = match imp.library.get().unwrap().listening_time_by_week(WEEKS) {
+            Ok(weeks) => weeks,
+            Err(err) => {
+                self.report("Failed to load the listening time", err);
+                return;
+            }
+        };
+
+        let longest_ms = weeks
+            .iter()
+            .map(|week| week.duration_ms)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+
+        // The most recent week comes first, like everywhere else on this page.
+        for week in weeks.iter().rev() {
+            let row = adw::ActionRow::builder()
+                .title(format_translated!(
+                    gettext("Week of {}"),
+                    week.week.format("%Y-%m-%d")
+                ))
+                .subtitle(format_translated!(
+                    gettext("{} · {} plays"),
+                    format_duration(week.duration_ms),
+                    week.play_count
+                ))
+                .build();
+
+            let level_bar = gtk::LevelBar::builder()
+                .value(week.duration_ms as f64 / longest_ms as f64)
+                .width_request(120)
+                .valign(gtk::Align::Center)
+                .build();
+            row.add_suffix(&level_bar);
+
+            imp.weeks_list.append(&row);
+        }
+    }
+
+    fn load_forgotten(&self) {
+        let imp = self.imp();
+        imp.forgotten_list.remove_all();
+
+        let recordings = match imp
+            .library
+            .get()
+            .unwrap()
+            .forgotten_recordings(Duration::days(180), 10)
+        {
+            Ok(recordings) => recordings,
+            Err(err) => {
+                self.report("Failed to load suggestions", err);
+                return;
+            }
+        };
+
+        for forgotten in &recordings {
+            imp.forgotten_list.append(&self.forgotten_row(forgotten));
+        }
+
+        if recordings.is_empty() {
+            imp.forgotten_list.append(&placeholder_row());
+        }
+    }
+
+    fn forgotten_row(&self, forgotten: &ForgottenRecording) -> adw::ActionRow {
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(
+                &forgotten.recording.work.to_string(),
+            ))
+            .subtitle(glib::markup_escape_text(&format_translated!(
+                gettext("{} · last played on {}"),
+                forgotten.recording.performers_string(),
+                date_label(forgotten.last_played_at)
+            )))
+            .subtitle_lines(0)
+            .build();
+
+        let play_button = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text(gettext("Play"))
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+
+        let recording = forgotten.recording.clone();
+        play_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                let player = obj.imp().player.get().unwrap();
+                player.append_and_play(player.recording_to_playlist(&recording));
+            }
+        ));
+
+        row.add_suffix(&play_button);
+        row
+    }
+
+    fn load_log(&self) {
+        let imp = self.imp();
+        imp.log_list.remove_all();
+
+        let entries = match imp
+            .library
+            .get()
+            .unwrap()
+            .play_log(0, imp.log_size.get() + 1)
+        {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.report("Failed to load the listening history", err);
+                return;
+            }
+        };
+
+        // One more entry than shown is loaded to know whether there are more.
+        let has_more = entries.len() as i64 > imp.log_size.get();
+
+        for entry in entries.iter().take(imp.log_size.get() as usize) {
+            imp.log_list.append(&self.log_row(entry));
+        }
+
+        if entries.is_empty() {
+            imp.log_list.append(&placeholder_row());
+        }
+
+        if has_more {
+            let row = adw::ButtonRow::builder()
+                .title(gettext("Show more"))
+                .build();
+
+            row.connect_activated(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_| {
+                    let imp = obj.imp();
+                    imp.log_size.set(imp.log_size.get() + LOG_PAGE_SIZE);
+                    obj.load_log();
+                }
+            ));
+
+            imp.log_list.append(&row);
+        }
+    }
+
+    fn log_row(&self, entry: &PlayLogEntry) -> adw::ActionRow {
+        // Tracks of a single part only belong to one work, which is the one of
+        // the recording anyway.
+        let title = match entry.track.as_ref().map(|track| track.works.as_slice()) {
+            Some([work]) if work.work_id != entry.recording.work.work_id => {
+                format!("{}: {}", entry.recording.work, work.name)
+            }
+            _ => entry.recording.work.to_string(),
+        };
+
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(&title))
+            .subtitle(glib::markup_escape_text(&format!(
+                "{} · {}",
+                time_label(entry.played_at),
+                entry.recording.performers_string()
+            )))
+            .subtitle_lines(0)
+            .build();
+
+        let delete_button = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text(gettext("Remove from history"))
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+
+        let play_id = entry.play_id.clone();
+        delete_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                // The page reloads once the library reports the change.
+                if let Err(err) = obj.imp().library.get().unwrap().delete_play(&play_id) {
+                    obj.report("Failed to remove the play", err);
+                }
+            }
+        ));
+
+        row.add_suffix(&delete_button);
+        row
+    }
+
+    fn report(&self, msgid: &str, err: anyhow::Error) {
+        util::error_toast(msgid, err, &self.imp().toast_overlay);
+    }
+}
+
+fn ranked_row(title: &str, subtitle: Option<&str>, play_count: i64) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .title(glib::markup_escape_text(title))
+        .build();
+
+    if let Some(subtitle) = subtitle {
+        row.set_subtitle(&glib::markup_escape_text(subtitle));
+    }
+
+    let count_label = gtk::Label::builder()
+        .label(format_translated!(gettext("{} plays"), play_count))
+        .css_classes(["dim-label", "numeric"])
+        .build();
+    row.add_suffix(&count_label);
+
+    row
+}
+
+fn placeholder_row() -> adw::ActionRow {
+    adw::ActionRow::builder()
+        .title(gettext("Nothing played yet"))
+        .css_classes(["dim-label"])
+        .build()
+}
+
+fn format_duration(duration_ms: i64) -> String {
+    let minutes = duration_ms / 60_000;
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+
+    if hours > 0 {
+        format_translated!(gettext("{} h {} min"), hours, minutes)
+    } else {
+        format_translated!(gettext("{} min"), minutes)
+    }
+}
+
+fn date_label(timestamp: NaiveDateTime) -> String {
+    Utc.from_utc_datetime(&timestamp)
+        .with_timezone(&Local)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+fn time_label(timestamp: NaiveDateTime) -> String {
+    Utc.from_utc_datetime(&timestamp)
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
@@ -0,0 +1,93 @@
+//! The music libraries the user has opened, so that they can switch between
+//! them.
+//!
+//! They are kept in the `libraries` setting. The library that is currently open
+//! is still the one in `library-path`.
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::Local;
+use gtk::{gio, prelude::*};
+
+const KEY: &str = "libraries";
+
+#[derive(Clone, Debug)]
+pub struct KnownLibrary {
+    pub name: String,
+    pub path: String,
+    /// When the library was last opened, as a Unix timestamp.
+    pub last_opened: i64,
+}
+
+/// Every known library, the most recently opened one first.
+pub fn list(settings: &gio::Settings) -> Vec<KnownLibrary> {
+    let mut libraries = settings
+        .value(KEY)
+        .get::<Vec<(String, String, i64)>>()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, path, last_opened)| KnownLibrary {
+            name,
+            path,
+            last_opened,
+        })
+        .collect::<Vec<_>>();
+
+    libraries.sort_by(|a, b| b.last_opened.cmp(&a.last_opened));
+    libraries
+}
+
+fn save(settings: &gio::Settings, libraries: &[KnownLibrary]) -> Result<()> {
+    let value = libraries
+        .iter()
+        .map(|library| {
+            (
+                library.name.clone(),
+                library.path.clone(),
+                library.last_opened,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    settings.set_value(KEY, &value.to_variant())?;
+    Ok(())
+}
+
+/// Note that the library at `path` was opened just now. A library that is not
+/// known yet is named after its folder.
+pub fn remember(settings: &gio::Settings, path: &str) -> Result<()> {
+    let mut libraries = list(settings);
+    let now = Local::now().timestamp();
+
+    match libraries.iter_mut().find(|library| library.path == path) {
+        Some(library) => library.last_opened = now,
+        None => libraries.push(KnownLibrary {
+            name: Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.to_owned()),
+            path: path.to_owned(),
+            last_opened: now,
+        }),
+    }
+
+    save(settings, &libraries)
+}
+
+pub fn rename(settings: &gio::Settings, path: &str, name: &str) -> Result<()> {
+    let mut libraries = list(settings);
+
+    if let Some(library) = libraries.iter_mut().find(|library| library.path == path) {
+        library.name = name.to_owned();
+    }
+
+    save(settings, &libraries)
+}
+
+/// Remove the library at `path` from the list. Its folder is left alone.
+pub fn forget(settings: &gio::Settings, path: &str) -> Result<()> {
+    let mut libraries = list(settings);
+    libraries.retain(|library| library.path != path);
+    save(settings, &libraries)
+}
@@ -45,6 +45,8 @@ impl Deref for Library {
 
 impl Library {
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        // Shared by all libraries, so that switching between them does not
+        // download the metadata again.
         let metadata_cache_dir = glib::user_cache_dir().join(config::APP_ID);
         let inner = musicus_library::Library::new(path, metadata_cache_dir)?;
         let changed_rx = inner.subscribe_changed();
@@ -12,7 +12,7 @@ use musicus_library::db::tables::Source;
 
 use crate::{
     config, entity_browser::EntityBrowser, library::Library, process::Process,
-    process_manager::ProcessManager, process_row::ProcessRow, window::Window,
+    process_manager::ProcessManager, process_row::ProcessRow, reorganization, window::Window,
 };
 
 mod imp {
@@ -184,10 +184,24 @@ impl LibraryManager {
 
     #[template_callback]
     async fn export_archive(&self) {
-        let dialog = gtk::FileDialog::builder()
-            .title(gettext("Export library"))
-            .modal(true)
-            .build();
+        self.write_archive(false).await;
+    }
+
+    #[template_callback]
+    async fn backup_archive(&self) {
+        self.write_archive(true).await;
+    }
+
+    /// Export the library to an archive chosen by the user. A backup includes
+    /// the listening history and the private tags.
+    async fn write_archive(&self, backup: bool) {
+        let title = if backup {
+            gettext("Back up library")
+        } else {
+            gettext("Export library")
+        };
+
+        let dialog = gtk::FileDialog::builder().title(title).modal(true).build();
 
         let root = self.root();
         let window = root
@@ -204,17 +218,25 @@ impl LibraryManager {
             }
             Ok(path) => {
                 if let Some(path) = path.path() {
-                    match self
-                        .imp()
-                        .library
-                        .get()
-                        .unwrap()
-                        .export_library_to_zip(&path)
-                    {
+                    let library = self.imp().library.get().unwrap();
+
+                    let (result, message) = if backup {
+                        (
+                            library.backup_library_to_zip(&path),
+                            gettext("Backing up music library to {}"),
+                        )
+                    } else {
+                        (
+                            library.export_library_to_zip(&path),
+                            gettext("Exporting music library to {}"),
+                        )
+                    };
+
+                    match result {
                         Ok(handle) => {
                             let process = Process::new(
                                 &format_translated!(
-                                    gettext("Exporting music library to {}"),
+                                    message,
                                     path.file_name()
                                         .map(|f| f.to_string_lossy().into_owned())
                                         .unwrap_or(gettext("archive"))
@@ -238,49 +260,85 @@ impl LibraryManager {
     }
 
     #[template_callback]
-    async fn reorganize_files(&self) {
-        let dialog = adw::AlertDialog::builder()
-            .heading(gettext("Reorganize files?"))
-            .body(gettext("Every track file within your music library folder will be renamed after the file name pattern, and its tags will be replaced with the ones Musicus generates. Replacing the tags cannot be undone."))
+    async fn sync_library(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Select music library to synchronize with"))
+            .modal(true)
             .build();
 
-        dialog.add_responses(&[
-            ("cancel", &gettext("Cancel")),
-            ("reorganize", &gettext("Reorganize")),
-        ]);
+        let root = self.root();
+        let window = root
+            .as_ref()
+            .and_then(|r| r.downcast_ref::<gtk::Window>())
+            .and_then(|w| w.downcast_ref::<Window>())
+            .expect("library manager is attached to a window");
 
-        dialog.set_response_appearance("reorganize", adw::ResponseAppearance::Suggested);
-        dialog.set_close_response("cancel");
-        dialog.set_default_response(Some("cancel"));
+        match dialog.select_folder_future(Some(window)).await {
+            Err(err) => {
+                if !err.matches(gtk::DialogError::Dismissed) {
+                    log::error!("Folder selection failed: {err:?}");
+                }
+            }
+            Ok(folder) => {
+                if let Some(path) = folder.path() {
+                    match self.imp().library.get().unwrap().sync_with(&path) {
+                        Ok(handle) => {
+                            let process = Process::new(
+                                &format_translated!(
+                                    gettext("Synchronizing with {}"),
+                                    path.file_name()
+                                        .map(|f| f.to_string_lossy().into_owned())
+                                        .unwrap_or(gettext("library"))
+                                ),
+                                handle,
+                            );
 
-        if dialog.choose_future(Some(self)).await != "reorganize" {
-            return;
-        }
+                            process.connect_finished_notify(clone!(
+                                #[weak(rename_to = obj)]
+                                self,
+                                move |_| {
+                                    obj.imp().library.get().unwrap().changed();
+                                }
+                            ));
 
-        match self.imp().library.get().unwrap().reorganize_files() {
-            Ok(handle) => {
-                let process = Process::new(&gettext("Reorganizing library files"), handle);
+                            self.imp()
+                                .process_manager
+                                .get()
+                                .unwrap()
+                                .add_process(&process);
 
-                process.connect_finished_notify(clone!(
-                    #[weak(rename_to = obj)]
-                    self,
-                    move |_| {
-                        obj.imp().library.get().unwrap().changed();
+                            self.add_process(&process);
+                        }
+                        Err(err) => log::error!("Failed to synchronize libraries: {err:?}"),
                     }
-                ));
-
-                self.imp()
-                    .process_manager
-                    .get()
-                    .unwrap()
-                    .add_process(&process);
-
-                self.add_process(&process);
+                }
             }
-            Err(err) => log::error!("Failed to reorganize library files: {err:?}"),
         }
     }
 
+    #[template_callback]
+    async fn reorganize_files(&self) {
+        reorganization::reorganize_files(
+            self,
+            self.imp().library.get().unwrap(),
+            None,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |process: &Process| {
+                    obj.imp()
+                        .process_manager
+                        .get()
+                        .unwrap()
+                        .add_process(process);
+
+                    obj.add_process(process);
+                }
+            ),
+        )
+        .await;
+    }
+
     #[template_callback]
     fn update_metadata(&self) {
         let settings = gio::Settings::new(config::APP_ID);
@@ -312,6 +370,147 @@ impl LibraryManager {
         }
     }
 
+    #[template_callback]
+    async fn update_metadata_from_file(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Select metadata database"))
+            .modal(true)
+            .build();
+
+        let root = self.root();
+        let window = root
+            .as_ref()
+            .and_then(|r| r.downcast_ref::<gtk::Window>())
+            .and_then(|w| w.downcast_ref::<Window>())
+            .expect("library manager is attached to a window");
+
+        let result = dialog.open_future(Some(window)).await;
+        self.update_metadata_from_selection(result).await;
+    }
+
+    #[template_callback]
+    async fn update_metadata_from_folder(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Select folder with metadata database"))
+            .modal(true)
+            .build();
+
+        let root = self.root();
+        let window = root
+            .as_ref()
+            .and_then(|r| r.downcast_ref::<gtk::Window>())
+            .and_then(|w| w.downcast_ref::<Window>())
+            .expect("library manager is attached to a window");
+
+        let result = dialog.select_folder_future(Some(window)).await;
+        self.update_metadata_from_selection(result).await;
+    }
+
+    async fn update_metadata_from_selection(&self, selection: Result<gio::File, glib::Error>) {
+        let path = match selection {
+            Err(err) => {
+                if !err.matches(gtk::DialogError::Dismissed) {
+                    log::error!("File selection failed: {err:?}");
+                }
+
+                return;
+            }
+            Ok(file) => match file.path() {
+                Some(path) => path,
+                None => return,
+            },
+        };
+
+        let library = self.imp().library.get().unwrap();
+
+        // Show what would change before changing anything.
+        let report_receiver = match library.preview_metadata_from_path(&path) {
+            Ok((handle, report_receiver)) => {
+                let process = Process::new(&gettext("Reading metadata database"), handle);
+
+                self.imp()
+                    .process_manager
+                    .get()
+                    .unwrap()
+                    .add_process(&process);
+
+                self.add_process(&process);
+
+                report_receiver
+            }
+            Err(err) => {
+                log::error!("Failed to read metadata database: {err:?}");
+                return;
+            }
+        };
+
+        // The process reports its own failure, the report only arrives on
+        // success.
+        let Ok(report) = report_receiver.recv().await else {
+            return;
+        };
+
+        let mut body = format_translated!(
+            gettext("{} items in your library will be updated and {} new items become available."),
+            report.updated.len().to_string(),
+            report.added.len().to_string()
+        );
+
+        const MAX_LISTED: usize = 10;
+
+        if !report.updated.is_empty() {
+            body.push('\n');
+
+            for entry in report.updated.iter().take(MAX_LISTED) {
+                body.push_str(&format!("\n• {}", entry.name));
+            }
+
+            if report.updated.len() > MAX_LISTED {
+                body.push_str("\n…");
+            }
+        }
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Update metadata?"))
+            .body(body)
+            .build();
+
+        dialog.add_responses(&[
+            ("cancel", &gettext("Cancel")),
+            ("update", &gettext("Update")),
+        ]);
+        dialog.set_response_appearance("update", adw::ResponseAppearance::Suggested);
+        dialog.set_close_response("cancel");
+        dialog.set_default_response(Some("update"));
+
+        if dialog.choose_future(Some(self)).await != "update" {
+            return;
+        }
+
+        match library.import_metadata_from_path(&path) {
+            Ok(handle) => {
+                let process = Process::new(&gettext("Updating metadata"), handle);
+
+                process.connect_finished_notify(clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_| {
+                        obj.imp().library.get().unwrap().changed();
+                    }
+                ));
+
+                self.imp()
+                    .process_manager
+                    .get()
+                    .unwrap()
+                    .add_process(&process);
+
+                self.add_process(&process);
+            }
+            Err(err) => log::error!("Failed to update metadata: {err:?}"),
+        }
+    }
+
     #[template_callback]
     fn update_library(&self) {
         let settings = gio::Settings::new(config::APP_ID);
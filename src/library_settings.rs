@@ -0,0 +1,102 @@
+//! Keeping the application settings in line with the settings of the open
+//! library.
+//!
+//! Everything reading a setting uses GSettings, including the preferences.
+//! While a library is open, the keys below reflect what it stores: opening the
+//! library copies its values over and every change is written back into it.
+//!
+//! A library that does not store a pattern yet takes over the one in effect,
+//! because its files may already be named after it, and keeps it from then on.
+//! Every other key it does not store is reset to its default, so that the
+//! values of one library do not leak into the next.
+
+use anyhow::{bail, Result};
+use gtk::{gio, glib, prelude::*};
+use musicus_library::library::settings::PATTERN_KEYS;
+
+use crate::library::Library;
+
+/// The settings that belong to a library, besides its patterns.
+const KEYS: &[&str] = &[
+    "prefer-least-recently-played",
+    "prefer-recently-added",
+    "avoid-repeated-composers",
+    "avoid-repeated-instruments",
+    "avoid-skipped",
+    "play-full-recordings",
+    "transition",
+    "transition-duration",
+    "program1",
+    "program2",
+    "program3",
+    "enable-automatic-metadata-updates",
+    "use-custom-metadata-url",
+    "custom-metadata-url",
+];
+
+/// Every key of a setting that belongs to a library.
+pub fn keys() -> impl Iterator<Item = &'static str> {
+    PATTERN_KEYS.iter().chain(KEYS).copied()
+}
+
+/// Copy the settings `library` stores into `settings`.
+pub fn load(library: &Library, settings: &gio::Settings) {
+    for key in keys() {
+        let result = library.setting(key).and_then(|value| {
+            let Some(value) = value else {
+                if PATTERN_KEYS.contains(&key) {
+                    return store(library, settings, key);
+                }
+
+                settings.reset(key);
+                return Ok(());
+            };
+
+            let current = settings.value(key);
+            let value = from_text(&current, &value)?;
+
+            if value != current {
+                settings.set_value(key, &value)?;
+            }
+
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            log::warn!("Failed to load the setting {key} from the library: {err:?}");
+        }
+    }
+}
+
+/// Store the current value of the setting `key` in `library`.
+pub fn store(library: &Library, settings: &gio::Settings, key: &str) -> Result<()> {
+    library.set_setting(key, &to_text(&settings.value(key))?)
+}
+
+/// The text a library stores for `value`.
+fn to_text(value: &glib::Variant) -> Result<String> {
+    if let Some(value) = value.str() {
+        Ok(value.to_owned())
+    } else if let Some(value) = value.get::<bool>() {
+        Ok(value.to_string())
+    } else if let Some(value) = value.get::<i32>() {
+        Ok(value.to_string())
+    } else {
+        bail!("Unsupported setting type {}", value.type_())
+    }
+}
+
+/// Read `text` as a value of the same type as `current`.
+fn from_text(current: &glib::Variant, text: &str) -> Result<glib::Variant> {
+    let value = if current.str().is_some() {
+        text.to_variant()
+    } else if current.get::<bool>().is_some() {
+        text.parse::<bool>()?.to_variant()
+    } else if current.get::<i32>().is_some() {
+        text.parse::<i32>()?.to_variant()
+    } else {
+        bail!("Unsupported setting type {}", current.type_())
+    };
+
+    Ok(value)
+}
@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use adw::{prelude::*, subclass::prelude::*};
+use chrono::{Local, TimeZone};
+use gettextrs::gettext;
+use gtk::{
+    gio,
+    glib::{self, clone, subclass::Signal},
+};
+use musicus_library::format_translated;
+use once_cell::sync::Lazy;
+
+use crate::{config, known_libraries, util};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(file = "data/ui/library_switcher.blp")]
+    pub struct LibrarySwitcher {
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub list_box: TemplateChild<gtk::ListBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for LibrarySwitcher {
+        const NAME: &'static str = "MusicusLibrarySwitcher";
+        type Type = super::LibrarySwitcher;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for LibrarySwitcher {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder("folder-selected")
+                    .param_types([gio::File::static_type()])
+                    .build()]
+            });
+
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.obj().reload();
+        }
+    }
+
+    impl WidgetImpl for LibrarySwitcher {}
+    impl AdwDialogImpl for LibrarySwitcher {}
+}
+
+glib::wrapper! {
+    /// The list of known libraries, to open one of them or to add another.
+    pub struct LibrarySwitcher(ObjectSubclass<imp::LibrarySwitcher>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+#[gtk::template_callbacks]
+impl LibrarySwitcher {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn connect_folder_selected<F: Fn(&Self, gio::File) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("folder-selected", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let folder = values[1].get::<gio::File>().unwrap();
+            f(&obj, folder);
+            None
+        })
+    }
+
+    fn reload(&self) {
+        let list_box = &self.imp().list_box;
+        list_box.remove_all();
+
+        let settings = gio::Settings::new(config::APP_ID);
+        let current_path = settings.string("library-path");
+
+        for library in known_libraries::list(&settings) {
+            let path = library.path.clone();
+            let is_current = path == current_path.as_str();
+            // A library on a removable drive is only there while the drive is.
+            let is_available = Path::new(&library.path).is_dir();
+
+            let status = if is_current {
+                gettext("Open now")
+            } else if !is_available {
+                gettext("Not available")
+            } else {
+                format_translated!(gettext("Last opened {}"), date_label(library.last_opened))
+            };
+
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&library.name))
+                .subtitle(glib::markup_escape_text(&format!(
+                    "{}\n{status}",
+                    library.path
+                )))
+                .subtitle_lines(2)
+                .activatable(!is_current && is_available)
+                .build();
+
+            if is_current {
+                row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
+            }
+
+            let rename_button = gtk::Button::builder()
+                .icon_name("document-edit-symbolic")
+                .tooltip_text(gettext("Rename"))
+                .valign(gtk::Align::Center)
+                .build();
+            rename_button.add_css_class("flat");
+
+            rename_button.connect_clicked(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[strong]
+                library,
+                move |_| {
+                    let library = library.clone();
+                    glib::spawn_future_local(async move {
+                        obj.rename(&library).await;
+                    });
+                }
+            ));
+
+            row.add_suffix(&rename_button);
+
+            // The open library cannot be forgotten, because it would only be
+            // remembered again the next time it is opened.
+            if !is_current {
+                let forget_button = gtk::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .tooltip_text(gettext("Remove from list"))
+                    .valign(gtk::Align::Center)
+                    .build();
+                forget_button.add_css_class("flat");
+
+                forget_button.connect_clicked(clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    #[strong]
+                    path,
+                    move |_| {
+                        if let Err(err) =
+                            known_libraries::forget(&gio::Settings::new(config::APP_ID), &path)
+                        {
+                            obj.report("Failed to remove the library from the list", err);
+                        }
+
+                        obj.reload();
+                    }
+                ));
+
+                row.add_suffix(&forget_button);
+            }
+
+            row.connect_activated(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[strong]
+                path,
+                move |_| {
+                    obj.emit_by_name::<()>("folder-selected", &[&gio::File::for_path(&path)]);
+                    obj.close();
+                }
+            ));
+
+            list_box.append(&row);
+        }
+    }
+
+    async fn rename(&self, library: &known_libraries::KnownLibrary) {
+        let entry = gtk::Entry::builder()
+            .text(&library.name)
+            .activates_default(true)
+            .build();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Rename library"))
+            .extra_child(&entry)
+            .build();
+
+        dialog.add_responses(&[
+            ("cancel", &gettext("Cancel")),
+            ("rename", &gettext("Rename")),
+        ]);
+        dialog.set_response_appearance("rename", adw::ResponseAppearance::Suggested);
+        dialog.set_close_response("cancel");
+        dialog.set_default_response(Some("rename"));
+
+        if dialog.choose_future(Some(self)).await != "rename" {
+            return;
+        }
+
+        let name = entry.text();
+        let name = name.trim();
+
+        if name.is_empty() {
+            return;
+        }
+
+        if let Err(err) =
+            known_libraries::rename(&gio::Settings::new(config::APP_ID), &library.path, name)
+        {
+            self.report("Failed to rename the library", err);
+        }
+
+        self.reload();
+    }
+
+    #[template_callback]
+    async fn add_library(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Select music library folder"))
+            .modal(true)
+            .build();
+
+        match dialog
+            .select_folder_future(
+                self.root()
+                    .as_ref()
+                    .and_then(|r| r.downcast_ref::<gtk::Window>()),
+            )
+            .await
+        {
+            Err(err) => {
+                if !err.matches(gtk::DialogError::Dismissed) {
+                    log::error!("Folder selection failed: {err:?}");
+                }
+            }
+            Ok(folder) => {
+                self.emit_by_name::<()>("folder-selected", &[&folder]);
+                self.close();
+            }
+        }
+    }
+
+    fn report(&self, msgid: &str, err: anyhow::Error) {
+        util::error_toast(msgid, err, &self.imp().toast_overlay);
+    }
+}
+
+fn date_label(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
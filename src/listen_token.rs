@@ -0,0 +1,44 @@
+//! The user token for submitting listens.
+//!
+//! It is a password, so it lives in the keyring of the user rather than in the
+//! settings. Inside the sandbox, the keyring is reached through the secret
+//! portal.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::config;
+
+const LABEL: &str = "Musicus listen submission token";
+
+fn attributes() -> HashMap<&'static str, &'static str> {
+    HashMap::from([("application", config::APP_ID), ("type", "listen-token")])
+}
+
+/// The stored token, or an empty string if there is none.
+pub async fn load() -> Result<String> {
+    let keyring = oo7::Keyring::new().await?;
+    keyring.unlock().await?;
+
+    match keyring.search_items(&attributes()).await?.first() {
+        Some(item) => Ok(String::from_utf8(item.secret().await?.to_vec())?),
+        None => Ok(String::new()),
+    }
+}
+
+/// Replace the stored token. An empty token removes it.
+pub async fn store(token: &str) -> Result<()> {
+    let keyring = oo7::Keyring::new().await?;
+    keyring.unlock().await?;
+
+    if token.is_empty() {
+        keyring.delete(&attributes()).await?;
+    } else {
+        keyring
+            .create_item(LABEL, &attributes(), token, true)
+            .await?;
+    }
+
+    Ok(())
+}
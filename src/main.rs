@@ -6,8 +6,14 @@ mod editor;
 mod empty_page;
 mod entity_browser;
 mod facet_tile;
+mod history_page;
+mod known_libraries;
 mod library;
 mod library_manager;
+mod library_settings;
+mod library_switcher;
+mod listen_token;
+mod mpris;
 mod player;
 mod player_bar;
 mod playlist_item;
@@ -21,6 +27,7 @@ mod program;
 mod program_section;
 mod program_tile;
 mod recording_tile;
+mod reorganization;
 mod search_page;
 mod selector;
 mod slider_row;
@@ -0,0 +1,409 @@
+//! The MPRIS interfaces of the player, through which desktop widgets, media
+//! keys and other applications control playback.
+//!
+//! Nothing is cached here. Every property is read from the [`Player`] when it
+//! is asked for, and the player publishes changes as they happen.
+
+use gtk::{glib, prelude::*};
+use mpris_server::{
+    zbus::{self, fdo},
+    LocalPlayerInterface, LocalRootInterface, LocalTrackListInterface, LoopStatus, Metadata,
+    PlaybackRate, PlaybackStatus, Time, TrackId, Volume,
+};
+
+use crate::{
+    config,
+    player::{Player, RepeatMode},
+    playlist_item::PlaylistItem,
+};
+
+pub struct Mpris {
+    player: glib::WeakRef<Player>,
+}
+
+impl Mpris {
+    pub fn new(player: &Player) -> Self {
+        Self {
+            player: player.downgrade(),
+        }
+    }
+
+    fn player(&self) -> fdo::Result<Player> {
+        self.player
+            .upgrade()
+            .ok_or_else(|| fdo::Error::Failed("The player is gone".to_owned()))
+    }
+}
+
+/// The ID of `item` on the bus. Items are told apart by their serial, because
+/// the same track can be queued more than once.
+pub fn track_id(item: &PlaylistItem) -> TrackId {
+    TrackId::try_from(format!("{}/Track/{}", config::PATH_ID, item.serial()))
+        .expect("the application path should be a valid object path")
+}
+
+/// The ID of the current item, or the special ID for no track.
+pub fn current_track_id(player: &Player) -> TrackId {
+    player
+        .current_item()
+        .map(|item| track_id(&item))
+        .unwrap_or(TrackId::NO_TRACK)
+}
+
+/// The metadata of `item`. Its length is only known once it has been loaded
+/// for playback.
+pub fn metadata(item: &PlaylistItem, length_ms: Option<u64>) -> Metadata {
+    let mut metadata = Metadata::builder()
+        .trackid(track_id(item))
+        .title(item.make_title())
+        .album(item.work());
+
+    if let Some(performers) = item.make_subtitle() {
+        metadata = metadata.artist([performers]);
+    }
+
+    if let Some(composers) = item.composers() {
+        metadata = metadata.composer([composers]);
+    }
+
+    if let Some(uri) = item
+        .cover_art()
+        .and_then(|path| glib::filename_to_uri(path, None).ok())
+    {
+        metadata = metadata.art_url(uri.as_str());
+    }
+
+    if let Some(length_ms) = length_ms {
+        metadata = metadata.length(Time::from_millis(length_ms as i64));
+    }
+
+    metadata.build()
+}
+
+/// The metadata of the current item, or empty metadata if there is none.
+pub fn current_metadata(player: &Player) -> Metadata {
+    match player.current_item() {
+        Some(item) => metadata(&item, player.current_duration_ms()),
+        None => Metadata::new(),
+    }
+}
+
+pub fn playback_status(player: &Player) -> PlaybackStatus {
+    if !player.active() || player.stopped() {
+        PlaybackStatus::Stopped
+    } else if player.playing() {
+        PlaybackStatus::Playing
+    } else {
+        PlaybackStatus::Paused
+    }
+}
+
+/// Repeating a recording is the closest the player comes to repeating a track,
+/// which is just one movement of it.
+pub fn loop_status(player: &Player) -> LoopStatus {
+    match player.repeat_mode() {
+        RepeatMode::Off => LoopStatus::None,
+        RepeatMode::Playlist => LoopStatus::Playlist,
+        RepeatMode::Recording => LoopStatus::Track,
+    }
+}
+
+fn item_by_track_id(player: &Player, track_id: &TrackId) -> Option<(u32, PlaylistItem)> {
+    player
+        .playlist()
+        .iter::<PlaylistItem>()
+        .enumerate()
+        .filter_map(|(index, item)| Some((index as u32, item.ok()?)))
+        .find(|(_, item)| &self::track_id(item) == track_id)
+}
+
+impl LocalRootInterface for Mpris {
+    async fn raise(&self) -> fdo::Result<()> {
+        self.player()?.emit_by_name::<()>("raise", &[]);
+        Ok(())
+    }
+
+    async fn quit(&self) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "Musicus cannot be quit over MPRIS".to_owned(),
+        ))
+    }
+
+    async fn can_quit(&self) -> fdo::Result<bool> {
+        Ok(false)
+    }
+
+    async fn fullscreen(&self) -> fdo::Result<bool> {
+        Ok(false)
+    }
+
+    async fn set_fullscreen(&self, _fullscreen: bool) -> zbus::Result<()> {
+        Ok(())
+    }
+
+    async fn can_set_fullscreen(&self) -> fdo::Result<bool> {
+        Ok(false)
+    }
+
+    async fn can_raise(&self) -> fdo::Result<bool> {
+        Ok(true)
+    }
+
+    async fn has_track_list(&self) -> fdo::Result<bool> {
+        Ok(true)
+    }
+
+    async fn identity(&self) -> fdo::Result<String> {
+        Ok(config::NAME.to_owned())
+    }
+
+    async fn desktop_entry(&self) -> fdo::Result<String> {
+        Ok(config::APP_ID.to_owned())
+    }
+
+    async fn supported_uri_schemes(&self) -> fdo::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    async fn supported_mime_types(&self) -> fdo::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+impl LocalPlayerInterface for Mpris {
+    async fn next(&self) -> fdo::Result<()> {
+        self.player()?.next();
+        Ok(())
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        self.player()?.previous();
+        Ok(())
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        self.player()?.pause();
+        Ok(())
+    }
+
+    async fn play_pause(&self) -> fdo::Result<()> {
+        self.player()?.play_pause();
+        Ok(())
+    }
+
+    async fn stop(&self) -> fdo::Result<()> {
+        self.player()?.stop();
+        Ok(())
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        let player = self.player()?;
+
+        if player.active() {
+            player.play();
+        }
+
+        Ok(())
+    }
+
+    async fn seek(&self, offset: Time) -> fdo::Result<()> {
+        let player = self.player()?;
+
+        let Some(duration_ms) = player.current_duration_ms() else {
+            return Ok(());
+        };
+
+        let position_ms = (player.position_ms() as i64).saturating_add(offset.as_millis());
+
+        if position_ms > duration_ms as i64 {
+            player.next();
+        } else {
+            player.seek_to(position_ms.max(0) as u64);
+        }
+
+        Ok(())
+    }
+
+    async fn set_position(&self, track_id: TrackId, position: Time) -> fdo::Result<()> {
+        let player = self.player()?;
+
+        // A request for a track that is no longer current is stale.
+        if track_id != current_track_id(&player) {
+            return Ok(());
+        }
+
+        let Some(duration_ms) = player.current_duration_ms() else {
+            return Ok(());
+        };
+
+        if position.is_negative() || position.as_millis() > duration_ms as i64 {
+            return Ok(());
+        }
+
+        player.seek_to(position.as_millis() as u64);
+        Ok(())
+    }
+
+    async fn open_uri(&self, _uri: String) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "Only tracks of the library can be played".to_owned(),
+        ))
+    }
+
+    async fn playback_status(&self) -> fdo::Result<PlaybackStatus> {
+        Ok(playback_status(&self.player()?))
+    }
+
+    async fn loop_status(&self) -> fdo::Result<LoopStatus> {
+        Ok(loop_status(&self.player()?))
+    }
+
+    async fn set_loop_status(&self, loop_status: LoopStatus) -> zbus::Result<()> {
+        self.player()?.set_repeat_mode(match loop_status {
+            LoopStatus::None => RepeatMode::Off,
+            LoopStatus::Playlist => RepeatMode::Playlist,
+            LoopStatus::Track => RepeatMode::Recording,
+        });
+
+        Ok(())
+    }
+
+    async fn rate(&self) -> fdo::Result<PlaybackRate> {
+        Ok(1.0)
+    }
+
+    async fn set_rate(&self, _rate: PlaybackRate) -> zbus::Result<()> {
+        Ok(())
+    }
+
+    async fn shuffle(&self) -> fdo::Result<bool> {
+        Ok(self.player()?.shuffled())
+    }
+
+    /// Turning shuffle on shuffles the recordings after the current one. The
+    /// previous order is gone by then, so turning it off only keeps the current
+    /// order from being reported as shuffled.
+    async fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()> {
+        let player = self.player()?;
+
+        if shuffle && !player.shuffled() {
+            player.shuffle();
+        } else if !shuffle {
+            player.set_shuffled(false);
+        }
+
+        Ok(())
+    }
+
+    async fn metadata(&self) -> fdo::Result<Metadata> {
+        Ok(current_metadata(&self.player()?))
+    }
+
+    async fn volume(&self) -> fdo::Result<Volume> {
+        Ok(self.player()?.volume())
+    }
+
+    async fn set_volume(&self, volume: Volume) -> zbus::Result<()> {
+        self.player()?.set_volume(volume.clamp(0.0, 1.0));
+        Ok(())
+    }
+
+    async fn position(&self) -> fdo::Result<Time> {
+        Ok(Time::from_millis(self.player()?.position_ms() as i64))
+    }
+
+    async fn minimum_rate(&self) -> fdo::Result<PlaybackRate> {
+        Ok(1.0)
+    }
+
+    async fn maximum_rate(&self) -> fdo::Result<PlaybackRate> {
+        Ok(1.0)
+    }
+
+    async fn can_go_next(&self) -> fdo::Result<bool> {
+        Ok(self.player()?.has_next())
+    }
+
+    async fn can_go_previous(&self) -> fdo::Result<bool> {
+        Ok(self.player()?.has_previous())
+    }
+
+    async fn can_play(&self) -> fdo::Result<bool> {
+        Ok(self.player()?.active())
+    }
+
+    async fn can_pause(&self) -> fdo::Result<bool> {
+        Ok(self.player()?.active())
+    }
+
+    async fn can_seek(&self) -> fdo::Result<bool> {
+        Ok(self.player()?.active())
+    }
+
+    async fn can_control(&self) -> fdo::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl LocalTrackListInterface for Mpris {
+    async fn get_tracks_metadata(&self, track_ids: Vec<TrackId>) -> fdo::Result<Vec<Metadata>> {
+        let player = self.player()?;
+        let current_index = player.current_index();
+
+        Ok(track_ids
+            .iter()
+            .filter_map(|track_id| {
+                let (index, item) = item_by_track_id(&player, track_id)?;
+
+                let length_ms = if index == current_index {
+                    player.current_duration_ms()
+                } else {
+                    None
+                };
+
+                Some(metadata(&item, length_ms))
+            })
+            .collect())
+    }
+
+    async fn add_track(
+        &self,
+        _uri: String,
+        _after_track: TrackId,
+        _set_as_current: bool,
+    ) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "The playlist cannot be edited over MPRIS".to_owned(),
+        ))
+    }
+
+    async fn remove_track(&self, _track_id: TrackId) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "The playlist cannot be edited over MPRIS".to_owned(),
+        ))
+    }
+
+    async fn go_to(&self, track_id: TrackId) -> fdo::Result<()> {
+        let player = self.player()?;
+
+        if let Some((index, _)) = item_by_track_id(&player, &track_id) {
+            player.set_current_index(index);
+        }
+
+        Ok(())
+    }
+
+    async fn tracks(&self) -> fdo::Result<Vec<TrackId>> {
+        Ok(self
+            .player()?
+            .playlist()
+            .iter::<PlaylistItem>()
+            .filter_map(|item| item.ok())
+            .map(|item| track_id(&item))
+            .collect())
+    }
+
+    async fn can_edit_tracks(&self) -> fdo::Result<bool> {
+        Ok(false)
+    }
+}
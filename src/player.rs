@@ -1,37 +1,50 @@
 use std::{
     cell::{Cell, OnceCell, RefCell},
+    future::Future,
+    ops::Range,
     path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context, Result};
 use fragile::Fragile;
 use gettextrs::gettext;
-use gstreamer_play::gst;
+use gstreamer_play::gst::{self, prelude::*};
 use gtk::{
     gio,
     glib::{self, clone, subclass::Signal, Properties},
     prelude::*,
     subclass::prelude::*,
 };
+use mpris_server::{
+    LocalServer, Property, Signal as MprisSignal, TrackListProperty, TrackListSignal,
+};
 use musicus_library::{
     db::models::{Recording, Track, Work},
     format_translated,
+    library::listens::ListenQueue,
 };
 use once_cell::sync::Lazy;
 
 use crate::{
     config,
     library::{GenerateRecordingParams, Library},
+    listen_token,
+    mpris::{self, Mpris},
     playlist_item::PlaylistItem,
-    program::Program,
+    process::ProcessMsg,
+    program::{Program, ProgramTransition},
 };
 
 /// How many tracks may fail in a row before playback gives up.
 const MAX_CONSECUTIVE_ERRORS: u32 = 5;
 
-/// How far into a track it counts as listened to: half of it, or this, whichever
-/// comes first.
-const PLAY_THRESHOLD_MS: u64 = 4 * 60 * 1000;
+/// How often a sleep timer that runs for a duration checks the time.
+const SLEEP_TIMER_INTERVAL_MS: u64 = 250;
+
+/// How often the volumes change during a crossfade.
+const CROSSFADE_INTERVAL_MS: u64 = 50;
 
 mod imp {
     use super::*;
@@ -45,6 +58,14 @@ mod imp {
         pub active: Cell<bool>,
         #[property(get, set)]
         pub playing: Cell<bool>,
+        /// Whether playback was stopped and returned to the start of the
+        /// current item, rather than just paused.
+        #[property(get)]
+        pub stopped: Cell<bool>,
+        /// Whether the recordings after the current one were shuffled since the
+        /// playlist was last cleared.
+        #[property(get, set)]
+        pub shuffled: Cell<bool>,
         #[property(get, set = Self::set_program)]
         pub program: RefCell<Option<Program>>,
         #[property(get, construct_only)]
@@ -55,18 +76,122 @@ mod imp {
         pub duration_ms: Cell<u64>,
         #[property(get, set)]
         pub position_ms: Cell<u64>,
+        #[property(get, set, builder(RepeatMode::default()))]
+        pub repeat_mode: Cell<RepeatMode>,
+        #[property(get, builder(SleepTimer::default()))]
+        pub sleep_timer: Cell<SleepTimer>,
+        /// How long is left until a sleep timer that runs for a duration stops
+        /// playback.
+        #[property(get)]
+        pub sleep_timer_remaining_s: Cell<u64>,
+        /// Over how many seconds the sleep timer fades out before it stops.
+        #[property(get, set)]
+        pub sleep_fade_out_s: Cell<u32>,
+        /// After how many seconds a track counts as played, unless
+        /// `play_threshold_percent` of it is reached earlier.
+        #[property(get, set)]
+        pub play_threshold_s: Cell<u32>,
+        /// After how much of its duration a track counts as played, in percent.
+        #[property(get, set)]
+        pub play_threshold_percent: Cell<u32>,
+        /// The linear volume chosen by the user, from silence at 0.0 to the
+        /// volume of the file at 1.0. It has no effect in bit-perfect mode.
+        #[property(get, set = Self::set_volume)]
+        pub volume: Cell<f64>,
+        /// The name of the GStreamer element to play through, or an empty string
+        /// to let GStreamer choose.
+        #[property(get, set = Self::set_audio_sink)]
+        pub audio_sink: RefCell<String>,
+        /// The device of the audio sink, or an empty string for its default one.
+        #[property(get, set = Self::set_audio_device)]
+        pub audio_device: RefCell<String>,
+        /// Whether to hand the samples to the audio sink as they are, without
+        /// converting, resampling or changing their volume.
+        #[property(get, set = Self::set_bit_perfect)]
+        pub bit_perfect: Cell<bool>,
+        /// Whether plays are submitted as listens to `listen_endpoint`.
+        #[property(get, set = Self::set_submit_listens)]
+        pub submit_listens: Cell<bool>,
+        #[property(get, set)]
+        pub listen_endpoint: RefCell<String>,
 
         pub play: OnceCell<gstreamer_play::Play>,
         pub play_signal_adapter: OnceCell<gstreamer_play::PlaySignalAdapter>,
-        pub mpris_player: OnceCell<mpris_server::Player>,
+        pub mpris: OnceCell<Rc<LocalServer<Mpris>>>,
 
+        /// Whether `duration_ms` belongs to the current item. It still holds the
+        /// duration of the previous item until the current one has been loaded.
+        pub duration_known: Cell<bool>,
         /// How many tracks failed in a row without one in between that played.
         pub consecutive_errors: Cell<u32>,
-        /// Whether the current item has already been counted as played.
+        /// Whether the current item has already been counted as played or
+        /// skipped.
         pub play_reported: Cell<bool>,
+        /// When a sleep timer that runs for a duration ends, in monotonic
+        /// microseconds.
+        pub sleep_deadline_us: Cell<i64>,
+        pub sleep_timeout: RefCell<Option<glib::SourceId>>,
+        /// The factor by which the sleep timer has faded out the volume.
+        pub sleep_fade: Cell<Option<f64>>,
+        /// Whether the pipeline is stopped, which is the only state in which its
+        /// output can be changed.
+        pub stopped: Cell<bool>,
+        /// Whether the output settings changed while the pipeline was running.
+        pub output_pending: Cell<bool>,
+        /// Where to continue once the pipeline was stopped to change the output.
+        pub resume_position_ms: Cell<Option<u64>>,
+        /// Listens that have not been submitted yet.
+        pub listen_queue: OnceCell<ListenQueue>,
+        /// Whether `set_current_index` only prepares the new item instead of
+        /// playing it, because a transition between recordings starts it.
+        pub hold_playback: Cell<bool>,
+        /// Whether a crossfade into the next recording was started from the
+        /// current item, so that one that fails is not tried again.
+        pub crossfade_started: Cell<bool>,
+        /// Ends the pause between two recordings, or moves a crossfade along.
+        pub transition_timeout: RefCell<Option<glib::SourceId>>,
+        /// The second pipeline, which plays out the end of the previous
+        /// recording during a crossfade.
+        pub crossfade_play: RefCell<Option<gstreamer_play::Play>>,
+        pub crossfade_signal_adapter: RefCell<Option<gstreamer_play::PlaySignalAdapter>>,
+        /// How far the crossfade has progressed, from 0.0 to 1.0.
+        pub crossfade: Cell<Option<f64>>,
+        /// When the crossfade began, in monotonic microseconds.
+        pub crossfade_start_us: Cell<i64>,
+        /// How long the crossfade lasts.
+        pub crossfade_ms: Cell<u64>,
     }
 
     impl Player {
+        fn set_volume(&self, volume: f64) {
+            self.volume.set(volume.clamp(0.0, 1.0));
+            self.obj().apply_volume();
+        }
+
+        fn set_audio_sink(&self, audio_sink: String) {
+            self.audio_sink.replace(audio_sink);
+            self.obj().update_output();
+        }
+
+        fn set_audio_device(&self, audio_device: String) {
+            self.audio_device.replace(audio_device);
+            self.obj().update_output();
+        }
+
+        fn set_bit_perfect(&self, bit_perfect: bool) {
+            self.bit_perfect.set(bit_perfect);
+            self.obj().update_output();
+        }
+
+        fn set_submit_listens(&self, submit_listens: bool) {
+            self.submit_listens.set(submit_listens);
+
+            // Submit what was queued before submission was turned off.
+            if submit_listens {
+                self.obj().submit_queued_listens();
+            }
+        }
+
         /// Set the program to play from.
         ///
         /// The player will always use its own copy of the program. Otherwise, changing the
@@ -80,6 +205,16 @@ mod imp {
             let playlist = self.playlist.get().unwrap();
 
             if let Some(item) = playlist.item(index) {
+                if index != self.current_index.get() {
+                    self.obj().report_skip_if_left_early();
+                }
+
+                // Choosing another item ends a transition, unless it is the
+                // transition itself that goes on.
+                if !self.hold_playback.get() {
+                    self.obj().cancel_transition();
+                }
+
                 if let Some(old_item) = playlist.item(self.current_index.get()) {
                     old_item
                         .downcast::<PlaylistItem>()
@@ -89,28 +224,6 @@ mod imp {
 
                 let item = item.downcast::<PlaylistItem>().unwrap();
 
-                let obj = self.obj().clone();
-                let item_clone = item.clone();
-                glib::spawn_future_local(async move {
-                    let Some(mpris_player) = obj.imp().mpris_player.get() else {
-                        return;
-                    };
-
-                    if let Err(err) = mpris_player
-                        .set_metadata(
-                            mpris_server::Metadata::builder()
-                                .title(item_clone.make_title())
-                                .artist(vec![item_clone
-                                    .make_subtitle()
-                                    .unwrap_or_else(String::new)])
-                                .build(),
-                        )
-                        .await
-                    {
-                        log::warn!("Failed to publish track metadata over MPRIS: {err}");
-                    }
-                });
-
                 let uri = match glib::filename_to_uri(item.path(), None) {
                     Ok(uri) => uri,
                     Err(err) => {
@@ -129,10 +242,18 @@ mod imp {
                 self.current_index.set(index);
                 item.set_is_playing(true);
 
+                self.duration_known.set(false);
                 self.play_reported.set(false);
+                self.crossfade_started.set(false);
+
+                self.obj().publish_current_item();
 
                 if self.playing.get() {
-                    play.play();
+                    if self.hold_playback.get() {
+                        play.pause();
+                    } else {
+                        play.play();
+                    }
                 }
             }
         }
@@ -188,7 +309,11 @@ mod imp {
 
             let obj = Fragile::new(self.obj().to_owned());
             play_signal_adapter.connect_end_of_stream(move |_| {
-                obj.get().next();
+                let obj = obj.get();
+                // Whatever the threshold, a track that was heard to the end
+                // was played.
+                obj.report_play();
+                obj.advance();
             });
 
             let obj = Fragile::new(self.obj().to_owned());
@@ -207,8 +332,15 @@ mod imp {
 
             let obj = Fragile::new(self.obj().to_owned());
             play_signal_adapter.connect_state_changed(move |_, state| {
-                if state == gstreamer_play::PlayState::Playing {
-                    obj.get().playback_started();
+                let obj = obj.get();
+                obj.imp()
+                    .stopped
+                    .set(state == gstreamer_play::PlayState::Stopped);
+
+                match state {
+                    gstreamer_play::PlayState::Playing => obj.playback_started(),
+                    gstreamer_play::PlayState::Stopped => obj.output_stopped(),
+                    _ => (),
                 }
             });
 
@@ -225,6 +357,8 @@ mod imp {
                     obj.imp().position_ms.set(position.mseconds());
                     obj.notify_position_ms();
                     obj.report_play_if_listened();
+                    obj.fade_out_at_end_of_recording();
+                    obj.crossfade_if_due();
                 }
             });
 
@@ -238,12 +372,78 @@ mod imp {
                     obj.notify_position_ms();
 
                     imp.duration_ms.set(duration.mseconds());
+                    imp.duration_known.set(true);
                     obj.notify_duration_ms();
+
+                    obj.publish_mpris_properties(vec![Property::Metadata(
+                        mpris::current_metadata(obj),
+                    )]);
                 }
             });
 
+            let obj = Fragile::new(self.obj().to_owned());
+            play_signal_adapter.connect_seek_done(move |_, position| {
+                let position = mpris_server::Time::from_millis(position.mseconds() as i64);
+                obj.get().publish_mpris(move |server| async move {
+                    server.emit(MprisSignal::Seeked { position }).await
+                });
+            });
+
+            let obj = self.obj().to_owned();
+            obj.connect_active_notify(|obj| {
+                obj.publish_mpris_properties(vec![
+                    Property::PlaybackStatus(mpris::playback_status(obj)),
+                    Property::CanPlay(obj.active()),
+                    Property::CanPause(obj.active()),
+                    Property::CanSeek(obj.active()),
+                ]);
+            });
+
+            obj.connect_volume_notify(|obj| {
+                obj.publish_mpris_properties(vec![Property::Volume(obj.volume())]);
+            });
+
+            obj.connect_playing_notify(|obj| {
+                obj.publish_mpris_properties(vec![Property::PlaybackStatus(
+                    mpris::playback_status(obj),
+                )]);
+            });
+
+            obj.connect_stopped_notify(|obj| {
+                obj.publish_mpris_properties(vec![Property::PlaybackStatus(
+                    mpris::playback_status(obj),
+                )]);
+            });
+
+            obj.connect_shuffled_notify(|obj| {
+                obj.publish_mpris_properties(vec![Property::Shuffle(obj.shuffled())]);
+            });
+
+            obj.connect_repeat_mode_notify(|obj| {
+                obj.publish_mpris_properties(vec![
+                    Property::LoopStatus(mpris::loop_status(obj)),
+                    Property::CanGoNext(obj.has_next()),
+                    Property::CanGoPrevious(obj.has_previous()),
+                ]);
+            });
+
+            obj.connect_program_notify(|obj| {
+                obj.publish_mpris_properties(vec![Property::CanGoNext(obj.has_next())]);
+            });
+
+            self.playlist.get().unwrap().connect_items_changed(clone!(
+                #[weak]
+                obj,
+                move |_, position, removed, added| {
+                    obj.publish_playlist_change(position, removed, added);
+                }
+            ));
+
             self.play.set(play).unwrap();
             self.play_signal_adapter.set(play_signal_adapter).unwrap();
+
+            self.stopped.set(true);
+            obj.apply_output();
         }
     }
 }
@@ -261,6 +461,9 @@ impl Player {
             .property("current-index", 0u32)
             .property("position-ms", 0u64)
             .property("duration-ms", 60_000u64)
+            .property("volume", 1.0)
+            .property("play-threshold-s", 240u32)
+            .property("play-threshold-percent", 50u32)
             .build()
     }
 
@@ -390,6 +593,7 @@ impl Player {
         tracks: &[(&Track, usize)],
     ) -> Vec<PlaylistItem> {
         let performances = recording.performers_string();
+        let cover_art = self.library().unwrap().recording_cover_art(recording);
 
         let track_title = |track: &Track, number: usize| -> Option<String> {
             let title = track
@@ -420,6 +624,7 @@ impl Player {
                     track_title(track, *number).as_deref(),
                     self.library_path_to_file_path(&track.path),
                     &track.track_id,
+                    cover_art.as_deref(),
                 )
             })
             .collect()
@@ -463,6 +668,218 @@ impl Player {
         }
     }
 
+    /// Insert playlist items right after the recording that is playing, so that they are played
+    /// next without cutting it short, and return the index of the first new item. Like
+    /// [`Self::append`] if the playlist is empty.
+    pub fn play_next(&self, items: Vec<PlaylistItem>) -> Result<u32> {
+        if items.is_empty() {
+            return Err(anyhow!("At least one item has to be added to the playlist"));
+        }
+
+        if self.playlist().n_items() == 0 {
+            return self.append(items);
+        }
+
+        let position = self.recording_range(self.current_index()).end;
+        self.edit_playlist(|playlist| playlist.splice(position, 0, &items));
+
+        Ok(position)
+    }
+
+    /// Move the recording of the item at `from` to where the recording of the item at `to` is.
+    /// Moving it down puts it after that recording, moving it up puts it before.
+    pub fn move_recording(&self, from: u32, to: u32) {
+        let playlist = self.playlist();
+        if from >= playlist.n_items() || to >= playlist.n_items() {
+            return;
+        }
+
+        let source = self.recording_range(from);
+        let target = self.recording_range(to);
+        if source == target {
+            return;
+        }
+
+        let items = source
+            .clone()
+            .filter_map(|index| playlist.item(index))
+            .collect::<Vec<_>>();
+        let n_items = source.end - source.start;
+
+        let position = if target.start > source.start {
+            target.end - n_items
+        } else {
+            target.start
+        };
+
+        self.edit_playlist(|playlist| {
+            playlist.splice(source.start, n_items, &[] as &[glib::Object]);
+            playlist.splice(position, 0, &items);
+        });
+    }
+
+    /// Remove the item at `index` from the playlist.
+    pub fn remove(&self, index: u32) {
+        if index < self.playlist().n_items() {
+            self.remove_range(index..index + 1);
+        }
+    }
+
+    /// Remove the recording of the item at `index` from the playlist.
+    pub fn remove_recording(&self, index: u32) {
+        if index < self.playlist().n_items() {
+            self.remove_range(self.recording_range(index));
+        }
+    }
+
+    /// Stop playing and empty the playlist. This also stops the program, which would otherwise
+    /// fill it again.
+    pub fn clear(&self) {
+        let imp = self.imp();
+
+        imp.play.get().unwrap().stop();
+        self.set_playing(false);
+        self.cancel_transition();
+        self.cancel_program();
+        self.cancel_sleep_timer();
+        self.set_active(false);
+        self.set_shuffled(false);
+        self.set_stopped(false);
+
+        imp.current_index.set(0);
+        self.notify_current_index();
+        imp.duration_known.set(false);
+        imp.position_ms.set(0);
+        self.notify_position_ms();
+
+        self.playlist().remove_all();
+    }
+
+    /// Shuffle the recordings after the one that is playing. The tracks of each recording stay
+    /// together and in order, so that a work is still heard from its beginning to its end.
+    pub fn shuffle(&self) {
+        let playlist = self.playlist();
+        if playlist.n_items() == 0 {
+            return;
+        }
+
+        let start = self.recording_range(self.current_index()).end;
+
+        let mut recordings: Vec<Vec<PlaylistItem>> = Vec::new();
+        for item in playlist
+            .iter::<PlaylistItem>()
+            .skip(start as usize)
+            .filter_map(|item| item.ok())
+        {
+            match recordings.last_mut() {
+                Some(recording) if !item.is_title() => recording.push(item),
+                _ => recordings.push(vec![item]),
+            }
+        }
+
+        for i in (1..recordings.len()).rev() {
+            let j = glib::random_int_range(0, i as i32 + 1) as usize;
+            recordings.swap(i, j);
+        }
+
+        let items = recordings.concat();
+        self.edit_playlist(|playlist| playlist.splice(start, items.len() as u32, &items));
+        self.set_shuffled(true);
+    }
+
+    /// The items that belong to the same recording as the item at `index`. Every recording
+    /// starts with a title item.
+    fn recording_range(&self, index: u32) -> Range<u32> {
+        let playlist = self.playlist();
+        let is_title = |index: u32| {
+            playlist
+                .item(index)
+                .and_downcast::<PlaylistItem>()
+                .is_some_and(|item| item.is_title())
+        };
+
+        let mut start = index;
+        while start > 0 && !is_title(start) {
+            start -= 1;
+        }
+
+        let mut end = index + 1;
+        while end < playlist.n_items() && !is_title(end) {
+            end += 1;
+        }
+
+        start..end
+    }
+
+    /// Remove the items in `range`. If the current item is among them, playback continues with
+    /// the item after them, or stops at the one before them if there is none.
+    fn remove_range(&self, range: Range<u32>) {
+        let playlist = self.playlist();
+
+        if range.start == 0 && range.end >= playlist.n_items() {
+            self.clear();
+            return;
+        }
+
+        let mut removed = range.end - range.start;
+        let mut replacements = Vec::new();
+
+        // The rest of a recording whose first items are removed needs a new title item. It is
+        // replaced in the playlist, so that it is shown as one.
+        let starts_recording = playlist
+            .item(range.start)
+            .and_downcast::<PlaylistItem>()
+            .is_some_and(|item| item.is_title());
+
+        if let Some(next) = playlist.item(range.end).and_downcast::<PlaylistItem>() {
+            if starts_recording && !next.is_title() {
+                next.set_is_title(true);
+                replacements.push(next);
+                removed += 1;
+            }
+        }
+
+        let current_index = self.current_index();
+        if !range.contains(&current_index) {
+            self.edit_playlist(|playlist| playlist.splice(range.start, removed, &replacements));
+            return;
+        }
+
+        // Removing the current item skips it. This has to be recorded while the
+        // index still points at it.
+        self.report_skip_if_left_early();
+
+        if let Some(item) = self.current_item() {
+            item.set_is_playing(false);
+        }
+
+        let following = playlist.item(range.end);
+        playlist.splice(range.start, removed, &replacements);
+
+        match following.and_then(|item| playlist.find(&item)) {
+            Some(index) => self.set_current_index(index),
+            None => {
+                self.pause();
+                self.set_current_index(range.start - 1);
+            }
+        }
+    }
+
+    /// Change the playlist without affecting the current item, which `edit` must not remove.
+    fn edit_playlist(&self, edit: impl FnOnce(&gio::ListStore)) {
+        let playlist = self.playlist();
+        let current_item = self.current_item();
+
+        edit(&playlist);
+
+        if let Some(index) = current_item.and_then(|item| playlist.find(&item)) {
+            if index != self.current_index() {
+                self.imp().current_index.set(index);
+                self.notify_current_index();
+            }
+        }
+    }
+
     /// Generate new playlist items based on the current program and immediately start playing the
     /// first new item.
     pub fn play_from_program(&self) {
@@ -498,28 +915,525 @@ impl Player {
     pub fn play(&self) {
         let imp = self.imp();
         imp.play.get().unwrap().play();
+        self.set_stopped(false);
         self.set_playing(true);
-        self.publish_playback_status(mpris_server::PlaybackStatus::Playing);
     }
 
     pub fn pause(&self) {
         let imp = self.imp();
+        self.cancel_transition();
         imp.play.get().unwrap().pause();
         self.set_playing(false);
-        self.publish_playback_status(mpris_server::PlaybackStatus::Paused);
     }
 
-    fn publish_playback_status(&self, status: mpris_server::PlaybackStatus) {
-        let obj = self.clone();
-        glib::spawn_future_local(async move {
-            let Some(mpris_player) = obj.imp().mpris_player.get() else {
+    /// Pause and return to the start of the current item.
+    pub fn stop(&self) {
+        self.set_stopped(true);
+        self.pause();
+        self.seek_to(0);
+    }
+
+    fn set_stopped(&self, stopped: bool) {
+        let imp = self.imp();
+        if imp.stopped.replace(stopped) != stopped {
+            self.notify_stopped();
+        }
+    }
+
+    /// Hand the volume to the pipeline, taking the sleep timer and a crossfade
+    /// into account. Bit-perfect output always plays at the volume of the file.
+    fn apply_volume(&self) {
+        let imp = self.imp();
+
+        let Some(play) = imp.play.get() else {
+            return;
+        };
+
+        let volume = if imp.bit_perfect.get() {
+            1.0
+        } else {
+            imp.volume.get() * imp.sleep_fade.get().unwrap_or(1.0)
+        };
+
+        // As with the sleep timer, the cube of the volume makes for an even
+        // fade. Until the crossfade begins, the second pipeline plays the same
+        // recording and stays silent, so that it is never heard twice.
+        let crossfade = imp.crossfade.get();
+        play.set_volume(volume * crossfade.map_or(1.0, |progress| progress.powi(3)));
+
+        if let Some(crossfade_play) = imp.crossfade_play.borrow().as_ref() {
+            crossfade_play
+                .set_volume(volume * crossfade.map_or(0.0, |progress| (1.0 - progress).powi(3)));
+        }
+    }
+
+    /// Use the output settings, stopping the pipeline first if it is running.
+    /// Playback continues where it was once it has stopped.
+    fn update_output(&self) {
+        let imp = self.imp();
+
+        if imp.play.get().is_none() || imp.stopped.get() {
+            self.apply_output();
+            return;
+        }
+
+        imp.output_pending.set(true);
+
+        if imp.resume_position_ms.get().is_none() {
+            imp.resume_position_ms.set(Some(imp.position_ms.get()));
+            imp.play.get().unwrap().stop();
+        }
+    }
+
+    fn output_stopped(&self) {
+        let imp = self.imp();
+
+        if !imp.output_pending.get() {
+            return;
+        }
+
+        self.apply_output();
+
+        if let Some(position_ms) = imp.resume_position_ms.take() {
+            // This is the same item going on, so it must not count as another
+            // play.
+            let play_reported = imp.play_reported.get();
+            self.set_current_index(self.current_index());
+            imp.play_reported.set(play_reported);
+
+            // The seek is carried out once the pipeline has been prerolled.
+            if !self.playing() {
+                imp.play.get().unwrap().pause();
+            }
+
+            if position_ms > 0 {
+                self.seek_to(position_ms);
+            }
+        }
+    }
+
+    /// Configure the pipeline, which has to be stopped, for the output settings.
+    fn apply_output(&self) {
+        let imp = self.imp();
+
+        let Some(play) = imp.play.get() else {
+            return;
+        };
+
+        imp.output_pending.set(false);
+
+        let pipeline = play.pipeline();
+        pipeline.set_property("audio-sink", self.make_audio_sink());
+
+        // Without software volume, the volume is left to the sink, and native
+        // audio keeps the sink from converting or resampling the samples.
+        pipeline.set_property_from_str(
+            "flags",
+            if imp.bit_perfect.get() {
+                "audio+native-audio"
+            } else {
+                "audio+soft-volume"
+            },
+        );
+
+        self.apply_volume();
+    }
+
+    /// The configured audio sink, or none to let GStreamer choose one.
+    fn make_audio_sink(&self) -> Option<gst::Element> {
+        let imp = self.imp();
+
+        let name = imp.audio_sink.borrow().clone();
+        if name.is_empty() {
+            return None;
+        }
+
+        let sink = match gst::ElementFactory::make(&name).build() {
+            Ok(sink) => sink,
+            Err(err) => {
+                log::warn!("Failed to create the audio sink {name}: {err}");
+                self.report_error(&format_translated!(
+                    gettext("The audio output {} is not available."),
+                    name
+                ));
+                return None;
+            }
+        };
+
+        let device = imp.audio_device.borrow().clone();
+        if !device.is_empty() {
+            // PipeWire calls the device a target object.
+            if sink.has_property_with_type("target-object", String::static_type()) {
+                sink.set_property("target-object", &device);
+            } else if sink.has_property_with_type("device", String::static_type()) {
+                sink.set_property("device", &device);
+            } else {
+                log::warn!("The audio sink {name} does not support choosing a device");
+            }
+        }
+
+        Some(sink)
+    }
+
+    /// Pause playback after `minutes`, fading out over the last
+    /// `sleep-fade-out-s` seconds. This replaces any sleep timer that was set
+    /// before.
+    pub fn sleep_after(&self, minutes: u32) {
+        self.cancel_sleep_timer();
+
+        let imp = self.imp();
+        imp.sleep_deadline_us
+            .set(glib::monotonic_time() + i64::from(minutes) * 60_000_000);
+
+        let source = glib::timeout_add_local(
+            Duration::from_millis(SLEEP_TIMER_INTERVAL_MS),
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || obj.sleep_timer_tick()
+            ),
+        );
+
+        imp.sleep_timeout.replace(Some(source));
+        imp.sleep_timer.set(SleepTimer::Duration);
+        imp.sleep_timer_remaining_s.set(u64::from(minutes) * 60);
+        self.notify_sleep_timer_remaining_s();
+        self.notify_sleep_timer();
+    }
+
+    /// Pause playback once the current recording has ended, so that a work is
+    /// not cut off in the middle of a movement. Its last track fades out.
+    pub fn sleep_at_end_of_recording(&self) {
+        self.cancel_sleep_timer();
+        self.imp().sleep_timer.set(SleepTimer::EndOfRecording);
+        self.notify_sleep_timer();
+    }
+
+    /// Let playback go on, restoring the volume if the sleep timer has already
+    /// started to fade out.
+    pub fn cancel_sleep_timer(&self) {
+        let imp = self.imp();
+
+        if let Some(source) = imp.sleep_timeout.take() {
+            source.remove();
+        }
+
+        if imp.sleep_fade.take().is_some() {
+            self.apply_volume();
+        }
+
+        imp.sleep_timer_remaining_s.set(0);
+        self.notify_sleep_timer_remaining_s();
+
+        if imp.sleep_timer.get() != SleepTimer::Off {
+            imp.sleep_timer.set(SleepTimer::Off);
+            self.notify_sleep_timer();
+        }
+    }
+
+    fn sleep_timer_tick(&self) -> glib::ControlFlow {
+        let imp = self.imp();
+        let remaining_ms = (imp.sleep_deadline_us.get() - glib::monotonic_time()) / 1000;
+
+        if remaining_ms <= 0 {
+            // The source ends by returning `Break` and must not be removed.
+            imp.sleep_timeout.take();
+            self.sleep();
+            return glib::ControlFlow::Break;
+        }
+
+        let remaining_s = (remaining_ms as u64).div_ceil(1000);
+        if imp.sleep_timer_remaining_s.get() != remaining_s {
+            imp.sleep_timer_remaining_s.set(remaining_s);
+            self.notify_sleep_timer_remaining_s();
+        }
+
+        if self.playing() {
+            self.fade_out(remaining_ms as u64);
+        }
+
+        glib::ControlFlow::Continue
+    }
+
+    /// Called from the position updates to fade out the last track of the
+    /// recording when the sleep timer waits for its end.
+    fn fade_out_at_end_of_recording(&self) {
+        if self.sleep_timer() != SleepTimer::EndOfRecording || !self.is_end_of_recording() {
+            return;
+        }
+
+        if let Some(duration_ms) = self.current_duration_ms() {
+            self.fade_out(duration_ms.saturating_sub(self.position_ms()));
+        }
+    }
+
+    /// Lower the volume for the sleep timer, given that playback stops in
+    /// `remaining_ms`. Nothing changes before the fade out begins, and nothing
+    /// at all in bit-perfect mode.
+    fn fade_out(&self, remaining_ms: u64) {
+        let imp = self.imp();
+
+        let fade_out_ms = u64::from(imp.sleep_fade_out_s.get()) * 1000;
+        if remaining_ms >= fade_out_ms {
+            return;
+        }
+
+        // Loudness follows the cube of the linear volume much more closely
+        // than the volume itself, so this sounds like an even fade.
+        let fraction = remaining_ms as f64 / fade_out_ms as f64;
+        imp.sleep_fade.set(Some(fraction.powi(3)));
+        self.apply_volume();
+    }
+
+    /// Stop playback because the sleep timer ran out.
+    fn sleep(&self) {
+        self.pause();
+        self.cancel_sleep_timer();
+    }
+
+    /// Whether the sleep timer is about to stop playback. A program does not
+    /// add recordings that would be cut off or faded out right away.
+    fn sleep_is_near(&self) -> bool {
+        let imp = self.imp();
+
+        match imp.sleep_timer.get() {
+            SleepTimer::Off => false,
+            SleepTimer::Duration => {
+                let remaining_ms = (imp.sleep_deadline_us.get() - glib::monotonic_time()) / 1000;
+                remaining_ms <= i64::from(imp.sleep_fade_out_s.get()) * 1000
+            }
+            SleepTimer::EndOfRecording => true,
+        }
+    }
+
+    /// How long the transition of the current program lasts, if it is `kind`.
+    fn transition_ms(&self, kind: ProgramTransition) -> Option<u64> {
+        self.program()
+            .filter(|program| program.transition() == kind)
+            .map(|program| u64::from(program.transition_duration_s()) * 1000)
+            .filter(|ms| *ms > 0)
+    }
+
+    /// Whether going on from the current item leads into another recording,
+    /// which is where a program's transition takes place.
+    fn is_recording_boundary(&self) -> bool {
+        self.is_end_of_recording()
+            && self.repeat_mode() != RepeatMode::Recording
+            && !self.sleep_is_near()
+    }
+
+    /// Go on with the next recording after a moment of silence.
+    ///
+    /// The next item is prepared right away, so that it starts without delay
+    /// once the pause is over. Pausing or choosing another item in between
+    /// ends the pause early.
+    fn pause_between_recordings(&self, pause_ms: u64) {
+        let imp = self.imp();
+
+        imp.hold_playback.set(true);
+        self.next();
+        imp.hold_playback.set(false);
+
+        // Generating the next recording may have failed and stopped playback.
+        if !self.playing() {
+            return;
+        }
+
+        let source = glib::timeout_add_local_once(
+            Duration::from_millis(pause_ms),
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move || {
+                    // The source is done and must not be removed.
+                    obj.imp().transition_timeout.take();
+                    obj.play();
+                }
+            ),
+        );
+
+        imp.transition_timeout.replace(Some(source));
+    }
+
+    /// Called from the position updates to start a crossfade into the next
+    /// recording once the current one is about to end.
+    ///
+    /// Crossfading needs a second pipeline playing at the same time, with
+    /// volumes of its own, so it is left out in bit-perfect mode.
+    fn crossfade_if_due(&self) {
+        let imp = self.imp();
+
+        if imp.crossfade_started.get() || !self.playing() || imp.bit_perfect.get() {
+            return;
+        }
+
+        let Some(crossfade_ms) = self.transition_ms(ProgramTransition::Crossfade) else {
+            return;
+        };
+
+        let Some(duration_ms) = self.current_duration_ms() else {
+            return;
+        };
+
+        let remaining_ms = duration_ms.saturating_sub(self.position_ms());
+        if remaining_ms == 0 || remaining_ms > crossfade_ms || !self.is_recording_boundary() {
+            return;
+        }
+
+        imp.crossfade_started.set(true);
+        self.start_crossfade(remaining_ms);
+    }
+
+    /// Hand the rest of the current item to a second pipeline, which goes on
+    /// with it while the next recording fades in. The switch happens as soon
+    /// as that pipeline plays, so that the end of the recording is not cut off.
+    fn start_crossfade(&self, crossfade_ms: u64) {
+        let imp = self.imp();
+
+        let Some(item) = self.current_item() else {
+            return;
+        };
+
+        let uri = match glib::filename_to_uri(item.path(), None) {
+            Ok(uri) => uri,
+            Err(err) => {
+                log::warn!("Failed to build a URI for {}: {err}", item.path().display());
                 return;
-            };
+            }
+        };
+
+        let play = gstreamer_play::Play::new(None::<gstreamer_play::PlayVideoRenderer>);
+        play.set_video_track_enabled(false);
+
+        let pipeline = play.pipeline();
+        pipeline.set_property("audio-sink", self.make_audio_sink());
+        pipeline.set_property_from_str("flags", "audio+soft-volume");
 
-            if let Err(err) = mpris_player.set_playback_status(status).await {
-                log::warn!("Failed to publish playback status over MPRIS: {err}");
+        let signal_adapter = gstreamer_play::PlaySignalAdapter::new(&play);
+
+        let obj = Fragile::new(self.to_owned());
+        signal_adapter.connect_state_changed(move |_, state| {
+            if state == gstreamer_play::PlayState::Playing {
+                obj.get().begin_crossfade();
             }
         });
+
+        // Without the second pipeline, the recording just plays to its end.
+        let obj = Fragile::new(self.to_owned());
+        signal_adapter.connect_error(move |_, error, _| {
+            log::warn!("Failed to crossfade into the next recording: {error}");
+            obj.get().cancel_transition();
+        });
+
+        let obj = Fragile::new(self.to_owned());
+        signal_adapter.connect_end_of_stream(move |_| {
+            obj.get().cancel_transition();
+        });
+
+        imp.crossfade_ms.set(crossfade_ms);
+        imp.crossfade_play.replace(Some(play.clone()));
+        imp.crossfade_signal_adapter.replace(Some(signal_adapter));
+        self.apply_volume();
+
+        // The position updates are up to 250 ms old, so the pipeline is asked
+        // directly. The seek is carried out once the pipeline has been
+        // prerolled.
+        let position = imp
+            .play
+            .get()
+            .unwrap()
+            .position()
+            .unwrap_or(gst::ClockTime::from_mseconds(self.position_ms()));
+
+        play.set_uri(Some(&uri));
+        play.pause();
+        play.seek(position);
+        play.play();
+    }
+
+    /// Switch to the next recording and start fading, once the second pipeline
+    /// has taken over the end of the current one.
+    fn begin_crossfade(&self) {
+        let imp = self.imp();
+
+        if imp.crossfade_play.borrow().is_none() || imp.crossfade.get().is_some() {
+            return;
+        }
+
+        // The rest of the recording is still being heard.
+        self.report_play();
+
+        imp.hold_playback.set(true);
+        self.next();
+        imp.hold_playback.set(false);
+
+        if !self.playing() {
+            self.cancel_transition();
+            return;
+        }
+
+        imp.crossfade.set(Some(0.0));
+        imp.crossfade_start_us.set(glib::monotonic_time());
+        self.apply_volume();
+        imp.play.get().unwrap().play();
+
+        let source = glib::timeout_add_local(
+            Duration::from_millis(CROSSFADE_INTERVAL_MS),
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || obj.crossfade_tick()
+            ),
+        );
+
+        imp.transition_timeout.replace(Some(source));
+    }
+
+    fn crossfade_tick(&self) -> glib::ControlFlow {
+        let imp = self.imp();
+
+        let elapsed_ms = (glib::monotonic_time() - imp.crossfade_start_us.get()) / 1000;
+        let progress = elapsed_ms as f64 / imp.crossfade_ms.get().max(1) as f64;
+
+        if progress >= 1.0 {
+            // The source ends by returning `Break` and must not be removed.
+            imp.transition_timeout.take();
+            self.cancel_transition();
+            return glib::ControlFlow::Break;
+        }
+
+        imp.crossfade.set(Some(progress.max(0.0)));
+        self.apply_volume();
+
+        glib::ControlFlow::Continue
+    }
+
+    /// End a pause between recordings or a crossfade. The next recording is
+    /// left where it is, at its full volume.
+    fn cancel_transition(&self) {
+        let imp = self.imp();
+
+        if let Some(source) = imp.transition_timeout.take() {
+            source.remove();
+        }
+
+        imp.crossfade_signal_adapter.take();
+        if let Some(play) = imp.crossfade_play.take() {
+            play.stop();
+        }
+
+        imp.crossfade.take();
+        self.apply_volume();
+    }
+
+    /// Whether the current item is the last track of its recording.
+    fn is_end_of_recording(&self) -> bool {
+        self.playlist().n_items() > 0
+            && self.current_index() + 1 >= self.recording_range(self.current_index()).end
     }
 
     fn playback_started(&self) {
@@ -536,18 +1450,67 @@ impl Player {
     /// mean accumulating playback time separately; going by position is what
     /// most players do and is close enough for statistics.
     fn report_play_if_listened(&self) {
+        let Some(duration_ms) = self.current_duration_ms().filter(|ms| *ms > 0) else {
+            return;
+        };
+
+        if self.position_ms() >= self.play_threshold_ms(duration_ms) {
+            self.report_play();
+        }
+    }
+
+    /// How far into a track of `duration_ms` it counts as played.
+    fn play_threshold_ms(&self, duration_ms: u64) -> u64 {
+        let share_ms = duration_ms * self.play_threshold_percent().min(100) as u64 / 100;
+        share_ms.min(self.play_threshold_s() as u64 * 1000)
+    }
+
+    /// Record the current item as played, unless it already was.
+    fn report_play(&self) {
         let imp = self.imp();
 
         if imp.play_reported.get() {
             return;
         }
 
-        let duration_ms = imp.duration_ms.get();
-        if duration_ms == 0 {
+        // The duration of the previous item is still around until the current
+        // one has been loaded.
+        let Some(duration_ms) = self.current_duration_ms().filter(|ms| *ms > 0) else {
+            return;
+        };
+
+        imp.play_reported.set(true);
+
+        let Some(item) = self.current_item() else {
+            return;
+        };
+
+        if let Some(library) = imp.library.borrow().as_ref() {
+            match library.track_played(&item.track_id(), duration_ms) {
+                Ok(play_id) => {
+                    if self.submit_listens() {
+                        self.queue_listen(library, &play_id);
+                    }
+                }
+                Err(err) => log::warn!("Failed to record that a track was played: {err:?}"),
+            }
+        }
+    }
+
+    /// Record the current item as skipped if it is left before it counted as
+    /// played.
+    ///
+    /// An item that never got going, because it failed or was left right
+    /// away while loading, is not counted either way.
+    fn report_skip_if_left_early(&self) {
+        let imp = self.imp();
+
+        if imp.play_reported.get() || self.current_duration_ms().is_none() {
             return;
         }
 
-        if imp.position_ms.get() < (duration_ms / 2).min(PLAY_THRESHOLD_MS) {
+        let position_ms = imp.position_ms.get();
+        if position_ms == 0 {
             return;
         }
 
@@ -558,12 +1521,64 @@ impl Player {
         };
 
         if let Some(library) = imp.library.borrow().as_ref() {
-            if let Err(err) = library.track_played(&item.track_id()) {
-                log::warn!("Failed to record that a track was played: {err:?}");
+            if let Err(err) = library.track_skipped(&item.track_id(), position_ms) {
+                log::warn!("Failed to record that a track was skipped: {err:?}");
             }
         }
     }
 
+    /// Queue the play `play_id` as a listen and submit it with the ones that
+    /// are still queued.
+    fn queue_listen(&self, library: &Library, play_id: &str) {
+        let result = library
+            .listen(play_id)
+            .and_then(|listen| self.listen_queue().push(&listen));
+
+        match result {
+            Ok(()) => self.submit_queued_listens(),
+            Err(err) => log::warn!("Failed to queue a listen: {err:?}"),
+        }
+    }
+
+    /// Submit the queued listens in the background, with the token from the
+    /// keyring. Those that cannot be submitted stay queued for the next play.
+    fn submit_queued_listens(&self) {
+        let endpoint = self.listen_endpoint();
+        if endpoint.is_empty() {
+            return;
+        }
+
+        let queue = self.listen_queue().clone();
+
+        glib::spawn_future_local(async move {
+            let token = match listen_token::load().await {
+                Ok(token) => token,
+                Err(err) => {
+                    log::warn!("Failed to read the listen token, keeping listens queued: {err:?}");
+                    return;
+                }
+            };
+
+            let handle = queue.submit(&endpoint, &token);
+
+            while let Ok(msg) = handle.receiver.recv().await {
+                if let ProcessMsg::Result(Err(err)) = msg {
+                    log::warn!("Failed to submit listens, keeping them queued: {err:?}");
+                }
+            }
+        });
+    }
+
+    fn listen_queue(&self) -> &ListenQueue {
+        self.imp().listen_queue.get_or_init(|| {
+            ListenQueue::new(
+                glib::user_data_dir()
+                    .join(config::APP_ID)
+                    .join("listens.jsonl"),
+            )
+        })
+    }
+
     /// Continue after the current item failed to play.
     fn skip_failed_item(&self) {
         let imp = self.imp();
@@ -578,9 +1593,10 @@ impl Player {
             return;
         }
 
-        // A program keeps generating items, so there is always something to
-        // skip to.
-        if self.current_index() + 1 < self.playlist().n_items() || self.program().is_some() {
+        // Failing is not the same as being skipped.
+        imp.play_reported.set(true);
+
+        if self.has_next() {
             self.next();
         } else {
             self.pause();
@@ -608,6 +1624,12 @@ impl Player {
             .seek(gst::ClockTime::from_mseconds(time_ms));
     }
 
+    /// The duration of the current item, once it has been loaded.
+    pub fn current_duration_ms(&self) -> Option<u64> {
+        let imp = self.imp();
+        imp.duration_known.get().then(|| imp.duration_ms.get())
+    }
+
     pub fn current_item(&self) -> Option<PlaylistItem> {
         let imp = self.imp();
         imp.playlist
@@ -617,9 +1639,27 @@ impl Player {
             .and_downcast::<PlaylistItem>()
     }
 
+    /// Whether there is an item to go on with. A program keeps generating
+    /// items, so there always is one while a program is playing, and so does
+    /// repeating the playlist.
+    pub fn has_next(&self) -> bool {
+        self.current_index() + 1 < self.playlist().n_items()
+            || self.program().is_some()
+            || (self.repeat_mode() == RepeatMode::Playlist && self.playlist().n_items() > 0)
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.current_index() > 0
+            || (self.repeat_mode() == RepeatMode::Playlist && self.playlist().n_items() > 0)
+    }
+
+    /// Go on with the next item. This is what the user asks for, so it leaves
+    /// a recording that is being repeated; see [`Self::advance`].
     pub fn next(&self) {
         if self.current_index() + 1 < self.playlist().n_items() {
             self.set_current_index(self.current_index() + 1);
+        } else if self.program().is_some() && self.sleep_is_near() {
+            self.sleep();
         } else if let Some(program) = self.program() {
             match self.generate_items(&program) {
                 Ok(index) => self.set_current_index(index),
@@ -629,72 +1669,161 @@ impl Player {
                     self.pause();
                 }
             }
+        } else if self.repeat_mode() == RepeatMode::Playlist && self.playlist().n_items() > 0 {
+            self.set_current_index(0);
+        }
+    }
+
+    /// Go on after the current item has ended. A recording that is being
+    /// repeated starts over with its first track after its last one.
+    ///
+    /// Every repetition goes through `set_current_index`, so each of them
+    /// counts as a play of its own.
+    ///
+    /// A sleep timer waiting for the end of the recording stops here. The next
+    /// recording is still selected, if there already is one, so that playback
+    /// picks up with it when resumed.
+    ///
+    /// Between two recordings, a program may ask for a pause. A crossfade has
+    /// already moved on before the end of the item.
+    fn advance(&self) {
+        if self.sleep_timer() == SleepTimer::EndOfRecording && self.is_end_of_recording() {
+            self.sleep();
+
+            if self.current_index() + 1 < self.playlist().n_items() {
+                self.set_current_index(self.current_index() + 1);
+            }
+        } else if self.repeat_mode() == RepeatMode::Recording && self.playlist().n_items() > 0 {
+            let recording = self.recording_range(self.current_index());
+
+            if self.current_index() + 1 < recording.end {
+                self.set_current_index(self.current_index() + 1);
+            } else {
+                self.set_current_index(recording.start);
+            }
+        } else if let Some(pause_ms) = self
+            .transition_ms(ProgramTransition::Pause)
+            .filter(|_| self.is_recording_boundary())
+        {
+            self.pause_between_recordings(pause_ms);
+        } else {
+            self.next();
         }
     }
 
     pub fn previous(&self) {
         if self.current_index() > 0 {
             self.set_current_index(self.current_index() - 1);
+        } else if self.repeat_mode() == RepeatMode::Playlist && self.playlist().n_items() > 0 {
+            self.set_current_index(self.playlist().n_items() - 1);
         }
     }
 
     async fn init_mpris(&self) -> Result<mpris_server::LocalServerRunTask> {
-        let mpris_player = mpris_server::Player::builder(config::APP_ID)
-            .desktop_entry(config::APP_ID)
-            .can_raise(true)
-            .can_play(true)
-            .can_pause(true)
-            .can_go_previous(true)
-            .can_go_next(true)
-            .build()
-            .await?;
-
-        mpris_player.connect_raise(clone!(
-            #[weak(rename_to = obj)]
-            self,
-            move |_| obj.emit_by_name::<()>("raise", &[])
-        ));
-
-        mpris_player.connect_play(clone!(
-            #[weak(rename_to = obj)]
-            self,
-            move |_| obj.play()
-        ));
-
-        mpris_player.connect_pause(clone!(
-            #[weak(rename_to = obj)]
-            self,
-            move |_| obj.pause()
-        ));
-
-        mpris_player.connect_play_pause(clone!(
-            #[weak(rename_to = obj)]
-            self,
-            move |_| obj.play_pause()
-        ));
-
-        mpris_player.connect_previous(clone!(
-            #[weak(rename_to = obj)]
-            self,
-            move |_| obj.previous()
-        ));
-
-        mpris_player.connect_next(clone!(
-            #[weak(rename_to = obj)]
-            self,
-            move |_| obj.next()
-        ));
-
-        let task = mpris_player.run();
+        let server = LocalServer::new_with_track_list(config::APP_ID, Mpris::new(self)).await?;
+        let task = server.run();
 
         self.imp()
-            .mpris_player
-            .set(mpris_player)
+            .mpris
+            .set(Rc::new(server))
             .map_err(|_| anyhow!("Player already initialized"))?;
 
         Ok(task)
     }
 
+    /// Publish a change over MPRIS, once the server is running.
+    fn publish_mpris<F, Fut>(&self, publish: F)
+    where
+        F: FnOnce(Rc<LocalServer<Mpris>>) -> Fut + 'static,
+        Fut: Future<Output = mpris_server::zbus::Result<()>> + 'static,
+    {
+        let Some(server) = self.imp().mpris.get().cloned() else {
+            return;
+        };
+
+        glib::spawn_future_local(async move {
+            if let Err(err) = publish(server).await {
+                log::warn!("Failed to publish a change over MPRIS: {err}");
+            }
+        });
+    }
+
+    fn publish_mpris_properties(&self, properties: Vec<Property>) {
+        self.publish_mpris(
+            move |server| async move { server.properties_changed(properties).await },
+        );
+    }
+
+    fn publish_current_item(&self) {
+        self.publish_mpris_properties(vec![
+            Property::Metadata(mpris::current_metadata(self)),
+            Property::CanGoNext(self.has_next()),
+            Property::CanGoPrevious(self.has_previous()),
+        ]);
+    }
+
+    /// Tell MPRIS clients about a change of the playlist. Additions are
+    /// reported one by one; for anything else, the removed items are already
+    /// gone, so the whole list is sent again.
+    ///
+    /// The playlist is edited in several steps at times, so everything that
+    /// does not describe the change itself is read once the edit is done.
+    fn publish_playlist_change(&self, position: u32, removed: u32, added: u32) {
+        let playlist = self.playlist();
+        let item = |index: u32| playlist.item(index).and_downcast::<PlaylistItem>();
+
+        let added_tracks = if removed == 0 {
+            (position..position + added)
+                .filter_map(|index| {
+                    let after_track = index
+                        .checked_sub(1)
+                        .and_then(&item)
+                        .map(|item| mpris::track_id(&item))
+                        .unwrap_or(mpris_server::TrackId::NO_TRACK);
+
+                    Some(TrackListSignal::TrackAdded {
+                        metadata: mpris::metadata(&item(index)?, None),
+                        after_track,
+                    })
+                })
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        let obj = self.clone();
+        self.publish_mpris(move |server| async move {
+            let signals = if removed == 0 {
+                added_tracks
+            } else {
+                vec![TrackListSignal::TrackListReplaced {
+                    tracks: obj
+                        .playlist()
+                        .iter::<PlaylistItem>()
+                        .filter_map(|item| item.ok())
+                        .map(|item| mpris::track_id(&item))
+                        .collect(),
+                    current_track: mpris::current_track_id(&obj),
+                }]
+            };
+
+            for signal in signals {
+                server.track_list_emit(signal).await?;
+            }
+
+            server
+                .track_list_properties_changed([TrackListProperty::Tracks])
+                .await?;
+
+            server
+                .properties_changed([
+                    Property::CanGoNext(obj.has_next()),
+                    Property::CanGoPrevious(obj.has_previous()),
+                ])
+                .await
+        });
+    }
+
     /// Generate new playlist items based on `program` and return the index of the first newly
     /// added item if successful.
     fn generate_items(&self, program: &Program) -> Result<u32> {
@@ -710,6 +1839,7 @@ impl Player {
             prefer_least_recently_played: program.prefer_least_recently_played(),
             avoid_repeated_composers: program.avoid_repeated_composers(),
             avoid_repeated_instruments: program.avoid_repeated_instruments(),
+            avoid_skipped: program.avoid_skipped(),
         };
 
         let recording = self
@@ -755,3 +1885,30 @@ impl Default for Player {
         Self::new()
     }
 }
+
+/// What the player does once it reaches the end of a recording or of the
+/// playlist.
+#[derive(glib::Enum, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[enum_type(name = "MusicusRepeatMode")]
+pub enum RepeatMode {
+    /// Stop at the end of the playlist, unless a program adds to it.
+    #[default]
+    Off,
+    /// Start over with the first item after the last one.
+    Playlist,
+    /// Play all tracks of the current recording over and over again.
+    Recording,
+}
+
+/// When the player stops playback by itself.
+#[derive(glib::Enum, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[enum_type(name = "MusicusSleepTimer")]
+pub enum SleepTimer {
+    /// Play until the playlist ends.
+    #[default]
+    Off,
+    /// Stop once a set time has passed.
+    Duration,
+    /// Stop at the end of the current recording.
+    EndOfRecording,
+}
@@ -1,14 +1,20 @@
 use std::cell::{Cell, OnceCell};
 
+use gettextrs::gettext;
 use gtk::{
-    gdk,
+    gdk, gio,
     glib::{self, clone, subclass::Signal, Properties},
     prelude::*,
     subclass::prelude::*,
 };
+use musicus_library::format_translated;
 use once_cell::sync::Lazy;
 
-use crate::player::Player;
+use crate::player::{Player, RepeatMode, SleepTimer};
+
+/// The durations the sleep timer menu offers, in minutes. Each has an action
+/// `sleep.after-{minutes}` in the menu of the template.
+const SLEEP_TIMER_MINUTES: &[u32] = &[15, 30, 45, 60, 90];
 
 mod imp {
     use super::*;
@@ -21,7 +27,10 @@ mod imp {
         pub player: OnceCell<Player>,
 
         pub seeking: Cell<bool>,
+        pub sleep_actions: OnceCell<gio::SimpleActionGroup>,
 
+        #[template_child]
+        pub cover_art_image: TemplateChild<gtk::Image>,
         #[template_child]
         pub title_label: TemplateChild<gtk::Label>,
         #[template_child]
@@ -40,6 +49,12 @@ mod imp {
         pub slider: TemplateChild<gtk::Scale>,
         #[template_child]
         pub remaining_time_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub volume_button: TemplateChild<gtk::ScaleButton>,
+        #[template_child]
+        pub repeat_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sleep_button: TemplateChild<gtk::MenuButton>,
     }
 
     impl PlayerBar {
@@ -53,6 +68,56 @@ mod imp {
                 } else {
                     self.subtitle_label.set_visible(false);
                 }
+
+                let cover_art = item.cover_art();
+                self.cover_art_image.set_from_file(cover_art.as_ref());
+                self.cover_art_image.set_visible(cover_art.is_some());
+            }
+        }
+
+        fn update_repeat_mode(&self) {
+            let (icon_name, tooltip) = match self.player.get().unwrap().repeat_mode() {
+                RepeatMode::Off => ("media-playlist-consecutive-symbolic", gettext("Repeat off")),
+                RepeatMode::Playlist => {
+                    ("media-playlist-repeat-symbolic", gettext("Repeat playlist"))
+                }
+                RepeatMode::Recording => (
+                    "media-playlist-repeat-song-symbolic",
+                    gettext("Repeat recording"),
+                ),
+            };
+
+            self.repeat_button.set_icon_name(icon_name);
+            self.repeat_button.set_tooltip_text(Some(&tooltip));
+        }
+
+        fn update_sleep_timer(&self) {
+            let player = self.player.get().unwrap();
+
+            let tooltip = match player.sleep_timer() {
+                SleepTimer::Off => gettext("Sleep timer"),
+                SleepTimer::Duration => format_translated!(
+                    gettext("Stops in {} min"),
+                    player.sleep_timer_remaining_s().div_ceil(60)
+                ),
+                SleepTimer::EndOfRecording => gettext("Stops after this recording"),
+            };
+
+            self.sleep_button.set_tooltip_text(Some(&tooltip));
+
+            if player.sleep_timer() == SleepTimer::Off {
+                self.sleep_button.remove_css_class("accent");
+            } else {
+                self.sleep_button.add_css_class("accent");
+            }
+
+            if let Some(action) = self
+                .sleep_actions
+                .get()
+                .and_then(|actions| actions.lookup_action("cancel"))
+                .and_downcast::<gio::SimpleAction>()
+            {
+                action.set_enabled(player.sleep_timer() != SleepTimer::Off);
             }
         }
 
@@ -122,6 +187,19 @@ mod imp {
                 .sync_create()
                 .build();
 
+            player
+                .bind_property("volume", &self.volume_button.get(), "value")
+                .bidirectional()
+                .sync_create()
+                .build();
+
+            // Bit-perfect output never changes the volume.
+            player
+                .bind_property("bit-perfect", &self.volume_button.get(), "sensitive")
+                .invert_boolean()
+                .sync_create()
+                .build();
+
             let obj = self.obj();
 
             player.connect_current_index_notify(clone!(
@@ -135,6 +213,54 @@ mod imp {
                 move |_, _, _, _| obj.imp().update_item()
             ));
 
+            player.connect_repeat_mode_notify(clone!(
+                #[weak]
+                obj,
+                move |_| obj.imp().update_repeat_mode()
+            ));
+            self.update_repeat_mode();
+
+            let mut sleep_actions = SLEEP_TIMER_MINUTES
+                .iter()
+                .map(|minutes| {
+                    let player = player.clone();
+                    gio::ActionEntry::builder(&format!("after-{minutes}"))
+                        .activate(move |_, _, _| player.sleep_after(*minutes))
+                        .build()
+                })
+                .collect::<Vec<_>>();
+
+            let player_clone = player.clone();
+            sleep_actions.push(
+                gio::ActionEntry::builder("end-of-recording")
+                    .activate(move |_, _, _| player_clone.sleep_at_end_of_recording())
+                    .build(),
+            );
+
+            let player_clone = player.clone();
+            sleep_actions.push(
+                gio::ActionEntry::builder("cancel")
+                    .activate(move |_, _, _| player_clone.cancel_sleep_timer())
+                    .build(),
+            );
+
+            let actions = gio::SimpleActionGroup::new();
+            actions.add_action_entries(sleep_actions);
+            obj.insert_action_group("sleep", Some(&actions));
+            self.sleep_actions.set(actions).unwrap();
+
+            player.connect_sleep_timer_notify(clone!(
+                #[weak]
+                obj,
+                move |_| obj.imp().update_sleep_timer()
+            ));
+            player.connect_sleep_timer_remaining_s_notify(clone!(
+                #[weak]
+                obj,
+                move |_| obj.imp().update_sleep_timer()
+            ));
+            self.update_sleep_timer();
+
             player.connect_position_ms_notify(clone!(
                 #[weak]
                 obj,
@@ -229,6 +355,17 @@ impl PlayerBar {
         self.emit_by_name::<()>("show-playlist", &[&button.is_active()]);
     }
 
+    #[template_callback]
+    fn cycle_repeat_mode(&self) {
+        let player = self.player();
+
+        player.set_repeat_mode(match player.repeat_mode() {
+            RepeatMode::Off => RepeatMode::Playlist,
+            RepeatMode::Playlist => RepeatMode::Recording,
+            RepeatMode::Recording => RepeatMode::Off,
+        });
+    }
+
     #[template_callback]
     fn next(&self) {
         self.player().next();
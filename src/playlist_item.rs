@@ -1,6 +1,7 @@
 use std::{
     cell::{Cell, OnceCell},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use gtk::{glib, glib::Properties, prelude::*, subclass::prelude::*};
@@ -14,8 +15,10 @@ mod imp {
         #[property(get, set)]
         pub is_playing: Cell<bool>,
 
-        #[property(get, construct_only)]
-        pub is_title: OnceCell<bool>,
+        /// Whether the item is the first one of its recording, which shows the
+        /// recording in the playlist.
+        #[property(get, set)]
+        pub is_title: Cell<bool>,
 
         #[property(get, construct_only)]
         pub composers: OnceCell<Option<String>>,
@@ -34,6 +37,13 @@ mod imp {
 
         #[property(get, construct_only)]
         pub track_id: OnceCell<String>,
+
+        #[property(get, construct_only, nullable)]
+        pub cover_art: OnceCell<Option<PathBuf>>,
+
+        /// Tells this item apart from every other one, including another item
+        /// for the same track.
+        pub serial: OnceCell<u64>,
     }
 
     #[glib::object_subclass]
@@ -43,7 +53,16 @@ mod imp {
     }
 
     #[glib::derived_properties]
-    impl ObjectImpl for PlaylistItem {}
+    impl ObjectImpl for PlaylistItem {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            static NEXT_SERIAL: AtomicU64 = AtomicU64::new(0);
+            self.serial
+                .set(NEXT_SERIAL.fetch_add(1, Ordering::Relaxed))
+                .unwrap();
+        }
+    }
 }
 
 glib::wrapper! {
@@ -59,6 +78,7 @@ impl PlaylistItem {
         part_title: Option<&str>,
         path: impl AsRef<Path>,
         track_id: &str,
+        cover_art: Option<&Path>,
     ) -> Self {
         glib::Object::builder()
             .property("is-title", is_title)
@@ -68,6 +88,7 @@ impl PlaylistItem {
             .property("part-title", part_title)
             .property("path", path.as_ref())
             .property("track-id", track_id)
+            .property("cover-art", cover_art)
             .build()
     }
 
@@ -92,4 +113,8 @@ impl PlaylistItem {
     pub fn make_subtitle(&self) -> Option<String> {
         self.performers()
     }
+
+    pub fn serial(&self) -> u64 {
+        *self.imp().serial.get().unwrap()
+    }
 }
@@ -82,7 +82,7 @@ mod imp {
                     let tile = match item.child().and_downcast::<PlaylistTile>() {
                         Some(tile) => tile,
                         None => {
-                            let tile = PlaylistTile::new();
+                            let tile = PlaylistTile::new(&player);
                             item.set_child(Some(&tile));
                             tile
                         }
@@ -177,6 +177,17 @@ impl PlaylistPage {
         self.player().set_current_index(index);
     }
 
+    #[template_callback]
+    fn shuffle(&self) {
+        self.player().shuffle();
+    }
+
+    #[template_callback]
+    fn clear(&self) {
+        self.player().clear();
+        self.close();
+    }
+
     #[template_callback]
     fn close(&self) {
         self.emit_by_name::<()>("close", &[]);
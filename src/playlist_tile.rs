@@ -1,8 +1,8 @@
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 
-use gtk::{glib, prelude::*, subclass::prelude::*};
+use gtk::{gdk, gio, glib, glib::clone, prelude::*, subclass::prelude::*};
 
-use crate::playlist_item::PlaylistItem;
+use crate::{player::Player, playlist_item::PlaylistItem};
 
 mod imp {
     use super::*;
@@ -21,6 +21,8 @@ mod imp {
         #[template_child]
         pub part_title_label: TemplateChild<gtk::Label>,
 
+        pub player: OnceCell<Player>,
+        pub item: RefCell<Option<PlaylistItem>>,
         pub binding: RefCell<Option<glib::Binding>>,
     }
 
@@ -39,7 +41,77 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for PlaylistTile {}
+    impl ObjectImpl for PlaylistTile {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let obj = self.obj().to_owned();
+            let remove_action = gio::ActionEntry::builder("remove")
+                .activate(move |_, _, _| {
+                    if let Some(index) = obj.index() {
+                        obj.imp().player.get().unwrap().remove(index);
+                    }
+                })
+                .build();
+
+            let obj = self.obj().to_owned();
+            let remove_recording_action = gio::ActionEntry::builder("remove-recording")
+                .activate(move |_, _, _| {
+                    if let Some(index) = obj.index() {
+                        obj.imp().player.get().unwrap().remove_recording(index);
+                    }
+                })
+                .build();
+
+            let actions = gio::SimpleActionGroup::new();
+            actions.add_action_entries([remove_action, remove_recording_action]);
+            self.obj().insert_action_group("item", Some(&actions));
+
+            // Tiles are dragged onto each other to move whole recordings.
+            let obj = self.obj().to_owned();
+
+            let drag_source = gtk::DragSource::new();
+            drag_source.set_actions(gdk::DragAction::MOVE);
+            drag_source.connect_prepare(clone!(
+                #[weak]
+                obj,
+                #[upgrade_or]
+                None,
+                move |_, _, _| {
+                    obj.imp()
+                        .item
+                        .borrow()
+                        .as_ref()
+                        .map(|item| gdk::ContentProvider::for_value(&item.to_value()))
+                }
+            ));
+            obj.add_controller(drag_source);
+
+            let drop_target =
+                gtk::DropTarget::new(PlaylistItem::static_type(), gdk::DragAction::MOVE);
+            drop_target.connect_drop(clone!(
+                #[weak]
+                obj,
+                #[upgrade_or]
+                false,
+                move |_, value, _, _| {
+                    let player = obj.imp().player.get().unwrap();
+
+                    let (Ok(dragged), Some(to)) = (value.get::<PlaylistItem>(), obj.index()) else {
+                        return false;
+                    };
+
+                    let Some(from) = player.playlist().find(&dragged) else {
+                        return false;
+                    };
+
+                    player.move_recording(from, to);
+                    true
+                }
+            ));
+            obj.add_controller(drop_target);
+        }
+    }
     impl WidgetImpl for PlaylistTile {}
     impl BoxImpl for PlaylistTile {}
 }
@@ -51,8 +123,10 @@ glib::wrapper! {
 }
 
 impl PlaylistTile {
-    pub fn new() -> Self {
-        glib::Object::new()
+    pub fn new(player: &Player) -> Self {
+        let obj: Self = glib::Object::new();
+        obj.imp().player.set(player.to_owned()).unwrap();
+        obj
     }
 
     pub fn set_item(&self, item: Option<&PlaylistItem>) {
@@ -62,6 +136,17 @@ impl PlaylistTile {
             binding.unbind();
         }
 
+        imp.item.replace(item.cloned());
+
+        // Tiles are reused for other items, which may not be the first of a recording.
+        imp.composers_label.set_visible(false);
+        imp.work_label.set_visible(false);
+        imp.performances_label.set_visible(false);
+        imp.part_title_label.set_visible(false);
+        imp.work_label.set_margin_top(32);
+        imp.work_label.set_margin_bottom(12);
+        self.set_margin_bottom(0);
+
         if let Some(item) = item {
             if item.is_title() {
                 if let Some(composers) = item.composers() {
@@ -94,4 +179,10 @@ impl PlaylistTile {
             ));
         }
     }
+
+    /// The position of the item in the playlist.
+    fn index(&self) -> Option<u32> {
+        let item = self.imp().item.borrow().clone()?;
+        self.imp().player.get().unwrap().playlist().find(&item)
+    }
 }
@@ -1,12 +1,16 @@
 use adw::{
-    prelude::{ActionRowExt, AdwDialogExt},
+    prelude::{ActionRowExt, AdwDialogExt, ExpanderRowExt},
     subclass::prelude::*,
 };
 use gettextrs::gettext;
 use gtk::{gio, glib, prelude::*};
 use musicus_library::library::naming::{audio_tags, filenames, pattern};
 
-use crate::{config, slider_row::SliderRow};
+use crate::{config, listen_token, program::ProgramTransition, slider_row::SliderRow};
+
+/// The GStreamer elements behind the entries of the output row, in the same
+/// order. The empty name lets GStreamer choose.
+const AUDIO_SINKS: &[&str] = &["", "pipewiresink", "pulsesink", "alsasink", "jackaudiosink"];
 
 /// One of the patterns that describe how a track file is named and tagged.
 ///
@@ -80,8 +84,26 @@ mod imp {
         #[template_child]
         pub avoid_repeated_instruments_adjustment: TemplateChild<gtk::Adjustment>,
         #[template_child]
+        pub avoid_skipped_adjustment: TemplateChild<gtk::Adjustment>,
+        #[template_child]
         pub play_full_recordings_row: TemplateChild<adw::SwitchRow>,
         #[template_child]
+        pub transition_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub transition_duration_adjustment: TemplateChild<gtk::Adjustment>,
+        #[template_child]
+        pub play_threshold_adjustment: TemplateChild<gtk::Adjustment>,
+        #[template_child]
+        pub play_threshold_percent_adjustment: TemplateChild<gtk::Adjustment>,
+        #[template_child]
+        pub audio_sink_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub audio_device_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub bit_perfect_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub sleep_timer_fade_out_adjustment: TemplateChild<gtk::Adjustment>,
+        #[template_child]
         pub track_filename_pattern_row: TemplateChild<adw::EntryRow>,
         #[template_child]
         pub filename_pattern_preview_row: TemplateChild<adw::ActionRow>,
@@ -98,6 +120,18 @@ mod imp {
         #[template_child]
         pub title_pattern_preview_row: TemplateChild<adw::ActionRow>,
         #[template_child]
+        pub preserve_cover_art_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub preserve_replay_gain_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub preserve_musicbrainz_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub preserve_other_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub automatic_file_maintenance_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub placeholders_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
         pub enable_automatic_metadata_updates_row: TemplateChild<adw::SwitchRow>,
         #[template_child]
         pub use_custom_metadata_url_row: TemplateChild<adw::SwitchRow>,
@@ -107,6 +141,12 @@ mod imp {
         pub use_custom_library_url_row: TemplateChild<adw::SwitchRow>,
         #[template_child]
         pub custom_library_url_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub submit_listens_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub listen_endpoint_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub listen_token_row: TemplateChild<adw::PasswordEntryRow>,
     }
 
     #[glib::object_subclass]
@@ -164,6 +204,10 @@ mod imp {
                 )
                 .build();
 
+            settings
+                .bind("avoid-skipped", &*self.avoid_skipped_adjustment, "value")
+                .build();
+
             settings
                 .bind(
                     "play-full-recordings",
@@ -172,6 +216,58 @@ mod imp {
                 )
                 .build();
 
+            let transition = settings.string("transition");
+            if let Some(index) = ProgramTransition::ALL
+                .iter()
+                .position(|t| t.to_string() == transition)
+            {
+                self.transition_row.set_selected(index as u32);
+            }
+
+            settings
+                .bind(
+                    "transition-duration",
+                    &*self.transition_duration_adjustment,
+                    "value",
+                )
+                .build();
+
+            settings
+                .bind("play-threshold", &*self.play_threshold_adjustment, "value")
+                .build();
+
+            settings
+                .bind(
+                    "play-threshold-percent",
+                    &*self.play_threshold_percent_adjustment,
+                    "value",
+                )
+                .build();
+
+            // A sink that was configured elsewhere and is not in the list stays
+            // as it is until another one is chosen.
+            let audio_sink = settings.string("audio-sink");
+            if let Some(index) = AUDIO_SINKS.iter().position(|sink| *sink == audio_sink) {
+                self.audio_sink_row.set_selected(index as u32);
+            }
+
+            // Every change of the device restarts the output, so it is only
+            // applied explicitly.
+            self.audio_device_row
+                .set_text(&settings.string("audio-device"));
+
+            settings
+                .bind("bit-perfect", &*self.bit_perfect_row, "active")
+                .build();
+
+            settings
+                .bind(
+                    "sleep-timer-fade-out",
+                    &*self.sleep_timer_fade_out_adjustment,
+                    "value",
+                )
+                .build();
+
             // Unlike the other rows, the patterns are not bound to their
             // setting: an invalid one may not be saved, so they are applied
             // explicitly.
@@ -181,6 +277,56 @@ mod imp {
                 self.obj().update_pattern_preview(*kind);
             }
 
+            settings
+                .bind(
+                    "track-tag-preserve-cover-art",
+                    &*self.preserve_cover_art_row,
+                    "active",
+                )
+                .build();
+
+            settings
+                .bind(
+                    "track-tag-preserve-replay-gain",
+                    &*self.preserve_replay_gain_row,
+                    "active",
+                )
+                .build();
+
+            settings
+                .bind(
+                    "track-tag-preserve-musicbrainz",
+                    &*self.preserve_musicbrainz_row,
+                    "active",
+                )
+                .build();
+
+            settings
+                .bind(
+                    "track-tag-preserve-other",
+                    &*self.preserve_other_row,
+                    "active",
+                )
+                .build();
+
+            settings
+                .bind(
+                    "automatic-file-maintenance",
+                    &*self.automatic_file_maintenance_row,
+                    "active",
+                )
+                .build();
+
+            for (placeholder, example) in pattern::examples() {
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&placeholder))
+                    .subtitle(glib::markup_escape_text(&example))
+                    .subtitle_selectable(true)
+                    .build();
+                row.add_css_class("property");
+                self.placeholders_row.add_row(&row);
+            }
+
             settings
                 .bind(
                     "enable-automatic-metadata-updates",
@@ -226,6 +372,32 @@ mod imp {
                 .bind_property("active", &*self.custom_library_url_row, "sensitive")
                 .sync_create()
                 .build();
+
+            settings
+                .bind("submit-listens", &*self.submit_listens_row, "active")
+                .build();
+
+            settings
+                .bind("listen-endpoint", &*self.listen_endpoint_row, "text")
+                .build();
+
+            let obj = self.obj().to_owned();
+            glib::spawn_future_local(async move {
+                match listen_token::load().await {
+                    Ok(token) => obj.imp().listen_token_row.set_text(&token),
+                    Err(err) => log::error!("Failed to read the listen token: {err:?}"),
+                }
+            });
+
+            for row in [
+                self.listen_endpoint_row.upcast_ref::<gtk::Widget>(),
+                self.listen_token_row.upcast_ref(),
+            ] {
+                self.submit_listens_row
+                    .bind_property("active", row, "sensitive")
+                    .sync_create()
+                    .build();
+            }
         }
     }
 
@@ -279,11 +451,11 @@ impl PreferencesDialog {
         match kind.preview(&row.text()) {
             Ok(preview) => {
                 row.remove_css_class("error");
-                preview_row.set_subtitle(&preview);
+                preview_row.set_subtitle(&glib::markup_escape_text(&preview));
             }
             Err(err) => {
                 row.add_css_class("error");
-                preview_row.set_subtitle(&err.to_string());
+                preview_row.set_subtitle(&glib::markup_escape_text(&err.to_string()));
             }
         }
     }
@@ -311,6 +483,51 @@ impl PreferencesDialog {
         }
     }
 
+    #[template_callback]
+    fn audio_sink_changed(&self) {
+        let Some(audio_sink) = AUDIO_SINKS.get(self.imp().audio_sink_row.selected() as usize)
+        else {
+            return;
+        };
+
+        let settings = gio::Settings::new(config::APP_ID);
+        if let Err(err) = settings.set_string("audio-sink", audio_sink) {
+            log::error!("Failed to save the audio sink: {err:?}");
+        }
+    }
+
+    #[template_callback]
+    fn transition_changed(&self) {
+        let Some(transition) =
+            ProgramTransition::ALL.get(self.imp().transition_row.selected() as usize)
+        else {
+            return;
+        };
+
+        let settings = gio::Settings::new(config::APP_ID);
+        if let Err(err) = settings.set_string("transition", &transition.to_string()) {
+            log::error!("Failed to save the transition between recordings: {err:?}");
+        }
+    }
+
+    #[template_callback]
+    fn apply_audio_device(&self) {
+        let settings = gio::Settings::new(config::APP_ID);
+        if let Err(err) = settings.set_string("audio-device", &self.imp().audio_device_row.text()) {
+            log::error!("Failed to save the audio device: {err:?}");
+        }
+    }
+
+    #[template_callback]
+    fn apply_listen_token(&self) {
+        let token = self.imp().listen_token_row.text();
+        glib::spawn_future_local(async move {
+            if let Err(err) = listen_token::store(&token).await {
+                log::error!("Failed to save the listen token: {err:?}");
+            }
+        });
+    }
+
     #[template_callback]
     fn filename_pattern_changed(&self) {
         self.update_pattern_preview(PatternKind::FileName);
@@ -59,8 +59,18 @@ mod imp {
         #[property(get, set)]
         pub avoid_repeated_instruments: Cell<i32>,
 
+        #[property(get, set)]
+        pub avoid_skipped: Cell<f64>,
+
         #[property(get, set)]
         pub play_full_recordings: Cell<bool>,
+
+        #[property(get, set, builder(ProgramTransition::default()))]
+        pub transition: Cell<ProgramTransition>,
+
+        /// How long the pause or crossfade between two recordings lasts.
+        #[property(get, set)]
+        pub transition_duration_s: Cell<u32>,
     }
 
     #[glib::object_subclass]
@@ -137,10 +147,22 @@ impl Program {
                     0
                 },
             )
+            .property(
+                "avoid-skipped",
+                settings.int("avoid-skipped") as f64 / 100.0,
+            )
             .property(
                 "play-full-recordings",
                 settings.boolean("play-full-recordings"),
             )
+            .property(
+                "transition",
+                ProgramTransition::from_str(&settings.string("transition")).unwrap_or_default(),
+            )
+            .property(
+                "transition-duration-s",
+                settings.int("transition-duration").max(0) as u32,
+            )
             .build()
     }
 
@@ -187,7 +209,10 @@ impl Program {
                 "avoid-repeated-instruments",
                 data.avoid_repeated_instruments.get(),
             )
+            .property("avoid-skipped", data.avoid_skipped.get())
             .property("play-full-recordings", data.play_full_recordings.get())
+            .property("transition", data.transition.get())
+            .property("transition-duration-s", data.transition_duration_s.get())
             .build();
 
         Ok(obj)
@@ -267,3 +292,50 @@ impl FromStr for ProgramDesign {
         }
     }
 }
+
+/// What happens between two recordings of a program. Within a recording, the
+/// tracks always follow each other without a gap.
+#[derive(glib::Enum, Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Debug)]
+#[enum_type(name = "MusicusProgramTransition")]
+pub enum ProgramTransition {
+    /// The next recording starts right away.
+    Direct,
+    /// A moment of silence before the next recording.
+    Pause,
+    /// The end of a recording fades into the beginning of the next one.
+    Crossfade,
+}
+
+impl ProgramTransition {
+    /// All transitions, in the order in which they are offered.
+    pub const ALL: &'static [Self] = &[Self::Direct, Self::Pause, Self::Crossfade];
+}
+
+impl Default for ProgramTransition {
+    fn default() -> Self {
+        Self::Direct
+    }
+}
+
+impl ToString for ProgramTransition {
+    fn to_string(&self) -> String {
+        String::from(match self {
+            ProgramTransition::Direct => "direct",
+            ProgramTransition::Pause => "pause",
+            ProgramTransition::Crossfade => "crossfade",
+        })
+    }
+}
+
+impl FromStr for ProgramTransition {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, ()> {
+        match s {
+            "direct" => Ok(ProgramTransition::Direct),
+            "pause" => Ok(ProgramTransition::Pause),
+            "crossfade" => Ok(ProgramTransition::Crossfade),
+            _ => Err(()),
+        }
+    }
+}
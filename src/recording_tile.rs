@@ -15,6 +15,10 @@ mod imp {
     #[derive(Debug, Default, gtk::CompositeTemplate)]
     #[template(file = "data/ui/recording_tile.blp")]
     pub struct RecordingTile {
+        #[template_child]
+        pub cover_art_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub cover_art_image: TemplateChild<gtk::Image>,
         #[template_child]
         pub title_label: TemplateChild<gtk::Label>,
         #[template_child]
@@ -68,6 +72,23 @@ mod imp {
                 })
                 .build();
 
+            let obj = self.obj().to_owned();
+            let play_next_action = gio::ActionEntry::builder("play-next")
+                .activate(move |_, _, _| {
+                    let player = obj.imp().player.get().unwrap();
+                    let recording = obj.imp().recording.get().unwrap();
+
+                    let playlist = match obj.imp().work.get().unwrap() {
+                        Some(work) => player.recording_to_playlist_for_work(recording, work),
+                        None => player.recording_to_playlist(recording),
+                    };
+
+                    if let Err(err) = player.play_next(playlist) {
+                        log::error!("Failed to add recording to playlist: {err:?}");
+                    }
+                })
+                .build();
+
             let obj = self.obj().to_owned();
             let edit_recording_action = gio::ActionEntry::builder("edit-recording")
                 .activate(move |_, _, _| {
@@ -123,6 +144,7 @@ mod imp {
             let actions = gio::SimpleActionGroup::new();
             actions.add_action_entries([
                 append_action,
+                play_next_action,
                 edit_recording_action,
                 edit_tracks_action,
                 delete_action,
@@ -186,6 +208,11 @@ impl RecordingTile {
             label.set_visible(false);
         }
 
+        if let Some(cover_art) = library.recording_cover_art(recording) {
+            imp.cover_art_image.set_from_file(Some(cover_art));
+            imp.cover_art_stack.set_visible_child_name("cover-art");
+        }
+
         imp.toast_overlay.set(toast_overlay.to_owned()).unwrap();
         imp.navigation.set(navigation.to_owned()).unwrap();
         imp.library.set(library.to_owned()).unwrap();
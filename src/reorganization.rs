@@ -0,0 +1,234 @@
+//! Bringing track files in line with the patterns, once the user has reviewed
+//! what would change.
+
+use adw::prelude::*;
+use gettextrs::gettext;
+use gtk::glib::{self, clone};
+use musicus_library::{
+    format_translated,
+    library::{ReorganizationPlan, ReorganizationScope},
+};
+
+use crate::{library::Library, process::Process, util, window::Window};
+
+/// Preview a reorganization of `scope`, or of the whole library if there is
+/// none, and carry it out once the user has confirmed it.
+///
+/// `add_process` is given the processes for planning and for reorganizing, so
+/// that the caller can show them.
+pub async fn reorganize_files(
+    parent: &impl IsA<gtk::Widget>,
+    library: &Library,
+    scope: Option<&ReorganizationScope>,
+    add_process: impl Fn(&Process),
+) {
+    let preview = match scope {
+        Some(scope) => library.preview_reorganization_in(scope),
+        None => library.preview_reorganization(),
+    };
+
+    // Nothing is touched before the user has seen what would happen.
+    let plan_receiver = match preview {
+        Ok((handle, plan_receiver)) => {
+            add_process(&Process::new(
+                &gettext("Planning the reorganization"),
+                handle,
+            ));
+
+            plan_receiver
+        }
+        Err(err) => {
+            log::error!("Failed to plan the reorganization: {err:?}");
+            return;
+        }
+    };
+
+    // The process reports its own failure, the plan only arrives on success.
+    let Ok(plan) = plan_receiver.recv().await else {
+        return;
+    };
+
+    if plan.is_empty() {
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Nothing to reorganize"))
+            .body(gettext(
+                "Every track file is already named and tagged after the patterns.",
+            ))
+            .build();
+
+        dialog.add_response("close", &gettext("Close"));
+        dialog.choose_future(Some(parent)).await;
+        return;
+    }
+
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Reorganize files?"))
+        .body(format_translated!(
+            gettext("{} track files will be renamed and the tags of {} track files will be replaced. Replacing the tags cannot be undone."),
+            plan.renames.len().to_string(),
+            plan.tag_changes.len().to_string()
+        ))
+        .extra_child(&plan_list(&plan))
+        .build();
+
+    dialog.add_responses(&[
+        ("cancel", &gettext("Cancel")),
+        ("reorganize", &gettext("Reorganize")),
+    ]);
+
+    dialog.set_response_appearance("reorganize", adw::ResponseAppearance::Suggested);
+    dialog.set_close_response("cancel");
+    dialog.set_default_response(Some("cancel"));
+
+    if dialog.choose_future(Some(parent)).await != "reorganize" {
+        return;
+    }
+
+    let result = match scope {
+        Some(scope) => library.reorganize_files_in(scope),
+        None => library.reorganize_files(),
+    };
+
+    match result {
+        Ok(handle) => {
+            let process = Process::new(&gettext("Reorganizing library files"), handle);
+
+            process.connect_finished_notify(clone!(
+                #[weak]
+                library,
+                move |_| {
+                    library.changed();
+                }
+            ));
+
+            add_process(&process);
+        }
+        Err(err) => log::error!("Failed to reorganize library files: {err:?}"),
+    }
+}
+
+/// Offer to bring the files of the tracks in `scope` in line after an edit.
+///
+/// The offer is a toast in the window, so this can be called right before an
+/// editor is closed.
+pub fn offer_update(page: &impl IsA<gtk::Widget>, library: &Library, scope: ReorganizationScope) {
+    match library.has_tracks_in(&scope) {
+        Ok(true) => (),
+        Ok(false) => return,
+        Err(err) => {
+            log::warn!("Failed to look for the tracks affected by an edit: {err:?}");
+            return;
+        }
+    }
+
+    let (Some(toast_overlay), Some(window)) = (
+        util::find_toast_overlay(page),
+        page.root().and_downcast::<Window>(),
+    ) else {
+        return;
+    };
+
+    let toast = adw::Toast::builder()
+        .title(gettext("Changes saved"))
+        .button_label(gettext("Update files"))
+        .timeout(10)
+        .build();
+
+    let library = library.to_owned();
+
+    toast.connect_button_clicked(move |_| {
+        let library = library.clone();
+        let window = window.clone();
+        let scope = scope.clone();
+
+        glib::spawn_future_local(async move {
+            reorganize_files(&window, &library, Some(&scope), |process| {
+                window.add_process(process)
+            })
+            .await;
+        });
+    });
+
+    toast_overlay.add_toast(toast);
+}
+
+/// The renames and tag changes of `plan`, one row per file, for reviewing them.
+fn plan_list(plan: &ReorganizationPlan) -> gtk::ScrolledWindow {
+    // Building a row for every file of a large library would freeze the
+    // dialog, and nobody reviews more than this anyway.
+    const MAX_LISTED: usize = 500;
+
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["boxed-list"])
+        .build();
+
+    for rename in plan.renames.iter().take(MAX_LISTED) {
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(&rename.to.to_string_lossy()))
+            .subtitle(glib::markup_escape_text(&format_translated!(
+                gettext("was {}"),
+                rename.from.display()
+            )))
+            .build();
+
+        row.add_prefix(&gtk::Image::from_icon_name("document-edit-symbolic"));
+        list.append(&row);
+    }
+
+    for tag_changes in plan
+        .tag_changes
+        .iter()
+        .take(MAX_LISTED.saturating_sub(plan.renames.len()))
+    {
+        let row = adw::ExpanderRow::builder()
+            .title(glib::markup_escape_text(
+                &tag_changes.path.to_string_lossy(),
+            ))
+            .subtitle(format_translated!(
+                gettext("{} changed tags"),
+                tag_changes.changes.len().to_string()
+            ))
+            .build();
+
+        row.add_prefix(&gtk::Image::from_icon_name("tag-symbolic"));
+
+        for change in &tag_changes.changes {
+            let none = gettext("none");
+
+            row.add_row(
+                &adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&change.field))
+                    .subtitle(glib::markup_escape_text(&format!(
+                        "{} → {}",
+                        change.old.as_deref().unwrap_or(&none),
+                        change.new.as_deref().unwrap_or(&none)
+                    )))
+                    .subtitle_selectable(true)
+                    .build(),
+            );
+        }
+
+        list.append(&row);
+    }
+
+    let n_files = plan.renames.len() + plan.tag_changes.len();
+
+    if n_files > MAX_LISTED {
+        list.append(
+            &adw::ActionRow::builder()
+                .title(format_translated!(
+                    gettext("And {} more"),
+                    (n_files - MAX_LISTED).to_string()
+                ))
+                .build(),
+        );
+    }
+
+    gtk::ScrolledWindow::builder()
+        .child(&list)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .propagate_natural_height(true)
+        .max_content_height(400)
+        .build()
+}
@@ -513,7 +513,8 @@ impl SearchPage {
             }
 
             for album in &results.albums {
-                imp.albums_flow_box.append(&AlbumTile::new(album));
+                imp.albums_flow_box
+                    .append(&AlbumTile::new(&self.library(), album));
             }
 
             imp.composers.replace(results.composers);
@@ -8,15 +8,19 @@ use anyhow::{anyhow, Result};
 use chrono::{Duration, Local};
 use gettextrs::gettext;
 use gtk::{gio, glib, glib::clone};
-use musicus_library::library::Patterns;
+use musicus_library::library::{settings::PATTERN_KEYS, Patterns, PreservedTags};
 
 use crate::{
     album_page::AlbumPage,
     config,
     editor::{album::AlbumEditor, tracks::TracksEditor},
     empty_page::EmptyPage,
+    history_page::HistoryPage,
+    known_libraries,
     library::{Library, LibraryQuery},
     library_manager::LibraryManager,
+    library_settings,
+    library_switcher::LibrarySwitcher,
     player::Player,
     player_bar::PlayerBar,
     playlist_page::PlaylistPage,
@@ -28,13 +32,9 @@ use crate::{
     welcome_page::WelcomePage,
 };
 
-/// The settings describing how a track file is named and tagged.
-const PATTERN_KEYS: &[&str] = &[
-    "track-filename-pattern",
-    "track-tag-album-pattern",
-    "track-tag-artist-pattern",
-    "track-tag-title-pattern",
-];
+/// How long the library has to stay unchanged before stale track files are
+/// updated, so that a series of edits results in a single run.
+const FILE_MAINTENANCE_DELAY_SECONDS: u32 = 5;
 
 mod imp {
     use super::*;
@@ -47,6 +47,8 @@ mod imp {
         pub player: Player,
         pub process_manager: ProcessManager,
         pub inhibitor_cookie: Cell<Option<u32>>,
+        pub file_maintenance_timeout: RefCell<Option<glib::SourceId>>,
+        pub file_maintenance: RefCell<Option<Process>>,
 
         #[template_child]
         pub toast_overlay: TemplateChild<adw::ToastOverlay>,
@@ -109,6 +111,16 @@ mod imp {
                 })
                 .build();
 
+            let obj = self.obj().to_owned();
+            let history_action = gio::ActionEntry::builder("history")
+                .activate(move |_, _, _| {
+                    if let Some(library) = &*obj.imp().library.borrow() {
+                        let history_page = HistoryPage::new(library, &obj.imp().player);
+                        obj.imp().navigation_view.push(&history_page);
+                    }
+                })
+                .build();
+
             let obj = self.obj().to_owned();
             let library_action = gio::ActionEntry::builder("library")
                 .activate(move |_, _, _| {
@@ -123,6 +135,19 @@ mod imp {
                 })
                 .build();
 
+            let obj = self.obj().to_owned();
+            let libraries_action = gio::ActionEntry::builder("libraries")
+                .activate(move |_, _, _| {
+                    let switcher = LibrarySwitcher::new();
+                    switcher.connect_folder_selected(clone!(
+                        #[weak]
+                        obj,
+                        move |_, folder| obj.set_library_folder(&folder)
+                    ));
+                    switcher.present(Some(&obj));
+                })
+                .build();
+
             let obj = self.obj().to_owned();
             let preferences_action = gio::ActionEntry::builder("preferences")
                 .activate(move |_, _, _| {
@@ -133,7 +158,9 @@ mod imp {
             self.obj().add_action_entries([
                 import_action,
                 create_album_action,
+                history_action,
                 library_action,
+                libraries_action,
                 preferences_action,
             ]);
 
@@ -175,6 +202,41 @@ mod imp {
                 .sync_create()
                 .build();
 
+            self.settings()
+                .bind("sleep-timer-fade-out", &self.player, "sleep-fade-out-s")
+                .get()
+                .build();
+
+            self.settings()
+                .bind("play-threshold", &self.player, "play-threshold-s")
+                .get()
+                .build();
+
+            self.settings()
+                .bind(
+                    "play-threshold-percent",
+                    &self.player,
+                    "play-threshold-percent",
+                )
+                .get()
+                .build();
+
+            self.settings()
+                .bind("volume", &self.player, "volume")
+                .build();
+
+            // Submission is turned on last, so that it uses the configured
+            // endpoint right away.
+            for key in [
+                "audio-sink",
+                "audio-device",
+                "bit-perfect",
+                "listen-endpoint",
+                "submit-listens",
+            ] {
+                self.settings().bind(key, &self.player, key).get().build();
+            }
+
             let obj = self.obj().to_owned();
             self.player.connect_raise(move |_| obj.present());
 
@@ -217,11 +279,39 @@ mod imp {
                 );
             }
 
+            for key in library_settings::keys() {
+                self.settings().connect_changed(
+                    Some(key),
+                    clone!(
+                        #[weak(rename_to = obj)]
+                        self,
+                        move |settings, key| obj.obj().store_library_setting(settings, key)
+                    ),
+                );
+            }
+
+            self.settings().connect_changed(
+                Some("automatic-file-maintenance"),
+                clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_, _| obj.obj().push_file_maintenance()
+                ),
+            );
+
             let settings = self.settings();
             let library_path = settings.string("library-path").to_string();
             if !library_path.is_empty() {
-                if let Err(err) = self.obj().load_library(&library_path) {
-                    util::error_toast("Failed to open music library", err, &self.toast_overlay);
+                match self.obj().load_library(&library_path) {
+                    Ok(()) => {
+                        // Libraries opened before there was a list of them.
+                        if let Err(err) = known_libraries::remember(settings, &library_path) {
+                            log::error!("Failed to remember the music library: {err:?}");
+                        }
+                    }
+                    Err(err) => {
+                        util::error_toast("Failed to open music library", err, &self.toast_overlay)
+                    }
                 }
             }
         }
@@ -308,10 +398,22 @@ impl Window {
         Ok(())
     }
 
+    /// Run `process` in the background, like the ones started from the library
+    /// manager.
+    pub fn add_process(&self, process: &Process) {
+        self.imp().process_manager.add_process(process);
+    }
+
     #[template_callback]
     pub fn set_library_folder(&self, folder: &gio::File) {
         let path = folder.path().unwrap();
 
+        if self.imp().library.borrow().is_some()
+            && path.to_str() == Some(self.imp().settings().string("library-path").as_str())
+        {
+            return;
+        }
+
         match self.load_library(&path) {
             Ok(_) => {
                 if let Err(err) = self.save_library_path(path) {
@@ -342,23 +444,150 @@ impl Window {
                 album: settings.string("track-tag-album-pattern").into(),
                 artist: settings.string("track-tag-artist-pattern").into(),
                 title: settings.string("track-tag-title-pattern").into(),
+                preserved_tags: PreservedTags {
+                    cover_art: settings.boolean("track-tag-preserve-cover-art"),
+                    replay_gain: settings.boolean("track-tag-preserve-replay-gain"),
+                    musicbrainz: settings.boolean("track-tag-preserve-musicbrainz"),
+                    other: settings.boolean("track-tag-preserve-other"),
+                },
             });
         }
     }
 
+    /// Keep a changed setting that belongs to the library in the library.
+    fn store_library_setting(&self, settings: &gio::Settings, key: &str) {
+        if let Some(library) = &*self.imp().library.borrow() {
+            if let Err(err) = library_settings::store(library, settings, key) {
+                util::error_toast(
+                    "Failed to save the setting in the library",
+                    err,
+                    &self.imp().toast_overlay,
+                );
+            }
+        }
+    }
+
+    /// Tell the library whether edits should mark tracks for file
+    /// maintenance.
+    fn push_file_maintenance(&self) {
+        if let Some(library) = &*self.imp().library.borrow() {
+            library
+                .set_file_maintenance(self.imp().settings().boolean("automatic-file-maintenance"));
+        }
+    }
+
+    /// Update the stale track files once the library has not changed for a
+    /// moment. Every change starts the wait anew.
+    fn schedule_file_maintenance(&self) {
+        if let Some(timeout) = self.imp().file_maintenance_timeout.take() {
+            timeout.remove();
+        }
+
+        if !self.imp().settings().boolean("automatic-file-maintenance") {
+            return;
+        }
+
+        let timeout = glib::timeout_add_seconds_local_once(
+            FILE_MAINTENANCE_DELAY_SECONDS,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move || {
+                    obj.imp().file_maintenance_timeout.take();
+                    obj.maintain_files();
+                }
+            ),
+        );
+
+        self.imp().file_maintenance_timeout.replace(Some(timeout));
+    }
+
+    fn maintain_files(&self) {
+        // Another run would compete for the same files. The one that is still
+        // going only covers the tracks that were stale when it started, so
+        // wait for it and look again.
+        if self
+            .imp()
+            .file_maintenance
+            .borrow()
+            .as_ref()
+            .is_some_and(|process| !process.finished())
+        {
+            self.schedule_file_maintenance();
+            return;
+        }
+
+        let Some(library) = self.imp().library.borrow().clone() else {
+            return;
+        };
+
+        match library.has_stale_tracks() {
+            Ok(false) => return,
+            Ok(true) => (),
+            Err(err) => {
+                log::error!("Failed to look for stale tracks: {err:?}");
+                return;
+            }
+        }
+
+        match library.maintain_files() {
+            Ok(handle) => {
+                let process = Process::new(&gettext("Updating track files"), handle);
+
+                // A failed run keeps its marks, so it is only tried again
+                // after the next edit instead of right away.
+                process.connect_finished_notify(clone!(
+                    #[weak]
+                    library,
+                    move |process| {
+                        if process.error().is_none() && !process.cancelled() {
+                            library.changed();
+                        }
+                    }
+                ));
+
+                self.imp().process_manager.add_process(&process);
+                self.imp().file_maintenance.replace(Some(process));
+            }
+            Err(err) => log::error!("Failed to update track files: {err:?}"),
+        }
+    }
+
     fn load_library(&self, path: impl AsRef<Path>) -> Result<()> {
         let library = Library::new(path)?;
 
         library.connect_changed(clone!(
             #[weak(rename_to = obj)]
             self,
-            move |_| obj.reset_view()
+            move |library| {
+                // A process started before switching libraries may still
+                // report changes to the previous one.
+                if obj.imp().library.borrow().as_ref() != Some(library) {
+                    return;
+                }
+
+                // An imported archive may have brought settings along.
+                library_settings::load(library, obj.imp().settings());
+                obj.reset_view();
+                obj.schedule_file_maintenance();
+            }
         ));
 
         self.imp().player.set_library(&library);
 
         let is_empty = library.is_empty()?;
 
+        self.imp().library.replace(Some(library.clone()));
+
+        // Only once the library is the current one, because every setting
+        // that changes here is stored in the current library.
+        library_settings::load(&library, self.imp().settings());
+
+        // The patterns describe how a track file is named and tagged, so the
+        // library needs them before anything can be imported.
+        self.push_patterns();
+        self.push_file_maintenance();
+
         let settings = self.imp().settings();
 
         if settings.boolean("enable-automatic-metadata-updates") {
@@ -387,11 +616,8 @@ impl Window {
             }
         }
 
-        self.imp().library.replace(Some(library));
-
-        // The patterns describe how a track file is named and tagged, so the
-        // library needs them before anything can be imported.
-        self.push_patterns();
+        // Edits that were marked before the application was closed last time.
+        self.schedule_file_maintenance();
 
         if is_empty {
             let navigation = self.imp().navigation_view.get();
@@ -418,12 +644,13 @@ impl Window {
 
     fn save_library_path(&self, path: impl AsRef<Path>) -> Result<()> {
         let settings = self.imp().settings();
-        settings.set_string(
-            "library-path",
-            path.as_ref()
-                .to_str()
-                .ok_or_else(|| anyhow!("Failed to convert path to string"))?,
-        )?;
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| anyhow!("Failed to convert path to string"))?;
+
+        settings.set_string("library-path", path)?;
+        known_libraries::remember(settings, path)?;
 
         Ok(())
     }